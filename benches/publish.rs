@@ -0,0 +1,133 @@
+//! How fast the publish path enqueues a large list - the half of a publish
+//! whose cost grows with the audience. Run with `cargo bench`; like the
+//! integration tests it needs a running Postgres with the credentials from
+//! `configuration/`, and it creates (and drops) its own scratch database.
+//!
+//! Queries are built at runtime rather than with `query!` so the bench
+//! compiles without a live database.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use secrecy::Secret;
+use sqlx::{Connection, Executor, PgConnection, PgPool};
+use uuid::Uuid;
+use zero2prod::configuration::{self, DatabaseSettings};
+
+// audience sizes worth watching - the top end is where a single
+// INSERT...SELECT starts to hold its transaction open noticeably
+const LIST_SIZES: &[i64] = &[1_000, 10_000, 50_000];
+
+fn enqueue_delivery_tasks(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to build a tokio runtime.");
+
+    // a scratch database with a random name, exactly like the test suite
+    let mut config = configuration::get_configuration()
+        .expect("Failed to read configuration.")
+        .database;
+    config.database_name = Uuid::new_v4().to_string();
+    let pool = runtime.block_on(create_database(&config));
+
+    let mut group = c.benchmark_group("enqueue_delivery_tasks");
+    // keep wall-clock time sane - each iteration is a full enqueue
+    group.sample_size(10);
+    for &size in LIST_SIZES {
+        runtime.block_on(seed_subscribers(&pool, size));
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.to_async(&runtime).iter(|| async {
+                let mut transaction = pool.begin().await.unwrap();
+                let issue_id = insert_issue(&mut transaction).await;
+                zero2prod::routes::enqueue_delivery_tasks(
+                    &mut transaction,
+                    issue_id,
+                    Uuid::nil(), // the default tenant
+                    false,
+                    None,
+                )
+                .await
+                .unwrap();
+                // roll back, so every iteration starts from an empty queue
+                transaction.rollback().await.unwrap();
+            });
+        });
+    }
+    group.finish();
+
+    runtime.block_on(drop_database(&config));
+}
+
+// the issue row the queue's foreign key points at
+async fn insert_issue(transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>) -> Uuid {
+    let issue_id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO newsletter_issues
+            (newsletter_issue_id, title, text_content, html_content, published_at)
+         VALUES ($1, 'bench', 'text', '<p>html</p>', now())",
+    )
+    .bind(issue_id)
+    .execute(&mut **transaction)
+    .await
+    .expect("Failed to insert the benchmark issue.");
+    issue_id
+}
+
+// replace whatever is there with exactly `count` confirmed subscribers,
+// spread across a handful of recipient domains like a real list
+async fn seed_subscribers(pool: &PgPool, count: i64) {
+    pool.execute("DELETE FROM subscriptions")
+        .await
+        .expect("Failed to clear the subscriptions table.");
+    sqlx::query(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+         SELECT gen_random_uuid(),
+            'bench-' || n || '@domain-' || (n % 7) || '.example',
+            'Bench Subscriber',
+            now(),
+            'confirmed'
+         FROM generate_series(1, $1) AS n",
+    )
+    .bind(count)
+    .execute(pool)
+    .await
+    .expect("Failed to seed the subscriptions table.");
+}
+
+async fn create_database(config: &DatabaseSettings) -> PgPool {
+    let mut connection = PgConnection::connect_with(&maintenance_settings(config).connection_options())
+        .await
+        .expect("Failed to connect to Postgres.");
+    connection
+        .execute(format!(r#"CREATE DATABASE "{}";"#, config.database_name).as_str())
+        .await
+        .expect("Failed to create the benchmark database.");
+
+    let pool = PgPool::connect_with(config.connection_options())
+        .await
+        .expect("Failed to connect to the benchmark database.");
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("Failed to migrate the benchmark database.");
+    pool
+}
+
+async fn drop_database(config: &DatabaseSettings) {
+    let mut connection = PgConnection::connect_with(&maintenance_settings(config).connection_options())
+        .await
+        .expect("Failed to connect to Postgres.");
+    connection
+        .execute(format!(r#"DROP DATABASE "{}" WITH (FORCE);"#, config.database_name).as_str())
+        .await
+        .expect("Failed to drop the benchmark database.");
+}
+
+fn maintenance_settings(config: &DatabaseSettings) -> DatabaseSettings {
+    DatabaseSettings {
+        database_name: "postgres".to_string(),
+        username: "postgres".to_string(),
+        password: Secret::new("password".to_string()),
+        ..config.clone()
+    }
+}
+
+criterion_group!(benches, enqueue_delivery_tasks);
+criterion_main!(benches);
@@ -0,0 +1,11 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // bakes VERGEN_GIT_SHA and VERGEN_BUILD_TIMESTAMP into the binary -
+    // read back in routes::health_check. vergen falls back to default
+    // values (rather than failing the build) when git isn't available,
+    // e.g. in a docker build context without the .git directory
+    vergen::EmitBuilder::builder()
+        .build_timestamp()
+        .git_sha(true)
+        .emit()?;
+    Ok(())
+}
@@ -0,0 +1,11 @@
+-- One row per delivered email - keeps the provider's MessageID around
+-- for threading and auditing.
+CREATE TABLE email_delivery_log (
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    recipient_email TEXT NOT NULL,
+    provider_message_id TEXT,
+    sent_at timestamptz NOT NULL
+);
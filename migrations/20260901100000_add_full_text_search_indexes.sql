@@ -0,0 +1,7 @@
+-- GIN indexes backing the admin search box - the expressions here must
+-- match the ones used in the search queries for the planner to use them.
+CREATE INDEX idx_subscriptions_fts ON subscriptions
+    USING GIN (to_tsvector('english', name || ' ' || email));
+
+CREATE INDEX idx_newsletter_issues_fts ON newsletter_issues
+    USING GIN (to_tsvector('english', title || ' ' || text_content));
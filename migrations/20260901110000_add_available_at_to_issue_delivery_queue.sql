@@ -0,0 +1,5 @@
+-- Tasks can be held back (e.g. the non-canary remainder of a soft launch)
+-- by setting available_at in the future - the worker only dequeues rows
+-- whose available_at has passed.
+ALTER TABLE issue_delivery_queue
+    ADD COLUMN available_at timestamptz NOT NULL DEFAULT now();
@@ -0,0 +1,10 @@
+-- Pending email address changes - the subscriptions row is only touched
+-- once the new address confirms via the emailed token.
+CREATE TABLE email_change_requests(
+    change_token TEXT NOT NULL,
+    PRIMARY KEY (change_token),
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    new_email TEXT NOT NULL,
+    requested_at timestamptz NOT NULL
+);
@@ -0,0 +1,26 @@
+-- Engagement tracking - one row per open/click a subscriber triggers on a
+-- delivered issue.
+CREATE TABLE email_tracking_events(
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    event_type TEXT NOT NULL
+        CHECK (event_type IN ('open', 'click')),
+    occurred_at timestamptz NOT NULL
+);
+CREATE INDEX idx_email_tracking_events_subscriber
+    ON email_tracking_events (subscriber_id);
+
+-- Outstanding re-engagement campaigns - one per inactive subscriber we
+-- have asked to re-confirm. The row disappears when they respond; if it
+-- lingers past the response window they get unsubscribed.
+CREATE TABLE reengagement_campaigns(
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    PRIMARY KEY (subscriber_id),
+    reconfirm_token TEXT NOT NULL UNIQUE,
+    sent_at timestamptz NOT NULL
+);
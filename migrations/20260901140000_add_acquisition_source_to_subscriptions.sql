@@ -0,0 +1,3 @@
+-- Where a subscriber came from - the optional `?ref=` on the subscribe
+-- endpoint. NULL means a direct / unattributed signup.
+ALTER TABLE subscriptions ADD COLUMN acquisition_source TEXT NULL;
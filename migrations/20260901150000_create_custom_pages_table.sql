@@ -0,0 +1,8 @@
+-- Operator-editable HTML for the public-facing pages (confirmation,
+-- unsubscribe, error). An absent row means the built-in default is served.
+CREATE TABLE custom_pages(
+    slug TEXT NOT NULL,
+    PRIMARY KEY (slug),
+    html TEXT NOT NULL,
+    updated_at timestamptz NOT NULL
+);
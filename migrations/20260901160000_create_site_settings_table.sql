@@ -0,0 +1,17 @@
+-- A single row of operator-editable branding and compliance details,
+-- injected into outgoing emails and the public pages.
+CREATE TABLE site_settings(
+    -- a bool primary key with a CHECK pins the table to exactly one row
+    singleton bool NOT NULL DEFAULT TRUE,
+    PRIMARY KEY (singleton),
+    CHECK (singleton),
+    newsletter_name TEXT NOT NULL,
+    logo_url TEXT NOT NULL,
+    accent_color TEXT NOT NULL,
+    footer_address TEXT NOT NULL,
+    social_links TEXT NOT NULL
+);
+INSERT INTO site_settings
+    (newsletter_name, logo_url, accent_color, footer_address, social_links)
+VALUES
+    ('Our newsletter', '', '#2a6f97', '', '');
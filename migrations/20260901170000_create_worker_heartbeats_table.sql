@@ -0,0 +1,8 @@
+-- Each delivery worker loop upserts a row here on a short interval. A row
+-- whose last_seen_at has gone stale means the worker is stuck or dead -
+-- the monitor task and the admin diagnostics page both read this table.
+CREATE TABLE worker_heartbeats (
+    worker_id uuid PRIMARY KEY,
+    started_at timestamptz NOT NULL,
+    last_seen_at timestamptz NOT NULL
+);
@@ -0,0 +1,7 @@
+-- Free-form labels attached to subscribers by integrations (the JSON API,
+-- imports) - a plain join table, a subscriber can carry any number.
+CREATE TABLE subscriber_tags (
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (subscriber_id, tag)
+);
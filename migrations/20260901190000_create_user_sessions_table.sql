@@ -0,0 +1,9 @@
+-- one row per live login session - the registry that enforces the
+-- per-user concurrent-session cap and lets a password change revoke
+-- every other session
+CREATE TABLE user_sessions (
+    session_id uuid PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL
+);
+CREATE INDEX idx_user_sessions_user ON user_sessions (user_id);
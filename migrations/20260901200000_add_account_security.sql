@@ -0,0 +1,12 @@
+-- a locked account refuses logins until an operator clears the flag
+ALTER TABLE users ADD COLUMN locked boolean NOT NULL DEFAULT false;
+
+-- every (ip, user agent) pair a user has ever signed in from - a login
+-- from a pair not in here triggers a security notification
+CREATE TABLE login_history (
+    user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    ip TEXT NOT NULL,
+    user_agent TEXT NOT NULL,
+    first_seen_at timestamptz NOT NULL,
+    PRIMARY KEY (user_id, ip, user_agent)
+);
@@ -0,0 +1,13 @@
+-- Issues-in-progress - separate from newsletter_issues so the archive,
+-- search and delivery queue never see half-written content. The version
+-- column backs optimistic locking: every save must quote the version it
+-- started from, so two editors can't silently overwrite each other.
+CREATE TABLE newsletter_drafts (
+    newsletter_draft_id uuid NOT NULL,
+    PRIMARY KEY (newsletter_draft_id),
+    title TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    version integer NOT NULL DEFAULT 1,
+    updated_at timestamptz NOT NULL
+);
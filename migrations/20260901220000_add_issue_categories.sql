@@ -0,0 +1,18 @@
+-- Categories for issues ("product", "essay", ...) - the same plain join
+-- table shape as subscriber_tags.
+CREATE TABLE newsletter_issue_tags (
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, tag)
+);
+
+-- A subscriber's category opt-ins. No rows means "send me everything";
+-- any rows mean "only issues in these categories" (issues without a
+-- category still go to everyone).
+CREATE TABLE subscriber_category_preferences (
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    category TEXT NOT NULL,
+    PRIMARY KEY (subscriber_id, category)
+);
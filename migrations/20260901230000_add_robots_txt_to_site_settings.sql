@@ -0,0 +1,5 @@
+-- Operator-editable robots.txt rules, served verbatim at /robots.txt
+-- (with a Sitemap: line appended). The default welcomes every crawler.
+ALTER TABLE site_settings
+    ADD COLUMN robots_txt TEXT NOT NULL DEFAULT 'User-agent: *
+Allow: /';
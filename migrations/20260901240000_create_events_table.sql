@@ -0,0 +1,12 @@
+-- An append-only log of domain events for analytics. Deliberately
+-- PII-free: the subject is a pseudonymous uuid (subscriber or issue id),
+-- never an email address or a name - analytics queries shouldn't need to
+-- touch the operational tables at all.
+CREATE TABLE events (
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    event_type TEXT NOT NULL,
+    subject_id uuid,
+    occurred_at timestamptz NOT NULL
+);
+CREATE INDEX idx_events_type_occurred_at ON events (event_type, occurred_at);
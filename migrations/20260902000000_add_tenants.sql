@@ -0,0 +1,26 @@
+-- Multi-tenant groundwork: one deployment can host several independent
+-- newsletters. Every existing row belongs to the default tenant (the nil
+-- uuid), which also catches requests on any host no other tenant claims -
+-- a single-tenant deployment never has to know tenants exist.
+CREATE TABLE tenants (
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    name TEXT NOT NULL,
+    -- requests on this host resolve to this tenant; NULL marks the
+    -- catch-all default
+    host TEXT UNIQUE,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+INSERT INTO tenants (id, name, host)
+VALUES ('00000000-0000-0000-0000-000000000000', 'default', NULL);
+
+-- the core domain tables get a tenant. The DEFAULT keeps every existing
+-- insert path working - rows written by code that doesn't know about
+-- tenants yet simply land in the default one
+ALTER TABLE subscriptions ADD COLUMN tenant_id uuid NOT NULL
+    DEFAULT '00000000-0000-0000-0000-000000000000' REFERENCES tenants (id);
+ALTER TABLE newsletter_issues ADD COLUMN tenant_id uuid NOT NULL
+    DEFAULT '00000000-0000-0000-0000-000000000000' REFERENCES tenants (id);
+ALTER TABLE users ADD COLUMN tenant_id uuid NOT NULL
+    DEFAULT '00000000-0000-0000-0000-000000000000' REFERENCES tenants (id);
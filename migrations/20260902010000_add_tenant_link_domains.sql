@@ -0,0 +1,7 @@
+-- A custom domain per tenant for the links we put into emails and feeds
+-- (news.example.com instead of the shared base url). The domain only
+-- takes effect once ownership is proven by publishing a DNS TXT record
+-- carrying the verification token - see routes::admin::settings::domain.
+ALTER TABLE tenants ADD COLUMN link_domain TEXT;
+ALTER TABLE tenants ADD COLUMN link_domain_token TEXT;
+ALTER TABLE tenants ADD COLUMN link_domain_verified_at timestamptz;
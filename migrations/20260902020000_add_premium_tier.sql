@@ -0,0 +1,7 @@
+-- The paid tier. `premium` is flipped by the Stripe webhook, never by
+-- hand; `stripe_customer_id` is what a cancellation webhook identifies
+-- the subscriber by. Issues marked premium_only are only fanned out to
+-- paying readers at enqueue time.
+ALTER TABLE subscriptions ADD COLUMN premium boolean NOT NULL DEFAULT false;
+ALTER TABLE subscriptions ADD COLUMN stripe_customer_id TEXT;
+ALTER TABLE newsletter_issues ADD COLUMN premium_only boolean NOT NULL DEFAULT false;
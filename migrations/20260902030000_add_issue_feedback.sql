@@ -0,0 +1,12 @@
+-- one-click feedback scores, one row per (issue, subscriber) - a second
+-- click overwrites the first, so the latest opinion wins
+CREATE TABLE issue_feedback (
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id)
+        ON DELETE CASCADE,
+    score smallint NOT NULL CHECK (score BETWEEN 1 AND 5),
+    received_at timestamptz NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_id)
+);
@@ -0,0 +1,31 @@
+-- a simple one-question poll attached to an issue, with its options and
+-- one vote per subscriber (the latest click wins, as with issue_feedback)
+CREATE TABLE issue_polls (
+    id uuid PRIMARY KEY,
+    newsletter_issue_id uuid NOT NULL UNIQUE
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    question TEXT NOT NULL
+);
+
+CREATE TABLE poll_options (
+    id uuid PRIMARY KEY,
+    poll_id uuid NOT NULL
+        REFERENCES issue_polls (id)
+        ON DELETE CASCADE,
+    position smallint NOT NULL,
+    label TEXT NOT NULL
+);
+
+CREATE TABLE poll_votes (
+    poll_id uuid NOT NULL
+        REFERENCES issue_polls (id)
+        ON DELETE CASCADE,
+    option_id uuid NOT NULL
+        REFERENCES poll_options (id)
+        ON DELETE CASCADE,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id)
+        ON DELETE CASCADE,
+    received_at timestamptz NOT NULL,
+    PRIMARY KEY (poll_id, subscriber_id)
+);
@@ -0,0 +1,4 @@
+-- permanently rejected sends used to vanish with only a log line - record
+-- them in the delivery log instead, flagged, so reports can count them
+ALTER TABLE email_delivery_log
+    ADD COLUMN failed boolean NOT NULL DEFAULT false;
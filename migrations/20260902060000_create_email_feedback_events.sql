@@ -0,0 +1,12 @@
+-- bounce and spam-complaint notifications from the email provider's
+-- webhook - the raw material for the deliverability dashboard
+CREATE TABLE email_feedback_events (
+    id uuid PRIMARY KEY,
+    event_type TEXT NOT NULL
+        CHECK (event_type IN ('bounce', 'complaint')),
+    recipient_email TEXT NOT NULL,
+    occurred_at timestamptz NOT NULL
+);
+
+CREATE INDEX idx_email_feedback_events_occurred
+    ON email_feedback_events (occurred_at);
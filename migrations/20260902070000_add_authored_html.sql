@@ -0,0 +1,5 @@
+-- html_content now holds what actually goes out (css inlined for email
+-- clients - see crate::premailer); the authored original is kept beside
+-- it. NULL for issues published before the premailer step existed.
+ALTER TABLE newsletter_issues
+    ADD COLUMN authored_html_content TEXT;
@@ -0,0 +1,10 @@
+-- operator-editable email copy, one row per (tenant, template). No row
+-- means the built-in copy is used - see crate::email_templates.
+CREATE TABLE email_templates (
+    tenant_id uuid NOT NULL REFERENCES tenants (id),
+    template TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    html_body TEXT NOT NULL,
+    text_body TEXT NOT NULL,
+    PRIMARY KEY (tenant_id, template)
+);
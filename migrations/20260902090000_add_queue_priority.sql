@@ -0,0 +1,9 @@
+-- Higher-priority tasks (transactional outbox messages, small urgent
+-- sends) jump ahead of a large bulk issue. Everything enqueued so far is
+-- ordinary bulk work, hence the default of 0.
+ALTER TABLE issue_delivery_queue
+    ADD COLUMN priority smallint NOT NULL DEFAULT 0;
+
+-- the dequeue order: highest priority first, oldest task first within it
+CREATE INDEX idx_issue_delivery_queue_dequeue
+    ON issue_delivery_queue (priority DESC, available_at ASC);
@@ -0,0 +1,12 @@
+-- A single row recording whether outbound delivery is paused - the big
+-- red button for incidents (a bad issue, a compromised provider). The
+-- delivery worker checks it on every pass.
+CREATE TABLE delivery_state(
+    -- a bool primary key with a CHECK pins the table to exactly one row
+    singleton bool NOT NULL DEFAULT TRUE,
+    PRIMARY KEY (singleton),
+    CHECK (singleton),
+    paused boolean NOT NULL,
+    paused_at timestamptz
+);
+INSERT INTO delivery_state (paused) VALUES (false);
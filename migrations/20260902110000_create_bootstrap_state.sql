@@ -0,0 +1,9 @@
+-- Records that the first-run bootstrap (see crate::bootstrap) has
+-- happened - its presence makes every later bootstrap attempt a no-op.
+CREATE TABLE bootstrap_state(
+    -- a bool primary key with a CHECK pins the table to exactly one row
+    singleton bool NOT NULL DEFAULT TRUE,
+    PRIMARY KEY (singleton),
+    CHECK (singleton),
+    completed_at timestamptz NOT NULL
+);
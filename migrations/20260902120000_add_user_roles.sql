@@ -0,0 +1,6 @@
+-- Two roles for now: owners hold the operational levers (settings,
+-- delivery controls, deliverability, imports, diagnostics), editors
+-- write and send content. Every existing user keeps full access.
+ALTER TABLE users
+    ADD COLUMN role TEXT NOT NULL DEFAULT 'owner'
+    CHECK (role IN ('owner', 'editor'));
@@ -0,0 +1,9 @@
+-- Who impersonated whom, and when - support sessions leave a trail.
+-- ended_at stays NULL while the impersonation is still running.
+CREATE TABLE impersonation_log (
+    id uuid PRIMARY KEY,
+    impersonator_user_id uuid NOT NULL REFERENCES users (user_id),
+    target_user_id uuid NOT NULL REFERENCES users (user_id),
+    started_at timestamptz NOT NULL,
+    ended_at timestamptz
+);
@@ -0,0 +1,4 @@
+-- Offboarding: a deactivated user can neither log in nor act on a
+-- session that survived the revocation sweep.
+ALTER TABLE users
+    ADD COLUMN active boolean NOT NULL DEFAULT true;
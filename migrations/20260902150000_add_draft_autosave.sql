@@ -0,0 +1,8 @@
+-- Autosaved editor content, pushed by PUT .../autosave on an interval.
+-- Kept apart from the real columns so a browser crash can offer
+-- "restore" instead of silently overwriting the last explicit save.
+ALTER TABLE newsletter_drafts
+    ADD COLUMN autosave_title TEXT,
+    ADD COLUMN autosave_text_content TEXT,
+    ADD COLUMN autosave_html_content TEXT,
+    ADD COLUMN autosaved_at timestamptz;
@@ -0,0 +1,7 @@
+-- Soft deletes: "deleting" a subscriber or a draft stamps deleted_at
+-- instead of dropping the row, so /admin/trash can restore it during
+-- the retention period. Every live-path query filters on
+-- deleted_at IS NULL - a trashed row behaves exactly like a missing one
+-- until it is restored or purged.
+ALTER TABLE subscriptions ADD COLUMN deleted_at timestamptz;
+ALTER TABLE newsletter_drafts ADD COLUMN deleted_at timestamptz;
@@ -0,0 +1,20 @@
+-- Saved audience segments - a named bundle of subscriber filters. The
+-- definition lives in plain columns (one per supported filter) and is
+-- compiled to SQL at query time by crate::domain::SegmentDefinition;
+-- membership is always computed live, never materialised.
+CREATE TABLE segments(
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    name TEXT NOT NULL UNIQUE,
+    -- NULL means "any status"
+    status TEXT,
+    -- every listed tag must be present; empty means "any tags"
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    -- opened or clicked one of the most recent N issues; NULL means
+    -- "any engagement"
+    engaged_within_issues integer,
+    -- signup date bounds, either side optional
+    signed_up_after date,
+    signed_up_before date,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
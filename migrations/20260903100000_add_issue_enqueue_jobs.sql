@@ -0,0 +1,26 @@
+-- Very large lists are not enqueued inside the publish transaction - the
+-- single INSERT...SELECT would hold it open for the whole subscriber scan.
+-- Publishing such an issue records a job here instead; the delivery worker
+-- fills issue_delivery_queue in chunks, moving last_email forward with each
+-- committed chunk, so an interrupted fill resumes at the checkpoint rather
+-- than rescanning from the start.
+CREATE TABLE issue_enqueue_jobs (
+    newsletter_issue_id uuid NOT NULL PRIMARY KEY
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    tenant_id uuid NOT NULL REFERENCES tenants (id),
+    premium_only boolean NOT NULL,
+    -- soft-launch settings, carried over from the publish form
+    canary_percent smallint,
+    canary_delay_minutes integer,
+    -- a snapshot of the picked segment's definition (one column per field
+    -- of domain::SegmentDefinition) - a snapshot, so deleting the segment
+    -- mid-fill cannot change the issue's audience half-way through
+    segment_status TEXT,
+    segment_tags TEXT[],
+    segment_engaged_within_issues integer,
+    segment_signed_up_after date,
+    segment_signed_up_before date,
+    -- the keyset checkpoint: every matching email <= this is already queued
+    last_email TEXT NOT NULL DEFAULT '',
+    created_at timestamptz NOT NULL DEFAULT now()
+);
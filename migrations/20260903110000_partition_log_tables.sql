@@ -0,0 +1,49 @@
+-- The delivery log and the tracking events grow with every send - after a
+-- year of issues the two append-only tables dominate the database. Both
+-- are rebuilt as range-partitioned tables, one partition per month; the
+-- delivery worker's maintenance pass (see src/log_maintenance.rs) creates
+-- partitions ahead of time and drops whole months past the configured
+-- retention, so pruning is a metadata operation instead of a giant DELETE.
+
+ALTER TABLE email_delivery_log RENAME TO email_delivery_log_old;
+CREATE TABLE email_delivery_log (
+    id uuid NOT NULL,
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    recipient_email TEXT NOT NULL,
+    provider_message_id TEXT,
+    sent_at timestamptz NOT NULL,
+    failed boolean NOT NULL DEFAULT false,
+    -- the partition key has to be part of the primary key
+    PRIMARY KEY (id, sent_at)
+) PARTITION BY RANGE (sent_at);
+-- rows from before the partitioning - and from any month whose partition
+-- has not been created yet - land here instead of erroring
+CREATE TABLE email_delivery_log_default PARTITION OF email_delivery_log DEFAULT;
+INSERT INTO email_delivery_log
+    SELECT id, newsletter_issue_id, recipient_email, provider_message_id,
+        sent_at, failed
+    FROM email_delivery_log_old;
+DROP TABLE email_delivery_log_old;
+
+ALTER TABLE email_tracking_events RENAME TO email_tracking_events_old;
+CREATE TABLE email_tracking_events (
+    id uuid NOT NULL,
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    event_type TEXT NOT NULL
+        CHECK (event_type IN ('open', 'click')),
+    occurred_at timestamptz NOT NULL,
+    PRIMARY KEY (id, occurred_at)
+) PARTITION BY RANGE (occurred_at);
+CREATE TABLE email_tracking_events_default PARTITION OF email_tracking_events DEFAULT;
+INSERT INTO email_tracking_events
+    SELECT id, newsletter_issue_id, subscriber_id, event_type, occurred_at
+    FROM email_tracking_events_old;
+DROP TABLE email_tracking_events_old;
+-- recreated only after the old table (which still owns this index name)
+-- is gone
+CREATE INDEX idx_email_tracking_events_subscriber
+    ON email_tracking_events (subscriber_id);
@@ -0,0 +1,4 @@
+-- whether outgoing issues get the dark-mode style variant appended at
+-- publish time - off by default, flipped at /admin/settings
+ALTER TABLE site_settings
+    ADD COLUMN dark_mode_emails BOOLEAN NOT NULL DEFAULT FALSE;
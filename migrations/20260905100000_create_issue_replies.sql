@@ -0,0 +1,17 @@
+-- Replies readers send to newsletter issues, captured by the provider's
+-- inbound webhook (see routes::inbound_email). The issue and subscriber
+-- columns stay NULL when the reply could not be matched - an unmatched
+-- reply is still worth keeping. SET NULL on delete, so purging a
+-- subscriber (or an issue) never trips over their replies.
+CREATE TABLE issue_replies (
+    id uuid PRIMARY KEY,
+    newsletter_issue_id uuid
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE SET NULL,
+    subscriber_id uuid REFERENCES subscriptions (id) ON DELETE SET NULL,
+    from_email TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    -- the provider's stripped reply when it could isolate one, otherwise
+    -- the full text body
+    body TEXT NOT NULL,
+    received_at timestamptz NOT NULL
+);
@@ -0,0 +1,4 @@
+-- Whether the inbound webhook auto-acknowledged this reply - the
+-- per-sender rate limit reads it back (see routes::inbound_email).
+ALTER TABLE issue_replies
+    ADD COLUMN acknowledged BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,11 @@
+-- Free-form notes admins attach to a subscriber (see
+-- routes::admin::subscriber_detail). CASCADE, so purging a subscriber
+-- from the trash takes their notes along.
+CREATE TABLE subscriber_notes (
+    id uuid PRIMARY KEY,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    author_user_id uuid NOT NULL REFERENCES users (user_id),
+    note TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
@@ -0,0 +1,9 @@
+-- Postmark redelivers webhook events it believes went unacknowledged -
+-- keeping the provider's own event id lets a redelivery be recognised
+-- and dropped instead of double-counting the bounce. NULL for events
+-- recorded before this column existed (or payloads without an ID).
+ALTER TABLE email_feedback_events ADD COLUMN provider_event_id BIGINT;
+
+CREATE UNIQUE INDEX email_feedback_events_provider_event_id_idx
+    ON email_feedback_events (provider_event_id)
+    WHERE provider_event_id IS NOT NULL;
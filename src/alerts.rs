@@ -0,0 +1,138 @@
+//! One place for "wake the operator up" notifications: the stuck-worker
+//! monitor, repeated login failures, and anything else that counts as
+//! critical funnel through an [`Alerter`], which fans out to the configured
+//! admin email addresses and/or a Slack-compatible webhook. Every alert
+//! carries a key, and repeats of the same key inside the de-duplication
+//! window are dropped - one incident, one notification.
+
+use crate::configuration::AlertSettings;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// how many failed logins inside the window before raising the alarm -
+// a fat-fingered password twice is not an incident, a dictionary run is
+const LOGIN_FAILURE_THRESHOLD: usize = 5;
+const LOGIN_FAILURE_WINDOW_SECONDS: i64 = 300;
+
+pub struct Alerter {
+    email_client: Arc<EmailClient>,
+    recipients: Vec<SubscriberEmail>,
+    slack_webhook_url: Option<String>,
+    http_client: reqwest::Client,
+    dedup_window: chrono::Duration,
+    // alert key -> when it last actually went out
+    recently_sent: Mutex<HashMap<String, DateTime<Utc>>>,
+    // rolling log of failed admin logins, pruned to the window
+    login_failures: Mutex<Vec<DateTime<Utc>>>,
+}
+
+impl Alerter {
+    /// Parse the configured addresses up front - a typo in an alert
+    /// address should fail the deploy, not the first incident.
+    pub fn new(
+        settings: &AlertSettings,
+        email_client: Arc<EmailClient>,
+    ) -> Result<Self, anyhow::Error> {
+        let recipients = settings
+            .admin_emails
+            .iter()
+            .cloned()
+            .map(SubscriberEmail::parse)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("Invalid alerts.admin_emails entry: {}", e))?;
+
+        Ok(Self {
+            email_client,
+            recipients,
+            slack_webhook_url: settings.slack_webhook_url.clone(),
+            http_client: reqwest::Client::new(),
+            dedup_window: chrono::Duration::seconds(settings.dedup_window_seconds as i64),
+            recently_sent: Mutex::new(HashMap::new()),
+            login_failures: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Fan an alert out to every configured channel. `key` names the
+    /// condition (not the occurrence) - the same key inside the dedup
+    /// window is silently swallowed.
+    pub async fn notify(&self, key: &str, subject: &str, body: &str, now: DateTime<Utc>) {
+        {
+            let mut recent = self.recently_sent.lock().unwrap();
+            if let Some(last_sent) = recent.get(key) {
+                if now - *last_sent < self.dedup_window {
+                    tracing::debug!(alert_key = key, "Suppressing a duplicate alert");
+                    return;
+                }
+            }
+            recent.insert(key.to_string(), now);
+        }
+
+        tracing::warn!(alert_key = key, alert_body = body, "Raising an operator alert");
+
+        let html_body = format!("<p>{}</p>", htmlescape::encode_minimal(body));
+        for recipient in &self.recipients {
+            // one bad channel shouldn't silence the others - log and move on
+            if let Err(e) = self
+                .email_client
+                .send_email(recipient, subject, &html_body, body)
+                .await
+            {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to deliver an alert email",
+                );
+            }
+        }
+
+        if let Some(url) = &self.slack_webhook_url {
+            // the minimal incoming-webhook payload - works for Slack,
+            // Mattermost and friends
+            let payload = serde_json::json!({ "text": format!("*{}*\n{}", subject, body) });
+            let outcome = self
+                .http_client
+                .post(url)
+                .json(&payload)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = outcome {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to post an alert to the webhook",
+                );
+            }
+        }
+    }
+
+    /// Called on every rejected login. Once the rolling count crosses the
+    /// threshold an alert goes out (de-duplicated like any other).
+    pub async fn record_login_failure(&self, now: DateTime<Utc>) {
+        let threshold_crossed = {
+            let mut failures = self.login_failures.lock().unwrap();
+            failures
+                .retain(|at| now - *at < chrono::Duration::seconds(LOGIN_FAILURE_WINDOW_SECONDS));
+            failures.push(now);
+            failures.len() >= LOGIN_FAILURE_THRESHOLD
+        };
+
+        if threshold_crossed {
+            self.notify(
+                "login-failures",
+                "Repeated failed admin logins",
+                &format!(
+                    "{} or more failed admin login attempts in the last {} minutes - \
+                    possibly a credential-stuffing run.",
+                    LOGIN_FAILURE_THRESHOLD,
+                    LOGIN_FAILURE_WINDOW_SECONDS / 60
+                ),
+                now,
+            )
+            .await;
+        }
+    }
+}
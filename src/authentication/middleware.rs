@@ -3,9 +3,11 @@ use crate::utils::{e500, see_other};
 use actix_web::body::MessageBody;
 use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::error::InternalError;
+use actix_web::http::header::ContentType;
 use actix_web::middleware::Next;
 use actix_web::FromRequest;
-use actix_web::HttpMessage;
+use actix_web::{HttpMessage, HttpResponse};
+use sqlx::PgPool;
 use std::ops::Deref;
 use uuid::Uuid;
 
@@ -25,6 +27,109 @@ impl Deref for UserId {
     }
 }
 
+/// Present on a request whose session carries an impersonation claim -
+/// the `UserId` extension is then the target's, and this records who is
+/// really behind the keyboard (for the banner and the audit trail).
+#[derive(Copy, Clone, Debug)]
+pub struct Impersonation {
+    pub impersonator_id: Uuid,
+}
+
+/// What a logged-in user may do. Editors write and send content; owners
+/// additionally hold the operational levers - settings, delivery
+/// controls, deliverability, imports, diagnostics.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Role {
+    Owner,
+    Editor,
+}
+
+// the /admin corners only owners may enter - everything else is open to
+// any logged-in user
+const OWNER_ONLY_PREFIXES: &[&str] = &[
+    "/admin/settings",
+    "/admin/delivery",
+    "/admin/deliverability",
+    "/admin/diagnostics",
+    "/admin/subscribers/import",
+    "/admin/users",
+    // purging from the trash is the one delete there is no way back from
+    "/admin/trash",
+];
+
+/// The user's role, read fresh from the database - a demotion takes
+/// effect on the next request, not the next login.
+pub async fn get_user_role(pool: &PgPool, user_id: Uuid) -> Result<Role, anyhow::Error> {
+    let row = sqlx::query!("SELECT role FROM users WHERE user_id = $1", user_id)
+        .fetch_one(pool)
+        .await?;
+    // the CHECK constraint keeps other values out, but default to the
+    // restrictive interpretation anyway should one slip in
+    Ok(match row.role.as_str() {
+        "owner" => Role::Owner,
+        _ => Role::Editor,
+    })
+}
+
+// whether the account is still allowed to do anything at all - a user
+// that no longer exists counts as inactive
+async fn user_is_active(pool: &PgPool, user_id: Uuid) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!("SELECT active FROM users WHERE user_id = $1", user_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.is_some_and(|r| r.active))
+}
+
+// the friendly "you may not" page - a named response, not a redirect
+// loop back to /login (the user IS logged in) and not an opaque 500
+fn forbidden_page() -> HttpResponse {
+    HttpResponse::Forbidden()
+        .content_type(ContentType::html())
+        .body(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Not allowed</title>
+</head>
+<body>
+    <h1>Not allowed</h1>
+    <p>Your account doesn't have access to this page. If you think it
+    should, ask the newsletter's owner to change your role.</p>
+    <p><a href="/admin/dashboard">&lt;- Back to the dashboard</a></p>
+</body>
+</html>"#,
+        )
+}
+
+/// The authorization layer on the /admin scope - runs after
+/// `reject_anonymous_users`, so the user id extension is always there.
+pub async fn enforce_role_permissions(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let owner_only = OWNER_ONLY_PREFIXES
+        .iter()
+        .any(|prefix| req.path().starts_with(prefix));
+    if owner_only {
+        let user_id = req
+            .extensions()
+            .get::<UserId>()
+            .copied()
+            .ok_or_else(|| e500(anyhow::anyhow!("Missing the user id extension")))?;
+        let pool = req
+            .app_data::<actix_web::web::Data<PgPool>>()
+            .ok_or_else(|| e500(anyhow::anyhow!("Missing database pool in app data")))?
+            .clone();
+        if get_user_role(&pool, *user_id).await.map_err(e500)? != Role::Owner {
+            let response = forbidden_page();
+            let e = anyhow::anyhow!("The user's role does not allow this route");
+            return Err(InternalError::from_response(e, response).into());
+        }
+    }
+    next.call(req).await
+}
+
 pub async fn reject_anonymous_users(
     mut req: ServiceRequest,
     next: Next<impl MessageBody>,
@@ -41,8 +146,64 @@ pub async fn reject_anonymous_users(
     match session.get_user_id().map_err(e500)? {
         // if so, invoke the session handler
         Some(user_id) => {
+            // a cookie can outlive its welcome: sessions evicted by the
+            // concurrent-session cap or revoked by a password change lose
+            // their registry row, so check it before letting them through
+            // (sessions from before the registry existed carry no id and
+            // pass - they die with their natural cookie expiry)
+            if let Some(session_id) = session.get_session_id().map_err(e500)? {
+                let pool = req
+                    .app_data::<actix_web::web::Data<sqlx::PgPool>>()
+                    .ok_or_else(|| e500(anyhow::anyhow!("Missing database pool in app data")))?
+                    .clone();
+                if !crate::session_state::session_is_active(&pool, session_id)
+                    .await
+                    .map_err(e500)?
+                {
+                    session.log_out();
+                    let response = see_other("/login");
+                    let e = anyhow::anyhow!("The session has been revoked");
+                    return Err(InternalError::from_response(e, response).into());
+                }
+            }
+            // a deactivated account cannot act, live cookie or not - the
+            // deactivation revokes its sessions too, but a request racing
+            // that sweep shouldn't slip through
+            {
+                let pool = req
+                    .app_data::<actix_web::web::Data<sqlx::PgPool>>()
+                    .ok_or_else(|| e500(anyhow::anyhow!("Missing database pool in app data")))?
+                    .clone();
+                if !user_is_active(&pool, user_id).await.map_err(e500)? {
+                    session.log_out();
+                    let response = see_other("/login");
+                    let e = anyhow::anyhow!("The account has been deactivated");
+                    return Err(InternalError::from_response(e, response).into());
+                }
+            }
+
+            // a super-admin may carry an impersonation claim - requests
+            // then run as the target user (that's the whole point: seeing
+            // what they see), while the session registry row stays the
+            // impersonator's own. The claim only holds as long as the
+            // impersonator is still an owner - a demotion voids it
+            let mut effective_id = user_id;
+            if let Some(target_id) = session.get_impersonated_user_id().map_err(e500)? {
+                let pool = req
+                    .app_data::<actix_web::web::Data<sqlx::PgPool>>()
+                    .ok_or_else(|| e500(anyhow::anyhow!("Missing database pool in app data")))?
+                    .clone();
+                if get_user_role(&pool, user_id).await.map_err(e500)? == Role::Owner {
+                    req.extensions_mut().insert(Impersonation {
+                        impersonator_id: user_id,
+                    });
+                    effective_id = target_id;
+                } else {
+                    session.stop_impersonating();
+                }
+            }
             // add the user id to the request via an 'extension'
-            req.extensions_mut().insert(UserId(user_id));
+            req.extensions_mut().insert(UserId(effective_id));
             next.call(req).await
         }
         // if no session redirect to login
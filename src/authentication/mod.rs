@@ -1,5 +1,12 @@
 mod middleware;
 mod password;
+mod password_policy;
 pub use middleware::reject_anonymous_users;
 pub use middleware::UserId;
-pub use password::{change_password, validate_credentials, AuthError, Credentials};
+pub use middleware::{enforce_role_permissions, get_user_role, Impersonation, Role};
+pub use password::{
+    change_password, constant_time_eq, spend_dummy_verification, validate_credentials, AuthError,
+    Credentials,
+};
+pub(crate) use password::compute_password_hash;
+pub use password_policy::validate_password_policy;
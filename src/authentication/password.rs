@@ -1,3 +1,4 @@
+use crate::configuration::PasswordHashSettings;
 use crate::telemetry::spawn_blocking_with_tracing;
 use anyhow::Context;
 use argon2::password_hash::SaltString;
@@ -18,10 +19,42 @@ pub struct Credentials {
     pub password: Secret<String>,
 }
 
-#[tracing::instrument(name = "Validate credentials", skip(credentials, pool))]
+// the hash of some password nobody knows, with the same parameters real
+// hashes use - every "no such user" path verifies against this so it
+// costs the same as a genuine failed login
+const DUMMY_PASSWORD_HASH: &str = "$argon2id$v=19$m=15000,t=2,p=1$\
+gZiV/M1gPc22ElAH/Jh1Hw$\
+CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno";
+
+/// Burn the CPU a real password verification would have cost. Any auth
+/// surface that can fail fast on a lookup (unknown API key, expired
+/// reset token, ...) should call this on that path, so an attacker can't
+/// tell "no such account" from "wrong secret" by the clock.
+pub async fn spend_dummy_verification() -> Result<(), anyhow::Error> {
+    spawn_blocking_with_tracing(|| {
+        let _ = verify_password_hash(
+            Secret::new(DUMMY_PASSWORD_HASH.to_string()),
+            Secret::new("definitely-not-the-password".to_string()),
+            None,
+        );
+    })
+    .await
+    .context("Failed to spawn blocking task.")
+}
+
+/// Compare two secrets without leaking where they first differ. Hashing
+/// both sides first makes the comparison independent of both content and
+/// length - a plain `==` on the raw bytes bails at the first mismatch.
+pub fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(left) == Sha256::digest(right)
+}
+
+#[tracing::instrument(name = "Validate credentials", skip(credentials, pool, hashing))]
 pub async fn validate_credentials(
     credentials: Credentials,
     pool: &PgPool,
+    hashing: &PasswordHashSettings,
 ) -> Result<uuid::Uuid, AuthError> {
     // first we generate some fake, invalid credentials.
     // if we match real ones in the db, these will be overwritten
@@ -30,12 +63,7 @@ pub async fn validate_credentials(
     // differences between valid and invalid credentials -which could be used
     // to find valid user_id's
     let mut user_id = None;
-    let mut expected_password_hash = Secret::new(
-        "$argon2id$v=19$m=15000,t=2,p=1$\
-gZiV/M1gPc22ElAH/Jh1Hw$\
-CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
-            .to_string(),
-    );
+    let mut expected_password_hash = Secret::new(DUMMY_PASSWORD_HASH.to_string());
 
     // retrieve the row for this user ID and overwite user_id and expected_pw_hash
     // if they are valid
@@ -50,13 +78,28 @@ CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
     // we do this inside a seperate thread - as it's a slow, CPU intensive
     // process that would otherwise block other async processes
 
+    // peppered hashes carry a `v<n>$` prefix naming the pepper they were
+    // made with - resolve it before handing off to the verifier
+    let (pepper_version, phc) = split_pepper_version(expected_password_hash.expose_secret());
+    let pepper = match pepper_version {
+        None => None,
+        Some(version) => Some(pepper_for_version(hashing, version).ok_or_else(|| {
+            anyhow::anyhow!("No pepper configured for pepper key version {}", version)
+        })?),
+    };
+    let phc = Secret::new(phc.to_string());
+
     // add the new thread's span to the current span
     let current_span = tracing::Span::current();
+    // the password and hash get moved into the closure - keep handles for
+    // the re-hash check below
+    let password = credentials.password.clone();
+    let stored_hash = expected_password_hash;
     // this fn defined in 'telemetry'
     spawn_blocking_with_tracing(move || {
         current_span.in_scope(|| {
             // add this to the current span
-            verify_password_hash(expected_password_hash, credentials.password)
+            verify_password_hash(phc, credentials.password, pepper)
         })
     })
     .await
@@ -64,27 +107,147 @@ CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
 
     // ok_or_else checks it's wrapped in Some
     // it's only Some() if it was found in the db
-    user_id
+    let user_id = user_id
         .ok_or_else(|| anyhow::anyhow!("Unknon username."))
-        .map_err(AuthError::InvalidCredentials)
+        .map_err(AuthError::InvalidCredentials)?;
+
+    // transparent migration: if the stored hash was made under different
+    // settings (older parameters, or the Argon2d this code once used by
+    // mistake), replace it now - the only time we hold the plaintext and
+    // know it's correct. A failure here mustn't block the login.
+    if hash_is_outdated(&stored_hash, hashing) {
+        let hashing = hashing.clone();
+        let outcome = spawn_blocking_with_tracing(move || {
+            compute_password_hash(password, &hashing)
+        })
+        .await
+        .context("Failed to spawn blocking task.")?;
+        match outcome {
+            Ok(new_hash) => {
+                if let Err(e) = sqlx::query!(
+                    "UPDATE users SET password_hash = $1 WHERE user_id = $2",
+                    new_hash.expose_secret(),
+                    user_id
+                )
+                .execute(pool)
+                .await
+                {
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        "Failed to store the re-hashed password"
+                    );
+                } else {
+                    tracing::info!("Re-hashed a password to the current parameters");
+                }
+            }
+            Err(e) => {
+                tracing::warn!(error.cause_chain = ?e, "Failed to re-hash a password");
+            }
+        }
+    }
+
+    Ok(user_id)
+}
+
+// whether a stored hash matches the currently configured algorithm,
+// parameters and pepper - unparseable input is left alone (verification
+// will have rejected it anyway)
+fn hash_is_outdated(stored: &Secret<String>, settings: &PasswordHashSettings) -> bool {
+    let (pepper_version, phc) = split_pepper_version(stored.expose_secret());
+    // a hash made without the current pepper (or under a retired one)
+    // should be replaced as soon as we can prove the password
+    let current_pepper_version = settings.pepper.as_ref().map(|_| settings.pepper_version);
+    if pepper_version != current_pepper_version {
+        return true;
+    }
+    let Ok(parsed) = PasswordHash::new(phc) else {
+        return false;
+    };
+    let Ok(algorithm) = configured_algorithm(settings) else {
+        return false;
+    };
+    if parsed.algorithm != algorithm.ident() {
+        return true;
+    }
+    let Ok(params) = Params::try_from(&parsed) else {
+        return true;
+    };
+    params.m_cost() != settings.memory_kib
+        || params.t_cost() != settings.iterations
+        || params.p_cost() != settings.parallelism
+}
+
+// `v2$argon2id$...` -> `(Some(2), "$argon2id$...")`; a bare PHC string
+// (which always starts with `$`) passes through untouched
+fn split_pepper_version(stored: &str) -> (Option<u32>, &str) {
+    if let Some(rest) = stored.strip_prefix('v') {
+        if let Some(separator) = rest.find('$') {
+            if let Ok(version) = rest[..separator].parse::<u32>() {
+                return (Some(version), &rest[separator..]);
+            }
+        }
+    }
+    (None, stored)
+}
+
+// the pepper a given key version refers to - the current one, or a
+// retired one still configured for the rotation grace period
+fn pepper_for_version(settings: &PasswordHashSettings, version: u32) -> Option<Secret<String>> {
+    if version == settings.pepper_version {
+        if let Some(pepper) = &settings.pepper {
+            return Some(pepper.clone());
+        }
+    }
+    settings
+        .previous_peppers
+        .iter()
+        .find(|p| p.version == version)
+        .map(|p| p.secret.clone())
+}
+
+fn configured_algorithm(settings: &PasswordHashSettings) -> Result<Algorithm, anyhow::Error> {
+    match settings.algorithm.as_str() {
+        "argon2id" => Ok(Algorithm::Argon2id),
+        "argon2i" => Ok(Algorithm::Argon2i),
+        "argon2d" => Ok(Algorithm::Argon2d),
+        other => Err(anyhow::anyhow!(
+            "Unknown password hashing algorithm '{}': expected argon2id, argon2i or argon2d",
+            other
+        )),
+    }
 }
 
 #[tracing::instrument(
     name = "Verify password hash",
-    skip(expected_password_hash, password_candidate)
+    skip(expected_password_hash, password_candidate, pepper)
 )]
 fn verify_password_hash(
     expected_password_hash: Secret<String>,
     password_candidate: Secret<String>,
+    pepper: Option<Secret<String>>,
 ) -> Result<(), AuthError> {
     // we store passwords as 'PHC' format - which contains the hashed password,
     // the 'SALT', the parameters, and the algorithm used to hash the password
     let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
         .context("Failed to parse hash in PHC string format.")?;
 
+    // verification re-derives the algorithm and parameters from the PHC
+    // string itself - only the pepper (Argon2's keyed-hashing secret) has
+    // to come from us
+    let argon2 = match &pepper {
+        Some(pepper) => Argon2::new_with_secret(
+            pepper.expose_secret().as_bytes(),
+            Algorithm::default(),
+            Version::default(),
+            Params::default(),
+        )
+        .context("Failed to build a peppered Argon2 context.")?,
+        None => Argon2::default(),
+    };
+
     // simple to verify, as all the required info is stored
     // in the password PHC from the db
-    Argon2::default()
+    argon2
         .verify_password(
             password_candidate.expose_secret().as_bytes(),
             &expected_password_hash,
@@ -98,12 +261,13 @@ async fn get_stored_credentials(
     username: &str,
     pool: &PgPool,
 ) -> Result<Option<(uuid::Uuid, Secret<String>)>, anyhow::Error> {
-    // get row from the user_id db
+    // get row from the user_id db - a deactivated account is treated
+    // exactly like a nonexistent one, dummy-hash timing defence included
     let row = sqlx::query!(
         r#"
         SELECT user_id, password_hash
         FROM users
-        WHERE username = $1
+        WHERE username = $1 AND active
         "#,
         username,
     )
@@ -114,15 +278,18 @@ async fn get_stored_credentials(
     Ok(row)
 }
 
-#[tracing::instrument(name = "Change password", skip(password, pool))]
+#[tracing::instrument(name = "Change password", skip(password, pool, hashing))]
 pub async fn change_password(
     user_id: uuid::Uuid,
     password: Secret<String>,
     pool: &PgPool,
+    hashing: &PasswordHashSettings,
 ) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await?
-        .context("Failed to hash password")?;
+    let hashing = hashing.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &hashing))
+            .await?
+            .context("Failed to hash password")?;
 
     sqlx::query!(
         r#"
@@ -140,18 +307,88 @@ pub async fn change_password(
     Ok(())
 }
 
-fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
+pub(crate) fn compute_password_hash(
+    password: Secret<String>,
+    settings: &PasswordHashSettings,
+) -> Result<Secret<String>, anyhow::Error> {
     // get a new 'salt' to append to the password
     let salt = SaltString::generate(&mut rand::thread_rng());
 
+    let algorithm = configured_algorithm(settings)?;
+    let params =
+        Params::new(settings.memory_kib, settings.iterations, settings.parallelism, None)
+            .map_err(|e| anyhow::anyhow!("Invalid Argon2 parameters: {}", e))?;
+
     // make the encrypted password with salt
-    let password_hash = Argon2::new(
-        Algorithm::Argon2d,
-        Version::V0x13,
-        Params::new(15000, 2, 1, None).unwrap(),
-    )
-    .hash_password(password.expose_secret().as_bytes(), &salt)?
-    .to_string();
+    // (algorithm and costs come from configuration now - this used to
+    // hard-code Argon2d, which verification only accepted because the
+    // argon2 crate reads the algorithm back out of the PHC string)
+    let argon2 = match &settings.pepper {
+        // keyed hashing - the pepper never appears in the stored string
+        Some(pepper) => Argon2::new_with_secret(
+            pepper.expose_secret().as_bytes(),
+            algorithm,
+            Version::V0x13,
+            params,
+        )
+        .map_err(|e| anyhow::anyhow!("Invalid pepper: {}", e))?,
+        None => Argon2::new(algorithm, Version::V0x13, params),
+    };
+    let password_hash = argon2
+        .hash_password(password.expose_secret().as_bytes(), &salt)?
+        .to_string();
+
+    // a peppered hash is prefixed with the pepper's key version, so
+    // verification knows which pepper to reach for after a rotation
+    let password_hash = match &settings.pepper {
+        Some(_) => format!("v{}{}", settings.pepper_version, password_hash),
+        None => password_hash,
+    };
 
     Ok(Secret::new(password_hash))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        compute_password_hash, constant_time_eq, verify_password_hash, DUMMY_PASSWORD_HASH,
+    };
+    use crate::configuration::PasswordHashSettings;
+    use secrecy::{ExposeSecret, Secret};
+
+    fn timed_verification(hash: &str, candidate: &str) -> std::time::Duration {
+        let start = std::time::Instant::now();
+        for _ in 0..3 {
+            let _ = verify_password_hash(
+                Secret::new(hash.to_string()),
+                Secret::new(candidate.to_string()),
+                None,
+            );
+        }
+        start.elapsed()
+    }
+
+    #[test]
+    fn the_dummy_verification_costs_as_much_as_a_real_one() {
+        let settings = PasswordHashSettings::default();
+        let hash =
+            compute_password_hash(Secret::new("correct horse battery".to_string()), &settings)
+                .unwrap();
+
+        let real = timed_verification(hash.expose_secret(), "correct horse battery");
+        let dummy = timed_verification(DUMMY_PASSWORD_HASH, "definitely-not-the-password");
+
+        // near-constant, not identical - generous bounds so a noisy CI
+        // machine doesn't flake, tight enough to catch a fast-path
+        assert!(dummy.as_secs_f64() > real.as_secs_f64() * 0.5);
+        assert!(dummy.as_secs_f64() < real.as_secs_f64() * 2.0);
+    }
+
+    #[test]
+    fn constant_time_eq_still_compares_correctly() {
+        assert!(constant_time_eq(b"the-api-key", b"the-api-key"));
+        assert!(!constant_time_eq(b"the-api-key", b"the-api-kez"));
+        assert!(!constant_time_eq(b"the-api-key", b"the-api-key-but-longer"));
+        assert!(!constant_time_eq(b"", b"the-api-key"));
+    }
+}
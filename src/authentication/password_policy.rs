@@ -0,0 +1,154 @@
+use crate::configuration::PasswordPolicySettings;
+use secrecy::{ExposeSecret, Secret};
+
+// what a rejected candidate tells the user - these end up verbatim in
+// flash messages, so keep them friendly and free of specifics about
+// what the user actually typed
+
+/// Check a candidate password against the configured policy. `Ok(())`
+/// means acceptable; `Err` carries the user-facing reason it was not.
+///
+/// The HaveIBeenPwned lookup fails open: an outage at a third party
+/// shouldn't lock admins out of changing their password. The check uses
+/// the range API, so only the first five characters of the candidate's
+/// SHA-1 hash ever leave this server.
+#[tracing::instrument(name = "Validate password policy", skip(candidate, policy))]
+pub async fn validate_password_policy(
+    candidate: &Secret<String>,
+    username: &str,
+    policy: &PasswordPolicySettings,
+) -> Result<(), String> {
+    if let Some(reason) = policy_violation(candidate, username, policy) {
+        return Err(reason);
+    }
+    if policy.check_haveibeenpwned {
+        match is_breached(candidate).await {
+            Ok(true) => {
+                return Err(
+                    "The new password has appeared in a known data breach - \
+                    please choose a different one."
+                        .to_string(),
+                )
+            }
+            Ok(false) => {}
+            Err(e) => {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    "Failed to query HaveIBeenPwned - skipping the breach check"
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+// the offline checks - split out so they can be tested without a runtime
+fn policy_violation(
+    candidate: &Secret<String>,
+    username: &str,
+    policy: &PasswordPolicySettings,
+) -> Option<String> {
+    let candidate = candidate.expose_secret();
+
+    if !(policy.min_length..=policy.max_length).contains(&candidate.len()) {
+        return Some(format!(
+            "The new password must be between {} & {} characters.",
+            policy.min_length, policy.max_length
+        ));
+    }
+
+    // lowercase, uppercase, digits, everything else
+    let mut classes = 0;
+    if candidate.chars().any(|c| c.is_lowercase()) {
+        classes += 1;
+    }
+    if candidate.chars().any(|c| c.is_uppercase()) {
+        classes += 1;
+    }
+    if candidate.chars().any(|c| c.is_ascii_digit()) {
+        classes += 1;
+    }
+    if candidate
+        .chars()
+        .any(|c| !c.is_alphanumeric())
+    {
+        classes += 1;
+    }
+    if classes < policy.required_character_classes {
+        return Some(format!(
+            "The new password must mix at least {} of: lowercase letters, \
+            uppercase letters, digits, other characters.",
+            policy.required_character_classes
+        ));
+    }
+
+    // a password containing your own username is guessable however long
+    // it is (comparison is case-insensitive for the same reason)
+    if !username.is_empty()
+        && candidate
+            .to_lowercase()
+            .contains(&username.to_lowercase())
+    {
+        return Some("The new password must not contain your username.".to_string());
+    }
+
+    None
+}
+
+// HaveIBeenPwned's range API: send the first five hex characters of the
+// SHA-1, get back every known-breached suffix sharing that prefix
+async fn is_breached(candidate: &Secret<String>) -> Result<bool, anyhow::Error> {
+    use sha1::{Digest, Sha1};
+
+    let digest = Sha1::digest(candidate.expose_secret().as_bytes());
+    let hash = hex::encode_upper(digest);
+    let (prefix, suffix) = hash.split_at(5);
+
+    let body = reqwest::Client::new()
+        .get(format!("https://api.pwnedpasswords.com/range/{}", prefix))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    // each line is `SUFFIX:COUNT` - a match on ours means breached
+    Ok(body
+        .lines()
+        .any(|line| line.split(':').next() == Some(suffix)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::policy_violation;
+    use crate::configuration::PasswordPolicySettings;
+    use secrecy::Secret;
+
+    fn check(candidate: &str, username: &str) -> Option<String> {
+        policy_violation(
+            &Secret::new(candidate.to_string()),
+            username,
+            &PasswordPolicySettings::default(),
+        )
+    }
+
+    #[test]
+    fn a_strong_password_passes() {
+        assert!(check("Correct-Horse-Battery-7", "admin").is_none());
+    }
+
+    #[test]
+    fn too_short_a_password_is_rejected() {
+        assert!(check("Ab1!", "admin").is_some());
+    }
+
+    #[test]
+    fn a_single_character_class_is_rejected() {
+        assert!(check("aaaaaaaaaaaaaaaaaaaa", "admin").is_some());
+    }
+
+    #[test]
+    fn a_password_containing_the_username_is_rejected() {
+        assert!(check("my-ADMIN-password-99", "admin").is_some());
+    }
+}
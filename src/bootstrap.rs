@@ -0,0 +1,90 @@
+//! First-run bootstrap: the operator's admin login, taken from the
+//! `APP_BOOTSTRAP_ADMIN_USERNAME` / `APP_BOOTSTRAP_ADMIN_PASSWORD`
+//! environment variables, so a fresh deployment needs no manual SQL.
+//! The migrations already seed the default site settings; bootstrap
+//! replaces the demo 'admin' credential with the operator's own (hashed
+//! with the configured Argon2 parameters). It runs exactly once per
+//! database - a marker row in `bootstrap_state` makes every later
+//! attempt a no-op, so a lingering environment variable can't silently
+//! reset the admin password months down the line.
+
+use crate::authentication::compute_password_hash;
+use crate::configuration::Settings;
+use crate::startup;
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub const USERNAME_VAR: &str = "APP_BOOTSTRAP_ADMIN_USERNAME";
+pub const PASSWORD_VAR: &str = "APP_BOOTSTRAP_ADMIN_PASSWORD";
+
+/// Create the initial admin user if the bootstrap variables are set and
+/// this database has never been bootstrapped. Entirely optional - with
+/// the variables unset this is a no-op.
+pub async fn run_once(configuration: &Settings) -> Result<(), anyhow::Error> {
+    let (username, password) = match (std::env::var(USERNAME_VAR), std::env::var(PASSWORD_VAR)) {
+        (Ok(username), Ok(password)) => (username, Secret::new(password)),
+        (Err(_), Err(_)) => return Ok(()),
+        // half a credential is a deployment mistake, not a request to skip
+        _ => anyhow::bail!(
+            "{} and {} must be set together (or not at all).",
+            USERNAME_VAR,
+            PASSWORD_VAR
+        ),
+    };
+
+    let pool = startup::get_connection_pool(&configuration.database);
+    bootstrap_admin(&pool, &username, password, configuration).await
+}
+
+async fn bootstrap_admin(
+    pool: &PgPool,
+    username: &str,
+    password: Secret<String>,
+    configuration: &Settings,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+
+    // the marker goes in first - if another process got there before us
+    // (or a previous deployment did, long ago), we refuse to re-run
+    let outcome = sqlx::query!(
+        r#"
+        INSERT INTO bootstrap_state (completed_at)
+        VALUES (now())
+        ON CONFLICT (singleton) DO NOTHING
+        "#,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to record the bootstrap marker")?;
+    if outcome.rows_affected() == 0 {
+        tracing::warn!(
+            "Ignoring {} - this database has already been bootstrapped.",
+            USERNAME_VAR
+        );
+        return Ok(());
+    }
+
+    let password_hash = compute_password_hash(password, &configuration.password_hashing)
+        .context("Failed to hash the bootstrap admin password")?;
+    // upsert rather than insert - when the chosen username is 'admin',
+    // this replaces the well-known demo credential the migrations seed
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (username) DO UPDATE SET password_hash = EXCLUDED.password_hash
+        "#,
+        Uuid::new_v4(),
+        username,
+        password_hash.expose_secret(),
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to create the bootstrap admin user")?;
+
+    transaction.commit().await?;
+    tracing::info!(username, "Bootstrapped the initial admin user.");
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::http::header::LOCATION;
+use actix_web::middleware::Next;
+use actix_web::HttpResponse;
+
+// Requests reaching the app on the wrong host (a raw IP, a www. alias, a
+// stale DNS name) or on plain http get a permanent redirect to the
+// canonical base URL, so confirmation links, the archive and anything a
+// crawler indexes all live under exactly one origin.
+//
+// Enforcement is tied to `application.behind_proxy`: the incoming
+// scheme/host come from the proxy's forwarded headers, which are only
+// meaningful (and only trustworthy) when a proxy we control sets them.
+// Local runs and the test suite, which talk to the app directly on
+// whatever port it bound, are left alone.
+
+/// Where requests are supposed to arrive, parsed once from
+/// `application.base_url` at startup.
+#[derive(Clone)]
+pub struct CanonicalOrigin {
+    enabled: bool,
+    scheme: String,
+    host: String,
+}
+
+impl CanonicalOrigin {
+    pub fn new(base_url: &str, behind_proxy: bool) -> Self {
+        let (scheme, host) = match base_url.split_once("://") {
+            Some((scheme, rest)) => {
+                // drop any path suffix - only the origin matters here
+                let host = rest.split('/').next().unwrap_or("").to_string();
+                (scheme.to_string(), host)
+            }
+            None => (String::new(), String::new()),
+        };
+        Self {
+            // a base_url we can't parse an origin out of disables the
+            // middleware rather than redirecting everything to nowhere
+            enabled: behind_proxy && !scheme.is_empty() && !host.is_empty(),
+            scheme,
+            host,
+        }
+    }
+}
+
+pub async fn enforce_canonical_origin(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let Some(canonical) = req.app_data::<actix_web::web::Data<CanonicalOrigin>>() else {
+        return next.call(req).await;
+    };
+    if !canonical.enabled {
+        return next.call(req).await;
+    }
+
+    // connection_info honours X-Forwarded-Proto / X-Forwarded-Host, which
+    // is exactly what we want behind a proxy
+    let (scheme, host) = {
+        let connection_info = req.connection_info();
+        (
+            connection_info.scheme().to_string(),
+            connection_info.host().to_string(),
+        )
+    };
+    if scheme == canonical.scheme && host == canonical.host {
+        return next.call(req).await;
+    }
+
+    let location = format!(
+        "{}://{}{}",
+        canonical.scheme,
+        canonical.host,
+        req.uri()
+            .path_and_query()
+            .map(|pq| pq.as_str())
+            .unwrap_or("/"),
+    );
+    let response = HttpResponse::MovedPermanently()
+        .insert_header((LOCATION, location))
+        .finish();
+    let e = anyhow::anyhow!("Redirected a request from a non-canonical origin");
+    Err(InternalError::from_response(e, response).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CanonicalOrigin;
+
+    #[test]
+    fn the_origin_is_parsed_from_the_base_url() {
+        let origin = CanonicalOrigin::new("https://newsletter.example.com", true);
+        assert!(origin.enabled);
+        assert_eq!(origin.scheme, "https");
+        assert_eq!(origin.host, "newsletter.example.com");
+    }
+
+    #[test]
+    fn a_path_suffix_on_the_base_url_is_ignored() {
+        let origin = CanonicalOrigin::new("https://example.com/newsletter", true);
+        assert_eq!(origin.host, "example.com");
+    }
+
+    #[test]
+    fn enforcement_stays_off_without_a_proxy_or_a_parsable_origin() {
+        assert!(!CanonicalOrigin::new("https://example.com", false).enabled);
+        assert!(!CanonicalOrigin::new("not-a-url", true).enabled);
+    }
+}
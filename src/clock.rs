@@ -0,0 +1,88 @@
+use chrono::{DateTime, Utc};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// the sleep future has to be boxed - `async fn` in a trait would stop us
+// handing the clock around as a `dyn Clock` trait object
+pub type SleepFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// A source of time. Production code uses [`SystemClock`]; tests inject a
+/// [`TestClock`] so anything that checks expiry timestamps or waits for a
+/// send window can be exercised without real sleeps.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+    fn sleep(&self, duration: Duration) -> SleepFuture;
+}
+
+/// The real thing - `Utc::now()` and `tokio::time::sleep`.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> SleepFuture {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// A clock that only moves when told to. `sleep` jumps the clock forward
+/// by the requested duration and resolves immediately, so a worker loop
+/// waiting out quiet hours spins through them instantly in tests.
+#[derive(Clone)]
+pub struct TestClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl TestClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    pub fn advance(&self, duration: chrono::Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) -> SleepFuture {
+        self.advance(chrono::Duration::from_std(duration).expect("Sleep duration out of range"));
+        Box::pin(std::future::ready(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_test_clock_only_moves_when_advanced() {
+        let start = Utc::now();
+        let clock = TestClock::new(start);
+        assert_eq!(clock.now(), start);
+
+        clock.advance(chrono::Duration::hours(3));
+        assert_eq!(clock.now(), start + chrono::Duration::hours(3));
+    }
+
+    #[tokio::test]
+    async fn sleeping_on_a_test_clock_advances_it_without_waiting() {
+        let start = Utc::now();
+        let clock = TestClock::new(start);
+
+        // a day-long sleep resolves immediately...
+        clock.sleep(Duration::from_secs(86_400)).await;
+
+        // ...and the clock has jumped past it
+        assert_eq!(clock.now(), start + chrono::Duration::days(1));
+    }
+}
@@ -6,6 +6,7 @@ use crate::domain::SubscriberEmail;
 use crate::email_client::EmailClient;
 use sqlx::postgres::PgConnectOptions;
 use sqlx::postgres::PgSslMode; // for secure db connection
+use sqlx::ConnectOptions; // for the statement-logging knobs
 
 // this code reads in and outputs app-specific settings from
 // and to a file, configuration.yaml
@@ -21,6 +22,323 @@ pub struct Settings {
     pub email_client: EmailClientSettings,
 
     pub redis_uri: Secret<String>, // for sessions
+
+    // quiet hours - when set, the delivery worker only sends inside this
+    // window and defers the queue outside it
+    #[serde(default)]
+    pub send_window: Option<SendWindowSettings>,
+
+    // thresholds for the stuck-worker monitor and the admin diagnostics
+    // page - the defaults are sensible, so this can be left out entirely
+    #[serde(default)]
+    pub worker_monitor: WorkerMonitorSettings,
+
+    // how eagerly the delivery worker polls its queue, and how hard it
+    // backs off after repeated errors
+    #[serde(default)]
+    pub worker: WorkerSettings,
+
+    // where operator alerts go (see crate::alerts) - with no channels
+    // configured, alerts only reach the logs
+    #[serde(default)]
+    pub alerts: AlertSettings,
+
+    // the happy-path counterpart to alerts: chat notifications for
+    // finished sends, confirmations and growth milestones
+    #[serde(default)]
+    pub event_webhooks: EventWebhookSettings,
+
+    // an optional NATS mirror of the analytics events - inert unless a
+    // url is configured AND the binary was built with the `message-bus`
+    // feature (see crate::message_bus)
+    #[serde(default)]
+    pub message_bus: MessageBusSettings,
+
+    // the optional Stripe-backed paid tier (see crate::payments) - with
+    // no keys configured the newsletter is simply free
+    #[serde(default)]
+    pub payments: PaymentSettings,
+
+    // what an acceptable admin password looks like - enforced whenever a
+    // password is changed (see authentication::password_policy)
+    #[serde(default)]
+    pub password_policy: PasswordPolicySettings,
+
+    // how passwords are hashed at rest - stored hashes made under older
+    // settings are transparently re-hashed on the next successful login
+    #[serde(default)]
+    pub password_hashing: PasswordHashSettings,
+
+    // how long the partitioned log tables keep their rows (see
+    // crate::log_maintenance) - left out, nothing is ever dropped
+    #[serde(default)]
+    pub retention: RetentionSettings,
+
+    // the oversized-issue guard on the publish form - Gmail clips
+    // messages over roughly 102KB
+    #[serde(default)]
+    pub email_size_guard: EmailSizeGuardSettings,
+}
+
+/// When the rendered HTML of an issue crosses `warn_kilobytes`, publishing
+/// warns the author; with `block_oversized` set it refuses outright unless
+/// the form's "send anyway" override is ticked.
+#[derive(serde::Deserialize, Clone)]
+pub struct EmailSizeGuardSettings {
+    #[serde(
+        default = "default_size_guard_warn_kilobytes",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub warn_kilobytes: u64,
+    #[serde(default)]
+    pub block_oversized: bool,
+}
+
+fn default_size_guard_warn_kilobytes() -> u64 {
+    // where Gmail's clipping reportedly starts
+    102
+}
+
+impl Default for EmailSizeGuardSettings {
+    fn default() -> Self {
+        Self {
+            warn_kilobytes: default_size_guard_warn_kilobytes(),
+            block_oversized: false,
+        }
+    }
+}
+
+/// How many days each append-only log table keeps its rows. 0 - the
+/// default - means forever. Enforcement drops whole monthly partitions,
+/// so the effective cutoff is fuzzy by up to a month.
+#[derive(serde::Deserialize, Clone, Default)]
+pub struct RetentionSettings {
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    pub delivery_log_days: i64,
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    pub tracking_events_days: i64,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct PasswordHashSettings {
+    // argon2id, argon2i or argon2d - argon2id unless you have a reason
+    #[serde(default = "default_hash_algorithm")]
+    pub algorithm: String,
+    // memory cost in KiB
+    #[serde(
+        default = "default_hash_memory_kib",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub memory_kib: u32,
+    // time cost (passes over the memory)
+    #[serde(
+        default = "default_hash_iterations",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub iterations: u32,
+    // lanes - more only helps if the hashing thread pool has cores spare
+    #[serde(
+        default = "default_hash_parallelism",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub parallelism: u32,
+    // optional server-side pepper (Argon2 keyed hashing) - a database dump
+    // alone is then not enough to brute-force the hashes. Peppered hashes
+    // are stored as `v<version>$<phc>` so the pepper can be rotated.
+    #[serde(default)]
+    pub pepper: Option<Secret<String>>,
+    // the version new hashes are stamped with - bump when rotating
+    #[serde(
+        default = "default_pepper_version",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub pepper_version: u32,
+    // retired peppers, still accepted for verification until every stored
+    // hash has been re-hashed under the current one on login
+    #[serde(default)]
+    pub previous_peppers: Vec<PepperKeySettings>,
+}
+
+// one retired pepper and the version its hashes are prefixed with
+#[derive(serde::Deserialize, Clone)]
+pub struct PepperKeySettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub version: u32,
+    pub secret: Secret<String>,
+}
+
+fn default_pepper_version() -> u32 {
+    1
+}
+
+fn default_hash_algorithm() -> String {
+    "argon2id".to_string()
+}
+
+fn default_hash_memory_kib() -> u32 {
+    15000
+}
+
+fn default_hash_iterations() -> u32 {
+    2
+}
+
+fn default_hash_parallelism() -> u32 {
+    1
+}
+
+impl Default for PasswordHashSettings {
+    fn default() -> Self {
+        Self {
+            algorithm: default_hash_algorithm(),
+            memory_kib: default_hash_memory_kib(),
+            iterations: default_hash_iterations(),
+            parallelism: default_hash_parallelism(),
+            pepper: None,
+            pepper_version: default_pepper_version(),
+            previous_peppers: Vec::new(),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct PasswordPolicySettings {
+    #[serde(
+        default = "default_min_password_length",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub min_length: usize,
+    // OWASP recommends allowing very long passphrases; the ceiling only
+    // exists to keep Argon2 input bounded
+    #[serde(
+        default = "default_max_password_length",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_length: usize,
+    // how many of the four character classes (lowercase, uppercase,
+    // digits, everything else) must appear - 0 disables the check
+    #[serde(
+        default = "default_required_character_classes",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub required_character_classes: u8,
+    // query the HaveIBeenPwned range API (k-anonymity - only a hash
+    // prefix leaves the server) and reject known-breached passwords
+    #[serde(default)]
+    pub check_haveibeenpwned: bool,
+}
+
+fn default_min_password_length() -> usize {
+    12
+}
+
+fn default_max_password_length() -> usize {
+    129
+}
+
+fn default_required_character_classes() -> u8 {
+    3
+}
+
+impl Default for PasswordPolicySettings {
+    fn default() -> Self {
+        Self {
+            min_length: default_min_password_length(),
+            max_length: default_max_password_length(),
+            required_character_classes: default_required_character_classes(),
+            check_haveibeenpwned: false,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct EventWebhookSettings {
+    // Slack/Discord incoming-webhook URLs - empty disables the feature
+    #[serde(default)]
+    pub urls: Vec<String>,
+    // confirmed-subscriber counts worth celebrating
+    #[serde(default = "default_milestones")]
+    pub milestones: Vec<i64>,
+}
+
+fn default_milestones() -> Vec<i64> {
+    vec![10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10_000]
+}
+
+impl Default for EventWebhookSettings {
+    fn default() -> Self {
+        Self {
+            urls: Vec::new(),
+            milestones: default_milestones(),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct MessageBusSettings {
+    // the NATS server, e.g. "nats://localhost:4222" - absent disables
+    // the mirror entirely
+    #[serde(default)]
+    pub url: Option<String>,
+    // events are published to "<subject_prefix>.<event_type>"
+    #[serde(default = "default_bus_subject_prefix")]
+    pub subject_prefix: String,
+}
+
+fn default_bus_subject_prefix() -> String {
+    "zero2prod.events".to_string()
+}
+
+impl Default for MessageBusSettings {
+    fn default() -> Self {
+        Self {
+            url: None,
+            subject_prefix: default_bus_subject_prefix(),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Default)]
+pub struct PaymentSettings {
+    // the sk_... API key - absent means no paid tier
+    #[serde(default)]
+    pub stripe_secret_key: Option<Secret<String>>,
+    // the whsec_... signing secret for /webhooks/stripe
+    #[serde(default)]
+    pub stripe_webhook_secret: Option<Secret<String>>,
+    // the recurring price readers are subscribed to at checkout
+    #[serde(default)]
+    pub stripe_price_id: String,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct AlertSettings {
+    // every address gets every alert email
+    #[serde(default)]
+    pub admin_emails: Vec<String>,
+    // a Slack/Mattermost-style incoming webhook
+    #[serde(default)]
+    pub slack_webhook_url: Option<String>,
+    // repeats of the same alert inside this window are dropped
+    #[serde(
+        default = "default_dedup_window_seconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub dedup_window_seconds: u64,
+}
+
+fn default_dedup_window_seconds() -> u64 {
+    900
+}
+
+impl Default for AlertSettings {
+    fn default() -> Self {
+        Self {
+            admin_emails: Vec::new(),
+            slack_webhook_url: None,
+            dedup_window_seconds: default_dedup_window_seconds(),
+        }
+    }
 }
 
 // port listening on and host environemnt (docker image - production, or debug)
@@ -30,8 +348,118 @@ pub struct ApplicationSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
     pub host: String,
+    // extra socket addresses bound alongside host:port - the usual reason
+    // is dual-stack support, e.g. a host of "0.0.0.0" plus "[::]:8000"
+    // here so IPv6-only clients can reach us too
+    #[serde(default)]
+    pub additional_bind_addresses: Vec<String>,
     pub base_url: String,
     pub hmac_secret: Secret<String>, // used to encode and decode the http requests in posts.rs
+    // the version number the current hmac_secret signs links under -
+    // bump it when rotating the secret
+    #[serde(default = "default_hmac_key_version")]
+    pub hmac_key_version: u32,
+    // retired secrets (with the versions they signed under) still accepted
+    // during a rotation grace period, so cookies, flash messages and
+    // signed links issued before the rotation keep working
+    #[serde(default)]
+    pub previous_hmac_secrets: Vec<HmacKeySettings>,
+    // the shared secret for the JSON API (X-Api-Key header) - unset means
+    // the /api/v1 routes are switched off
+    #[serde(default)]
+    pub api_key: Option<Secret<String>>,
+    // connection handling knobs, applied to the HttpServer in startup::run
+    #[serde(default)]
+    pub tuning: ServerTuningSettings,
+    // concurrent sessions allowed per user - logging in past the cap
+    // evicts the user's oldest session; 0 means uncapped
+    #[serde(
+        default = "default_max_sessions_per_user",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_sessions_per_user: usize,
+    // set when a reverse proxy terminates TLS in front of us - turns on
+    // canonical-host enforcement (see crate::canonical) and makes the
+    // forwarded scheme/host headers trustworthy
+    #[serde(default)]
+    pub behind_proxy: bool,
+    // how long deleted subscribers and drafts stay restorable on
+    // /admin/trash before the sweep purges them for good
+    #[serde(
+        default = "default_trash_retention_days",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub trash_retention_days: i32,
+}
+
+fn default_max_sessions_per_user() -> usize {
+    5
+}
+
+fn default_trash_retention_days() -> i32 {
+    30
+}
+
+// the defaults mirror actix-web's own, so leaving this block out of the
+// yaml changes nothing
+#[derive(serde::Deserialize, Clone)]
+pub struct ServerTuningSettings {
+    // how long an idle keep-alive connection is held open
+    #[serde(
+        default = "default_keep_alive_seconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub keep_alive_seconds: u64,
+    // how long a client gets to send its full request head
+    #[serde(
+        default = "default_client_request_timeout_milliseconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub client_request_timeout_milliseconds: u64,
+    // cap on concurrent connections per worker
+    #[serde(
+        default = "default_max_connections",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_connections: usize,
+    // number of worker threads - 0 means one per logical core
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    pub worker_threads: usize,
+}
+
+fn default_keep_alive_seconds() -> u64 {
+    5
+}
+
+fn default_client_request_timeout_milliseconds() -> u64 {
+    5000
+}
+
+fn default_max_connections() -> usize {
+    25_000
+}
+
+impl Default for ServerTuningSettings {
+    fn default() -> Self {
+        Self {
+            keep_alive_seconds: default_keep_alive_seconds(),
+            client_request_timeout_milliseconds: default_client_request_timeout_milliseconds(),
+            max_connections: default_max_connections(),
+            worker_threads: 0,
+        }
+    }
+}
+
+fn default_hmac_key_version() -> u32 {
+    1
+}
+
+// one retired signing secret and the version it was known under
+#[derive(serde::Deserialize, Clone)]
+pub struct HmacKeySettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub version: u32,
+    pub secret: Secret<String>,
 }
 
 // A struct holding settings relevent to setting up the db
@@ -46,6 +474,17 @@ pub struct DatabaseSettings {
     pub database_name: String,
     // determine if we need secure connection
     pub require_ssl: bool,
+    // statements slower than this get logged at WARN with their span
+    // context - the first place to look when p99 latency spikes
+    #[serde(
+        default = "default_slow_statement_threshold_milliseconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub slow_statement_threshold_milliseconds: u64,
+}
+
+fn default_slow_statement_threshold_milliseconds() -> u64 {
+    100
 }
 
 // generate a connection_string from data in the config struct, which will allow us to connect
@@ -69,6 +508,16 @@ impl DatabaseSettings {
             .port(self.port)
             .ssl_mode(ssl_mode)
             .database(&self.database_name)
+            // ordinary statements are emitted at TRACE - invisible in the
+            // logs at the default filter, but still seen by the per-query
+            // counters in crate::telemetry
+            .log_statements(tracing_log::log::LevelFilter::Trace)
+            // anything over the threshold is loud, and carries the span of
+            // whatever request or worker loop ran it
+            .log_slow_statements(
+                tracing_log::log::LevelFilter::Warn,
+                std::time::Duration::from_millis(self.slow_statement_threshold_milliseconds),
+            )
     }
 }
 
@@ -80,6 +529,34 @@ pub struct EmailClientSettings {
     pub sender_email: String,
     pub auth_token: Secret<String>,
     pub timeout_milliseconds: u64,
+    // provider-facing throttle - how many sends per second we allow
+    // ourselves, and how far we may burst above that momentarily
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub send_rate_per_second: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub send_burst_size: u32,
+    // list-wide headers (List-ID, List-Unsubscribe, Message-ID domain)
+    // stamped on every outgoing email
+    #[serde(default)]
+    pub headers: crate::email_client::MailingListHeaders,
+    // live: really send; sandbox: log the rendered request and skip the
+    // network - for staging environments
+    #[serde(default)]
+    pub mode: crate::email_client::EmailClientMode,
+    // the shared secret the provider's bounce/complaint webhook must
+    // present (see POST /webhooks/email) - the endpoint is disabled when
+    // this is unset
+    #[serde(default)]
+    pub webhook_token: Option<Secret<String>>,
+    // the provider streams transactional and bulk mail ride on, so the
+    // provider can throttle/suppress them independently
+    #[serde(default)]
+    pub message_streams: crate::email_client::MessageStreams,
+    // connection-pool and retry tuning for the underlying http client -
+    // every knob has a sensible default, so config files only need to
+    // mention the ones they change
+    #[serde(default)]
+    pub transport: crate::email_client::HttpTransportSettings,
 }
 
 impl EmailClientSettings {
@@ -97,7 +574,189 @@ impl EmailClientSettings {
     pub fn client(self) -> EmailClient {
         let sender_email = self.sender().expect("Invalid sender email address.");
         let timeout = self.timeout();
-        EmailClient::new(self.base_url, sender_email, self.auth_token, timeout)
+        EmailClient::new(
+            self.base_url,
+            sender_email,
+            self.auth_token,
+            timeout,
+            self.headers,
+            self.mode,
+            self.message_streams,
+            self.transport,
+        )
+    }
+}
+
+// the hours (in a fixed-offset local timezone) during which newsletters may
+// be delivered - nobody wants their inbox pinged at 3am
+#[derive(serde::Deserialize, Clone)]
+pub struct SendWindowSettings {
+    // first hour (0-23) of the window, inclusive
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub start_hour: u8,
+    // last hour (0-23) of the window, exclusive - may be below start_hour
+    // for windows that wrap past midnight
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub end_hour: u8,
+    // the list's timezone, expressed as a fixed offset from UTC
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub utc_offset_hours: i8,
+}
+
+impl SendWindowSettings {
+    /// `None` if sending is allowed right now, otherwise how long until the
+    /// window next opens.
+    pub fn time_until_open(&self, now: chrono::DateTime<chrono::Utc>) -> Option<std::time::Duration> {
+        use chrono::Timelike;
+
+        let offset = chrono::FixedOffset::east_opt(self.utc_offset_hours as i32 * 3600)
+            .expect("utc_offset_hours is out of range");
+        let local = now.with_timezone(&offset);
+        let hour = local.hour() as u8;
+
+        let open = if self.start_hour <= self.end_hour {
+            (self.start_hour..self.end_hour).contains(&hour)
+        } else {
+            // a window that wraps past midnight, e.g. 22:00-06:00
+            hour >= self.start_hour || hour < self.end_hour
+        };
+        if open {
+            return None;
+        }
+
+        // the next time the window opens: today at start_hour, or tomorrow
+        // if that has already gone by
+        let mut next_open = local
+            .date_naive()
+            .and_hms_opt(self.start_hour as u32, 0, 0)
+            .expect("start_hour is out of range")
+            .and_local_timezone(offset)
+            .single()
+            .expect("Failed to resolve the window opening time");
+        if next_open <= local {
+            next_open += chrono::Duration::days(1);
+        }
+
+        (next_open - local).to_std().ok()
+    }
+}
+
+// when a worker heartbeat goes quiet, or a delivery task sits in the queue
+// past its welcome, someone should hear about it
+#[derive(serde::Deserialize, Clone)]
+pub struct WorkerMonitorSettings {
+    // how long without a heartbeat before a worker counts as stuck
+    #[serde(
+        default = "default_heartbeat_timeout_seconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub heartbeat_timeout_seconds: u64,
+    // how old the oldest dequeued-able row may get before we assume
+    // deliveries have stalled
+    #[serde(
+        default = "default_max_queue_age_seconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_queue_age_seconds: u64,
+    // how often the monitor task re-runs its checks
+    #[serde(
+        default = "default_check_interval_seconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub check_interval_seconds: u64,
+    // where the alert email goes - no address, no alerts (the diagnostics
+    // page still works either way)
+    #[serde(default)]
+    pub alert_email: Option<String>,
+}
+
+fn default_heartbeat_timeout_seconds() -> u64 {
+    90
+}
+
+fn default_max_queue_age_seconds() -> u64 {
+    3600
+}
+
+fn default_check_interval_seconds() -> u64 {
+    60
+}
+
+impl Default for WorkerMonitorSettings {
+    fn default() -> Self {
+        Self {
+            heartbeat_timeout_seconds: default_heartbeat_timeout_seconds(),
+            max_queue_age_seconds: default_max_queue_age_seconds(),
+            check_interval_seconds: default_check_interval_seconds(),
+            alert_email: None,
+        }
+    }
+}
+
+/// How eagerly the delivery worker polls. The defaults reproduce the old
+/// hard-coded behaviour - ten seconds between passes over an empty queue,
+/// one second after an error - except that repeated errors now back off
+/// exponentially, so a broken provider or database isn't hammered once a
+/// second for hours.
+#[derive(serde::Deserialize, Clone)]
+pub struct WorkerSettings {
+    // the fallback poll when the queue is empty - normally the enqueue
+    // notification wakes the worker long before this elapses
+    #[serde(
+        default = "default_empty_queue_sleep_seconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub empty_queue_sleep_seconds: u64,
+    // the pause after the first delivery error; every consecutive error
+    // doubles it
+    #[serde(
+        default = "default_error_backoff_seconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub error_backoff_seconds: u64,
+    // where the doubling stops
+    #[serde(
+        default = "default_max_error_backoff_seconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_error_backoff_seconds: u64,
+}
+
+fn default_empty_queue_sleep_seconds() -> u64 {
+    10
+}
+
+fn default_error_backoff_seconds() -> u64 {
+    1
+}
+
+fn default_max_error_backoff_seconds() -> u64 {
+    60
+}
+
+impl Default for WorkerSettings {
+    fn default() -> Self {
+        Self {
+            empty_queue_sleep_seconds: default_empty_queue_sleep_seconds(),
+            error_backoff_seconds: default_error_backoff_seconds(),
+            max_error_backoff_seconds: default_max_error_backoff_seconds(),
+        }
+    }
+}
+
+impl WorkerSettings {
+    /// The pause after the `consecutive_errors`-th failure in a row -
+    /// exponential doubling capped at the configured maximum, plus up to
+    /// 25% of random jitter so a fleet of workers retrying against the
+    /// same recovering database desynchronises instead of stampeding.
+    pub fn error_backoff(&self, consecutive_errors: u32) -> std::time::Duration {
+        use rand::Rng;
+        let seconds = self
+            .error_backoff_seconds
+            .saturating_mul(2u64.saturating_pow(consecutive_errors.saturating_sub(1)))
+            .min(self.max_error_backoff_seconds);
+        let jitter_ms = rand::thread_rng().gen_range(0..=seconds.saturating_mul(250));
+        std::time::Duration::from_secs(seconds) + std::time::Duration::from_millis(jitter_ms)
     }
 }
 
@@ -113,15 +772,19 @@ pub fn get_configuration() -> Result<Settings, config::ConfigError> {
         .try_into()
         .expect("Failed to parse APP_ENVIRONMENT.");
 
-    let environment_filename = format!("{}.yaml", environment.as_str());
+    // resolve the environment's inheritance chain (see environment_chain) -
+    // parents are applied first so children override them
+    let chain = environment_chain(&configuration_directory, environment.as_str())?;
 
-    let settings = config::Config::builder()
-        .add_source(config::File::from(
-            configuration_directory.join("base.yaml"),
-        ))
-        .add_source(config::File::from(
-            configuration_directory.join(environment_filename),
-        ))
+    let mut builder = config::Config::builder().add_source(config::File::from(
+        configuration_directory.join("base.yaml"),
+    ));
+    for environment_name in chain {
+        builder = builder.add_source(config::File::from(
+            configuration_directory.join(format!("{environment_name}.yaml")),
+        ));
+    }
+    let settings = builder
         // Add in settings from environment variables (with a prefix of APP and
         // '__' as separator)
         // E.g. `APP_APPLICATION__PORT=5001 would set `Settings.application.port`
@@ -132,19 +795,86 @@ pub fn get_configuration() -> Result<Settings, config::ConfigError> {
         )
         .build()?;
 
-    settings.try_deserialize::<Settings>()
+    let mut settings = settings.try_deserialize::<Settings>()?;
+    resolve_secret_files(&mut settings)?;
+    Ok(settings)
 }
-/// The possible runtime environment for our application.
-pub enum Environment {
-    Local,
-    Production,
+
+// `*_FILE` indirection for the sensitive values, in the style of the
+// official Docker images: `APP_DATABASE__PASSWORD_FILE=/run/secrets/db_pw`
+// reads the password from that file instead of embedding it in YAML or a
+// plain environment variable. Docker/K8s secrets mount as files, and the
+// Vault Agent / secrets-store CSI drivers materialize external secrets
+// the same way - so this one mechanism covers all of them.
+fn resolve_secret_files(settings: &mut Settings) -> Result<(), config::ConfigError> {
+    if let Ok(path) = std::env::var("APP_DATABASE__PASSWORD_FILE") {
+        settings.database.password = secret_from_file(&path)?;
+    }
+    if let Ok(path) = std::env::var("APP_EMAIL_CLIENT__AUTH_TOKEN_FILE") {
+        settings.email_client.auth_token = secret_from_file(&path)?;
+    }
+    if let Ok(path) = std::env::var("APP_APPLICATION__HMAC_SECRET_FILE") {
+        settings.application.hmac_secret = secret_from_file(&path)?;
+    }
+    if let Ok(path) = std::env::var("APP_REDIS_URI_FILE") {
+        settings.redis_uri = secret_from_file(&path)?;
+    }
+    Ok(())
 }
-impl Environment {
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            Environment::Local => "local",
-            Environment::Production => "production",
+
+fn secret_from_file(path: &str) -> Result<Secret<String>, config::ConfigError> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        config::ConfigError::Message(format!("Failed to read the secret file at {path}: {e}"))
+    })?;
+    // mounted secrets routinely end with a newline - strip it, or the
+    // database password silently stops matching
+    Ok(Secret::new(contents.trim_end_matches(['\r', '\n']).to_string()))
+}
+// An environment's YAML file may name another environment to inherit from:
+//
+//     # configuration/preview-42.yaml
+//     inherits: staging
+//
+// This walks that chain and returns it parent-first, so preview
+// deployments only override what differs from their parent. A missing
+// file or a cycle is a configuration error.
+fn environment_chain(
+    configuration_directory: &std::path::Path,
+    environment: &str,
+) -> Result<Vec<String>, config::ConfigError> {
+    let mut chain = vec![environment.to_string()];
+    let mut current = environment.to_string();
+    loop {
+        let file = configuration_directory.join(format!("{current}.yaml"));
+        let parsed = config::Config::builder()
+            .add_source(config::File::from(file))
+            .build()?;
+        let Ok(parent) = parsed.get_string("inherits") else {
+            break;
+        };
+        if chain.contains(&parent) {
+            return Err(config::ConfigError::Message(format!(
+                "Environment inheritance cycle detected: {} -> {}",
+                chain.join(" -> "),
+                parent
+            )));
         }
+        chain.push(parent.clone());
+        current = parent;
+    }
+    // parent-first, so later (more specific) files win
+    chain.reverse();
+    Ok(chain)
+}
+
+/// The runtime environment for our application - any lowercase name
+/// (local, production, staging, ci, preview-42, ...) with a matching
+/// YAML file under `configuration/`.
+pub struct Environment(String);
+
+impl Environment {
+    pub fn as_str(&self) -> &str {
+        &self.0
     }
 }
 
@@ -152,14 +882,141 @@ impl TryFrom<String> for Environment {
     type Error = String;
 
     fn try_from(s: String) -> Result<Self, Self::Error> {
-        match s.to_lowercase().as_str() {
-            "local" => Ok(Self::Local),
-            "production" => Ok(Self::Production),
-            other => Err(format!(
-                "{} is not a supported environment. \
-                Use either `local` or `production`.",
-                other
-            )),
+        let s = s.to_lowercase();
+        if s.is_empty()
+            || !s
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "{} is not a valid environment name. \
+                Use lowercase letters, digits, `-` and `_`.",
+                s
+            ));
         }
+        Ok(Self(s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{environment_chain, secret_from_file, Environment, SendWindowSettings, WorkerSettings};
+    use chrono::{TimeZone, Utc};
+    use secrecy::ExposeSecret;
+
+    fn window(start_hour: u8, end_hour: u8, utc_offset_hours: i8) -> SendWindowSettings {
+        SendWindowSettings {
+            start_hour,
+            end_hour,
+            utc_offset_hours,
+        }
+    }
+
+    #[test]
+    fn environment_names_are_validated_not_enumerated() {
+        assert!(Environment::try_from("preview-42".to_string()).is_ok());
+        assert!(Environment::try_from("staging".to_string()).is_ok());
+        assert!(Environment::try_from("not valid!".to_string()).is_err());
+        assert!(Environment::try_from(String::new()).is_err());
+    }
+
+    #[test]
+    fn inheritance_chains_resolve_parent_first() {
+        let dir = std::env::temp_dir().join("zero2prod-environment-chain-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("staging.yaml"), "inherits: production\n").unwrap();
+        std::fs::write(dir.join("production.yaml"), "a: 1\n").unwrap();
+        std::fs::write(dir.join("preview-42.yaml"), "inherits: staging\n").unwrap();
+
+        let chain = environment_chain(&dir, "preview-42").unwrap();
+        assert_eq!(chain, vec!["production", "staging", "preview-42"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn an_inheritance_cycle_is_reported() {
+        let dir = std::env::temp_dir().join("zero2prod-environment-cycle-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.yaml"), "inherits: b\n").unwrap();
+        std::fs::write(dir.join("b.yaml"), "inherits: a\n").unwrap();
+
+        assert!(environment_chain(&dir, "a").is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_secret_file_is_read_without_its_trailing_newline() {
+        let path = std::env::temp_dir().join("zero2prod-secret-file-test");
+        std::fs::write(&path, "s3cr3t\n").unwrap();
+        let secret = secret_from_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(secret.expose_secret(), "s3cr3t");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_missing_secret_file_is_an_error() {
+        assert!(secret_from_file("/definitely/not/a/real/path").is_err());
+    }
+
+    #[test]
+    fn window_is_open_during_the_configured_hours() {
+        let window = window(8, 20, 0);
+        let noon = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        assert!(window.time_until_open(noon).is_none());
+    }
+
+    #[test]
+    fn window_defers_until_the_next_opening() {
+        let window = window(8, 20, 0);
+        // 03:00 - five hours before the window opens
+        let small_hours = Utc.with_ymd_and_hms(2024, 6, 1, 3, 0, 0).unwrap();
+        let wait = window.time_until_open(small_hours).unwrap();
+        assert_eq!(wait.as_secs(), 5 * 3600);
+        // 21:00 - eleven hours until tomorrow's opening
+        let late_evening = Utc.with_ymd_and_hms(2024, 6, 1, 21, 0, 0).unwrap();
+        let wait = window.time_until_open(late_evening).unwrap();
+        assert_eq!(wait.as_secs(), 11 * 3600);
+    }
+
+    #[test]
+    fn window_accounts_for_the_timezone_offset() {
+        // 08:00-20:00 at UTC+2 is 06:00-18:00 UTC
+        let window = window(8, 20, 2);
+        let evening_utc = Utc.with_ymd_and_hms(2024, 6, 1, 19, 0, 0).unwrap();
+        assert!(window.time_until_open(evening_utc).is_some());
+        let morning_utc = Utc.with_ymd_and_hms(2024, 6, 1, 7, 0, 0).unwrap();
+        assert!(window.time_until_open(morning_utc).is_none());
+    }
+
+    #[test]
+    fn error_backoff_doubles_and_is_capped() {
+        let worker = WorkerSettings {
+            empty_queue_sleep_seconds: 10,
+            error_backoff_seconds: 1,
+            max_error_backoff_seconds: 60,
+        };
+        // the jitter adds at most a quarter on top of the doubled base
+        for (errors, base) in [(1u32, 1u64), (2, 2), (3, 4), (7, 60), (64, 60)] {
+            let backoff = worker.error_backoff(errors).as_secs_f64();
+            assert!(
+                backoff >= base as f64 && backoff <= base as f64 * 1.25,
+                "{} error(s) gave a backoff of {}s, expected {}s-ish",
+                errors,
+                backoff,
+                base,
+            );
+        }
+    }
+
+    #[test]
+    fn window_may_wrap_past_midnight() {
+        let window = window(22, 6, 0);
+        let midnight = Utc.with_ymd_and_hms(2024, 6, 1, 0, 30, 0).unwrap();
+        assert!(window.time_until_open(midnight).is_none());
+        let afternoon = Utc.with_ymd_and_hms(2024, 6, 1, 15, 0, 0).unwrap();
+        let wait = window.time_until_open(afternoon).unwrap();
+        assert_eq!(wait.as_secs(), 7 * 3600);
     }
 }
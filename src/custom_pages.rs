@@ -0,0 +1,107 @@
+use sqlx::PgPool;
+
+// The handful of public pages a subscriber lands on after clicking a link
+// in an email. Operators can replace the built-in HTML via
+// /admin/settings/pages (see routes/admin/settings) - an absent or
+// unreadable override falls back to the defaults below, so page rendering
+// can never fail.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Page {
+    Confirmation,
+    Unsubscribe,
+    Error,
+}
+
+impl Page {
+    pub const ALL: [Page; 3] = [Page::Confirmation, Page::Unsubscribe, Page::Error];
+
+    // the key used in the `custom_pages` table
+    pub fn slug(&self) -> &'static str {
+        match self {
+            Page::Confirmation => "confirmation",
+            Page::Unsubscribe => "unsubscribe",
+            Page::Error => "error",
+        }
+    }
+
+    pub fn from_slug(slug: &str) -> Option<Page> {
+        Self::ALL.into_iter().find(|p| p.slug() == slug)
+    }
+
+    fn default_html(&self) -> &'static str {
+        match self {
+            Page::Confirmation => {
+                "<!DOCTYPE html>\
+                <html lang=\"en\"><head><title>Subscription confirmed</title></head>\
+                <body><p>Your subscription is confirmed - welcome aboard!</p></body></html>"
+            }
+            Page::Unsubscribe => {
+                "<!DOCTYPE html>\
+                <html lang=\"en\"><head><title>Unsubscribed</title></head>\
+                <body><p>You have been unsubscribed. Sorry to see you go.</p></body></html>"
+            }
+            Page::Error => {
+                "<!DOCTYPE html>\
+                <html lang=\"en\"><head><title>Something went wrong</title></head>\
+                <body><p>Something went wrong on our side - please try again later.</p></body></html>"
+            }
+        }
+    }
+}
+
+/// The HTML to serve for `page` - the operator's override if one is
+/// stored, the built-in default otherwise.
+#[tracing::instrument(skip(pool))]
+pub async fn render(pool: &PgPool, page: Page) -> String {
+    match get_override(pool, page).await {
+        Ok(Some(html)) => html,
+        Ok(None) => page.default_html().to_string(),
+        Err(e) => {
+            // the page must render even if the lookup fails
+            tracing::warn!(
+                error.cause_chain = ?e,
+                "Failed to load the custom page - serving the default"
+            );
+            page.default_html().to_string()
+        }
+    }
+}
+
+/// The stored override for `page`, if any - used by the admin editor to
+/// pre-fill its textareas.
+pub async fn get_override(pool: &PgPool, page: Page) -> Result<Option<String>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT html
+        FROM custom_pages
+        WHERE slug = $1
+        "#,
+        page.slug(),
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| r.html))
+}
+
+/// Store (or replace) the operator's HTML for `page`.
+pub async fn set_override(pool: &PgPool, page: Page, html: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO custom_pages (slug, html, updated_at)
+        VALUES ($1, $2, now())
+        ON CONFLICT (slug)
+        DO UPDATE SET html = EXCLUDED.html, updated_at = EXCLUDED.updated_at
+        "#,
+        page.slug(),
+        html,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The default HTML shown in the editor before an override exists.
+pub fn default_html(page: Page) -> &'static str {
+    page.default_html()
+}
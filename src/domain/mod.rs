@@ -1,8 +1,10 @@
 mod new_subscriber;
+mod segment;
 mod subscriber_email;
 mod subscriber_name;
 
 // re-export
 pub use new_subscriber::NewSubscriber;
+pub use segment::SegmentDefinition;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
@@ -0,0 +1,64 @@
+use crate::message_bus::MessageBus;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// A structured, PII-free event log - rows in the `events` table, one per
+// domain event, carrying only pseudonymous uuids. Tracing output is for
+// operators debugging the system; this is for analytics, and the two
+// shouldn't be tangled up in each other's retention rules.
+//
+// Recording is always best-effort: an analytics hiccup must never fail a
+// signup or hold up a delivery, so errors are logged and swallowed.
+
+/// A subscriber flipped from pending to confirmed. Subject: subscriber id.
+pub const SUBSCRIBER_CONFIRMED: &str = "subscriber_confirmed";
+
+/// An issue was queued for delivery. Subject: newsletter issue id.
+pub const ISSUE_PUBLISHED: &str = "issue_published";
+
+/// One email of an issue run was accepted by the provider. Subject:
+/// subscriber id (None if they were deleted mid-run).
+pub const EMAIL_DELIVERED: &str = "email_delivered";
+
+/// A reader opted out via the one-click link. Subject: subscriber id.
+/// (Bulk admin actions and the re-engagement sweep change status without
+/// an event per row - this tracks readers leaving of their own accord.)
+pub const SUBSCRIBER_UNSUBSCRIBED: &str = "subscriber_unsubscribed";
+
+/// One email of an issue run was permanently rejected. Subject:
+/// subscriber id (None if they were deleted mid-run).
+pub const DELIVERY_FAILED: &str = "delivery_failed";
+
+/// Append an event to the log and mirror it onto the message bus (a
+/// no-op unless one is configured). Failures are logged, never returned.
+pub async fn record(
+    pool: &PgPool,
+    bus: &MessageBus,
+    event_type: &str,
+    subject_id: Option<Uuid>,
+    occurred_at: DateTime<Utc>,
+) {
+    let outcome = sqlx::query!(
+        r#"
+        INSERT INTO events (id, event_type, subject_id, occurred_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        Uuid::new_v4(),
+        event_type,
+        subject_id,
+        occurred_at,
+    )
+    .execute(pool)
+    .await;
+    if let Err(e) = outcome {
+        tracing::warn!(
+            error.cause_chain = ?e,
+            event_type,
+            "Failed to record a domain event",
+        );
+    }
+    // the mirror doesn't depend on the insert - a database hiccup
+    // shouldn't also silence the bus
+    bus.publish(event_type, subject_id, occurred_at).await;
+}
@@ -1,6 +1,49 @@
 use crate::domain::SubscriberEmail;
-use reqwest::Client;
+use crate::routes::error_chain_fmt;
+use reqwest::{header, Client, StatusCode};
 use secrecy::{ExposeSecret, Secret};
+use std::collections::HashMap;
+use std::time::Duration;
+
+// not every send failure is equal - a malformed address will never succeed
+// however often we retry, while a 5xx / timeout / 429 is worth another go.
+// callers use this split to decide whether to suppress or requeue
+#[derive(thiserror::Error)]
+pub enum SendError {
+    /// A 4xx rejection (other than 429) - the provider will never accept
+    /// this request, so don't retry it.
+    #[error("The email provider permanently rejected the request.")]
+    Permanent(#[source] reqwest::Error),
+    /// A 5xx, a 429 or a network-level failure - the request may well
+    /// succeed if retried later.
+    #[error("A transient failure occurred while sending the email.")]
+    Transient {
+        #[source]
+        source: reqwest::Error,
+        /// Parsed from the provider's `Retry-After` header, when present.
+        retry_after: Option<Duration>,
+    },
+}
+
+impl SendError {
+    /// How long the provider asked us to back off, if it told us at all.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            SendError::Permanent(_) => None,
+            SendError::Transient { retry_after, .. } => *retry_after,
+        }
+    }
+
+    pub fn is_transient(&self) -> bool {
+        matches!(self, SendError::Transient { .. })
+    }
+}
+
+impl std::fmt::Debug for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
 
 #[derive(serde::Serialize)]
 #[serde(rename_all = "PascalCase")] // ensures pascal case for html
@@ -10,6 +53,169 @@ struct SendEmailRequest<'a> {
     subject: &'a str,
     html_body: &'a str,
     text_body: &'a str,
+    // extra smtp headers, serialised in the [{"Name": .., "Value": ..}]
+    // shape Postmark expects
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<MailHeader>,
+    // provider-side metadata tags - not part of the delivered email, but
+    // echoed back in webhooks and the provider's activity log
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    metadata: HashMap<String, String>,
+    // which provider-side stream the message rides on (Postmark's name
+    // for it) - omitted entirely when no stream is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message_stream: Option<&'a str>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct MailHeader {
+    name: String,
+    value: String,
+}
+
+/// What kind of email this is, from the provider's point of view.
+/// Transactional mail (confirmations, password resets) and bulk mail
+/// (newsletter issues) ride on separate provider streams, so a throttled
+/// or suppression-heavy bulk stream can never block account-critical
+/// emails. Everything is transactional unless a caller says otherwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MessageCategory {
+    #[default]
+    Transactional,
+    Bulk,
+}
+
+/// Per-message additions layered on top of the list-wide defaults - used by
+/// callers that need tracking tags, threading headers and the like.
+#[derive(Default, Clone)]
+pub struct MessageExtras {
+    /// extra smtp headers as (name, value) pairs
+    pub headers: Vec<(String, String)>,
+    /// provider metadata tags
+    pub metadata: HashMap<String, String>,
+    /// transactional (the default) or bulk - picks the provider stream
+    pub category: MessageCategory,
+}
+
+/// The provider-side stream names each category maps onto - for Postmark,
+/// typically "outbound" (transactional) and "broadcast" (bulk). Left unset,
+/// no stream field is sent and the provider's default applies.
+#[derive(serde::Deserialize, Clone, Default)]
+pub struct MessageStreams {
+    pub transactional: Option<String>,
+    pub bulk: Option<String>,
+}
+
+/// Tuning for the underlying HTTP transport: how the connection pool
+/// behaves between sends, and how hard the client tries before giving a
+/// failure back to the caller. A newsletter run fires thousands of
+/// requests at one host, so warm connections matter - the per-request
+/// timeout alone doesn't cover that.
+#[derive(serde::Deserialize, Clone)]
+pub struct HttpTransportSettings {
+    // how long an idle connection is kept around for reuse
+    #[serde(default = "default_pool_idle_timeout_seconds")]
+    pub pool_idle_timeout_seconds: u64,
+    // idle connections retained per host - the provider is a single host,
+    // so this is effectively the pool size
+    #[serde(default = "default_pool_max_idle_per_host")]
+    pub pool_max_idle_per_host: usize,
+    // os-level keepalive probes, so half-dead connections are noticed
+    // before a send is attempted on them
+    #[serde(default = "default_tcp_keepalive_seconds")]
+    pub tcp_keepalive_seconds: u64,
+    // in-client retries for failures that provably never left the machine
+    // (see `send_email_with`) - exponential backoff from the base delay,
+    // with jitter so parallel workers don't retry in lockstep
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    #[serde(default = "default_retry_base_delay_milliseconds")]
+    pub retry_base_delay_milliseconds: u64,
+}
+
+fn default_pool_idle_timeout_seconds() -> u64 {
+    90
+}
+
+fn default_pool_max_idle_per_host() -> usize {
+    8
+}
+
+fn default_tcp_keepalive_seconds() -> u64 {
+    60
+}
+
+fn default_max_retries() -> u32 {
+    2
+}
+
+fn default_retry_base_delay_milliseconds() -> u64 {
+    100
+}
+
+impl Default for HttpTransportSettings {
+    fn default() -> Self {
+        Self {
+            pool_idle_timeout_seconds: default_pool_idle_timeout_seconds(),
+            pool_max_idle_per_host: default_pool_max_idle_per_host(),
+            tcp_keepalive_seconds: default_tcp_keepalive_seconds(),
+            max_retries: default_max_retries(),
+            retry_base_delay_milliseconds: default_retry_base_delay_milliseconds(),
+        }
+    }
+}
+
+impl HttpTransportSettings {
+    // the delay before retry number `attempt` (1-based): the base delay
+    // doubled per attempt, plus up to half a base of random jitter
+    fn retry_delay(&self, attempt: u32) -> Duration {
+        use rand::Rng;
+        let backoff = self.retry_base_delay_milliseconds
+            * u64::from(2u32.saturating_pow(attempt.saturating_sub(1)));
+        let jitter = rand::thread_rng().gen_range(0..=self.retry_base_delay_milliseconds / 2);
+        Duration::from_millis(backoff + jitter)
+    }
+}
+
+// the slice of Postmark's response we care about - the id it assigned
+// to the accepted message
+#[derive(serde::Deserialize)]
+struct SendEmailResponse {
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+}
+
+/// What a successful send gives us back.
+#[derive(Debug)]
+pub struct SendOutcome {
+    /// The id the provider assigned to the message, if it returned one.
+    pub message_id: Option<String>,
+}
+
+/// Whether the client actually talks to the provider. In `Sandbox` mode the
+/// fully rendered request is logged and a fake message id handed back, so
+/// staging environments can exercise the whole pipeline without sending
+/// real email.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailClientMode {
+    #[default]
+    Live,
+    Sandbox,
+}
+
+/// Static, list-wide headers stamped onto every outgoing email.
+/// `List-Unsubscribe` (with its one-click companion) is required by the
+/// Gmail/Yahoo bulk-sender rules these days.
+#[derive(serde::Deserialize, Clone, Default)]
+pub struct MailingListHeaders {
+    /// e.g. `Zero2Prod Newsletter <newsletter.example.com>`
+    pub list_id: Option<String>,
+    /// a mailto: or https: unsubscribe target
+    pub list_unsubscribe: Option<String>,
+    /// the domain used to mint RFC 5322 `Message-ID`s for threading
+    pub message_id_domain: Option<String>,
 }
 
 // these are costly to connect - instead we make one instance and get refs to it
@@ -21,17 +227,32 @@ pub struct EmailClient {
     base_url: String,
     sender: SubscriberEmail,
     auth_token: Secret<String>,
+    list_headers: MailingListHeaders,
+    mode: EmailClientMode,
+    streams: MessageStreams,
+    transport: HttpTransportSettings,
 }
 
 impl EmailClient {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         base_url: String,
         sender: SubscriberEmail,
         auth_token: Secret<String>,
         timeout: std::time::Duration,
+        list_headers: MailingListHeaders,
+        mode: EmailClientMode,
+        streams: MessageStreams,
+        transport: HttpTransportSettings,
     ) -> Self {
-        // create a client with a timeout of 10s if no response from server
-        let http_client = Client::builder().timeout(timeout).build();
+        // create a client with a timeout of 10s if no response from server,
+        // keeping connections to the provider warm between sends
+        let http_client = Client::builder()
+            .timeout(timeout)
+            .pool_idle_timeout(Duration::from_secs(transport.pool_idle_timeout_seconds))
+            .pool_max_idle_per_host(transport.pool_max_idle_per_host)
+            .tcp_keepalive(Some(Duration::from_secs(transport.tcp_keepalive_seconds)))
+            .build();
 
         let http_client = match http_client {
             Ok(client) => client,
@@ -46,16 +267,69 @@ impl EmailClient {
             base_url,
             sender,
             auth_token,
+            list_headers,
+            mode,
+            streams,
+            transport,
         }
     }
 
+    // build the per-message header set from the configured list values
+    fn build_headers(&self) -> Vec<MailHeader> {
+        let mut headers = Vec::new();
+        if let Some(domain) = &self.list_headers.message_id_domain {
+            headers.push(MailHeader {
+                name: "Message-ID".to_string(),
+                value: format!("<{}@{}>", uuid::Uuid::new_v4(), domain),
+            });
+        }
+        if let Some(list_id) = &self.list_headers.list_id {
+            headers.push(MailHeader {
+                name: "List-ID".to_string(),
+                value: list_id.clone(),
+            });
+        }
+        if let Some(list_unsubscribe) = &self.list_headers.list_unsubscribe {
+            headers.push(MailHeader {
+                name: "List-Unsubscribe".to_string(),
+                value: list_unsubscribe.clone(),
+            });
+            // the one-click marker Gmail/Yahoo insist on for bulk senders
+            headers.push(MailHeader {
+                name: "List-Unsubscribe-Post".to_string(),
+                value: "List-Unsubscribe=One-Click".to_string(),
+            });
+        }
+        headers
+    }
+
     pub async fn send_email(
         &self,
         recipient: &SubscriberEmail,
         subject: &str,
         html_content: &str,
         text_content: &str,
-    ) -> Result<(), reqwest::Error> {
+    ) -> Result<SendOutcome, SendError> {
+        self.send_email_with(
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            MessageExtras::default(),
+        )
+        .await
+    }
+
+    /// As `send_email`, but with per-message headers and metadata attached
+    /// on top of the configured list-wide defaults.
+    pub async fn send_email_with(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        extras: MessageExtras,
+    ) -> Result<SendOutcome, SendError> {
         // Need to build a request that looks like this:
         // curl "https://api.postmarkapp.com/email" \
         //     -X POST \
@@ -73,32 +347,125 @@ impl EmailClient {
         // this is firing to https://api.postmarkapp.com/email
         let url = format!("{}/email", self.base_url);
 
+        // the list-wide headers first, then whatever this message adds
+        let mut headers = self.build_headers();
+        headers.extend(
+            extras
+                .headers
+                .into_iter()
+                .map(|(name, value)| MailHeader { name, value }),
+        );
+
+        // route the message onto the stream configured for its category
+        let message_stream = match extras.category {
+            MessageCategory::Transactional => self.streams.transactional.as_deref(),
+            MessageCategory::Bulk => self.streams.bulk.as_deref(),
+        };
+
         let request_body = SendEmailRequest {
             from: self.sender.as_ref(), // we could put these as 'to_owned' and have them as Strings
             to: recipient.as_ref(),
             subject,
             html_body: html_content,
             text_body: text_content,
+            headers,
+            metadata: extras.metadata,
+            message_stream,
+        };
+
+        if self.mode == EmailClientMode::Sandbox {
+            // log the fully rendered request instead of dispatching it - the
+            // fake message id flows into the delivery log like a real one
+            tracing::info!(
+                request_body = %serde_json::to_string(&request_body).unwrap_or_default(),
+                "Sandbox mode - outgoing email was rendered but not dispatched.",
+            );
+            return Ok(SendOutcome {
+                message_id: Some(format!("sandbox-{}", uuid::Uuid::new_v4())),
+            });
+        }
+
+        // sends carry no idempotency key on the provider's side, so only
+        // failures that provably never left the machine are retried in
+        // place: a refused connection cannot have delivered anything. a
+        // timeout or 5xx may well have reached the provider, and resending
+        // those here would risk a double delivery - they are surfaced as
+        // transient and left to the caller's requeue logic
+        let mut attempt = 0u32;
+        let response = loop {
+            let result = self
+                .http_client
+                .post(&url)
+                // .header("Accept", "application/json")
+                // .header("Content-Type", "application/json")
+                .header("X-Postmark-Server-Token", self.auth_token.expose_secret())
+                .json(&request_body)
+                .send()
+                .await;
+            match result {
+                Ok(response) => break response,
+                Err(e) if e.is_connect() && attempt < self.transport.max_retries => {
+                    attempt += 1;
+                    let delay = self.transport.retry_delay(attempt);
+                    tracing::warn!(
+                        error = %e,
+                        attempt,
+                        delay_ms = delay.as_millis() as u64,
+                        "Could not connect to the email provider - retrying.",
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => {
+                    return Err(SendError::Transient {
+                        source: e,
+                        retry_after: None,
+                    })
+                }
+            }
         };
 
-        self.http_client
-            .post(&url)
-            // .header("Accept", "application/json")
-            // .header("Content-Type", "application/json")
-            .header("X-Postmark-Server-Token", self.auth_token.expose_secret())
-            .json(&request_body)
-            .send()
-            .await?
-            .error_for_status()?; // converts an error code, e.g. 404, into a reqwest error
+        // converts an error code, e.g. 404, into a reqwest error
+        if let Err(e) = response.error_for_status_ref() {
+            let status = response.status();
+            if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+                return Err(SendError::Transient {
+                    retry_after: parse_retry_after(&response),
+                    source: e,
+                });
+            }
+            // any other 4xx - our request is the problem, retrying won't help
+            return Err(SendError::Permanent(e));
+        }
+
+        // pull the provider-assigned message id out of the response body -
+        // best effort, an unparseable body shouldn't fail the send
+        let message_id = response
+            .json::<SendEmailResponse>()
+            .await
+            .ok()
+            .and_then(|r| r.message_id);
 
-        Ok(())
+        Ok(SendOutcome { message_id })
     }
 }
 
+// providers send `Retry-After` in seconds alongside a 429/503 - we ignore
+// the (rarely used) http-date form
+fn parse_retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::domain::SubscriberEmail;
-    use crate::email_client::EmailClient;
+    use crate::email_client::{EmailClient, EmailClientMode, MailingListHeaders};
     use fake::faker::internet::en::SafeEmail;
     use fake::faker::lorem::en::{Paragraph, Sentence};
     use fake::{Fake, Faker};
@@ -218,6 +585,44 @@ mod tests {
         assert_err!(outcome); // we want this to err
     }
 
+    #[tokio::test]
+    async fn send_email_classifies_a_500_as_transient_and_reads_retry_after() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        wiremock::Mock::given(matchers::any())
+            .respond_with(wiremock::ResponseTemplate::new(500).insert_header("Retry-After", "5"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        let error = outcome.unwrap_err();
+        assert!(error.is_transient());
+        assert_eq!(error.retry_after(), Some(std::time::Duration::from_secs(5)));
+    }
+
+    #[tokio::test]
+    async fn send_email_classifies_a_400_as_permanent() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        wiremock::Mock::given(matchers::any())
+            .respond_with(wiremock::ResponseTemplate::new(400)) // e.g. a rejected address
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert!(!outcome.unwrap_err().is_transient());
+    }
+
     // Generate a random email subject
     fn subject() -> String {
         Sentence(1..2).fake()
@@ -233,6 +638,249 @@ mod tests {
     /// Get a test instance of `EmailClient`.
     fn email_client(base_url: String) -> EmailClient {
         let timeout = std::time::Duration::from_millis(200);
-        EmailClient::new(base_url, email(), Secret::new(Faker.fake()), timeout)
+        EmailClient::new(
+            base_url,
+            email(),
+            Secret::new(Faker.fake()),
+            timeout,
+            MailingListHeaders::default(),
+            EmailClientMode::Live,
+            crate::email_client::MessageStreams::default(),
+            crate::email_client::HttpTransportSettings::default(),
+        )
+    }
+
+    #[test]
+    fn retry_delay_backs_off_exponentially_within_the_jitter_bound() {
+        let transport = crate::email_client::HttpTransportSettings::default();
+        for attempt in 1..=3u32 {
+            let delay = transport.retry_delay(attempt).as_millis() as u64;
+            // doubled per attempt, plus at most half a base of jitter
+            let floor = transport.retry_base_delay_milliseconds * 2u64.pow(attempt - 1);
+            let ceiling = floor + transport.retry_base_delay_milliseconds / 2;
+            assert!(
+                (floor..=ceiling).contains(&delay),
+                "attempt {attempt}: delay {delay}ms outside {floor}..={ceiling}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn sandbox_mode_does_not_hit_the_network() {
+        let mock_server = MockServer::start().await;
+        let email_client = EmailClient::new(
+            mock_server.uri(),
+            email(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+            MailingListHeaders::default(),
+            EmailClientMode::Sandbox,
+            crate::email_client::MessageStreams::default(),
+            crate::email_client::HttpTransportSettings::default(),
+        );
+
+        // no request should reach the (stand-in) provider
+        wiremock::Mock::given(matchers::any())
+            .respond_with(wiremock::ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await
+            .unwrap();
+
+        // a recognisable fake id still flows into the delivery log
+        assert!(outcome.message_id.unwrap().starts_with("sandbox-"));
+    }
+
+    #[tokio::test]
+    async fn send_email_attaches_the_configured_list_headers() {
+        let mock_server = MockServer::start().await;
+        let email_client = EmailClient::new(
+            mock_server.uri(),
+            email(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+            MailingListHeaders {
+                list_id: Some("Test List <list.example.com>".into()),
+                list_unsubscribe: Some("<mailto:unsubscribe@example.com>".into()),
+                message_id_domain: Some("example.com".into()),
+            },
+            EmailClientMode::Live,
+            crate::email_client::MessageStreams::default(),
+            crate::email_client::HttpTransportSettings::default(),
+        );
+
+        // a matcher that digs the header names out of the request body
+        struct ListHeadersMatcher;
+        impl wiremock::Match for ListHeadersMatcher {
+            fn matches(&self, request: &Request) -> bool {
+                let Ok(body) = serde_json::from_slice::<serde_json::Value>(&request.body) else {
+                    return false;
+                };
+                let Some(headers) = body.get("Headers").and_then(|h| h.as_array()) else {
+                    return false;
+                };
+                let names: Vec<_> = headers
+                    .iter()
+                    .filter_map(|h| h.get("Name").and_then(|n| n.as_str()))
+                    .collect();
+                names.contains(&"List-ID")
+                    && names.contains(&"List-Unsubscribe")
+                    && names.contains(&"List-Unsubscribe-Post")
+                    && names.contains(&"Message-ID")
+            }
+        }
+
+        wiremock::Mock::given(ListHeadersMatcher)
+            .respond_with(wiremock::ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_with_attaches_custom_headers_and_metadata() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        // a matcher asserting our per-message additions made it into the body
+        struct ExtrasMatcher;
+        impl wiremock::Match for ExtrasMatcher {
+            fn matches(&self, request: &Request) -> bool {
+                let Ok(body) = serde_json::from_slice::<serde_json::Value>(&request.body) else {
+                    return false;
+                };
+                let has_header = body
+                    .get("Headers")
+                    .and_then(|h| h.as_array())
+                    .is_some_and(|headers| {
+                        headers.iter().any(|h| {
+                            h.get("Name").and_then(|n| n.as_str()) == Some("X-Campaign")
+                                && h.get("Value").and_then(|v| v.as_str()) == Some("launch")
+                        })
+                    });
+                let has_metadata = body
+                    .get("Metadata")
+                    .and_then(|m| m.get("issue-id"))
+                    .and_then(|v| v.as_str())
+                    == Some("42");
+                has_header && has_metadata
+            }
+        }
+
+        wiremock::Mock::given(ExtrasMatcher)
+            .respond_with(wiremock::ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let extras = crate::email_client::MessageExtras {
+            headers: vec![("X-Campaign".to_string(), "launch".to_string())],
+            metadata: std::collections::HashMap::from([(
+                "issue-id".to_string(),
+                "42".to_string(),
+            )]),
+            ..Default::default()
+        };
+
+        let outcome = email_client
+            .send_email_with(&email(), &subject(), &content(), &content(), extras)
+            .await;
+
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn messages_ride_on_the_stream_configured_for_their_category() {
+        let mock_server = MockServer::start().await;
+        let email_client = EmailClient::new(
+            mock_server.uri(),
+            email(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+            MailingListHeaders::default(),
+            EmailClientMode::Live,
+            crate::email_client::MessageStreams {
+                transactional: Some("outbound".into()),
+                bulk: Some("broadcast".into()),
+            },
+            crate::email_client::HttpTransportSettings::default(),
+        );
+
+        // a matcher pinned to a specific MessageStream value
+        struct StreamMatcher(&'static str);
+        impl wiremock::Match for StreamMatcher {
+            fn matches(&self, request: &Request) -> bool {
+                let Ok(body) = serde_json::from_slice::<serde_json::Value>(&request.body) else {
+                    return false;
+                };
+                body.get("MessageStream").and_then(|s| s.as_str()) == Some(self.0)
+            }
+        }
+
+        // a plain send is transactional by default
+        wiremock::Mock::given(StreamMatcher("outbound"))
+            .respond_with(wiremock::ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        // a bulk send goes out on the broadcast stream
+        wiremock::Mock::given(StreamMatcher("broadcast"))
+            .respond_with(wiremock::ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+        assert_ok!(outcome);
+
+        let extras = crate::email_client::MessageExtras {
+            category: crate::email_client::MessageCategory::Bulk,
+            ..Default::default()
+        };
+        let outcome = email_client
+            .send_email_with(&email(), &subject(), &content(), &content(), extras)
+            .await;
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_returns_the_provider_message_id() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        wiremock::Mock::given(matchers::any())
+            .respond_with(wiremock::ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "To": "receiver@example.com",
+                    "MessageID": "0a129f77-17cb-4ed6-9c8e-caa1ac5bd82f",
+                    "ErrorCode": 0,
+                    "Message": "OK"
+                }),
+            ))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            outcome.message_id.as_deref(),
+            Some("0a129f77-17cb-4ed6-9c8e-caa1ac5bd82f")
+        );
     }
 }
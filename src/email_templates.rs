@@ -0,0 +1,140 @@
+//! Operator-editable email copy, kept in `email_templates` (one row per
+//! tenant and template, edited at /admin/settings/templates). A missing
+//! row means the built-in copy - the templates are an override, not a
+//! requirement. Bodies carry their dynamic parts as merge tags (for the
+//! confirmation email, `{{confirmation_link}}`; for the compliance
+//! footer, `{{unsubscribe_link}}` and friends), substituted at send time.
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// The template name for the double-opt-in confirmation email.
+pub const CONFIRMATION: &str = "confirmation";
+
+/// The merge tag a confirmation template must carry in both bodies.
+pub const CONFIRMATION_LINK_TAG: &str = "{{confirmation_link}}";
+
+/// The template name for the compliance footer the delivery worker
+/// appends to every outgoing issue.
+pub const COMPLIANCE_FOOTER: &str = "compliance_footer";
+
+/// The template name for the auto-acknowledgement sent when a reader
+/// replies to an issue. There is no built-in copy: the auto-responder is
+/// off until the tenant saves this template, and deleting it switches
+/// the responder back off.
+pub const REPLY_ACK: &str = "reply_ack";
+
+/// The merge tag for the per-recipient signed unsubscribe link.
+pub const UNSUBSCRIBE_LINK_TAG: &str = "{{unsubscribe_link}}";
+
+/// The merge tag for the physical mailing address from the site settings.
+pub const FOOTER_ADDRESS_TAG: &str = "{{footer_address}}";
+
+/// The merge tag for the newsletter name from the site settings.
+pub const NEWSLETTER_NAME_TAG: &str = "{{newsletter_name}}";
+
+pub struct EmailTemplate {
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
+
+/// The built-in compliance footer: why the email arrived, the physical
+/// mailing address CAN-SPAM requires, and a visible unsubscribe link.
+/// Everything dynamic rides in merge tags, so the same copy serves the
+/// template editor and the delivery worker. A footer has no subject -
+/// the column stores an empty string.
+pub fn built_in_compliance_footer() -> EmailTemplate {
+    EmailTemplate {
+        subject: String::new(),
+        html_body: format!(
+            "<hr><p>You are receiving this because you subscribed to {}.<br>\
+             {}<br>\
+             <a href=\"{}\">Unsubscribe</a></p>",
+            NEWSLETTER_NAME_TAG, FOOTER_ADDRESS_TAG, UNSUBSCRIBE_LINK_TAG
+        ),
+        text_body: format!(
+            "\n\n--\nYou are receiving this because you subscribed to {}.\n{}\nUnsubscribe: {}\n",
+            NEWSLETTER_NAME_TAG, FOOTER_ADDRESS_TAG, UNSUBSCRIBE_LINK_TAG
+        ),
+    }
+}
+
+/// The tenant's override for `template`, if one has been saved.
+#[tracing::instrument(skip(pool))]
+pub async fn get(
+    pool: &PgPool,
+    tenant_id: Uuid,
+    template: &str,
+) -> Result<Option<EmailTemplate>, sqlx::Error> {
+    sqlx::query_as!(
+        EmailTemplate,
+        r#"
+        SELECT subject, html_body, text_body
+        FROM email_templates
+        WHERE tenant_id = $1 AND template = $2
+        "#,
+        tenant_id,
+        template,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Save (or replace) the tenant's override for `template`.
+#[tracing::instrument(skip(pool, body))]
+pub async fn upsert(
+    pool: &PgPool,
+    tenant_id: Uuid,
+    template: &str,
+    body: &EmailTemplate,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO email_templates (tenant_id, template, subject, html_body, text_body)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (tenant_id, template)
+        DO UPDATE SET
+            subject = EXCLUDED.subject,
+            html_body = EXCLUDED.html_body,
+            text_body = EXCLUDED.text_body
+        "#,
+        tenant_id,
+        template,
+        body.subject,
+        body.html_body,
+        body.text_body,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Drop the override, returning the template to the built-in copy.
+#[tracing::instrument(skip(pool))]
+pub async fn delete(pool: &PgPool, tenant_id: Uuid, template: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM email_templates WHERE tenant_id = $1 AND template = $2",
+        tenant_id,
+        template,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_built_in_footer_carries_the_required_merge_tags() {
+        // both bodies must be able to receive the unsubscribe link and
+        // the physical address - without them the footer is decorative
+        let footer = built_in_compliance_footer();
+        for body in [&footer.html_body, &footer.text_body] {
+            assert!(body.contains(UNSUBSCRIBE_LINK_TAG));
+            assert!(body.contains(FOOTER_ADDRESS_TAG));
+        }
+    }
+}
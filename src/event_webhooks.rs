@@ -0,0 +1,66 @@
+//! Outgoing "something nice happened" notifications. Where crate::alerts
+//! is for incidents, this is for the team channel: an issue finishing its
+//! delivery run, a new confirmed subscriber, the list crossing a milestone.
+//! Messages go to every configured webhook URL with a payload that both
+//! Slack (`text`) and Discord (`content`) understand.
+
+use crate::configuration::EventWebhookSettings;
+
+pub struct EventWebhooks {
+    urls: Vec<String>,
+    milestones: Vec<i64>,
+    http_client: reqwest::Client,
+}
+
+impl EventWebhooks {
+    pub fn new(settings: &EventWebhookSettings) -> Self {
+        Self {
+            urls: settings.urls.clone(),
+            milestones: settings.milestones.clone(),
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    /// Lets callers skip the bookkeeping (count queries and the like) that
+    /// only exists to feed a notification nobody is listening for.
+    pub fn is_enabled(&self) -> bool {
+        !self.urls.is_empty()
+    }
+
+    /// Post a message to every configured webhook. Failures are logged and
+    /// swallowed - a chat outage must never affect the newsletter itself.
+    pub async fn announce(&self, text: &str) {
+        for url in &self.urls {
+            // one payload for both dialects: Slack reads `text` and
+            // ignores `content`, Discord does the opposite
+            let payload = serde_json::json!({ "text": text, "content": text });
+            let outcome = self
+                .http_client
+                .post(url)
+                .json(&payload)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = outcome {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to post an event notification to a webhook",
+                );
+            }
+        }
+    }
+
+    /// Called with the confirmed-subscriber count right after a
+    /// confirmation - announces if the count landed exactly on one of the
+    /// configured milestones.
+    pub async fn announce_if_milestone(&self, confirmed_count: i64) {
+        if self.milestones.contains(&confirmed_count) {
+            self.announce(&format!(
+                "The list just reached {} confirmed subscribers!",
+                confirmed_count
+            ))
+            .await;
+        }
+    }
+}
@@ -1,10 +1,207 @@
+use crate::clock::{Clock, SystemClock};
 use crate::domain::SubscriberEmail;
-use crate::email_client::EmailClient;
+use crate::email_client::{EmailClient, MessageCategory, MessageExtras};
+use crate::email_templates::{
+    EmailTemplate, FOOTER_ADDRESS_TAG, NEWSLETTER_NAME_TAG, UNSUBSCRIBE_LINK_TAG,
+};
+use crate::event_webhooks::EventWebhooks;
+use crate::signed_link::{LinkSigner, ISSUE_FEEDBACK, ONE_CLICK_UNSUBSCRIBE, POLL_VOTE};
 use crate::{configuration::Settings, startup};
+use governor::clock::DefaultClock;
+use governor::state::{InMemoryState, NotKeyed};
+use governor::{Quota, RateLimiter};
+use sqlx::postgres::PgListener;
 use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::num::NonZeroU32;
 use std::time::Duration;
 use tracing::{field::display, Span};
 use uuid::Uuid;
+
+// a single, non-keyed limiter - shared by reference between however many
+// worker tasks we spin up, so the cap applies to the process as a whole
+pub type EmailRateLimiter = RateLimiter<NotKeyed, InMemoryState, DefaultClock>;
+
+/// Build the shared send throttle from the configured messages-per-second
+/// and burst allowance.
+pub fn email_rate_limiter(messages_per_second: u32, burst_size: u32) -> EmailRateLimiter {
+    let rate = NonZeroU32::new(messages_per_second)
+        .expect("send_rate_per_second must be greater than zero");
+    let burst = NonZeroU32::new(burst_size).expect("send_burst_size must be greater than zero");
+    RateLimiter::direct(Quota::per_second(rate).allow_burst(burst))
+}
+
+// the notification channel pinged (via pg_notify) whenever new delivery
+// tasks are committed - the worker listens on this to wake up immediately
+// instead of waiting out its polling interval
+pub const DELIVERY_NOTIFICATION_CHANNEL: &str = "issue_delivery";
+
+// how long the unsubscribe link in an email header stays valid - generous,
+// because people click "Unsubscribe" on months-old newsletters
+const UNSUBSCRIBE_LINK_VALIDITY_DAYS: i64 = 90;
+
+// feedback and poll links live in the email body, so they should outlast
+// the few days an issue typically sits unread in an inbox
+const FEEDBACK_LINK_VALIDITY_DAYS: i64 = 30;
+
+// an issue's poll, loaded once per delivery task alongside the content
+struct IssuePoll {
+    id: Uuid,
+    question: String,
+    // (option id, label), in the order the author wrote them
+    options: Vec<(Uuid, String)>,
+}
+
+/// Mints the signed per-recipient links injected into every outgoing
+/// issue: the RFC 8058 `List-Unsubscribe` headers (plus the
+/// `List-Unsubscribe-Post` marker that lets mail clients - Gmail, most
+/// notably - POST to it without showing the reader a page) and the
+/// one-click feedback footer.
+pub struct RecipientLinks {
+    base_url: String,
+    signer: LinkSigner,
+}
+
+impl RecipientLinks {
+    pub fn new(base_url: String, signer: LinkSigner) -> Self {
+        Self { base_url, signer }
+    }
+
+    // the two headers for one recipient. These are appended after any
+    // static list-wide `List-Unsubscribe` from the email client settings -
+    // with Postmark the later occurrence wins, so the signed per-recipient
+    // link takes precedence
+    fn headers(
+        &self,
+        subscriber_id: Uuid,
+        // a tenant's verified custom domain, overriding the shared base url
+        link_base_url: Option<&str>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Vec<(String, String)> {
+        let expires_at = now + chrono::Duration::days(UNSUBSCRIBE_LINK_VALIDITY_DAYS);
+        let fragment = self
+            .signer
+            .query_fragment(subscriber_id, ONE_CLICK_UNSUBSCRIBE, expires_at);
+        let base_url = link_base_url.unwrap_or(&self.base_url);
+        vec![
+            (
+                "List-Unsubscribe".into(),
+                format!("<{}/unsubscribe/one-click?{}>", base_url, fragment),
+            ),
+            (
+                "List-Unsubscribe-Post".into(),
+                "List-Unsubscribe=One-Click".into(),
+            ),
+        ]
+    }
+
+    // the "reply with one click" footer for one recipient, as an (html,
+    // text) pair to append to the issue content. Every score shares one
+    // signature - the score itself rides in the query, the link only has
+    // to prove who is clicking
+    fn feedback_footer(
+        &self,
+        issue_id: Uuid,
+        subscriber_id: Uuid,
+        link_base_url: Option<&str>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> (String, String) {
+        let expires_at = now + chrono::Duration::days(FEEDBACK_LINK_VALIDITY_DAYS);
+        let fragment = self
+            .signer
+            .query_fragment(subscriber_id, ISSUE_FEEDBACK, expires_at);
+        let base_url = link_base_url.unwrap_or(&self.base_url);
+
+        let mut html = String::from(
+            "<hr><p>How was this issue? Reply with one click \
+             (1 = not for me, 5 = loved it):",
+        );
+        let mut text =
+            String::from("\n\n--\nHow was this issue? Reply with one click (1 = not for me, 5 = loved it):\n");
+        for score in 1..=5 {
+            let link = format!("{}/feedback/{}?score={}&{}", base_url, issue_id, score, fragment);
+            html.push_str(&format!(" <a href=\"{}\">{}</a>", link, score));
+            text.push_str(&format!("{}: {}\n", score, link));
+        }
+        html.push_str("</p>");
+        (html, text)
+    }
+
+    // the compliance footer every outgoing issue must carry: why the
+    // email arrived, the physical mailing address and a visible
+    // unsubscribe link. The copy is the tenant's template (or the
+    // built-in), with the dynamic parts substituted per recipient; the
+    // link reuses the one-click signing scheme, so a click unsubscribes
+    // without any form in between
+    fn compliance_footer(
+        &self,
+        template: &EmailTemplate,
+        settings: &crate::site_settings::SiteSettings,
+        subscriber_id: Uuid,
+        link_base_url: Option<&str>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> (String, String) {
+        let expires_at = now + chrono::Duration::days(UNSUBSCRIBE_LINK_VALIDITY_DAYS);
+        let fragment = self
+            .signer
+            .query_fragment(subscriber_id, ONE_CLICK_UNSUBSCRIBE, expires_at);
+        let base_url = link_base_url.unwrap_or(&self.base_url);
+        let link = format!("{}/unsubscribe/one-click?{}", base_url, fragment);
+
+        let html = template
+            .html_body
+            .replace(
+                NEWSLETTER_NAME_TAG,
+                &htmlescape::encode_minimal(&settings.newsletter_name),
+            )
+            .replace(
+                FOOTER_ADDRESS_TAG,
+                &htmlescape::encode_minimal(&settings.footer_address),
+            )
+            .replace(UNSUBSCRIBE_LINK_TAG, &link);
+        let text = template
+            .text_body
+            .replace(NEWSLETTER_NAME_TAG, &settings.newsletter_name)
+            .replace(FOOTER_ADDRESS_TAG, &settings.footer_address)
+            .replace(UNSUBSCRIBE_LINK_TAG, &link);
+        (html, text)
+    }
+
+    // the voting block for an issue's poll, as an (html, text) pair. Same
+    // signing scheme as the feedback footer: one signature per recipient,
+    // the chosen option rides in the query
+    fn poll_block(
+        &self,
+        poll: &IssuePoll,
+        subscriber_id: Uuid,
+        link_base_url: Option<&str>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> (String, String) {
+        let expires_at = now + chrono::Duration::days(FEEDBACK_LINK_VALIDITY_DAYS);
+        let fragment = self.signer.query_fragment(subscriber_id, POLL_VOTE, expires_at);
+        let base_url = link_base_url.unwrap_or(&self.base_url);
+
+        let mut html = format!(
+            "<hr><p><b>{}</b><br>",
+            htmlescape::encode_minimal(&poll.question)
+        );
+        let mut text = format!("\n\n--\n{}\n", poll.question);
+        for (option_id, label) in &poll.options {
+            let link = format!(
+                "{}/poll/{}/vote?option={}&{}",
+                base_url, poll.id, option_id, fragment
+            );
+            html.push_str(&format!(
+                "<a href=\"{}\">{}</a><br>",
+                link,
+                htmlescape::encode_minimal(label)
+            ));
+            text.push_str(&format!("{}: {}\n", label, link));
+        }
+        html.push_str("</p>");
+        (html, text)
+    }
+}
+
 // used to define if there is a task in the queue or not
 pub enum ExecutionOutcome {
     TaskCompleted,
@@ -22,6 +219,11 @@ pub enum ExecutionOutcome {
 pub async fn try_execute_task(
     pool: &PgPool,
     email_client: &EmailClient,
+    rate_limiter: &EmailRateLimiter,
+    webhooks: &EventWebhooks,
+    recipient_links: &RecipientLinks,
+    bus: &crate::message_bus::MessageBus,
+    now: chrono::DateTime<chrono::Utc>,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
     // send the emails
     let task = dequeue_task(pool).await?;
@@ -31,41 +233,153 @@ pub async fn try_execute_task(
     }
 
     // otherwise, proceed
-    let (transaction, issue_id, email) = task.unwrap();
+    let (mut transaction, issue_id, email) = task.unwrap();
 
     Span::current()
         .record("newsletter_issue_id", display(issue_id))
         .record("subscriber_email", display(&email));
 
-    // remove the task from the queue - this commits the transaction
-    delete_task(transaction, issue_id, &email).await?;
-
-    // NOTE - we do not retry to send - if the below fails, it has already
-    // been removed from the queue. You can implement this easily enough -
-    // keep track of number of retries for that row (add another column) and
-    // keep the row in the queue until it is successful or has had x retries
+    // NOTE - permanently failed sends are not retried - a 4xx means the
+    // request itself is bad, so the task is removed from the queue and the
+    // subscriber skipped. Transient failures (5xx, 429, timeouts) roll the
+    // transaction back so the task stays queued for another attempt
 
     // try to parse the email address into our Subscriber Email type
     match SubscriberEmail::parse(email.clone()) {
         Ok(email_address) => {
             // get the email body to send
-            let issue = get_issue(pool, issue_id).await?;
+            let mut issue = get_issue(pool, issue_id).await?;
+
+            // the queue stores only the email address, but the per-recipient
+            // links are signed over the subscriber's id - look it up. A miss
+            // (the subscriber was deleted mid-run) just means no header and
+            // no feedback footer
+            let subscriber = match get_subscriber_id(pool, &email).await {
+                Ok(subscriber) => subscriber,
+                Err(e) => {
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        "Failed to look up a subscriber id for the unsubscribe header",
+                    );
+                    None
+                }
+            };
+            let subscriber_id = subscriber.as_ref().map(|(id, _, _)| *id);
+            // a newsletter issue is bulk mail - it rides on the bulk
+            // stream, well away from account-critical sends
+            let mut extras = MessageExtras {
+                category: MessageCategory::Bulk,
+                ..Default::default()
+            };
+            if let Some((subscriber_id, tenant_id, link_base_url)) = &subscriber {
+                extras.headers =
+                    recipient_links.headers(*subscriber_id, link_base_url.as_deref(), now);
+                // the issue's poll, if the author attached one, goes above
+                // the feedback footer
+                if let Some(poll) = get_issue_poll(pool, issue_id).await? {
+                    let (poll_html, poll_text) = recipient_links.poll_block(
+                        &poll,
+                        *subscriber_id,
+                        link_base_url.as_deref(),
+                        now,
+                    );
+                    issue.html_content.push_str(&poll_html);
+                    issue.text_content.push_str(&poll_text);
+                }
+                let (footer_html, footer_text) = recipient_links.feedback_footer(
+                    issue_id,
+                    *subscriber_id,
+                    link_base_url.as_deref(),
+                    now,
+                );
+                issue.html_content.push_str(&footer_html);
+                issue.text_content.push_str(&footer_text);
+
+                // the compliance footer goes last - below even the
+                // feedback links, where readers expect to find the
+                // unsubscribe link. Appending it here (rather than at
+                // publish time) means no issue can leave without it
+                let settings = crate::site_settings::get(pool).await?;
+                let template = crate::email_templates::get(
+                    pool,
+                    *tenant_id,
+                    crate::email_templates::COMPLIANCE_FOOTER,
+                )
+                .await?
+                .unwrap_or_else(crate::email_templates::built_in_compliance_footer);
+                let (compliance_html, compliance_text) = recipient_links.compliance_footer(
+                    &template,
+                    &settings,
+                    *subscriber_id,
+                    link_base_url.as_deref(),
+                    now,
+                );
+                issue.html_content.push_str(&compliance_html);
+                issue.text_content.push_str(&compliance_text);
+            }
+
+            // wait for the shared throttle to hand us a send slot - this is
+            // what keeps a burst of concurrent workers inside the provider's
+            // per-second limits
+            rate_limiter.until_ready().await;
+
             // try to send the email
-            if let Err(e) = email_client
-                .send_email(
+            match email_client
+                .send_email_with(
                     &email_address,
                     &issue.title,
                     &issue.html_content,
                     &issue.text_content,
+                    extras,
                 )
                 .await
             {
-                // if error sending the email, log it
-                tracing::error!(
-                    error.cause_chain = ?e,
-                    error.message = %e,
-                    "Failed to deliver issue to a confirmed subscriber. Skipping.",
-                );
+                Ok(outcome) => {
+                    // record the send (and the provider's MessageID) in the
+                    // delivery log - same transaction as the dequeue, so the
+                    // log entry and the task removal commit together
+                    record_delivery(
+                        &mut transaction,
+                        issue_id,
+                        &email,
+                        outcome.message_id.as_deref(),
+                    )
+                    .await?;
+
+                    // and the pseudonymous analytics trail
+                    crate::domain_events::record(
+                        pool,
+                        bus,
+                        crate::domain_events::EMAIL_DELIVERED,
+                        subscriber_id,
+                        now,
+                    )
+                    .await;
+                }
+                Err(e) if e.is_transient() => {
+                    // keep the task in the queue and surface the error - the
+                    // worker loop reads any Retry-After off it to back off
+                    transaction.rollback().await?;
+                    return Err(anyhow::Error::from(e)
+                        .context("A transient failure occurred while delivering an issue."));
+                }
+                Err(e) => {
+                    // a permanent rejection - record it and drop the task
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to deliver issue to a confirmed subscriber. Skipping.",
+                    );
+                    record_failure(&mut transaction, issue_id, &email).await?;
+                    crate::domain_events::record(
+                        pool,
+                        bus,
+                        crate::domain_events::DELIVERY_FAILED,
+                        subscriber_id,
+                        now,
+                    )
+                    .await;
+                }
             }
         } // if an error parsing the email address, log it
         Err(e) => {
@@ -77,9 +391,88 @@ pub async fn try_execute_task(
         }
     }
 
+    // remove the task from the queue - this commits the transaction
+    delete_task(transaction, issue_id, &email).await?;
+
+    // was that the issue's last task? then the delivery run just finished.
+    // (with several workers racing on the final few rows the announcement
+    // can occasionally double up - tolerable for a chat message)
+    if webhooks.is_enabled() {
+        match count_remaining_tasks(pool, issue_id).await {
+            Ok(0) => {
+                let title = get_issue(pool, issue_id)
+                    .await
+                    .map(|issue| issue.title)
+                    .unwrap_or_else(|_| issue_id.to_string());
+                webhooks
+                    .announce(&format!("Issue \"{}\" has finished delivering.", title))
+                    .await;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    "Failed to check whether an issue finished delivering",
+                );
+            }
+        }
+    }
+
     Ok(ExecutionOutcome::TaskCompleted)
 }
 
+// the id behind a queued email address - the queue predates signed links
+// and only carries the address itself. The tenant id (which compliance
+// footer template applies) and the tenant's verified link domain come
+// along for the ride, so the unsubscribe link lives on the same domain
+// as everything else in the email
+async fn get_subscriber_id(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<(Uuid, Uuid, Option<String>)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT s.id, s.tenant_id, t.link_domain, t.link_domain_verified_at
+        FROM subscriptions s
+        JOIN tenants t ON t.id = s.tenant_id
+        WHERE s.email = $1 AND s.deleted_at IS NULL
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| {
+        let link_base_url = r
+            .link_domain
+            .filter(|_| r.link_domain_verified_at.is_some())
+            .map(|domain| format!("https://{}", domain));
+        (r.id, r.tenant_id, link_base_url)
+    }))
+}
+
+// whether the operator has hit the pause button - see routes::admin::delivery
+async fn delivery_paused(pool: &PgPool) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(r#"SELECT paused FROM delivery_state"#)
+        .fetch_one(pool)
+        .await?;
+    Ok(row.paused)
+}
+
+// how many deliveries (ready or deferred) an issue still has queued
+async fn count_remaining_tasks(pool: &PgPool, issue_id: Uuid) -> Result<i64, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT COUNT(*) as "remaining!"
+        FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row.remaining)
+}
+
 // make a short name for the sqlx transaction
 type PgTransaction = Transaction<'static, Postgres>;
 
@@ -91,12 +484,36 @@ async fn dequeue_task(
 
     // get the first row of the 'email's to send' queue - actually
     // the first one that is not locked by another thread - we will have
-    // multiple threads sending these out
+    // multiple threads sending these out. Highest priority first, so an
+    // urgent send doesn't sit behind a 50k-recipient bulk issue.
+    //
+    // Within a priority band the queue is walked in domain-aware waves:
+    // each recipient domain's rows are ranked, and rank 1 of every domain
+    // goes out before rank 2 of any. A list heavy on one provider would
+    // otherwise hammer a single MX with a long unbroken run - exactly the
+    // pattern that triggers greylisting and temporary deferrals - while
+    // the interleave looks, from any one provider's side, like a slow
+    // trickle. The ranking is recomputed per dequeue; the ready set is
+    // rarely more than a few tens of thousands of rows, and the spread
+    // it buys is worth a window function
     let row = sqlx::query!(
         r#"
-            SELECT newsletter_issue_id, subscriber_email
-            FROM issue_delivery_queue
-            FOR UPDATE
+            WITH ranked AS (
+                SELECT newsletter_issue_id, subscriber_email,
+                    row_number() OVER (
+                        PARTITION BY split_part(subscriber_email, '@', 2)
+                        ORDER BY available_at ASC
+                    ) AS wave
+                FROM issue_delivery_queue
+                WHERE available_at <= now()
+            )
+            SELECT q.newsletter_issue_id, q.subscriber_email
+            FROM issue_delivery_queue q
+            JOIN ranked
+                ON ranked.newsletter_issue_id = q.newsletter_issue_id
+                AND ranked.subscriber_email = q.subscriber_email
+            ORDER BY q.priority DESC, ranked.wave ASC, q.available_at ASC
+            FOR UPDATE OF q
             SKIP LOCKED
             LIMIT 1
         "#,
@@ -139,6 +556,61 @@ async fn delete_task(
     Ok(())
 }
 
+// note down a completed send, keyed by issue + recipient, with whatever
+// message id the provider handed back
+#[tracing::instrument(skip_all)]
+async fn record_delivery(
+    transaction: &mut PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+    provider_message_id: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO email_delivery_log (
+            id,
+            newsletter_issue_id,
+            recipient_email,
+            provider_message_id,
+            sent_at
+        )
+        VALUES ($1, $2, $3, $4, now())
+        "#,
+        Uuid::new_v4(),
+        issue_id,
+        email,
+        provider_message_id
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+// a permanently rejected send - same log table as successes, flagged, so
+// the comparison report can put a failure count next to the delivery count
+async fn record_failure(
+    transaction: &mut PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO email_delivery_log (
+            id,
+            newsletter_issue_id,
+            recipient_email,
+            sent_at,
+            failed
+        )
+        VALUES ($1, $2, $3, now(), true)
+        "#,
+        Uuid::new_v4(),
+        issue_id,
+        email,
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
 struct NewsletterIssue {
     title: String,
     text_content: String,
@@ -164,34 +636,270 @@ async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, any
     Ok(issue)
 }
 
+// the issue's poll, if the author attached one when composing it
+async fn get_issue_poll(pool: &PgPool, issue_id: Uuid) -> Result<Option<IssuePoll>, anyhow::Error> {
+    let poll = sqlx::query!(
+        "SELECT id, question FROM issue_polls WHERE newsletter_issue_id = $1",
+        issue_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(poll) = poll else {
+        return Ok(None);
+    };
+    let options = sqlx::query!(
+        "SELECT id, label FROM poll_options WHERE poll_id = $1 ORDER BY position",
+        poll.id,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(Some(IssuePoll {
+        id: poll.id,
+        question: poll.question,
+        options: options.into_iter().map(|o| (o.id, o.label)).collect(),
+    }))
+}
+
+// how often a busy loop bothers writing its heartbeat - every task would
+// just be noise on the database
+const HEARTBEAT_INTERVAL_SECONDS: i64 = 15;
+
+// how often the partition upkeep for the log tables runs (see
+// crate::log_maintenance) - months only roll over so often
+const LOG_MAINTENANCE_INTERVAL_SECONDS: i64 = 6 * 60 * 60;
+
+// upsert this worker's row - same id, newer last_seen_at
+#[tracing::instrument(skip(pool))]
+async fn record_heartbeat(
+    pool: &PgPool,
+    worker_id: Uuid,
+    started_at: chrono::DateTime<chrono::Utc>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO worker_heartbeats (worker_id, started_at, last_seen_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (worker_id)
+        DO UPDATE SET last_seen_at = EXCLUDED.last_seen_at
+        "#,
+        worker_id,
+        started_at,
+        now
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
 // an infinite loop that attempts to complete all tasks
-async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
+#[allow(clippy::too_many_arguments)]
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    rate_limiter: EmailRateLimiter,
+    send_window: Option<crate::configuration::SendWindowSettings>,
+    clock: std::sync::Arc<dyn Clock>,
+    webhooks: EventWebhooks,
+    recipient_links: RecipientLinks,
+    bus: crate::message_bus::MessageBus,
+    retention: crate::configuration::RetentionSettings,
+    worker: crate::configuration::WorkerSettings,
+) -> Result<(), anyhow::Error> {
+    // subscribe to the channel notified by `enqueue_delivery_tasks` - postgres
+    // only dispatches the notification once the enqueueing transaction commits,
+    // so a wake-up always means there is (or was) work to pick up
+    let mut listener = PgListener::connect_with(&pool).await?;
+    listener.listen(DELIVERY_NOTIFICATION_CHANNEL).await?;
+
+    // this loop's identity in worker_heartbeats - a fresh row per start-up,
+    // so the diagnostics page can tell a restart from a recovery
+    let worker_id = Uuid::new_v4();
+    let started_at = clock.now();
+    let mut last_heartbeat = None;
+    let mut last_log_maintenance = None;
+    // errors in a row - drives the exponential backoff below
+    let mut consecutive_errors = 0u32;
+
     loop {
+        // let the monitor know we're alive - a failed beat is logged but
+        // doesn't stop deliveries
+        let now = clock.now();
+        let beat_due = last_heartbeat
+            .is_none_or(|at| now - at >= chrono::Duration::seconds(HEARTBEAT_INTERVAL_SECONDS));
+        if beat_due {
+            match record_heartbeat(&pool, worker_id, started_at, now).await {
+                Ok(()) => last_heartbeat = Some(now),
+                Err(e) => {
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        "Failed to record a worker heartbeat",
+                    );
+                }
+            }
+        }
+
+        // keep the log-table partitions ahead of the calendar (and apply
+        // any configured retention) - idempotent, so racing workers are
+        // harmless, and a failure only means the default partition catches
+        // the rows until the next pass
+        let maintenance_due = last_log_maintenance.is_none_or(|at| {
+            now - at >= chrono::Duration::seconds(LOG_MAINTENANCE_INTERVAL_SECONDS)
+        });
+        if maintenance_due {
+            match crate::log_maintenance::maintain(&pool, &retention, now).await {
+                Ok(()) => last_log_maintenance = Some(now),
+                Err(e) => {
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        "Failed to maintain the log-table partitions",
+                    );
+                    // don't hammer a broken database every pass
+                    last_log_maintenance = Some(now);
+                }
+            }
+        }
+
+        // the operator's pause switch (POST /admin/delivery/pause) - polled
+        // every pass, so a pause takes effect within seconds, mid-issue
+        // included. A failed read is logged and delivery carries on: if the
+        // database is down, the dequeue below fails anyway
+        match delivery_paused(&pool).await {
+            Ok(true) => {
+                tracing::info!("Deliveries are paused by the operator. Holding the queue.");
+                clock.sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    "Failed to read the delivery pause flag",
+                );
+            }
+        }
+
+        // respect quiet hours - defer dequeuing entirely until the send
+        // window opens rather than emailing people in the small hours
+        if let Some(window) = &send_window {
+            if let Some(wait) = window.time_until_open(clock.now()) {
+                tracing::info!(
+                    wait_seconds = wait.as_secs(),
+                    "Outside the configured send window. Deferring deliveries.",
+                );
+                clock.sleep(wait).await;
+                continue;
+            }
+        }
+
+        // a very large publish leaves a fill job instead of queue rows
+        // (see routes::admin::newsletter) - advance it by one chunk per
+        // pass, so sends interleave with the fill and delivery starts
+        // before the subscriber scan finishes
+        if let Err(e) = crate::routes::advance_enqueue_job(&pool).await {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                "Failed to advance a bulk enqueue job",
+            );
+        }
+
         // if there is nothing in the db but task is not completed,
         // wait a few seconds and retry
         // if there's an error wait 1 second and retry
         // when task completed, return
-        match try_execute_task(&pool, &email_client).await {
+        match try_execute_task(
+            &pool,
+            &email_client,
+            &rate_limiter,
+            &webhooks,
+            &recipient_links,
+            &bus,
+            clock.now(),
+        )
+        .await
+        {
             Ok(ExecutionOutcome::EmptyQueue) => {
-                tokio::time::sleep(Duration::from_secs(10)).await;
+                consecutive_errors = 0;
+                // wait for a notification that new tasks have been enqueued -
+                // keeping the configurable sleep as a fallback in case the
+                // listener connection drops and we miss a notification
+                tokio::select! {
+                    _ = listener.recv() => {}
+                    _ = clock.sleep(Duration::from_secs(worker.empty_queue_sleep_seconds)) => {}
+                }
+            }
+            Err(e) => {
+                consecutive_errors += 1;
+                // if the provider told us when to come back, honour that -
+                // otherwise back off exponentially on repeated failures
+                // (see WorkerSettings::error_backoff)
+                let delay = e
+                    .downcast_ref::<crate::email_client::SendError>()
+                    .and_then(|e| e.retry_after())
+                    .unwrap_or_else(|| worker.error_backoff(consecutive_errors));
+                clock.sleep(delay).await;
             }
-            Err(_) => {
-                tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok(ExecutionOutcome::TaskCompleted) => {
+                consecutive_errors = 0;
             }
-            Ok(ExecutionOutcome::TaskCompleted) => {}
         }
     }
 }
 
 // use the above fn to complete all tasks - this is run as a task in Main()
 pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    run_worker_with_clock(configuration, std::sync::Arc::new(SystemClock)).await
+}
+
+// the clock is a parameter so scheduled-send tests can drive the loop
+// with a `TestClock` instead of waiting out real quiet hours
+pub async fn run_worker_with_clock(
+    configuration: Settings,
+    clock: std::sync::Arc<dyn Clock>,
+) -> Result<(), anyhow::Error> {
     // get a separate connection tot he db - note we don't NEED to do this
     // could get an ARC pointer as we have been doing elsewhere
     let connection_pool = startup::get_connection_pool(&configuration.database);
 
+    // the shared throttle - built before `client()` consumes the settings
+    let rate_limiter = email_rate_limiter(
+        configuration.email_client.send_rate_per_second,
+        configuration.email_client.send_burst_size,
+    );
+
     // get the client from config
     let email_client = configuration.email_client.client();
 
+    // the channel(s) to ping when an issue finishes its run
+    let webhooks = EventWebhooks::new(&configuration.event_webhooks);
+
+    // the same signing keys the app uses, so the endpoint can verify what
+    // the worker mints
+    let mut signer = LinkSigner::with_current_key(
+        configuration.application.hmac_key_version,
+        configuration.application.hmac_secret.clone(),
+    );
+    for previous in configuration.application.previous_hmac_secrets {
+        signer.add_previous_key(previous.version, previous.secret);
+    }
+    let recipient_links =
+        RecipientLinks::new(configuration.application.base_url.clone(), signer);
+
+    // the optional analytics mirror - inert unless configured
+    let bus = crate::message_bus::MessageBus::new(&configuration.message_bus);
+
     // start sending
-    worker_loop(connection_pool, email_client).await
+    worker_loop(
+        connection_pool,
+        email_client,
+        rate_limiter,
+        configuration.send_window,
+        clock,
+        webhooks,
+        recipient_links,
+        bus,
+        configuration.retention,
+        configuration.worker,
+    )
+    .await
 }
@@ -1,12 +1,36 @@
 //! src/lib.rs
+pub mod alerts;
 pub mod authentication;
+pub mod backup;
+pub mod bootstrap;
+pub mod canonical;
+pub mod clock;
 pub mod configuration;
+pub mod custom_pages;
 pub mod domain;
+pub mod domain_events;
 pub mod email_client;
+pub mod email_templates;
+pub mod event_webhooks;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod lifecycle;
+pub mod log_maintenance;
+pub mod message_bus;
+pub mod migration_guard;
+pub mod payments;
+pub mod premailer;
+pub mod queue_metrics;
+pub mod redis_sessions;
 pub mod routes;
+pub mod schema_docs;
+pub mod seed;
 pub mod session_state;
+pub mod signed_link;
+pub mod site_settings;
 pub mod startup;
 pub mod telemetry;
+pub mod templates;
+pub mod tenancy;
 pub mod utils;
+pub mod worker_monitor;
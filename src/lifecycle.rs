@@ -0,0 +1,192 @@
+//! The process lifecycle. Subsystems register startup hooks, long-running
+//! tasks and shutdown hooks; `run` then owns the whole arc: startup hooks
+//! in registration order (a failure aborts the launch), tasks spawned
+//! together, and - once the first task exits - the rest aborted and the
+//! shutdown hooks run in reverse registration order, so nothing is torn
+//! down before something registered after it (and therefore possibly
+//! depending on it). Replaces the pile of `tokio::spawn`/`select!` calls
+//! that used to live in main.rs.
+
+use anyhow::Context;
+use std::future::Future;
+use std::pin::Pin;
+use tokio::task::JoinSet;
+
+type HookFuture = Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + Send>>;
+
+// a named future - the name is what the logs call it
+struct Hook {
+    name: String,
+    future: HookFuture,
+}
+
+fn hook<F, E>(name: &str, future: F) -> Hook
+where
+    F: Future<Output = Result<(), E>> + Send + 'static,
+    E: Into<anyhow::Error>,
+{
+    Hook {
+        name: name.to_string(),
+        future: Box::pin(async move { future.await.map_err(Into::into) }),
+    }
+}
+
+#[derive(Default)]
+pub struct Lifecycle {
+    on_startup: Vec<Hook>,
+    tasks: Vec<Hook>,
+    on_shutdown: Vec<Hook>,
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `future` before any task starts. Hooks run sequentially, in
+    /// registration order; a failure aborts the whole launch.
+    pub fn on_startup<F, E>(&mut self, name: &str, future: F)
+    where
+        F: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<anyhow::Error>,
+    {
+        self.on_startup.push(hook(name, future));
+    }
+
+    /// Register a long-running subsystem. The tasks start together once
+    /// every startup hook has passed, and the process lives until the
+    /// first of them exits - however it exits.
+    pub fn task<F, E>(&mut self, name: &str, future: F)
+    where
+        F: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<anyhow::Error>,
+    {
+        self.tasks.push(hook(name, future));
+    }
+
+    /// Run `future` after the tasks have stopped. Hooks run sequentially
+    /// in reverse registration order; failures are logged, never
+    /// propagated - a shutdown must run to the end.
+    pub fn on_shutdown<F, E>(&mut self, name: &str, future: F)
+    where
+        F: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<anyhow::Error>,
+    {
+        self.on_shutdown.push(hook(name, future));
+    }
+
+    /// Startup hooks, then the tasks, then the shutdown hooks.
+    pub async fn run(mut self) -> Result<(), anyhow::Error> {
+        for startup in self.on_startup.drain(..) {
+            tracing::info!("Running the '{}' startup hook", startup.name);
+            startup
+                .future
+                .await
+                .with_context(|| format!("The '{}' startup hook failed", startup.name))?;
+        }
+
+        let mut running = JoinSet::new();
+        for task in self.tasks.drain(..) {
+            let name = task.name;
+            let future = task.future;
+            running.spawn(async move { (name, future.await) });
+        }
+
+        // the first exit - clean or not - takes the whole process down;
+        // half an application quietly serving is worse than a restart
+        if let Some(first) = running.join_next().await {
+            match first {
+                Ok((name, Ok(()))) => tracing::info!("{} has exited", name),
+                Ok((name, Err(e))) => tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "{} failed",
+                    name,
+                ),
+                Err(e) => tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "A task failed to complete",
+                ),
+            }
+            running.abort_all();
+            while running.join_next().await.is_some() {}
+        }
+
+        for shutdown in self.on_shutdown.drain(..).rev() {
+            tracing::info!("Running the '{}' shutdown hook", shutdown.name);
+            if let Err(e) = shutdown.future.await {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    "The '{}' shutdown hook failed",
+                    shutdown.name,
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    // a hook that appends its label to a shared trace when it runs
+    fn recorder(trace: &Arc<Mutex<Vec<&'static str>>>, label: &'static str) -> HookFuture {
+        let trace = trace.clone();
+        Box::pin(async move {
+            trace.lock().unwrap().push(label);
+            Ok::<(), anyhow::Error>(())
+        })
+    }
+
+    #[tokio::test]
+    async fn hooks_run_in_order_and_shutdown_runs_in_reverse() {
+        let trace = Arc::new(Mutex::new(Vec::new()));
+        let mut lifecycle = Lifecycle::new();
+        lifecycle.on_startup("first", recorder(&trace, "startup-1"));
+        lifecycle.on_startup("second", recorder(&trace, "startup-2"));
+        lifecycle.task("task", recorder(&trace, "task"));
+        lifecycle.on_shutdown("first", recorder(&trace, "shutdown-1"));
+        lifecycle.on_shutdown("second", recorder(&trace, "shutdown-2"));
+
+        lifecycle.run().await.unwrap();
+
+        assert_eq!(
+            *trace.lock().unwrap(),
+            vec!["startup-1", "startup-2", "task", "shutdown-2", "shutdown-1"],
+        );
+    }
+
+    #[tokio::test]
+    async fn a_failing_startup_hook_aborts_the_launch() {
+        let trace = Arc::new(Mutex::new(Vec::new()));
+        let mut lifecycle = Lifecycle::new();
+        lifecycle.on_startup("doomed", async {
+            Err::<(), _>(anyhow::anyhow!("the database is on fire"))
+        });
+        lifecycle.task("task", recorder(&trace, "task"));
+
+        let outcome = lifecycle.run().await;
+
+        assert!(outcome.is_err());
+        assert!(trace.lock().unwrap().is_empty(), "no task should have run");
+    }
+
+    #[tokio::test]
+    async fn one_task_exiting_stops_the_rest() {
+        let trace = Arc::new(Mutex::new(Vec::new()));
+        let mut lifecycle = Lifecycle::new();
+        lifecycle.task("short-lived", recorder(&trace, "task"));
+        // without the abort this would hold `run` open forever
+        lifecycle.task("long-lived", async {
+            std::future::pending::<Result<(), anyhow::Error>>().await
+        });
+        lifecycle.on_shutdown("cleanup", recorder(&trace, "shutdown"));
+
+        lifecycle.run().await.unwrap();
+
+        assert_eq!(*trace.lock().unwrap(), vec!["task", "shutdown"]);
+    }
+}
@@ -0,0 +1,173 @@
+//! Partition upkeep for the append-only log tables.
+//!
+//! `email_delivery_log` and `email_tracking_events` are range-partitioned
+//! by month (see the partitioning migration). The delivery worker calls
+//! [`maintain`] periodically to create the current and the next month's
+//! partitions ahead of the rows that will land in them, and - where a
+//! retention is configured - to drop whole months that have aged out.
+//! Dropping a partition is a metadata operation, so a year of tracking
+//! events disappears without a long-running DELETE touching the table.
+
+use crate::configuration::RetentionSettings;
+use anyhow::Context;
+use chrono::{Datelike, NaiveDate};
+use sqlx::PgPool;
+
+/// One maintenance pass over both partitioned tables. Everything in here
+/// is idempotent (`IF NOT EXISTS` / `IF EXISTS`), so concurrent workers
+/// racing on the same pass do no harm.
+#[tracing::instrument(skip_all)]
+pub async fn maintain(
+    pool: &PgPool,
+    settings: &RetentionSettings,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(), anyhow::Error> {
+    let today = now.date_naive();
+    for (table, column, retention_days) in [
+        ("email_delivery_log", "sent_at", settings.delivery_log_days),
+        (
+            "email_tracking_events",
+            "occurred_at",
+            settings.tracking_events_days,
+        ),
+    ] {
+        ensure_partitions(pool, table, today)
+            .await
+            .with_context(|| format!("Failed to create partitions for {}", table))?;
+        // 0 means keep forever - the configured default
+        if retention_days > 0 {
+            drop_expired_partitions(pool, table, retention_days, today)
+                .await
+                .with_context(|| {
+                    format!("Failed to drop expired {} partitions ({})", table, column)
+                })?;
+        }
+    }
+    Ok(())
+}
+
+// e.g. "email_delivery_log_y2026m09" - one month's rows
+fn partition_name(table: &str, month_start: NaiveDate) -> String {
+    format!(
+        "{}_y{:04}m{:02}",
+        table,
+        month_start.year(),
+        month_start.month()
+    )
+}
+
+fn month_start(date: NaiveDate) -> NaiveDate {
+    NaiveDate::from_ymd_opt(date.year(), date.month(), 1)
+        .expect("The first of a valid month is always a valid date")
+}
+
+fn next_month(month_start: NaiveDate) -> NaiveDate {
+    if month_start.month() == 12 {
+        NaiveDate::from_ymd_opt(month_start.year() + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(month_start.year(), month_start.month() + 1, 1)
+    }
+    .expect("The first of a valid month is always a valid date")
+}
+
+// the current and the next month - created ahead of time, so rows never
+// have to fall back to the default partition under normal operation.
+// DDL takes no bind parameters; every piece interpolated here is built
+// locally from a date, never from user input
+async fn ensure_partitions(
+    pool: &PgPool,
+    table: &str,
+    today: NaiveDate,
+) -> Result<(), sqlx::Error> {
+    let this_month = month_start(today);
+    for from in [this_month, next_month(this_month)] {
+        let to = next_month(from);
+        let sql = format!(
+            r#"CREATE TABLE IF NOT EXISTS {name} PARTITION OF {table}
+                FOR VALUES FROM ('{from}') TO ('{to}')"#,
+            name = partition_name(table, from),
+        );
+        sqlx::query(&sql).execute(pool).await?;
+    }
+    Ok(())
+}
+
+// every monthly partition whose whole range lies past the retention cutoff
+// is dropped outright. The default partition (pre-partitioning rows) is
+// never touched - its name doesn't parse as a month
+async fn drop_expired_partitions(
+    pool: &PgPool,
+    table: &str,
+    retention_days: i64,
+    today: NaiveDate,
+) -> Result<(), sqlx::Error> {
+    let cutoff = today - chrono::Duration::days(retention_days);
+    let children = sqlx::query!(
+        r#"
+        SELECT c.relname as "relname!"
+        FROM pg_inherits i
+        JOIN pg_class c ON c.oid = i.inhrelid
+        JOIN pg_class p ON p.oid = i.inhparent
+        WHERE p.relname = $1
+        "#,
+        table
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for child in children {
+        let Some(from) = parse_partition_month(table, &child.relname) else {
+            continue;
+        };
+        // the partition holds rows up to (exclusive) the next month's start
+        if next_month(from) <= cutoff {
+            let sql = format!("DROP TABLE IF EXISTS {}", child.relname);
+            sqlx::query(&sql).execute(pool).await?;
+            tracing::info!(
+                table,
+                partition = %child.relname,
+                "Dropped a log partition past its retention",
+            );
+        }
+    }
+    Ok(())
+}
+
+// the inverse of `partition_name` - None for anything that isn't one of
+// ours (the default partition, most notably)
+fn parse_partition_month(table: &str, relname: &str) -> Option<NaiveDate> {
+    let suffix = relname.strip_prefix(table)?.strip_prefix("_y")?;
+    let (year, month) = suffix.split_once('m')?;
+    NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{next_month, parse_partition_month, partition_name};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn partition_names_round_trip() {
+        let december = NaiveDate::from_ymd_opt(2026, 12, 1).unwrap();
+        let name = partition_name("email_delivery_log", december);
+        assert_eq!(name, "email_delivery_log_y2026m12");
+        assert_eq!(
+            parse_partition_month("email_delivery_log", &name),
+            Some(december)
+        );
+        // the default partition never parses, so it can never be dropped
+        assert_eq!(
+            parse_partition_month("email_delivery_log", "email_delivery_log_default"),
+            None
+        );
+    }
+
+    #[test]
+    fn next_month_rolls_over_the_year() {
+        let december = NaiveDate::from_ymd_opt(2026, 12, 1).unwrap();
+        assert_eq!(
+            next_month(december),
+            NaiveDate::from_ymd_opt(2027, 1, 1).unwrap()
+        );
+    }
+}
@@ -1,9 +1,14 @@
-use std::fmt::{Debug, Display};
-use tokio::task::JoinError;
+use zero2prod::backup;
+use zero2prod::bootstrap;
 use zero2prod::configuration;
 use zero2prod::issue_delivery_worker;
+use zero2prod::lifecycle::Lifecycle;
+use zero2prod::migration_guard;
+use zero2prod::schema_docs;
+use zero2prod::seed;
 use zero2prod::startup::Application;
 use zero2prod::telemetry;
+use zero2prod::worker_monitor;
 
 #[tokio::main] // a procedural macro that wraps synchronous main() in async fn -
                // otherwise async main not allowed, and this return type not allowed
@@ -17,47 +22,168 @@ async fn main() -> anyhow::Result<()> {
     let configuration =
         configuration::get_configuration().expect("Failed to read configuration.yaml");
 
-    // await the future here - we can call main as a non-blocking
-    // task in tests etc
-    let application = Application::build(configuration.clone()).await?; // build the app
+    // maintenance subcommands - `clean-test-dbs` sweeps away the uniquely
+    // named databases the test suite creates; `backup`/`restore` move a
+    // JSON archive of the list in and out (see crate::backup); `seed`
+    // fills a fresh database with demo data (see crate::seed); `schema`
+    // prints an ERD of the live schema (see crate::schema_docs)
+    match std::env::args().nth(1).as_deref() {
+        Some("clean-test-dbs") => return clean_test_dbs(&configuration.database).await,
+        Some("schema") => {
+            let format = schema_docs::format_argument()?;
+            return schema_docs::emit(&configuration.database, format).await;
+        }
+        Some("backup") => {
+            let path = archive_path_argument("backup")?;
+            return backup::backup(&configuration.database, &path).await;
+        }
+        Some("restore") => {
+            let path = archive_path_argument("restore")?;
+            return backup::restore(&configuration.database, &path).await;
+        }
+        Some("seed") => return seed::seed(&configuration.database).await,
+        _ => {}
+    }
 
-    // the tokio::spawn will run each task in a separate thread
-    let application_task = tokio::spawn(application.run_until_stopped());
+    // which halves of the application this process runs - in a container
+    // orchestrator the web servers and the delivery workers scale on very
+    // different curves, so each can be deployed as its own fleet
+    let mode = parse_mode()?;
 
-    // start a concurrent task to look for new 'newsletter to send' entries in the email to send table
-    let worker_task = tokio::spawn(issue_delivery_worker::run_worker_until_stopped(
-        configuration,
-    ));
+    // every subsystem goes through the shared lifecycle - startup hooks
+    // run first (in order), the long-running tasks start together, and
+    // the first task to exit takes the rest down (see crate::lifecycle)
+    let mut lifecycle = Lifecycle::new();
 
-    // select the tasks to run and run them
-    tokio::select! {
-        o = application_task => report_exit("API", o), // this will be called when the task completes
-        o = worker_task => report_exit("Background worker", o),
-    };
+    // refuse to serve against a database that is ahead of this binary -
+    // the blue/green rollback scenario (see crate::migration_guard)
+    let database = configuration.database.clone();
+    lifecycle.on_startup("migration guard", async move {
+        migration_guard::assert_database_compatible(&database).await
+    });
 
-    Ok(())
+    // first-run bootstrap - creates the operator's admin login from the
+    // APP_BOOTSTRAP_ADMIN_* variables unless this database has already
+    // been bootstrapped (see crate::bootstrap)
+    let bootstrap_configuration = configuration.clone();
+    lifecycle.on_startup("bootstrap", async move {
+        bootstrap::run_once(&bootstrap_configuration).await
+    });
+
+    if let Mode::All | Mode::Api = mode {
+        // building inside the task keeps the server from binding its port
+        // until every startup hook has passed
+        let api_configuration = configuration.clone();
+        lifecycle.task("API", async move {
+            Application::build(api_configuration)
+                .await?
+                .run_until_stopped()
+                .await
+                .map_err(anyhow::Error::from)
+        });
+
+        // the monitor lives with the API rather than the workers - a
+        // watchdog inside a crashed worker process raises no alarms
+        lifecycle.task(
+            "Worker monitor",
+            worker_monitor::run_monitor_until_stopped(configuration.clone()),
+        );
+    }
+
+    if let Mode::All | Mode::Worker = mode {
+        // looks for new 'newsletter to send' entries in the delivery queue
+        lifecycle.task(
+            "Background worker",
+            issue_delivery_worker::run_worker_until_stopped(configuration),
+        );
+    }
+
+    // "did it crash or was it told to stop?" should be answerable from
+    // the logs alone - this line only appears on an orderly exit
+    lifecycle.on_shutdown("farewell", async {
+        tracing::info!("All subsystems have stopped - shutting down");
+        Ok::<(), anyhow::Error>(())
+    });
+
+    lifecycle.run().await
 }
 
-fn report_exit(task_name: &str, outcome: Result<Result<(), impl Debug + Display>, JoinError>) {
-    match outcome {
-        Ok(Ok(())) => {
-            tracing::info!("{} has exited", task_name)
-        }
-        Ok(Err(e)) => {
-            tracing::error!(
-                error.cause_chain = ?e,
-                error.message = %e,
-                "{} failed",
-                task_name
-            )
-        }
-        Err(e) => {
-            tracing::error!(
-                error.cause_chain = ?e,
-                error.message = %e,
-                "{}' task failed to complete",
-                task_name
-            )
+// both archive subcommands take the file path as their only argument
+fn archive_path_argument(subcommand: &str) -> Result<std::path::PathBuf, anyhow::Error> {
+    std::env::args()
+        .nth(2)
+        .map(std::path::PathBuf::from)
+        .ok_or_else(|| anyhow::anyhow!("Usage: zero2prod {} <path-to-archive.json>", subcommand))
+}
+
+// which tasks this process should run
+enum Mode {
+    Api,
+    Worker,
+    All,
+}
+
+// read `--mode api|worker|all` (or `--mode=...`) off the command line -
+// absent means `all`, which is what the book's single-process deploy did
+fn parse_mode() -> Result<Mode, anyhow::Error> {
+    let mut args = std::env::args().skip(1);
+    let mode = loop {
+        match args.next() {
+            Some(arg) if arg == "--mode" => {
+                break args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--mode requires a value: api, worker or all"))?
+            }
+            Some(arg) if arg.starts_with("--mode=") => {
+                break arg.trim_start_matches("--mode=").to_string()
+            }
+            Some(_) => continue,
+            None => return Ok(Mode::All),
         }
+    };
+    match mode.as_str() {
+        "api" => Ok(Mode::Api),
+        "worker" => Ok(Mode::Worker),
+        "all" => Ok(Mode::All),
+        other => Err(anyhow::anyhow!(
+            "Unknown mode '{}': expected api, worker or all",
+            other
+        )),
+    }
+}
+
+// Each `spawn_app` in the test suite creates a database named with a fresh
+// UUID and never drops it - over time the instance fills up with junk.
+// This finds every database whose name looks like one of those UUIDs and
+// drops it (forcibly - lingering connections from a crashed test run
+// shouldn't keep garbage alive).
+async fn clean_test_dbs(
+    database: &configuration::DatabaseSettings,
+) -> anyhow::Result<()> {
+    use sqlx::{Connection, Executor, Row};
+
+    let maintenance = configuration::DatabaseSettings {
+        database_name: "postgres".to_string(),
+        ..database.clone()
+    };
+    let mut connection = sqlx::PgConnection::connect_with(&maintenance.connection_options())
+        .await
+        .expect("Failed to connect to Postgres");
+
+    let rows = connection
+        .fetch_all(
+            r#"SELECT datname FROM pg_database
+            WHERE datname ~ '^[0-9a-f]{8}(-[0-9a-f]{4}){3}-[0-9a-f]{12}$'"#,
+        )
+        .await?;
+
+    for row in &rows {
+        let datname: String = row.get("datname");
+        connection
+            .execute(format!(r#"DROP DATABASE "{}" WITH (FORCE);"#, datname).as_str())
+            .await?;
+        tracing::info!("Dropped test database {}", datname);
     }
+    tracing::info!("Dropped {} test database(s)", rows.len());
+    Ok(())
 }
@@ -0,0 +1,195 @@
+//! An optional mirror of the analytics trail (see crate::domain_events)
+//! onto a NATS subject, so other internal systems can react to signups
+//! and sends without polling the events table. The table stays the
+//! source of truth - the bus is fire-and-forget, and a publish failure
+//! is logged and swallowed just like a failed insert.
+//!
+//! The whole module is gated behind the `message-bus` cargo feature:
+//! most deployments have no consumers, and they shouldn't carry the
+//! networking code (or its tokio features) for a bus they never talk to.
+//! NATS rather than Kafka because its text protocol is simple enough to
+//! speak over a raw TCP stream - no client library, no librdkafka build
+//! dependency. Kafka shops can run the nats-kafka bridge in front of us.
+
+use crate::configuration::MessageBusSettings;
+
+pub struct MessageBus {
+    #[cfg(feature = "message-bus")]
+    inner: Option<Inner>,
+}
+
+#[cfg(feature = "message-bus")]
+struct Inner {
+    // host:port, scheme already stripped
+    address: String,
+    // events go to "<subject_prefix>.<event_type>"
+    subject_prefix: String,
+    // one lazily opened connection, dropped on the first error and
+    // reopened by the next publish
+    connection: tokio::sync::Mutex<Option<tokio::net::TcpStream>>,
+}
+
+impl MessageBus {
+    #[cfg(feature = "message-bus")]
+    pub fn new(settings: &MessageBusSettings) -> Self {
+        let inner = settings.url.as_deref().map(|url| Inner {
+            address: nats_url_to_address(url),
+            subject_prefix: settings.subject_prefix.clone(),
+            connection: tokio::sync::Mutex::new(None),
+        });
+        Self { inner }
+    }
+
+    #[cfg(not(feature = "message-bus"))]
+    pub fn new(settings: &MessageBusSettings) -> Self {
+        // a configured url in a binary built without the feature is almost
+        // certainly a deployment mistake - say so, once, at start-up
+        if settings.url.is_some() {
+            tracing::warn!(
+                "A message bus url is configured but this binary was built \
+                without the `message-bus` feature. Events will not be mirrored.",
+            );
+        }
+        Self {}
+    }
+
+    /// Mirror one domain event onto the bus. Best-effort: failures are
+    /// logged, the event is not retried - it is already in the events table.
+    #[cfg(feature = "message-bus")]
+    pub async fn publish(
+        &self,
+        event_type: &str,
+        subject_id: Option<uuid::Uuid>,
+        occurred_at: chrono::DateTime<chrono::Utc>,
+    ) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        let subject = format!("{}.{}", inner.subject_prefix, event_type);
+        let payload = serde_json::json!({
+            "event_type": event_type,
+            "subject_id": subject_id,
+            "occurred_at": occurred_at.to_rfc3339(),
+        })
+        .to_string();
+        if let Err(e) = inner.send(&subject, payload.as_bytes()).await {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                event_type,
+                "Failed to publish a domain event to the message bus",
+            );
+        }
+    }
+
+    #[cfg(not(feature = "message-bus"))]
+    pub async fn publish(
+        &self,
+        _event_type: &str,
+        _subject_id: Option<uuid::Uuid>,
+        _occurred_at: chrono::DateTime<chrono::Utc>,
+    ) {
+    }
+}
+
+#[cfg(feature = "message-bus")]
+impl Inner {
+    async fn send(&self, subject: &str, payload: &[u8]) -> Result<(), anyhow::Error> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut guard = self.connection.lock().await;
+        if guard.is_none() {
+            *guard = Some(self.connect().await?);
+        }
+        // the unwrap is fine - we just put a stream there if it was missing
+        let stream = guard.as_mut().unwrap();
+
+        // PUB <subject> <#bytes>\r\n<payload>\r\n
+        let mut frame = format!("PUB {} {}\r\n", subject, payload.len()).into_bytes();
+        frame.extend_from_slice(payload);
+        frame.extend_from_slice(b"\r\n");
+        if let Err(e) = stream.write_all(&frame).await {
+            // a stale connection (the server restarted between publishes) -
+            // drop it so the next publish reconnects. The current event is
+            // lost from the bus, not from the events table
+            *guard = None;
+            return Err(anyhow::Error::from(e).context("Failed to write the PUB frame"));
+        }
+
+        // drain whatever the server has sent us and answer its keep-alive
+        // PINGs - an unanswered PING eventually gets us disconnected
+        let mut buffer = [0u8; 512];
+        while let Ok(read) = stream.try_read(&mut buffer) {
+            if read == 0 {
+                // the server closed the connection on us
+                *guard = None;
+                anyhow::bail!("The message bus server closed the connection");
+            }
+            if buffer[..read].windows(4).any(|w| w == b"PING") {
+                if let Err(e) = stream.write_all(b"PONG\r\n").await {
+                    *guard = None;
+                    return Err(anyhow::Error::from(e).context("Failed to answer a PING"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn connect(&self) -> Result<tokio::net::TcpStream, anyhow::Error> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // a bounded wait - the bus must never be able to stall a signup or
+        // a delivery run for longer than this
+        let mut stream = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            tokio::net::TcpStream::connect(&self.address),
+        )
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out connecting to the message bus"))?
+        .map_err(|e| {
+            anyhow::Error::from(e).context(format!(
+                "Failed to connect to the message bus at {}",
+                self.address
+            ))
+        })?;
+
+        // the server greets us with an INFO line; read (and ignore) it, then
+        // introduce ourselves. `"verbose":false` suppresses the +OK acks, so
+        // publishing never needs to read a response
+        let mut greeting = [0u8; 1024];
+        tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            stream.read(&mut greeting),
+        )
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out waiting for the server greeting"))??;
+        stream
+            .write_all(b"CONNECT {\"verbose\":false}\r\n")
+            .await
+            .map_err(|e| anyhow::Error::from(e).context("Failed to send CONNECT"))?;
+        Ok(stream)
+    }
+}
+
+// "nats://localhost:4222" -> "localhost:4222"; a bare host gets the
+// default NATS port appended
+#[cfg(feature = "message-bus")]
+fn nats_url_to_address(url: &str) -> String {
+    let stripped = url.strip_prefix("nats://").unwrap_or(url);
+    if stripped.contains(':') {
+        stripped.to_string()
+    } else {
+        format!("{}:4222", stripped)
+    }
+}
+
+#[cfg(all(test, feature = "message-bus"))]
+mod tests {
+    use super::nats_url_to_address;
+
+    #[test]
+    fn the_scheme_is_stripped_and_the_default_port_applied() {
+        assert_eq!(nats_url_to_address("nats://localhost:4222"), "localhost:4222");
+        assert_eq!(nats_url_to_address("localhost:4222"), "localhost:4222");
+        assert_eq!(nats_url_to_address("nats://bus.internal"), "bus.internal:4222");
+    }
+}
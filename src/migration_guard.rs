@@ -0,0 +1,136 @@
+//! A startup guard for blue/green deployments and rollbacks.
+//!
+//! Migrations are applied out-of-band (sqlx-cli in the deploy pipeline),
+//! so nothing stops an old binary from being pointed at a database that
+//! has already moved on - the usual rollback scenario. An old binary
+//! running against a newer schema can corrupt data in ways a crash never
+//! would, so before serving anything we compare the migrations compiled
+//! into this binary against the `_sqlx_migrations` ledger and refuse to
+//! start if the database is ahead (or has diverged). A database that is
+//! merely *behind* only gets a warning - the pipeline may simply not
+//! have migrated yet, and the first failing query is a louder alarm.
+
+use crate::configuration::DatabaseSettings;
+use anyhow::Context;
+use sqlx::{Connection, PgConnection};
+
+/// A row of the `_sqlx_migrations` ledger, as much of it as the guard needs.
+struct AppliedMigration {
+    version: i64,
+    description: String,
+    checksum: Vec<u8>,
+}
+
+/// Refuse to start against a database whose applied migrations are not a
+/// prefix of the ones compiled into this binary.
+pub async fn assert_database_compatible(
+    database: &DatabaseSettings,
+) -> Result<(), anyhow::Error> {
+    let migrator = sqlx::migrate!("./migrations");
+    let mut connection = PgConnection::connect_with(&database.connection_options())
+        .await
+        .context("Failed to connect to Postgres for the migration check")?;
+
+    // a database with no ledger at all has never been migrated - it is as
+    // far behind as possible, which the guard deliberately tolerates
+    let ledger_exists = sqlx::query_scalar!(
+        r#"SELECT to_regclass('public._sqlx_migrations') IS NOT NULL AS "exists!""#
+    )
+    .fetch_one(&mut connection)
+    .await
+    .context("Failed to look for the migration ledger")?;
+    if !ledger_exists {
+        tracing::warn!("The database has no applied migrations - has the pipeline run sqlx-cli?");
+        return Ok(());
+    }
+
+    let applied = sqlx::query_as!(
+        AppliedMigration,
+        r#"SELECT version, description, checksum FROM _sqlx_migrations ORDER BY version"#
+    )
+    .fetch_all(&mut connection)
+    .await
+    .context("Failed to read the applied migrations")?;
+
+    let expected: Vec<_> = migrator
+        .iter()
+        .map(|m| (m.version, m.checksum.as_ref()))
+        .collect();
+    let problems = incompatibilities(&expected, &applied);
+    if !problems.is_empty() {
+        anyhow::bail!(
+            "The database schema is ahead of (or has diverged from) this binary - \
+            refusing to start. Deploy a binary that knows these migrations, \
+            or roll the database back first:\n  {}",
+            problems.join("\n  ")
+        );
+    }
+
+    let behind = expected.len().saturating_sub(applied.len());
+    if behind > 0 {
+        tracing::warn!(
+            pending = behind,
+            "The database is missing migrations this binary expects."
+        );
+    }
+    Ok(())
+}
+
+// the pure comparison, separated out so it can be tested without a
+// database: every applied migration must exist in the binary with a
+// matching checksum
+fn incompatibilities(expected: &[(i64, &[u8])], applied: &[AppliedMigration]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for migration in applied {
+        match expected.iter().find(|(v, _)| *v == migration.version) {
+            None => problems.push(format!(
+                "{} ({}) is applied but unknown to this binary",
+                migration.version, migration.description
+            )),
+            Some((_, checksum)) if *checksum != migration.checksum.as_slice() => {
+                problems.push(format!(
+                    "{} ({}) was applied with different contents than this binary carries",
+                    migration.version, migration.description
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{incompatibilities, AppliedMigration};
+
+    fn applied(version: i64, checksum: &[u8]) -> AppliedMigration {
+        AppliedMigration {
+            version,
+            description: "test".into(),
+            checksum: checksum.to_vec(),
+        }
+    }
+
+    #[test]
+    fn a_database_that_is_behind_or_in_sync_is_compatible() {
+        let expected = [(1i64, b"aa".as_slice()), (2, b"bb".as_slice())];
+        assert!(incompatibilities(&expected, &[applied(1, b"aa")]).is_empty());
+        assert!(incompatibilities(&expected, &[applied(1, b"aa"), applied(2, b"bb")]).is_empty());
+    }
+
+    #[test]
+    fn an_unknown_applied_migration_is_flagged() {
+        let expected = [(1i64, b"aa".as_slice())];
+        let problems = incompatibilities(&expected, &[applied(1, b"aa"), applied(2, b"bb")]);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("unknown to this binary"));
+    }
+
+    #[test]
+    fn a_checksum_mismatch_is_flagged() {
+        let expected = [(1i64, b"aa".as_slice())];
+        let problems = incompatibilities(&expected, &[applied(1, b"XX")]);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("different contents"));
+    }
+}
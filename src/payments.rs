@@ -0,0 +1,188 @@
+//! The optional paid tier, backed by Stripe. We talk to their REST API
+//! with the reqwest client we already carry - the official SDK-sized
+//! dependencies aren't worth it for the two calls we make. With no
+//! `payments` block in the configuration everything here is inert and
+//! the newsletter stays free.
+//!
+//! The flow: a reader starts a checkout from /premium, pays on Stripe's
+//! hosted page, and Stripe calls our webhook - which is the only thing
+//! allowed to flip the `premium` flag, in either direction. The webhook
+//! is authenticated with Stripe's signature scheme (an HMAC over
+//! "<timestamp>.<payload>"), not with a session.
+
+use crate::configuration::PaymentSettings;
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+
+// how far a webhook's timestamp may drift before we call it a replay
+const WEBHOOK_TOLERANCE_SECONDS: i64 = 300;
+
+pub struct Payments {
+    secret_key: Option<Secret<String>>,
+    webhook_secret: Option<Secret<String>>,
+    price_id: String,
+    http_client: reqwest::Client,
+}
+
+impl Payments {
+    pub fn new(settings: &PaymentSettings) -> Self {
+        Self {
+            secret_key: settings.stripe_secret_key.clone(),
+            webhook_secret: settings.stripe_webhook_secret.clone(),
+            price_id: settings.stripe_price_id.clone(),
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    /// Whether a paid tier is configured at all - the /premium page says
+    /// so instead of offering a checkout that can't work.
+    pub fn is_enabled(&self) -> bool {
+        self.secret_key.is_some() && !self.price_id.is_empty()
+    }
+
+    /// Create a Stripe Checkout session for a subscriber and hand back
+    /// the url to redirect them to.
+    pub async fn create_checkout_session(
+        &self,
+        subscriber_id: uuid::Uuid,
+        email: &str,
+        base_url: &str,
+    ) -> Result<String, anyhow::Error> {
+        let secret_key = self
+            .secret_key
+            .as_ref()
+            .context("No Stripe secret key is configured.")?;
+        let response = self
+            .http_client
+            .post("https://api.stripe.com/v1/checkout/sessions")
+            .bearer_auth(secret_key.expose_secret())
+            // Stripe speaks form encoding, with bracketed array keys
+            .form(&[
+                ("mode", "subscription"),
+                ("line_items[0][price]", &self.price_id),
+                ("line_items[0][quantity]", "1"),
+                // ties the eventual webhook back to our subscriber row
+                ("client_reference_id", &subscriber_id.to_string()),
+                ("customer_email", email),
+                ("success_url", &format!("{}/premium?outcome=success", base_url)),
+                ("cancel_url", &format!("{}/premium?outcome=cancelled", base_url)),
+            ])
+            .send()
+            .await
+            .context("The checkout session request failed.")?
+            .error_for_status()
+            .context("Stripe rejected the checkout session request.")?;
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .context("Failed to parse the checkout session response.")?;
+        body["url"]
+            .as_str()
+            .map(|url| url.to_string())
+            .context("The checkout session response carried no url.")
+    }
+
+    /// Check a webhook payload against its `Stripe-Signature` header.
+    pub fn verify_webhook_signature(
+        &self,
+        payload: &[u8],
+        signature_header: &str,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), anyhow::Error> {
+        let webhook_secret = self
+            .webhook_secret
+            .as_ref()
+            .context("No Stripe webhook secret is configured.")?;
+        verify_signature(webhook_secret.expose_secret(), payload, signature_header, now)
+    }
+}
+
+// the header looks like "t=1698000000,v1=abc...,v1=def..." - multiple v1
+// entries are legal during a webhook secret rotation
+fn parse_signature_header(header: &str) -> (Option<i64>, Vec<&str>) {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = value.parse().ok(),
+            Some(("v1", value)) => signatures.push(value),
+            _ => {}
+        }
+    }
+    (timestamp, signatures)
+}
+
+fn verify_signature(
+    secret: &str,
+    payload: &[u8],
+    signature_header: &str,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(), anyhow::Error> {
+    let (timestamp, signatures) = parse_signature_header(signature_header);
+    let timestamp = timestamp.context("The signature header carried no timestamp.")?;
+    if (now.timestamp() - timestamp).abs() > WEBHOOK_TOLERANCE_SECONDS {
+        anyhow::bail!("The webhook timestamp is outside the tolerance window.");
+    }
+
+    // the signed material is "<timestamp>.<payload>"; the hmac crate's
+    // verify gives us the constant-time comparison
+    for signature in signatures {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepts keys of any size");
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(payload);
+        if let Ok(candidate) = hex::decode(signature) {
+            if mac.verify_slice(&candidate).is_ok() {
+                return Ok(());
+            }
+        }
+    }
+    anyhow::bail!("No signature in the header matched the payload.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_signature_header, verify_signature, WEBHOOK_TOLERANCE_SECONDS};
+    use chrono::{TimeZone, Utc};
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    fn sign(secret: &str, timestamp: i64, payload: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(payload);
+        format!("t={},v1={}", timestamp, hex::encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn the_header_is_parsed_into_timestamp_and_signatures() {
+        let (timestamp, signatures) = parse_signature_header("t=12345,v1=aa,v1=bb");
+        assert_eq!(timestamp, Some(12345));
+        assert_eq!(signatures, vec!["aa", "bb"]);
+    }
+
+    #[test]
+    fn a_correctly_signed_payload_verifies() {
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let header = sign("whsec_test", now.timestamp(), b"{}");
+        assert!(verify_signature("whsec_test", b"{}", &header, now).is_ok());
+    }
+
+    #[test]
+    fn a_tampered_payload_is_rejected() {
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let header = sign("whsec_test", now.timestamp(), b"{}");
+        assert!(verify_signature("whsec_test", b"{\"evil\":1}", &header, now).is_err());
+    }
+
+    #[test]
+    fn a_stale_timestamp_is_rejected() {
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let stale = now.timestamp() - WEBHOOK_TOLERANCE_SECONDS - 1;
+        let header = sign("whsec_test", stale, b"{}");
+        assert!(verify_signature("whsec_test", b"{}", &header, now).is_err());
+    }
+}
@@ -0,0 +1,535 @@
+//! A small premailer: Gmail clips long messages and Outlook's Word engine
+//! ignores most of `<head>`, so CSS that should survive an email client
+//! has to live in `style` attributes. At publish time the authored HTML
+//! is run through [`inline_css`], which moves declarations from `<style>`
+//! blocks onto the matching elements; the authored original is stored
+//! alongside the inlined version on the issue.
+//!
+//! [`minify_html`] is the second half of the pipeline: Gmail clips
+//! messages over ~102KB, so the inlined document is also stripped of
+//! comments and formatting whitespace. Both steps can be skipped per
+//! issue from the compose form, for authors who need their HTML sent
+//! byte-for-byte.
+//!
+//! This is deliberately not a full CSS engine. Supported selectors are
+//! `tag`, `.class`, `#id` and `tag.class`; anything fancier (pseudo
+//! classes, combinators, attribute selectors) and all `@`-rules are left
+//! in a `<style>` block for the clients that do read it. Specificity is
+//! source order, and an author's existing inline `style` always wins over
+//! anything moved in from a block.
+
+// a parsed rule with one of the supported selector shapes
+enum Selector {
+    Tag(String),
+    Class(String),
+    Id(String),
+    TagClass(String, String),
+}
+
+struct Rule {
+    selector: Selector,
+    declarations: String,
+}
+
+/// Inline `<style>` blocks into `style` attributes, returning the
+/// rewritten document.
+pub fn inline_css(html: &str) -> String {
+    let (html, css) = extract_style_blocks(html);
+    if css.trim().is_empty() {
+        return html;
+    }
+    let (rules, preserved) = parse_rules(&css);
+    let html = apply_rules(&html, &rules);
+    if preserved.trim().is_empty() {
+        html
+    } else {
+        reinsert_style_block(&html, preserved.trim())
+    }
+}
+
+/// Strip comments and formatting whitespace, leaving rendering intact:
+/// whitespace runs inside text collapse to a single space, runs that sit
+/// between tags and span a newline (i.e. indentation) are dropped, and
+/// `<pre>` blocks pass through untouched. Outlook's `<!--[if mso]>`
+/// conditional comments survive - they are markup, not commentary.
+pub fn minify_html(html: &str) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    // protect <pre> blocks - their whitespace is the content
+    loop {
+        let Some(open) = rest.find("<pre") else {
+            output.push_str(&minify_fragment(rest));
+            break;
+        };
+        let after_open = &rest[open..];
+        let Some(close) = after_open.find("</pre>") else {
+            output.push_str(&minify_fragment(rest));
+            break;
+        };
+        output.push_str(&minify_fragment(&rest[..open]));
+        output.push_str(&after_open[..close + "</pre>".len()]);
+        rest = &after_open[close + "</pre>".len()..];
+    }
+    output
+}
+
+fn minify_fragment(fragment: &str) -> String {
+    let fragment = strip_html_comments(fragment);
+    let mut output = String::with_capacity(fragment.len());
+    let mut chars = fragment.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !c.is_whitespace() {
+            output.push(c);
+            continue;
+        }
+        let mut spans_newline = c == '\n';
+        while let Some(&next) = chars.peek() {
+            if !next.is_whitespace() {
+                break;
+            }
+            spans_newline |= next == '\n';
+            chars.next();
+        }
+        let between_tags = output.ends_with('>') && chars.peek() == Some(&'<');
+        // indentation between tags goes entirely; anything else keeps a
+        // single space so words don't run together
+        if !(output.is_empty() || spans_newline && between_tags) && chars.peek().is_some() {
+            output.push(' ');
+        }
+    }
+    output
+}
+
+fn strip_html_comments(html: &str) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(open) = rest.find("<!--") {
+        // Outlook's conditional comments are load-bearing - keep them
+        if rest[open..].starts_with("<!--[if") || rest[open..].starts_with("<!--<![endif]") {
+            let end = match rest[open..].find("-->") {
+                Some(end) => open + end + 3,
+                None => rest.len(),
+            };
+            output.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        output.push_str(&rest[..open]);
+        match rest[open..].find("-->") {
+            Some(close) => rest = &rest[open + close + 3..],
+            None => return output,
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+// pull every <style>...</style> out of the document, returning what's
+// left and the concatenated css
+fn extract_style_blocks(html: &str) -> (String, String) {
+    let mut remaining = html;
+    let mut output = String::with_capacity(html.len());
+    let mut css = String::new();
+    loop {
+        let Some(open) = remaining.find("<style") else {
+            output.push_str(remaining);
+            break;
+        };
+        let after_open = &remaining[open..];
+        let (Some(tag_end), Some(close)) = (after_open.find('>'), after_open.find("</style>"))
+        else {
+            // a malformed block - leave the document alone from here on
+            output.push_str(remaining);
+            break;
+        };
+        if tag_end > close {
+            output.push_str(remaining);
+            break;
+        }
+        output.push_str(&remaining[..open]);
+        css.push_str(&after_open[tag_end + 1..close]);
+        css.push('\n');
+        remaining = &after_open[close + "</style>".len()..];
+    }
+    (output, css)
+}
+
+// split the css into inlinable rules and a preserved remainder
+fn parse_rules(css: &str) -> (Vec<Rule>, String) {
+    let css = strip_comments(css);
+    let mut rules = Vec::new();
+    let mut preserved = String::new();
+    let mut rest = css.as_str();
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        if rest.starts_with('@') {
+            // @-rules can't be inlined - keep the whole block (or, for the
+            // braceless @import/@charset kind, up to the semicolon)
+            let brace = rest.find('{');
+            let semicolon = rest.find(';');
+            match (brace, semicolon) {
+                (Some(brace), semicolon) if semicolon.is_none_or(|s| brace < s) => {
+                    let Some(end) = matching_brace(rest, brace) else {
+                        break;
+                    };
+                    preserved.push_str(&rest[..=end]);
+                    preserved.push('\n');
+                    rest = &rest[end + 1..];
+                }
+                (_, Some(semicolon)) => {
+                    preserved.push_str(&rest[..=semicolon]);
+                    preserved.push('\n');
+                    rest = &rest[semicolon + 1..];
+                }
+                _ => break,
+            }
+            continue;
+        }
+        let Some(open) = rest.find('{') else {
+            break;
+        };
+        let Some(close_offset) = rest[open..].find('}') else {
+            break;
+        };
+        let close = open + close_offset;
+        let declarations = rest[open + 1..close]
+            .trim()
+            .trim_end_matches(';')
+            .to_string();
+        for selector in rest[..open].split(',') {
+            let selector = selector.trim();
+            match parse_selector(selector) {
+                Some(selector) => rules.push(Rule {
+                    selector,
+                    declarations: declarations.clone(),
+                }),
+                None => {
+                    // too clever to inline - preserve it verbatim
+                    preserved.push_str(&format!("{}{{{}}}\n", selector, declarations));
+                }
+            }
+        }
+        rest = &rest[close + 1..];
+    }
+    (rules, preserved)
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut output = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(open) = rest.find("/*") {
+        output.push_str(&rest[..open]);
+        match rest[open..].find("*/") {
+            Some(close) => rest = &rest[open + close + 2..],
+            None => return output,
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+// the index of the brace closing the one at `open`
+fn matching_brace(text: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_selector(selector: &str) -> Option<Selector> {
+    if selector.is_empty()
+        || selector
+            .chars()
+            .any(|c| c.is_whitespace() || ":>+~[*".contains(c))
+    {
+        return None;
+    }
+    if let Some(class) = selector.strip_prefix('.') {
+        return (!class.contains('.')).then(|| Selector::Class(class.to_string()));
+    }
+    if let Some(id) = selector.strip_prefix('#') {
+        return (!id.contains('#')).then(|| Selector::Id(id.to_string()));
+    }
+    if selector.contains('#') {
+        return None;
+    }
+    match selector.split_once('.') {
+        Some((tag, class)) => (!class.contains('.')).then(|| {
+            Selector::TagClass(tag.to_ascii_lowercase(), class.to_string())
+        }),
+        None => Some(Selector::Tag(selector.to_ascii_lowercase())),
+    }
+}
+
+// walk the document, rewriting every start tag that a rule matches
+fn apply_rules(html: &str, rules: &[Rule]) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    loop {
+        let Some(open) = rest.find('<') else {
+            output.push_str(rest);
+            break;
+        };
+        output.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        // only start tags are interesting - closers, comments and doctype
+        // pass straight through
+        if !after.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            output.push('<');
+            rest = after;
+            continue;
+        }
+        let Some(close) = tag_end(after) else {
+            output.push('<');
+            output.push_str(after);
+            break;
+        };
+        let tag = &after[..close];
+        output.push_str(&rewrite_tag(tag, rules));
+        rest = &after[close + 1..];
+    }
+    output
+}
+
+// the index of the '>' ending the tag, skipping quoted attribute values
+fn tag_end(after: &str) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    for (i, c) in after.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+// `tag` is everything between '<' and '>' of a start tag
+fn rewrite_tag(tag: &str, rules: &[Rule]) -> String {
+    let name: String = tag
+        .chars()
+        .take_while(|c| !c.is_whitespace() && *c != '/' && *c != '>')
+        .collect::<String>()
+        .to_ascii_lowercase();
+    let class_attr = attribute_value(tag, "class");
+    let classes: Vec<&str> = class_attr
+        .as_deref()
+        .map(|c| c.split_whitespace().collect())
+        .unwrap_or_default();
+    let id = attribute_value(tag, "id");
+
+    let mut declarations = String::new();
+    for rule in rules {
+        let matched = match &rule.selector {
+            Selector::Tag(tag_name) => *tag_name == name,
+            Selector::Class(class) => classes.contains(&class.as_str()),
+            Selector::Id(rule_id) => id.as_deref() == Some(rule_id),
+            Selector::TagClass(tag_name, class) => {
+                *tag_name == name && classes.contains(&class.as_str())
+            }
+        };
+        if matched {
+            if !declarations.is_empty() {
+                declarations.push_str("; ");
+            }
+            declarations.push_str(&rule.declarations);
+        }
+    }
+    if declarations.is_empty() {
+        return format!("<{}>", tag);
+    }
+
+    // the author's inline style goes last, so it wins over the block rules
+    if let Some(existing) = attribute_value(tag, "style") {
+        let merged = format!("{}; {}", declarations, existing);
+        return format!("<{}>", replace_attribute(tag, "style", &merged));
+    }
+    let insert_at = if tag.trim_end().ends_with('/') {
+        tag.rfind('/').unwrap()
+    } else {
+        tag.len()
+    };
+    format!(
+        "<{} style=\"{}\"{}>",
+        tag[..insert_at].trim_end(),
+        declarations,
+        &tag[insert_at..]
+    )
+}
+
+// the value of a (double- or single-quoted) attribute, if present
+fn attribute_value(tag: &str, attribute: &str) -> Option<String> {
+    let lowered = tag.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(found) = lowered[from..].find(attribute) {
+        let start = from + found;
+        // must be a whole attribute name preceded by whitespace
+        let preceded = start > 0 && lowered[..start].ends_with(|c: char| c.is_whitespace());
+        let after = &tag[start + attribute.len()..];
+        let after_trimmed = after.trim_start();
+        if preceded && after_trimmed.starts_with('=') {
+            let value = after_trimmed[1..].trim_start();
+            let quote = value.chars().next()?;
+            if quote == '"' || quote == '\'' {
+                return value[1..].find(quote).map(|end| value[1..1 + end].to_string());
+            }
+        }
+        from = start + attribute.len();
+    }
+    None
+}
+
+// swap an existing attribute's value - only called when it is present
+fn replace_attribute(tag: &str, attribute: &str, new_value: &str) -> String {
+    let current = attribute_value(tag, attribute).expect("the attribute was just found");
+    let quoted_double = format!("{}=\"{}\"", attribute, current);
+    let quoted_single = format!("{}='{}'", attribute, current);
+    let replacement = format!("{}=\"{}\"", attribute, new_value);
+    if tag.contains(&quoted_double) {
+        tag.replacen(&quoted_double, &replacement, 1)
+    } else {
+        tag.replacen(&quoted_single, &replacement, 1)
+    }
+}
+
+// what couldn't be inlined goes back into the <head> (or the front of the
+// fragment, when there isn't one)
+// the palette clients flip to when the reader's OS is in dark mode -
+// `!important` throughout because by the time this runs the light styles
+// have been inlined into `style` attributes, which would otherwise win
+const DARK_MODE_BLOCK: &str = "<meta name=\"color-scheme\" content=\"light dark\">\
+<style>\
+:root { color-scheme: light dark; }\
+@media (prefers-color-scheme: dark) {\
+body, table, td, div { background-color: #1b1b1d !important; color: #e8e6e3 !important; }\
+h1, h2, h3, h4, p, li, span, blockquote { color: #e8e6e3 !important; }\
+a { color: #8ab4f8 !important; }\
+}\
+</style>";
+
+/// Append the dark-mode variant: a `prefers-color-scheme: dark` media
+/// query (plus the `color-scheme` hint) that flips the issue to a dark
+/// palette in clients that honour it. Runs after inlining - media
+/// queries cannot be inlined, they have to stay in a `<style>` block.
+pub fn add_dark_mode_styles(html: &str) -> String {
+    match html.find("</head>") {
+        Some(at) => format!("{}{}{}", &html[..at], DARK_MODE_BLOCK, &html[at..]),
+        None => format!("{}{}", DARK_MODE_BLOCK, html),
+    }
+}
+
+fn reinsert_style_block(html: &str, preserved: &str) -> String {
+    let block = format!("<style>{}</style>", preserved);
+    match html.find("</head>") {
+        Some(at) => format!("{}{}{}", &html[..at], block, &html[at..]),
+        None => format!("{}{}", block, html),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_and_class_rules_are_inlined() {
+        let html = "<style>p { margin: 0 } .intro { color: red }</style>\
+                    <p class=\"intro\">Hi</p><p>Bye</p>";
+        assert_eq!(
+            inline_css(html),
+            "<p class=\"intro\" style=\"margin: 0; color: red\">Hi</p>\
+             <p style=\"margin: 0\">Bye</p>"
+        );
+    }
+
+    #[test]
+    fn the_authors_inline_style_wins() {
+        let html = "<style>p { color: red }</style><p style=\"color: blue\">Hi</p>";
+        assert_eq!(
+            inline_css(html),
+            "<p style=\"color: red; color: blue\">Hi</p>"
+        );
+    }
+
+    #[test]
+    fn id_and_tag_class_selectors_match() {
+        let html = "<style>#hero { font-weight: bold } div.box { padding: 4px }</style>\
+                    <div id=\"hero\" class=\"box\">Hi</div>";
+        assert_eq!(
+            inline_css(html),
+            "<div id=\"hero\" class=\"box\" style=\"font-weight: bold; padding: 4px\">Hi</div>"
+        );
+    }
+
+    #[test]
+    fn media_queries_and_pseudo_classes_are_preserved() {
+        let html = "<style>@media (max-width: 600px) { p { font-size: 18px } }\
+                    a:hover { color: red }</style><a href=\"/\">Hi</a>";
+        let inlined = inline_css(html);
+        assert!(inlined.starts_with("<style>"));
+        assert!(inlined.contains("@media (max-width: 600px) { p { font-size: 18px } }"));
+        assert!(inlined.contains("a:hover{color: red}"));
+        assert!(inlined.ends_with("</style><a href=\"/\">Hi</a>"));
+    }
+
+    #[test]
+    fn documents_without_styles_pass_through() {
+        let html = "<p>Nothing to do</p>";
+        assert_eq!(inline_css(html), html);
+    }
+
+    #[test]
+    fn minification_drops_indentation_but_not_word_spacing() {
+        let html = "<div>\n    <p>Hello <b>bold</b>\n    world</p>\n</div>";
+        assert_eq!(
+            minify_html(html),
+            "<div><p>Hello <b>bold</b> world</p></div>"
+        );
+    }
+
+    #[test]
+    fn minification_strips_comments_but_keeps_conditional_ones() {
+        let html = "<!-- a note to self --><p>Hi</p>\
+                    <!--[if mso]><table><tr><td><![endif]-->";
+        assert_eq!(
+            minify_html(html),
+            "<p>Hi</p><!--[if mso]><table><tr><td><![endif]-->"
+        );
+    }
+
+    #[test]
+    fn minification_leaves_pre_blocks_alone() {
+        let html = "<div>\n  <pre>  two\n  lines  </pre>\n</div>";
+        assert_eq!(minify_html(html), "<div><pre>  two\n  lines  </pre></div>");
+    }
+
+    #[test]
+    fn dark_mode_styles_land_in_the_head_when_there_is_one() {
+        let html = "<html><head><title>Hi</title></head><body><p>Hi</p></body></html>";
+        let dark = add_dark_mode_styles(html);
+        assert!(dark.contains("@media (prefers-color-scheme: dark)"));
+        let head_end = dark.find("</head>").unwrap();
+        assert!(dark.find("prefers-color-scheme").unwrap() < head_end);
+    }
+
+    #[test]
+    fn dark_mode_styles_are_prepended_to_bare_fragments() {
+        let dark = add_dark_mode_styles("<p>Hi</p>");
+        assert!(dark.starts_with("<meta name=\"color-scheme\""));
+        assert!(dark.ends_with("<p>Hi</p>"));
+    }
+}
@@ -0,0 +1,169 @@
+//! Gauges for the delivery pipeline, served at /metrics in the
+//! OpenMetrics text format so the usual alerting stack (Prometheus and
+//! friends) can fire on a silting queue before subscribers notice the
+//! delay. The worker monitor takes one sample per check interval and the
+//! endpoint serves the cached copy - a tight scrape loop can never put
+//! load on the queue tables.
+//!
+//! Like the telemetry counters, the sample lives in a process-wide
+//! static: it belongs to the binary, not a request, and resets on deploy.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// The OpenMetrics content type, version and all - plain
+/// `text/plain` makes strict scrapers fall back to the older exposition
+/// format.
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// One sample of the delivery pipeline, as taken by the worker monitor.
+#[derive(Clone, Default)]
+pub struct QueueSample {
+    /// every queued delivery, ready or deferred
+    pub depth: i64,
+    /// how long the oldest dequeue-able task has been waiting - 0 when
+    /// nothing is ready, because a retry scheduled for the future is
+    /// waiting by design, not late
+    pub oldest_age_seconds: i64,
+    /// permanently rejected sends, from the delivery log
+    pub dead_letters: i64,
+    /// queued deliveries per issue - how far each send still has to go
+    pub per_issue_remaining: Vec<(Uuid, i64)>,
+}
+
+// the monitor writes, the endpoint reads - never across an await, so a
+// plain Mutex does. None until the first sample lands
+static LATEST_SAMPLE: Mutex<Option<QueueSample>> = Mutex::new(None);
+
+/// Take one sample of the queue and cache it for the /metrics endpoint.
+#[tracing::instrument(skip_all)]
+pub async fn sample(pool: &PgPool, now: DateTime<Utc>) -> Result<(), anyhow::Error> {
+    let queue = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "depth!",
+            min(available_at) FILTER (WHERE available_at <= $1) AS oldest_available_at
+        FROM issue_delivery_queue
+        "#,
+        now,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let dead_letters = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM email_delivery_log WHERE failed"#
+    )
+    .fetch_one(pool)
+    .await?
+    .count;
+
+    let per_issue = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, COUNT(*) AS "remaining!"
+        FROM issue_delivery_queue
+        GROUP BY newsletter_issue_id
+        ORDER BY COUNT(*) DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let sample = QueueSample {
+        depth: queue.depth,
+        oldest_age_seconds: queue
+            .oldest_available_at
+            .map(|oldest| (now - oldest).num_seconds().max(0))
+            .unwrap_or(0),
+        dead_letters,
+        per_issue_remaining: per_issue
+            .into_iter()
+            .map(|r| (r.newsletter_issue_id, r.remaining))
+            .collect(),
+    };
+    *LATEST_SAMPLE.lock().unwrap() = Some(sample);
+    Ok(())
+}
+
+/// The cached sample in exposition format - just the `# EOF` marker
+/// until the monitor has taken one.
+pub fn render() -> String {
+    let sample = LATEST_SAMPLE.lock().unwrap().clone();
+    match sample {
+        Some(sample) => render_sample(&sample),
+        None => "# EOF\n".to_string(),
+    }
+}
+
+fn render_sample(sample: &QueueSample) -> String {
+    let mut out = String::new();
+    out.push_str("# TYPE issue_delivery_queue_depth gauge\n");
+    out.push_str("# HELP issue_delivery_queue_depth Queued deliveries, ready or deferred.\n");
+    writeln!(out, "issue_delivery_queue_depth {}", sample.depth).unwrap();
+
+    out.push_str("# TYPE issue_delivery_queue_oldest_age_seconds gauge\n");
+    out.push_str(
+        "# HELP issue_delivery_queue_oldest_age_seconds Age of the oldest dequeue-able task.\n",
+    );
+    writeln!(
+        out,
+        "issue_delivery_queue_oldest_age_seconds {}",
+        sample.oldest_age_seconds
+    )
+    .unwrap();
+
+    out.push_str("# TYPE issue_delivery_dead_letters gauge\n");
+    out.push_str("# HELP issue_delivery_dead_letters Permanently rejected sends.\n");
+    writeln!(out, "issue_delivery_dead_letters {}", sample.dead_letters).unwrap();
+
+    out.push_str("# TYPE issue_delivery_queue_remaining gauge\n");
+    out.push_str("# HELP issue_delivery_queue_remaining Queued deliveries per issue.\n");
+    for (issue_id, remaining) in &sample.per_issue_remaining {
+        writeln!(
+            out,
+            "issue_delivery_queue_remaining{{newsletter_issue_id=\"{}\"}} {}",
+            issue_id, remaining
+        )
+        .unwrap();
+    }
+
+    out.push_str("# EOF\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_sample_renders_every_gauge_and_the_eof_marker() {
+        let issue_id = Uuid::new_v4();
+        let sample = QueueSample {
+            depth: 12,
+            oldest_age_seconds: 34,
+            dead_letters: 2,
+            per_issue_remaining: vec![(issue_id, 12)],
+        };
+        let rendered = render_sample(&sample);
+        assert!(rendered.contains("issue_delivery_queue_depth 12\n"));
+        assert!(rendered.contains("issue_delivery_queue_oldest_age_seconds 34\n"));
+        assert!(rendered.contains("issue_delivery_dead_letters 2\n"));
+        assert!(rendered.contains(&format!(
+            "issue_delivery_queue_remaining{{newsletter_issue_id=\"{}\"}} 12\n",
+            issue_id
+        )));
+        // the spec requires the stream to end with the marker
+        assert!(rendered.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn an_idle_queue_still_reports_its_zeros() {
+        // absent series make "is it zero or is it broken?" alerts
+        // impossible - an empty queue must still say so explicitly
+        let rendered = render_sample(&QueueSample::default());
+        assert!(rendered.contains("issue_delivery_queue_depth 0\n"));
+        assert!(rendered.contains("issue_delivery_queue_oldest_age_seconds 0\n"));
+    }
+}
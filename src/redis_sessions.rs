@@ -0,0 +1,279 @@
+//! The Redis-backed session store, hardened for production.
+//!
+//! The book's version called `RedisSessionStore::new` once at startup: a
+//! single multiplexed connection, established eagerly - so Redis being
+//! down blocked deployment, and a Redis restart could poison sessions
+//! until the app was bounced. This module replaces it with:
+//!
+//! - a connection pool (deadpool) built lazily, so broken connections
+//!   are discarded and replaced instead of being held onto;
+//! - an optional `redis+sentinel://host:port[,host:port]/service-name`
+//!   URI that asks the sentinels (with backoff) for the current master;
+//! - a wrapping store that treats "couldn't reach Redis" on a session
+//!   *read* as "no session" - the user is asked to log in again rather
+//!   than shown a 500 on every page.
+//!
+//! Redis Cluster is deliberately not supported: actix-session's pool
+//! speaks single-node Redis, and Sentinel already covers the HA story
+//! for a workload this small.
+
+use actix_session::storage::{
+    LoadError, RedisSessionStore, SaveError, SessionKey, SessionStore, UpdateError,
+};
+use actix_web::cookie::time::Duration;
+use anyhow::Context;
+use deadpool_redis::{redis, Config, Runtime};
+use secrecy::{ExposeSecret, Secret};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+const SENTINEL_SCHEME: &str = "redis+sentinel://";
+// how often (and how patiently) we ask the sentinels for a master before
+// giving up on startup
+const SENTINEL_ATTEMPTS: u32 = 5;
+const SENTINEL_BASE_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Build the session store from the configured URI. The pool connects
+/// lazily, so plain-URI startup succeeds even while Redis is down - the
+/// probe below only warns, it is the sessions that will fail (softly,
+/// see [`ResilientSessionStore`]) until Redis returns.
+pub async fn build_store(
+    redis_uri: &Secret<String>,
+) -> Result<ResilientSessionStore, anyhow::Error> {
+    let url = resolve_redis_uri(redis_uri.expose_secret()).await?;
+    let pool = Config::from_url(url)
+        .create_pool(Some(Runtime::Tokio1))
+        .context("Failed to configure the Redis session pool")?;
+
+    // one early connection attempt so a typo'd URI shows up in the logs
+    // at deploy time rather than at the first login
+    if let Err(e) = pool.get().await {
+        tracing::warn!(
+            error.cause_chain = ?e,
+            "Redis is not reachable - sessions will fail until it is."
+        );
+    }
+
+    let store = RedisSessionStore::new_pooled(pool.clone()).await?;
+    Ok(ResilientSessionStore { store, pool })
+}
+
+/// What the session store has been through since this process started -
+/// the same in-memory, reset-on-deploy model as the query timings in
+/// `crate::telemetry`. Surfaced on /ready and /admin/diagnostics.
+#[derive(Clone, Copy)]
+pub struct SessionStoreStats {
+    pub operations: u64,
+    pub errors: u64,
+    pub total_secs: f64,
+    pub max_secs: f64,
+}
+
+static STORE_STATS: Mutex<SessionStoreStats> = Mutex::new(SessionStoreStats {
+    operations: 0,
+    errors: 0,
+    total_secs: 0.0,
+    max_secs: 0.0,
+});
+
+pub fn session_store_snapshot() -> SessionStoreStats {
+    *STORE_STATS.lock().unwrap()
+}
+
+fn record_outcome(started: Instant, failed: bool) {
+    let elapsed = started.elapsed().as_secs_f64();
+    let mut stats = STORE_STATS.lock().unwrap();
+    stats.operations += 1;
+    stats.errors += u64::from(failed);
+    stats.total_secs += elapsed;
+    stats.max_secs = stats.max_secs.max(elapsed);
+}
+
+// turn a sentinel URI into a plain one by asking the sentinels who the
+// master is right now; plain URIs pass straight through. Resolution
+// happens once, at startup - after a failover the pool's reconnects hit
+// the old address and the process needs a restart, which the platform's
+// health checks already arrange.
+async fn resolve_redis_uri(uri: &str) -> Result<String, anyhow::Error> {
+    let Some((sentinels, service_name)) = parse_sentinel_uri(uri)? else {
+        return Ok(uri.to_string());
+    };
+
+    let mut delay = SENTINEL_BASE_DELAY;
+    let mut last_error = None;
+    for attempt in 1..=SENTINEL_ATTEMPTS {
+        for sentinel in &sentinels {
+            match master_address(sentinel, &service_name).await {
+                Ok((host, port)) => {
+                    tracing::info!(%host, %port, "Resolved the Redis master via sentinel");
+                    return Ok(format!("redis://{}:{}", host, port));
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        sentinel,
+                        attempt,
+                        error.cause_chain = ?e,
+                        "Failed to ask a sentinel for the Redis master"
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+        if attempt < SENTINEL_ATTEMPTS {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+    Err(last_error.unwrap()).context("No sentinel could name the Redis master")
+}
+
+// `redis+sentinel://host:port[,host:port]/service-name` - returns None
+// for anything that isn't a sentinel URI
+fn parse_sentinel_uri(uri: &str) -> Result<Option<(Vec<String>, String)>, anyhow::Error> {
+    let Some(rest) = uri.strip_prefix(SENTINEL_SCHEME) else {
+        return Ok(None);
+    };
+    let Some((hosts, service_name)) = rest.split_once('/') else {
+        anyhow::bail!(
+            "Invalid sentinel URI: expected {}host:port[,host:port]/service-name",
+            SENTINEL_SCHEME
+        );
+    };
+    if hosts.is_empty() || service_name.is_empty() {
+        anyhow::bail!("Invalid sentinel URI: no sentinel hosts or no service name");
+    }
+    Ok(Some((
+        hosts.split(',').map(str::to_string).collect(),
+        service_name.to_string(),
+    )))
+}
+
+async fn master_address(
+    sentinel: &str,
+    service_name: &str,
+) -> Result<(String, String), anyhow::Error> {
+    let client = redis::Client::open(format!("redis://{}", sentinel))?;
+    let mut connection = client.get_multiplexed_async_connection().await?;
+    let address: (String, String) = redis::cmd("SENTINEL")
+        .arg("get-master-addr-by-name")
+        .arg(service_name)
+        .query_async(&mut connection)
+        .await?;
+    Ok(address)
+}
+
+/// [`RedisSessionStore`], except that a session *read* failing - Redis
+/// down, or a session blob we can no longer parse - looks like "not
+/// logged in" instead of an error. The admin middleware then redirects
+/// to /login as it would for any anonymous visitor; writes (login
+/// itself) still fail loudly, there is nothing sensible to degrade to.
+#[derive(Clone)]
+pub struct ResilientSessionStore {
+    store: RedisSessionStore,
+    // a handle of our own, so the readiness probe can talk to Redis
+    // without going through the session API
+    pool: deadpool_redis::Pool,
+}
+
+impl ResilientSessionStore {
+    /// A round trip to Redis and how long it took - the readiness probe
+    /// and the diagnostics page both lean on this.
+    pub async fn ping(&self) -> Result<std::time::Duration, anyhow::Error> {
+        let started = Instant::now();
+        let mut connection = self
+            .pool
+            .get()
+            .await
+            .context("Failed to check out a Redis connection")?;
+        redis::cmd("PING")
+            .query_async::<()>(&mut connection)
+            .await
+            .context("Redis did not answer the ping")?;
+        Ok(started.elapsed())
+    }
+}
+
+impl SessionStore for ResilientSessionStore {
+    async fn load(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<HashMap<String, String>>, LoadError> {
+        let started = Instant::now();
+        let outcome = self.store.load(session_key).await;
+        record_outcome(started, outcome.is_err());
+        match outcome {
+            Err(e) => {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    "Failed to load the session from Redis - treating the visitor as logged out"
+                );
+                Ok(None)
+            }
+            outcome => outcome,
+        }
+    }
+
+    async fn save(
+        &self,
+        session_state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let started = Instant::now();
+        let outcome = self.store.save(session_state, ttl).await;
+        record_outcome(started, outcome.is_err());
+        outcome
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        let started = Instant::now();
+        let outcome = self.store.update(session_key, session_state, ttl).await;
+        record_outcome(started, outcome.is_err());
+        outcome
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> Result<(), anyhow::Error> {
+        let started = Instant::now();
+        let outcome = self.store.update_ttl(session_key, ttl).await;
+        record_outcome(started, outcome.is_err());
+        outcome
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+        let started = Instant::now();
+        let outcome = self.store.delete(session_key).await;
+        record_outcome(started, outcome.is_err());
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_sentinel_uri;
+
+    #[test]
+    fn plain_uris_are_not_sentinel_uris() {
+        assert!(parse_sentinel_uri("redis://127.0.0.1:6379").unwrap().is_none());
+    }
+
+    #[test]
+    fn sentinel_uris_list_hosts_and_name_the_service() {
+        let (hosts, service_name) =
+            parse_sentinel_uri("redis+sentinel://10.0.0.1:26379,10.0.0.2:26379/sessions")
+                .unwrap()
+                .unwrap();
+        assert_eq!(hosts, vec!["10.0.0.1:26379", "10.0.0.2:26379"]);
+        assert_eq!(service_name, "sessions");
+    }
+
+    #[test]
+    fn a_sentinel_uri_without_a_service_name_is_rejected() {
+        assert!(parse_sentinel_uri("redis+sentinel://10.0.0.1:26379").is_err());
+        assert!(parse_sentinel_uri("redis+sentinel://10.0.0.1:26379/").is_err());
+    }
+}
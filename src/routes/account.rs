@@ -0,0 +1,173 @@
+//! Security notifications for the admin account, and the "this wasn't
+//! me" kill switch those emails link to. Notifications go through the
+//! shared `Alerter` (so they reach the configured operator inboxes and
+//! Slack); the lock link is signed with the same `LinkSigner` the
+//! confirmation emails use, so it works without being logged in.
+//!
+//! There is no self-service unlock: clearing `users.locked` is a
+//! deliberate operator action (`UPDATE users SET locked = false ...`).
+
+use crate::alerts::Alerter;
+use crate::clock::Clock;
+use crate::session_state::revoke_other_sessions;
+use crate::signed_link::{LinkSigner, ACCOUNT_LOCK};
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// how long the kill switch in a notification email stays usable
+const LOCK_LINK_VALIDITY_DAYS: i64 = 7;
+
+// the signed parameters of a lock link (the names mirror the
+// confirmation links - the signer covers a generic id)
+#[derive(serde::Deserialize)]
+pub struct LockParameters {
+    subscriber_id: Uuid,
+    expires_at: i64,
+    purpose: String,
+    key_version: u32,
+    tag: String,
+}
+
+#[tracing::instrument(name = "Lock an account", skip_all)]
+pub async fn lock_account(
+    parameters: web::Query<LockParameters>,
+    pool: web::Data<PgPool>,
+    link_signer: web::Data<LinkSigner>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // the signature first - nothing else in the link can be trusted
+    // until it checks out
+    if let Err(e) = link_signer.verify(
+        parameters.subscriber_id,
+        parameters.expires_at,
+        &parameters.purpose,
+        parameters.key_version,
+        &parameters.tag,
+        ACCOUNT_LOCK,
+        clock.now(),
+    ) {
+        tracing::warn!(error.cause_chain = ?e, "Rejected an invalid account-lock link");
+        return Ok(HttpResponse::Unauthorized().body("This link is invalid or has expired."));
+    }
+    let user_id = parameters.subscriber_id;
+
+    sqlx::query!("UPDATE users SET locked = true WHERE user_id = $1", user_id)
+        .execute(pool.get_ref())
+        .await
+        .map_err(e500)?;
+    // and throw out whoever is currently signed in as this user
+    revoke_other_sessions(&pool, user_id, None)
+        .await
+        .map_err(e500)?;
+
+    tracing::info!(%user_id, "An account was locked via a security notification link");
+    Ok(HttpResponse::Ok().body(
+        "The account has been locked and all of its sessions have been \
+        signed out. Contact the operator to unlock it.",
+    ))
+}
+
+/// Whether an account has been locked via the kill switch.
+pub async fn is_locked(pool: &PgPool, user_id: Uuid) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!("SELECT locked FROM users WHERE user_id = $1", user_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.map(|r| r.locked).unwrap_or(false))
+}
+
+/// Note a successful login in the history and, if this (ip, user agent)
+/// pair has never been seen for this user before, email the operators.
+/// Best-effort - a notification failure never blocks the login itself.
+#[allow(clippy::too_many_arguments)]
+pub async fn note_login(
+    pool: &PgPool,
+    alerter: &Alerter,
+    base_url: &ApplicationBaseUrl,
+    link_signer: &LinkSigner,
+    user_id: Uuid,
+    ip: &str,
+    user_agent: &str,
+    now: DateTime<Utc>,
+) {
+    let outcome = async {
+        // only notify when the user already has history - otherwise the
+        // very first login after this feature ships would cry wolf
+        let known_pairs = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM login_history WHERE user_id = $1",
+            user_id,
+        )
+        .fetch_one(pool)
+        .await?
+        .unwrap_or(0);
+
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO login_history (user_id, ip, user_agent, first_seen_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT DO NOTHING
+            "#,
+            user_id,
+            ip,
+            user_agent,
+            now,
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        if inserted > 0 && known_pairs > 0 {
+            let body = format!(
+                "A login to the admin account just happened from a device \
+                we haven't seen before.\n\nIP: {}\nUser agent: {}\n\n\
+                If this wasn't you, lock the account now:\n{}",
+                ip,
+                user_agent,
+                lock_link(base_url, link_signer, user_id, now),
+            );
+            alerter
+                .notify("unrecognized-login", "New sign-in to your account", &body, now)
+                .await;
+        }
+        Ok::<(), anyhow::Error>(())
+    }
+    .await;
+    if let Err(e) = outcome {
+        tracing::warn!(error.cause_chain = ?e, "Failed to process the login notification");
+    }
+}
+
+/// Email the operators that the admin password just changed, with the
+/// same kill switch in case it wasn't them. Best-effort.
+pub async fn notify_password_changed(
+    alerter: &Alerter,
+    base_url: &ApplicationBaseUrl,
+    link_signer: &LinkSigner,
+    user_id: Uuid,
+    now: DateTime<Utc>,
+) {
+    let body = format!(
+        "The admin account's password was just changed.\n\n\
+        If this wasn't you, lock the account now:\n{}",
+        lock_link(base_url, link_signer, user_id, now),
+    );
+    alerter
+        .notify("password-changed", "Your password was changed", &body, now)
+        .await;
+}
+
+fn lock_link(
+    base_url: &ApplicationBaseUrl,
+    link_signer: &LinkSigner,
+    user_id: Uuid,
+    now: DateTime<Utc>,
+) -> String {
+    format!(
+        "{}/account/lock?{}",
+        base_url.0,
+        link_signer.query_fragment(user_id, ACCOUNT_LOCK, now + Duration::days(LOCK_LINK_VALIDITY_DAYS)),
+    )
+}
@@ -0,0 +1,161 @@
+use crate::domain_events::{
+    DELIVERY_FAILED, ISSUE_PUBLISHED, SUBSCRIBER_CONFIRMED, SUBSCRIBER_UNSUBSCRIBED,
+};
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+// The recent-activity stream: confirmations, unsubscribes, published
+// issues and failed deliveries, read back from the `events` table (see
+// crate::domain_events). Per-email delivery events are deliberately left
+// out - a 50k-recipient send would drown everything else.
+//
+// Pagination is keyset on (occurred_at, id): the "load more" link
+// carries the last row's position, so a stream that grows while you read
+// it never shows a row twice or skips one - the failure mode of
+// OFFSET-based paging over moving data.
+
+// enough to catch up on at a glance; older pages are one click away
+const ACTIVITY_PAGE_SIZE: i64 = 20;
+
+// where "load more" should resume from - the position of the last row
+// the reader has already seen. The timestamp travels as unix
+// microseconds: unambiguous, and no RFC 3339 `+` to lose in a query string
+#[derive(serde::Deserialize)]
+pub struct ActivityCursor {
+    before_us: Option<i64>,
+    before_id: Option<Uuid>,
+}
+
+impl ActivityCursor {
+    fn position(&self) -> Option<(DateTime<Utc>, Uuid)> {
+        let occurred_at = self.before_us.and_then(DateTime::from_timestamp_micros)?;
+        Some((occurred_at, self.before_id?))
+    }
+}
+
+// one page of the stream as rendered <li> items, plus the "load more"
+// href when the page came back full
+pub(super) async fn activity_page(
+    pool: &PgPool,
+    before: Option<(DateTime<Utc>, Uuid)>,
+) -> Result<(String, Option<String>), anyhow::Error> {
+    let (before_at, before_id) = match before {
+        Some((at, id)) => (Some(at), Some(id)),
+        None => (None, None),
+    };
+    let events = sqlx::query!(
+        r#"
+        SELECT e.id, e.event_type, e.occurred_at,
+            s.email AS "email?", i.title AS "title?"
+        FROM events e
+        LEFT JOIN subscriptions s ON s.id = e.subject_id
+        LEFT JOIN newsletter_issues i ON i.newsletter_issue_id = e.subject_id
+        WHERE e.event_type = ANY($1)
+        AND ($2::timestamptz IS NULL
+            OR (e.occurred_at, e.id) < ($2::timestamptz, $3::uuid))
+        ORDER BY e.occurred_at DESC, e.id DESC
+        LIMIT $4
+        "#,
+        &[
+            SUBSCRIBER_CONFIRMED.to_string(),
+            SUBSCRIBER_UNSUBSCRIBED.to_string(),
+            ISSUE_PUBLISHED.to_string(),
+            DELIVERY_FAILED.to_string(),
+        ],
+        before_at,
+        before_id,
+        ACTIVITY_PAGE_SIZE,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the activity stream.")?;
+
+    let mut items_html = String::new();
+    for event in &events {
+        // subjects can outlive their rows - a purged subscriber's events
+        // stay in the stream, just anonymously
+        let email = event.email.as_deref().unwrap_or("(deleted subscriber)");
+        let label = match event.event_type.as_str() {
+            SUBSCRIBER_CONFIRMED => {
+                format!("{} confirmed their subscription", htmlescape::encode_minimal(email))
+            }
+            SUBSCRIBER_UNSUBSCRIBED => {
+                format!("{} unsubscribed", htmlescape::encode_minimal(email))
+            }
+            ISSUE_PUBLISHED => format!(
+                "Issue \"{}\" was queued for delivery",
+                htmlescape::encode_minimal(event.title.as_deref().unwrap_or("(deleted issue)"))
+            ),
+            DELIVERY_FAILED => format!(
+                "A delivery to {} failed permanently",
+                htmlescape::encode_minimal(email)
+            ),
+            other => htmlescape::encode_minimal(other),
+        };
+        writeln!(
+            items_html,
+            "<li>{} - {}</li>",
+            event.occurred_at.format("%Y-%m-%d %H:%M"),
+            label,
+        )
+        .unwrap();
+    }
+    if events.is_empty() && before.is_none() {
+        items_html.push_str("<li>No activity yet.</li>");
+    }
+
+    // a full page probably has more behind it; a short one is the end
+    let more_href = if events.len() as i64 == ACTIVITY_PAGE_SIZE {
+        events.last().map(|last| {
+            format!(
+                "/admin/activity?before_us={}&before_id={}",
+                last.occurred_at.timestamp_micros(),
+                last.id,
+            )
+        })
+    } else {
+        None
+    };
+    Ok((items_html, more_href))
+}
+
+/// GET /admin/activity - older pages of the dashboard's activity stream.
+#[tracing::instrument(name = "View the activity stream", skip_all)]
+pub async fn activity_feed(
+    pool: web::Data<PgPool>,
+    cursor: web::Query<ActivityCursor>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (items_html, more_href) = activity_page(&pool, cursor.position())
+        .await
+        .map_err(e500)?;
+    let more_html = match more_href {
+        Some(href) => format!(r#"<p><a href="{href}">Load more</a></p>"#),
+        None => "<p><i>That is the whole stream.</i></p>".to_string(),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Activity</title>
+</head>
+<body>
+    <h1>Activity</h1>
+    <ul>
+        {items_html}
+    </ul>
+    {more_html}
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
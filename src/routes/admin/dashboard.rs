@@ -1,30 +1,147 @@
+use crate::authentication::UserId;
 use crate::utils::e500;
-use actix_web::http::header::LOCATION;
 use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
 use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
 use sqlx::PgPool;
+use std::fmt::Write;
 use uuid::Uuid;
 
-use crate::session_state::TypedSession;
-
 pub async fn admin_dashboard(
-    session: TypedSession,
     pool: web::Data<PgPool>,
+    // the middleware's effective user - while impersonating, the target
+    user_id: web::ReqData<UserId>,
+    impersonation: Option<web::ReqData<crate::authentication::Impersonation>>,
+    flash_messages: IncomingFlashMessages,
 ) -> Result<HttpResponse, actix_web::Error> {
-    // we stored the user_id into the session state as part of login -
-    // now we retrieve that with session::get("user_id")
-    // this gives us the username to look up in the redis db and check
-    // their cookie session state is ok
+    let msg_html = crate::utils::flash_messages_html(&flash_messages);
+
+    // the prominent "you are not yourself" banner - hard to miss, so a
+    // support session is never mistaken for the real thing
+    let impersonation_banner = match &impersonation {
+        Some(imp) => {
+            let impersonator = get_username(imp.impersonator_id, &pool)
+                .await
+                .map_err(e500)?;
+            format!(
+                r#"<p style="background:#ffd54f;padding:0.5em"><b>Impersonation mode</b> -
+                you ({}) are viewing the admin as another user.
+                <form action="/admin/impersonate/stop" method="post" style="display:inline">
+                    <button type="submit">Stop impersonating</button>
+                </form></p>"#,
+                htmlescape::encode_minimal(&impersonator),
+            )
+        }
+        None => String::new(),
+    };
+
+    let username = get_username(**user_id, &pool).await.map_err(e500)?;
+    let role = crate::authentication::get_user_role(&pool, **user_id)
+        .await
+        .map_err(e500)?;
+
+    // gather the numbers for the metrics widgets
+    let subscriber_counts = get_subscriber_counts(&pool).await.map_err(e500)?;
+    let daily_signups = get_daily_signups(&pool).await.map_err(e500)?;
+    let acquisition_sources = get_acquisition_sources(&pool).await.map_err(e500)?;
+    let last_issue = get_last_issue_stats(&pool).await.map_err(e500)?;
+
+    // the first page of the activity stream - "load more" continues it
+    // on /admin/activity from exactly where this page stops
+    let (activity_html, activity_more) = super::activity::activity_page(&pool, None)
+        .await
+        .map_err(e500)?;
+    let activity_more_html = match activity_more {
+        Some(href) => format!(r#"<p><a href="{href}">Load more</a></p>"#),
+        None => String::new(),
+    };
+
+    let mut signups_html = String::new();
+    for (day, count) in &daily_signups {
+        writeln!(signups_html, "<li>{}: {} new subscribers</li>", day, count).unwrap();
+    }
+    if daily_signups.is_empty() {
+        signups_html.push_str("<li>No signups in the last 30 days</li>");
+    }
+
+    let mut sources_html = String::new();
+    for (source, count) in &acquisition_sources {
+        writeln!(
+            sources_html,
+            "<li>{}: {} subscribers</li>",
+            htmlescape::encode_minimal(source),
+            count
+        )
+        .unwrap();
+    }
 
-    // this reads, if session.get("user_id") returns Some(user_id), {username = x} else {username = y}
+    let last_issue_html = match &last_issue {
+        Some(issue) => {
+            let feedback = match issue.average_score {
+                Some(average) => format!(
+                    "<br />Rated {:.1}/5 by {} reader(s)",
+                    average, issue.feedback_count
+                ),
+                None => "<br />No feedback yet".to_string(),
+            };
+            let mut poll_html = String::new();
+            if let Some(poll) = &issue.poll {
+                write!(
+                    poll_html,
+                    "<br />Poll: <b>{}</b>",
+                    htmlescape::encode_minimal(&poll.question)
+                )
+                .unwrap();
+                for (label, votes) in &poll.options {
+                    write!(
+                        poll_html,
+                        "<br />&nbsp;&nbsp;{}: {} vote(s)",
+                        htmlescape::encode_minimal(label),
+                        votes
+                    )
+                    .unwrap();
+                }
+            }
+            format!(
+                "<p><b>{}</b> (published {})<br />\
+                {} delivered, {} still queued{}{}</p>",
+                htmlescape::encode_minimal(&issue.title),
+                issue.published_at.format("%Y-%m-%d %H:%M UTC"),
+                issue.delivered,
+                issue.queued,
+                feedback,
+                poll_html
+            )
+        }
+        None => "<p>No issues published yet.</p>".to_string(),
+    };
 
-    let username = if let Some(user_id) = session.get_user_id().map_err(e500)? {
-        get_username(user_id, &pool).await.map_err(e500)?
+    // the navigation only offers what this user may actually reach - the
+    // owner-only corners return 403 for editors regardless
+    let owner_links = if role == crate::authentication::Role::Owner {
+        r#"<li><a href="/admin/settings">Site settings</a></li>
+                <li><a href="/admin/deliverability">Deliverability</a></li>
+                <li><a href="/admin/replies">Reader replies</a></li>
+                <li><a href="/admin/diagnostics">Worker diagnostics</a></li>
+                <li><a href="/admin/subscribers/import">Import subscribers</a></li>
+                <li><a href="/admin/trash">Trash</a></li>
+                <li>
+                    <form action="/admin/impersonate" method="post">
+                    <input type="text" name="username" placeholder="username">
+                    <button type="submit">View as</button>
+                    </form>
+                </li>
+                <li>
+                    <form action="/admin/users/deactivate" method="post">
+                    <input type="text" name="username" placeholder="username">
+                    <button type="submit">Deactivate user</button>
+                    </form>
+                </li>"#
     } else {
-        return Ok(HttpResponse::SeeOther()
-            .insert_header((LOCATION, "/login"))
-            .finish());
+        ""
     };
+
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
@@ -35,11 +152,34 @@ pub async fn admin_dashboard(
             <title>Admin dashboard</title>
         </head>
         <body>
+            {impersonation_banner}
+            {msg_html}
             <p>Welcome {username}!</p>
+            <h2>Subscribers</h2>
+            <p>{total} total - {confirmed} confirmed, {pending} pending confirmation</p>
+            <h2>Signups - last 30 days</h2>
+            <ul>
+                {signups_html}
+            </ul>
+            <h2>Acquisition sources</h2>
+            <ul>
+                {sources_html}
+            </ul>
+            <h2>Last issue</h2>
+            {last_issue_html}
+            <h2>Recent activity</h2>
+            <ul>
+                {activity_html}
+            </ul>
+            {activity_more_html}
             <p>Available actions:</p>
             <ol>
                 <li><a href="/admin/password">Change password</a></li>
                 <li><a href="/admin/newsletter">Send a newsletter</a></li>
+                <li><a href="/admin/search">Search subscribers</a></li>
+                <li><a href="/admin/subscribers">Subscribers</a></li>
+                <li><a href="/admin/segments">Segments</a></li>
+                {owner_links}
                 <li>
                     <form name="logoutForm" action="/admin/logout" method="post">
                     <input type="submit" value="Logout">
@@ -47,7 +187,10 @@ pub async fn admin_dashboard(
                 </li>
             </ol>
         </body>
-        </html>"#
+        </html>"#,
+            total = subscriber_counts.total,
+            confirmed = subscriber_counts.confirmed,
+            pending = subscriber_counts.pending,
         )))
 }
 
@@ -68,3 +211,180 @@ pub async fn get_username(user_id: Uuid, pool: &PgPool) -> Result<String, anyhow
 
     Ok(row.username)
 }
+
+struct SubscriberCounts {
+    total: i64,
+    confirmed: i64,
+    pending: i64,
+}
+
+// one pass over the subscriptions table gives us all three headline numbers
+#[tracing::instrument(name = "Get subscriber counts", skip_all)]
+async fn get_subscriber_counts(pool: &PgPool) -> Result<SubscriberCounts, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(*) as "total!",
+            COUNT(*) FILTER (WHERE status = 'confirmed') as "confirmed!",
+            COUNT(*) FILTER (WHERE status = 'pending_confirmation') as "pending!"
+        FROM subscriptions
+        WHERE deleted_at IS NULL
+        "#,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count subscribers.")?;
+
+    Ok(SubscriberCounts {
+        total: row.total,
+        confirmed: row.confirmed,
+        pending: row.pending,
+    })
+}
+
+// daily signup counts over the last 30 days - days with no signups are
+// simply absent from the result
+#[tracing::instrument(name = "Get daily signups", skip_all)]
+async fn get_daily_signups(pool: &PgPool) -> Result<Vec<(NaiveDate, i64)>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            subscribed_at::date as "day!",
+            COUNT(*) as "signups!"
+        FROM subscriptions
+        WHERE subscribed_at > now() - interval '30 days'
+        AND deleted_at IS NULL
+        GROUP BY subscribed_at::date
+        ORDER BY subscribed_at::date
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch daily signup counts.")?;
+
+    Ok(rows.into_iter().map(|r| (r.day, r.signups)).collect())
+}
+
+// how many subscribers each `?ref=` channel has brought in - signups with
+// no referrer are grouped under "(direct)"
+#[tracing::instrument(name = "Get acquisition sources", skip_all)]
+async fn get_acquisition_sources(pool: &PgPool) -> Result<Vec<(String, i64)>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            COALESCE(acquisition_source, '(direct)') as "source!",
+            COUNT(*) as "subscribers!"
+        FROM subscriptions
+        WHERE deleted_at IS NULL
+        GROUP BY COALESCE(acquisition_source, '(direct)')
+        ORDER BY COUNT(*) DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch acquisition source counts.")?;
+
+    Ok(rows.into_iter().map(|r| (r.source, r.subscribers)).collect())
+}
+
+struct LastIssueStats {
+    title: String,
+    published_at: DateTime<Utc>,
+    delivered: i64,
+    queued: i64,
+    // from the one-click feedback links - `None` until somebody clicks
+    average_score: Option<f64>,
+    feedback_count: i64,
+    // the issue's poll and its per-option vote counts, if it had one
+    poll: Option<PollStats>,
+}
+
+struct PollStats {
+    question: String,
+    options: Vec<(String, i64)>,
+}
+
+// the most recently published issue plus how far its delivery has got
+#[tracing::instrument(name = "Get last issue stats", skip_all)]
+async fn get_last_issue_stats(pool: &PgPool) -> Result<Option<LastIssueStats>, anyhow::Error> {
+    let issue = sqlx::query!(
+        r#"
+        SELECT
+            newsletter_issue_id,
+            title,
+            published_at::timestamptz as "published_at!"
+        FROM newsletter_issues
+        ORDER BY published_at::timestamptz DESC
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the last published issue.")?;
+
+    let Some(issue) = issue else {
+        return Ok(None);
+    };
+
+    let stats = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM email_delivery_log
+                WHERE newsletter_issue_id = $1 AND NOT failed) as "delivered!",
+            (SELECT COUNT(*) FROM issue_delivery_queue
+                WHERE newsletter_issue_id = $1) as "queued!",
+            (SELECT AVG(score)::float8 FROM issue_feedback
+                WHERE newsletter_issue_id = $1) as "average_score",
+            (SELECT COUNT(*) FROM issue_feedback
+                WHERE newsletter_issue_id = $1) as "feedback_count!"
+        "#,
+        issue.newsletter_issue_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to fetch delivery stats for the last issue.")?;
+
+    Ok(Some(LastIssueStats {
+        title: issue.title,
+        published_at: issue.published_at,
+        delivered: stats.delivered,
+        queued: stats.queued,
+        average_score: stats.average_score,
+        feedback_count: stats.feedback_count,
+        poll: get_poll_stats(pool, issue.newsletter_issue_id).await?,
+    }))
+}
+
+// the issue's poll, if it had one, with a vote count per option
+#[tracing::instrument(name = "Get poll stats", skip_all)]
+async fn get_poll_stats(pool: &PgPool, issue_id: Uuid) -> Result<Option<PollStats>, anyhow::Error> {
+    let poll = sqlx::query!(
+        "SELECT id, question FROM issue_polls WHERE newsletter_issue_id = $1",
+        issue_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the last issue's poll.")?;
+    let Some(poll) = poll else {
+        return Ok(None);
+    };
+    let options = sqlx::query!(
+        r#"
+        SELECT o.label, COUNT(v.subscriber_id) as "votes!"
+        FROM poll_options o
+        LEFT JOIN poll_votes v ON v.option_id = o.id
+        WHERE o.poll_id = $1
+        GROUP BY o.id, o.label, o.position
+        ORDER BY o.position
+        "#,
+        poll.id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the last issue's poll results.")?;
+
+    Ok(Some(PollStats {
+        question: poll.question,
+        options: options.into_iter().map(|o| (o.label, o.votes)).collect(),
+    }))
+}
@@ -0,0 +1,164 @@
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::NaiveDate;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+// The deliverability dashboard: daily bounce and complaint rates broken
+// down by recipient domain (gmail.com, outlook.com, ...), computed from
+// the delivery log and the provider's feedback webhook. A cliff on one
+// domain with the others healthy usually means that provider has started
+// junking us - worth knowing before the list notices.
+//
+// Rates pair each day's feedback with that day's deliveries. Bounces can
+// trail the send by a day or two, so single days are noisy - it's the
+// trend across a couple of weeks that matters.
+
+// far enough back to show a trend without the table scrolling forever
+const REPORT_WINDOW_DAYS: i32 = 14;
+
+#[derive(serde::Serialize)]
+struct DomainDayStats {
+    day: NaiveDate,
+    domain: String,
+    delivered: i64,
+    bounces: i64,
+    complaints: i64,
+}
+
+impl DomainDayStats {
+    // "3.1%" against that day's deliveries, or "-" when nothing was sent
+    // (feedback without a delivery - a late bounce - still shows its count)
+    fn rate(&self, count: i64) -> String {
+        if self.delivered == 0 {
+            return "-".to_string();
+        }
+        format!("{:.1}%", (count as f64 / self.delivered as f64) * 100.0)
+    }
+}
+
+/// GET /admin/deliverability - the dashboard page.
+#[tracing::instrument(name = "View the deliverability dashboard", skip_all)]
+pub async fn deliverability_dashboard(
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let rows = get_domain_day_stats(&pool).await.map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for row in &rows {
+        writeln!(
+            rows_html,
+            "<tr>\
+             <td>{day}</td>\
+             <td>{domain}</td>\
+             <td>{delivered}</td>\
+             <td>{bounces} ({bounce_rate})</td>\
+             <td>{complaints} ({complaint_rate})</td>\
+             </tr>",
+            day = row.day,
+            domain = htmlescape::encode_minimal(&row.domain),
+            delivered = row.delivered,
+            bounces = row.bounces,
+            bounce_rate = row.rate(row.bounces),
+            complaints = row.complaints,
+            complaint_rate = row.rate(row.complaints),
+        )
+        .unwrap();
+    }
+    if rows.is_empty() {
+        rows_html.push_str("<tr><td colspan=\"5\">No deliveries in the window.</td></tr>");
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Deliverability</title>
+</head>
+<body>
+    <h1>Deliverability - last {REPORT_WINDOW_DAYS} days</h1>
+    <table border="1" cellpadding="5">
+        <tr>
+            <th>Day</th>
+            <th>Domain</th>
+            <th>Delivered</th>
+            <th>Bounces</th>
+            <th>Complaints</th>
+        </tr>
+        {rows_html}
+    </table>
+    <p>Rates are against the same day's deliveries - bounces can trail a
+    send by a day or two, so watch the trend, not single days.</p>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
+
+/// GET /admin/deliverability/data - the same numbers as JSON, for anyone
+/// plotting them properly.
+#[tracing::instrument(name = "Fetch deliverability data", skip_all)]
+pub async fn deliverability_data(
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let rows = get_domain_day_stats(&pool).await.map_err(e500)?;
+    Ok(HttpResponse::Ok().json(rows))
+}
+
+// deliveries and feedback per (day, domain) over the window - a full
+// outer join, so a late bounce on a day with no sends still shows up
+async fn get_domain_day_stats(pool: &PgPool) -> Result<Vec<DomainDayStats>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        WITH deliveries AS (
+            SELECT
+                sent_at::date AS day,
+                split_part(recipient_email, '@', 2) AS domain,
+                COUNT(*) AS delivered
+            FROM email_delivery_log
+            WHERE NOT failed
+            AND sent_at > now() - make_interval(days => $1)
+            GROUP BY 1, 2
+        ),
+        feedback AS (
+            SELECT
+                occurred_at::date AS day,
+                split_part(recipient_email, '@', 2) AS domain,
+                COUNT(*) FILTER (WHERE event_type = 'bounce') AS bounces,
+                COUNT(*) FILTER (WHERE event_type = 'complaint') AS complaints
+            FROM email_feedback_events
+            WHERE occurred_at > now() - make_interval(days => $1)
+            GROUP BY 1, 2
+        )
+        SELECT
+            COALESCE(d.day, f.day) AS "day!",
+            COALESCE(d.domain, f.domain) AS "domain!",
+            COALESCE(d.delivered, 0) AS "delivered!",
+            COALESCE(f.bounces, 0) AS "bounces!",
+            COALESCE(f.complaints, 0) AS "complaints!"
+        FROM deliveries d
+        FULL OUTER JOIN feedback f ON f.day = d.day AND f.domain = d.domain
+        ORDER BY 1 DESC, 3 DESC
+        "#,
+        REPORT_WINDOW_DAYS,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to compute deliverability stats.")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| DomainDayStats {
+            day: r.day,
+            domain: r.domain,
+            delivered: r.delivered,
+            bounces: r.bounces,
+            complaints: r.complaints,
+        })
+        .collect())
+}
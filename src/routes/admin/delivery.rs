@@ -0,0 +1,40 @@
+use crate::clock::Clock;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+// The delivery pause switch - POST /admin/delivery/pause halts all
+// outbound email (the worker checks the persisted flag on every pass, so
+// it takes effect within seconds, mid-issue included), and /resume lifts
+// it. Paused tasks stay queued; nothing is lost, only delayed.
+
+/// POST /admin/delivery/pause - halt all outbound email.
+#[tracing::instrument(name = "Pause all deliveries", skip_all)]
+pub async fn pause_delivery(
+    pool: web::Data<PgPool>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    sqlx::query!(
+        "UPDATE delivery_state SET paused = true, paused_at = $1",
+        clock.now(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+
+    FlashMessage::info("Deliveries are paused. Queued emails are held, not lost.").send();
+    Ok(see_other("/admin/dashboard"))
+}
+
+/// POST /admin/delivery/resume - lift the pause.
+#[tracing::instrument(name = "Resume deliveries", skip_all)]
+pub async fn resume_delivery(pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    sqlx::query!("UPDATE delivery_state SET paused = false, paused_at = NULL")
+        .execute(pool.get_ref())
+        .await
+        .map_err(e500)?;
+
+    FlashMessage::info("Deliveries have resumed.").send();
+    Ok(see_other("/admin/dashboard"))
+}
@@ -0,0 +1,206 @@
+use crate::clock::Clock;
+use crate::configuration::WorkerMonitorSettings;
+use crate::utils::e500;
+use crate::worker_monitor;
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+// GET /admin/diagnostics - the state of the delivery pipeline at a glance:
+// every worker that has ever heartbeated, the queue depth, and whatever the
+// stuck-worker monitor would currently be complaining about
+pub async fn worker_diagnostics(
+    pool: web::Data<PgPool>,
+    monitor: web::Data<WorkerMonitorSettings>,
+    clock: web::Data<dyn Clock>,
+    session_store: web::Data<crate::redis_sessions::ResilientSessionStore>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let now = clock.now();
+
+    let workers = get_worker_heartbeats(&pool).await.map_err(e500)?;
+    let queue = get_queue_summary(&pool, now).await.map_err(e500)?;
+    let problems = worker_monitor::find_problems(&pool, &monitor, now)
+        .await
+        .map_err(e500)?;
+
+    // the health banner - green silence or a red list of complaints
+    let problems_html = if problems.is_empty() {
+        "<p>All checks passing.</p>".to_string()
+    } else {
+        let mut items = String::new();
+        for problem in &problems {
+            writeln!(
+                items,
+                "<li><b>{}</b></li>",
+                htmlescape::encode_minimal(problem)
+            )
+            .unwrap();
+        }
+        format!("<ul>{items}</ul>")
+    };
+
+    let mut workers_html = String::new();
+    for worker in &workers {
+        let silence = (now - worker.last_seen_at).num_seconds();
+        // anything past the configured timeout gets flagged inline
+        let status = if silence >= monitor.heartbeat_timeout_seconds as i64 {
+            "STALE"
+        } else {
+            "ok"
+        };
+        writeln!(
+            workers_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}s ago</td><td>{}</td></tr>",
+            worker.worker_id,
+            worker.started_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            worker.last_seen_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            silence,
+            status
+        )
+        .unwrap();
+    }
+    if workers.is_empty() {
+        workers_html.push_str("<tr><td colspan=\"5\">No worker has reported yet.</td></tr>");
+    }
+
+    let oldest_html = match queue.oldest_available_at {
+        Some(oldest) => format!("oldest has been waiting {}s", (now - oldest).num_seconds()),
+        None => "nothing waiting".to_string(),
+    };
+
+    // where this process spends its database time, heaviest first - the
+    // counters live in memory, so they reset on every deploy
+    let timings = crate::telemetry::query_timing_snapshot();
+    let mut queries_html = String::new();
+    for (summary, stats) in timings.iter().take(20) {
+        writeln!(
+            queries_html,
+            "<tr><td>{}</td><td>{}</td><td>{:.1}ms</td><td>{:.1}ms</td><td>{:.1}ms</td></tr>",
+            htmlescape::encode_minimal(summary),
+            stats.count,
+            stats.total_secs * 1000.0,
+            stats.total_secs / stats.count as f64 * 1000.0,
+            stats.max_secs * 1000.0,
+        )
+        .unwrap();
+    }
+    if timings.is_empty() {
+        queries_html
+            .push_str("<tr><td colspan=\"5\">No queries recorded since this process started.</td></tr>");
+    }
+
+    // the session backend - a live round trip plus the in-memory counters
+    // the store keeps (same reset-on-deploy caveat as the query timings)
+    let session_stats = crate::redis_sessions::session_store_snapshot();
+    let ping_html = match session_store.ping().await {
+        Ok(latency) => format!("PING answered in {:.1}ms", latency.as_secs_f64() * 1000.0),
+        Err(_) => "<b>Redis is unreachable</b>".to_string(),
+    };
+    let session_mean_html = if session_stats.operations > 0 {
+        format!(
+            "mean {:.1}ms, max {:.1}ms",
+            session_stats.total_secs / session_stats.operations as f64 * 1000.0,
+            session_stats.max_secs * 1000.0,
+        )
+    } else {
+        "no operations yet".to_string()
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta http-equiv="content-type" content="text/html; charset=utf-8">
+            <title>Worker diagnostics</title>
+        </head>
+        <body>
+            <h1>Worker diagnostics</h1>
+            <h2>Health</h2>
+            {problems_html}
+            <h2>Delivery queue</h2>
+            <p>{depth} deliverable task(s) - {oldest_html}</p>
+            <h2>Workers</h2>
+            <table border="1">
+                <tr>
+                    <th>Worker</th><th>Started</th><th>Last seen</th>
+                    <th>Silence</th><th>Status</th>
+                </tr>
+                {workers_html}
+            </table>
+            <h2>Query timings (since process start)</h2>
+            <table border="1">
+                <tr>
+                    <th>Statement</th><th>Count</th><th>Total</th>
+                    <th>Mean</th><th>Max</th>
+                </tr>
+                {queries_html}
+            </table>
+            <h2>Session store</h2>
+            <p>{ping_html} - {operations} operation(s), {errors} error(s), {session_mean_html}</p>
+            <p><a href="/admin/dashboard">&lt;- Back</a></p>
+        </body>
+        </html>"#,
+            depth = queue.depth,
+            operations = session_stats.operations,
+            errors = session_stats.errors,
+        )))
+}
+
+struct WorkerHeartbeat {
+    worker_id: uuid::Uuid,
+    started_at: chrono::DateTime<chrono::Utc>,
+    last_seen_at: chrono::DateTime<chrono::Utc>,
+}
+
+// every row in worker_heartbeats, most recently seen first - rows are never
+// deleted, so dead workers stay visible (and flagged) until a redeploy
+// truncates the table
+#[tracing::instrument(name = "Get worker heartbeats", skip_all)]
+async fn get_worker_heartbeats(pool: &PgPool) -> Result<Vec<WorkerHeartbeat>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        WorkerHeartbeat,
+        r#"
+        SELECT worker_id, started_at, last_seen_at
+        FROM worker_heartbeats
+        ORDER BY last_seen_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the worker heartbeats.")?;
+
+    Ok(rows)
+}
+
+struct QueueSummary {
+    depth: i64,
+    oldest_available_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+// how much deliverable work is waiting, and how long the front of the
+// queue has been waiting for it
+#[tracing::instrument(name = "Get queue summary", skip_all)]
+async fn get_queue_summary(
+    pool: &PgPool,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<QueueSummary, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT COUNT(*) as "depth!", min(available_at) as oldest_available_at
+        FROM issue_delivery_queue
+        WHERE available_at <= $1
+        "#,
+        now
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to summarise the delivery queue.")?;
+
+    Ok(QueueSummary {
+        depth: row.depth,
+        oldest_available_at: row.oldest_available_at,
+    })
+}
@@ -0,0 +1,116 @@
+use crate::authentication::{get_user_role, Impersonation, Role, UserId};
+use crate::clock::Clock;
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// Support impersonation: an owner can temporarily view the admin as
+// another user, to debug what that user sees. The claim lives in the
+// owner's own session (see reject_anonymous_users), the dashboard shows
+// a prominent banner while it's active, and every impersonation leaves a
+// row in impersonation_log.
+
+#[derive(serde::Deserialize)]
+pub struct ImpersonateForm {
+    username: String,
+}
+
+/// POST /admin/impersonate - start viewing the admin as another user.
+#[tracing::instrument(name = "Start impersonating a user", skip(form, pool, session, clock))]
+pub async fn start_impersonation(
+    form: web::Form<ImpersonateForm>,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+    user_id: ReqData<UserId>,
+    impersonation: Option<ReqData<Impersonation>>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // no nesting - one mask at a time
+    if impersonation.is_some() {
+        FlashMessage::error("Stop the current impersonation first.").send();
+        return Ok(see_other("/admin/dashboard"));
+    }
+    if get_user_role(&pool, **user_id).await.map_err(e500)? != Role::Owner {
+        FlashMessage::error("Only owners may impersonate other users.").send();
+        return Ok(see_other("/admin/dashboard"));
+    }
+
+    let target = sqlx::query!(
+        "SELECT user_id, username FROM users WHERE username = $1",
+        form.username.trim(),
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    let Some(target) = target else {
+        FlashMessage::error("No user with that username exists.").send();
+        return Ok(see_other("/admin/dashboard"));
+    };
+    if target.user_id == **user_id {
+        FlashMessage::error("You are already yourself.").send();
+        return Ok(see_other("/admin/dashboard"));
+    }
+
+    // the audit row goes in before the claim - an impersonation that
+    // leaves no trail must not start
+    sqlx::query!(
+        r#"
+        INSERT INTO impersonation_log
+            (id, impersonator_user_id, target_user_id, started_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        Uuid::new_v4(),
+        **user_id,
+        target.user_id,
+        clock.now(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    session
+        .insert_impersonated_user_id(target.user_id)
+        .map_err(e500)?;
+
+    FlashMessage::info(format!(
+        "You are now viewing the admin as '{}'.",
+        target.username
+    ))
+    .send();
+    Ok(see_other("/admin/dashboard"))
+}
+
+/// POST /admin/impersonate/stop - drop the mask.
+#[tracing::instrument(name = "Stop impersonating a user", skip_all)]
+pub async fn stop_impersonation(
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+    user_id: ReqData<UserId>,
+    impersonation: Option<ReqData<Impersonation>>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Some(impersonation) = impersonation {
+        session.stop_impersonating();
+        // close the audit row - the UserId extension is the target's
+        sqlx::query!(
+            r#"
+            UPDATE impersonation_log
+            SET ended_at = $1
+            WHERE impersonator_user_id = $2
+            AND target_user_id = $3
+            AND ended_at IS NULL
+            "#,
+            clock.now(),
+            impersonation.impersonator_id,
+            **user_id,
+        )
+        .execute(pool.get_ref())
+        .await
+        .map_err(e500)?;
+        FlashMessage::info("Impersonation ended - you are yourself again.").send();
+    }
+    Ok(see_other("/admin/dashboard"))
+}
@@ -0,0 +1,349 @@
+use crate::clock::Clock;
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+// Bulk import from other newsletter tools. The CSV header row tells us
+// which tool produced the export (Mailchimp and Buttondown name their
+// columns differently), and the matching adapter maps each row onto our
+// schema. Crucially, a subscriber who was already confirmed (or already
+// unsubscribed) over there keeps that status here - nobody who double
+// opted in once should be asked to do it again.
+
+/// GET /admin/subscribers/import - paste-a-CSV form.
+pub async fn import_form(
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let msg_html = crate::utils::flash_messages_html(&flash_messages);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Import subscribers</title>
+</head>
+<body>
+    {msg_html}
+    <p>Paste a CSV export below. Mailchimp and Buttondown exports are
+    recognised by their column names; anything else needs at least an
+    <code>email</code> column (plus optional <code>name</code>,
+    <code>status</code>, <code>tags</code>, <code>subscribed_at</code>).</p>
+    <form action="/admin/subscribers/import" method="post">
+        <textarea name="csv" rows="20" cols="100" placeholder="Email Address,First Name,..."></textarea>
+        <br>
+        <button type="submit">Import</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ImportForm {
+    csv: String,
+}
+
+/// POST /admin/subscribers/import - parse, map and insert.
+#[tracing::instrument(name = "Import subscribers from CSV", skip_all)]
+pub async fn import_subscribers(
+    form: web::Form<ImportForm>,
+    pool: web::Data<PgPool>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (records, invalid_rows) = match parse_export(&form.csv) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            FlashMessage::error(format!("Could not read the CSV: {}", e)).send();
+            return Ok(see_other("/admin/subscribers/import"));
+        }
+    };
+
+    let mut transaction = pool.begin().await.map_err(e500)?;
+
+    let now = clock.now();
+    let mut imported = 0;
+    let mut duplicates = 0;
+    for record in &records {
+        match insert_imported_subscriber(&mut transaction, record, now).await {
+            Ok(Some(subscriber_id)) => {
+                store_tags(&mut transaction, subscriber_id, &record.tags)
+                    .await
+                    .map_err(e500)?;
+                imported += 1;
+            }
+            // already on the list - the import is not allowed to clobber
+            // whatever status they have with us
+            Ok(None) => duplicates += 1,
+            Err(e) => return Err(e500(e)),
+        }
+    }
+
+    transaction.commit().await.map_err(e500)?;
+
+    FlashMessage::info(format!(
+        "Imported {} subscriber(s) - {} already on the list, {} row(s) invalid.",
+        imported, duplicates, invalid_rows
+    ))
+    .send();
+    Ok(see_other("/admin/subscribers/import"))
+}
+
+// a row from any supported export, mapped onto our vocabulary
+#[derive(Debug, PartialEq)]
+struct ImportedSubscriber {
+    email: String,
+    name: String,
+    // confirmed | pending_confirmation | unsubscribed
+    status: String,
+    tags: Vec<String>,
+    subscribed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ExportFormat {
+    Mailchimp,
+    Buttondown,
+    Generic,
+}
+
+// the column names give the tool away: Mailchimp shouts in Title Case,
+// Buttondown has its subscriber_type column, anything else is generic
+fn detect_format(headers: &[String]) -> ExportFormat {
+    if headers.iter().any(|h| h == "email address") {
+        ExportFormat::Mailchimp
+    } else if headers.iter().any(|h| h == "subscriber_type") {
+        ExportFormat::Buttondown
+    } else {
+        ExportFormat::Generic
+    }
+}
+
+// parse the pasted text; returns the usable records plus a count of rows
+// that couldn't be mapped (no email, unparseable address)
+fn parse_export(text: &str) -> Result<(Vec<ImportedSubscriber>, usize), anyhow::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(text.trim().as_bytes());
+
+    // header lookup is case-insensitive - exports are not consistent
+    let headers: Vec<String> = reader
+        .headers()?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    let format = detect_format(&headers);
+    let column = |name: &str| headers.iter().position(|h| h == name);
+
+    let mut records = Vec::new();
+    let mut invalid_rows = 0;
+    for row in reader.records() {
+        let row = row?;
+        let field = |index: Option<usize>| -> &str {
+            index.and_then(|i| row.get(i)).unwrap_or("").trim()
+        };
+        let mapped = match format {
+            ExportFormat::Mailchimp => {
+                let email = field(column("email address"));
+                // Mailchimp splits the name; exports of a single segment
+                // often have no Status column at all, in which case every
+                // row is a live, double-opted-in subscriber
+                let name = format!(
+                    "{} {}",
+                    field(column("first name")),
+                    field(column("last name"))
+                )
+                .trim()
+                .to_string();
+                let status = match field(column("status")).to_lowercase().as_str() {
+                    "unsubscribed" | "cleaned" => "unsubscribed",
+                    "pending" => "pending_confirmation",
+                    _ => "confirmed",
+                };
+                ImportedSubscriber {
+                    email: email.to_string(),
+                    name,
+                    status: status.to_string(),
+                    tags: split_tags(field(column("tags"))),
+                    subscribed_at: parse_timestamp(field(column("confirm_time")))
+                        .or_else(|| parse_timestamp(field(column("optin_time")))),
+                }
+            }
+            ExportFormat::Buttondown => {
+                let status = match field(column("subscriber_type")).to_lowercase().as_str() {
+                    "unactivated" => "pending_confirmation",
+                    "unsubscribed" | "removed" => "unsubscribed",
+                    // regular, premium, gifted - all live subscribers
+                    _ => "confirmed",
+                };
+                ImportedSubscriber {
+                    email: field(column("email")).to_string(),
+                    name: field(column("name")).to_string(),
+                    status: status.to_string(),
+                    tags: split_tags(field(column("tags"))),
+                    subscribed_at: parse_timestamp(field(column("creation_date")))
+                        .or_else(|| parse_timestamp(field(column("date")))),
+                }
+            }
+            ExportFormat::Generic => {
+                let status = match field(column("status")).to_lowercase().as_str() {
+                    "unsubscribed" => "unsubscribed",
+                    "pending" | "pending_confirmation" => "pending_confirmation",
+                    _ => "confirmed",
+                };
+                ImportedSubscriber {
+                    email: field(column("email")).to_string(),
+                    name: field(column("name")).to_string(),
+                    status: status.to_string(),
+                    tags: split_tags(field(column("tags"))),
+                    subscribed_at: parse_timestamp(field(column("subscribed_at"))),
+                }
+            }
+        };
+
+        // a quick sanity check rather than full domain validation - the
+        // exporting tool already vetted these addresses once
+        if mapped.email.is_empty() || !mapped.email.contains('@') {
+            invalid_rows += 1;
+            continue;
+        }
+        records.push(mapped);
+    }
+
+    Ok((records, invalid_rows))
+}
+
+// Mailchimp and Buttondown both pack tags into one comma-separated field
+fn split_tags(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|tag| tag.trim().trim_matches('"').to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+// exports disagree on timestamp formats - try the usual suspects
+fn parse_timestamp(raw: &str) -> Option<DateTime<Utc>> {
+    if raw.is_empty() {
+        return None;
+    }
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(raw) {
+        return Some(parsed.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d"] {
+        if let Ok(parsed) = chrono::NaiveDateTime::parse_from_str(raw, format) {
+            return Some(parsed.and_utc());
+        }
+        if let Ok(parsed) = chrono::NaiveDate::parse_from_str(raw, format) {
+            return Some(parsed.and_hms_opt(0, 0, 0).unwrap().and_utc());
+        }
+    }
+    None
+}
+
+// insert the row, or return None if that email is already on the list
+async fn insert_imported_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    record: &ImportedSubscriber,
+    now: DateTime<Utc>,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let subscriber_id = Uuid::new_v4();
+    // imported rows sometimes have no name - fall back to the address
+    // rather than rejecting the subscriber
+    let name = if record.name.is_empty() {
+        record.email.as_str()
+    } else {
+        record.name.as_str()
+    };
+    let outcome = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, acquisition_source)
+        VALUES ($1, $2, $3, $4, $5, 'import')
+        ON CONFLICT (email) DO NOTHING
+        "#,
+        subscriber_id,
+        record.email,
+        name,
+        record.subscribed_at.unwrap_or(now),
+        record.status,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    if outcome.rows_affected() == 0 {
+        return Ok(None);
+    }
+    Ok(Some(subscriber_id))
+}
+
+async fn store_tags(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    tags: &[String],
+) -> Result<(), sqlx::Error> {
+    for tag in tags {
+        let query = sqlx::query!(
+            r#"
+            INSERT INTO subscriber_tags (subscriber_id, tag)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+            subscriber_id,
+            tag,
+        );
+        transaction.execute(query).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_mailchimp_export_is_recognised_and_mapped() {
+        let csv = "Email Address,First Name,Last Name,TAGS,CONFIRM_TIME\n\
+            jane@example.com,Jane,Doe,\"vip, beta\",2023-05-01 10:30:00\n";
+        let (records, invalid) = parse_export(csv).unwrap();
+        assert_eq!(invalid, 0);
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.email, "jane@example.com");
+        assert_eq!(record.name, "Jane Doe");
+        // no Status column - everyone in the export is confirmed
+        assert_eq!(record.status, "confirmed");
+        assert_eq!(record.tags, vec!["vip", "beta"]);
+        assert!(record.subscribed_at.is_some());
+    }
+
+    #[test]
+    fn a_buttondown_export_preserves_the_subscriber_lifecycle() {
+        let csv = "email,name,subscriber_type,tags,creation_date\n\
+            a@example.com,Ann,regular,,2023-01-01T09:00:00+00:00\n\
+            b@example.com,Bob,unactivated,,\n\
+            c@example.com,Cat,unsubscribed,,\n";
+        let (records, invalid) = parse_export(csv).unwrap();
+        assert_eq!(invalid, 0);
+        let statuses: Vec<&str> = records.iter().map(|r| r.status.as_str()).collect();
+        assert_eq!(
+            statuses,
+            vec!["confirmed", "pending_confirmation", "unsubscribed"]
+        );
+    }
+
+    #[test]
+    fn rows_without_a_usable_email_are_counted_not_imported() {
+        let csv = "email,name\n,No Address\nnot-an-email,Bad Address\nok@example.com,Fine\n";
+        let (records, invalid) = parse_export(csv).unwrap();
+        assert_eq!(invalid, 2);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].email, "ok@example.com");
+    }
+}
@@ -1,12 +1,21 @@
-use crate::session_state::TypedSession;
+use crate::session_state::{remove_session, TypedSession};
 use crate::utils::{e500, see_other};
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
 
-pub async fn log_out(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
+pub async fn log_out(
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
     if session.get_user_id().map_err(e500)?.is_none() {
         Ok(see_other("/login"))
     } else {
+        // tidy the registry row so it doesn't count against the
+        // concurrent-session cap
+        if let Some(session_id) = session.get_session_id().map_err(e500)? {
+            remove_session(&pool, session_id).await.map_err(e500)?;
+        }
         session.log_out();
         FlashMessage::info("You have successfully logged out.").send();
         Ok(see_other("/"))
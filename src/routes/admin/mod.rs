@@ -1,3 +1,6 @@
+mod activity;
+pub use activity::activity_feed;
+
 mod dashboard;
 pub use dashboard::{admin_dashboard, get_username};
 
@@ -9,3 +12,47 @@ pub use logout::log_out;
 
 mod newsletter;
 pub use newsletter::*;
+
+mod search;
+pub use search::admin_search;
+
+mod reengagement;
+pub use reengagement::{reengagement_form, run_reengagement};
+
+mod users;
+pub use users::deactivate_user;
+
+mod settings;
+pub use settings::*;
+
+mod diagnostics;
+pub use diagnostics::worker_diagnostics;
+
+mod deliverability;
+pub use deliverability::{deliverability_dashboard, deliverability_data};
+
+mod delivery;
+pub use delivery::{pause_delivery, resume_delivery};
+
+mod replies;
+pub use replies::reply_list;
+
+mod impersonate;
+pub use impersonate::{start_impersonation, stop_impersonation};
+
+mod import;
+pub use import::{import_form, import_subscribers};
+
+mod subscribers;
+pub use subscribers::{bulk_subscriber_action, delete_subscriber, subscriber_list};
+
+mod subscriber_detail;
+pub use subscriber_detail::{add_subscriber_note, subscriber_detail};
+
+mod segments;
+pub use segments::{create_segment, delete_segment, segments_page};
+
+mod trash;
+pub use trash::{
+    purge_draft, purge_subscriber, restore_draft, restore_subscriber, trash_page, TrashRetention,
+};
@@ -0,0 +1,192 @@
+use crate::utils::{e400, e500};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+// The issue comparison report: a side-by-side table of engagement metrics
+// for a handful of issues, computed from the delivery log and the
+// tracking events. Rates are against delivered emails; opens and clicks
+// count unique subscribers, not raw events. "Unsubscribed" counts
+// recipients of the issue whose subscription has since lapsed - an
+// approximation (they may have left over a later issue), but an honest
+// one for spotting an issue that drove readers away.
+
+// enough to compare a season's worth of issues without producing a table
+// wider than anyone can read
+const MAX_COMPARED_ISSUES: usize = 10;
+
+#[derive(serde::Deserialize)]
+pub struct CompareParams {
+    // comma-separated issue ids
+    ids: String,
+}
+
+struct IssueMetrics {
+    title: String,
+    published_at: DateTime<Utc>,
+    delivered: i64,
+    failed: i64,
+    unique_opens: i64,
+    unique_clicks: i64,
+    unsubscribed: i64,
+}
+
+impl IssueMetrics {
+    // "41.2%" against the delivered count, or "-" when nothing was sent
+    fn rate(&self, count: i64) -> String {
+        if self.delivered == 0 {
+            return "-".to_string();
+        }
+        format!("{:.1}%", (count as f64 / self.delivered as f64) * 100.0)
+    }
+}
+
+/// GET /admin/newsletter/compare?ids=a,b,c - engagement metrics for the
+/// selected issues, side by side.
+#[tracing::instrument(name = "Compare issue performance", skip_all)]
+pub async fn compare_issues(
+    query: web::Query<CompareParams>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let ids: Vec<Uuid> = query
+        .ids
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            id.parse()
+                .map_err(|_| format!("'{}' is not a valid issue id.", id))
+        })
+        .collect::<Result<_, _>>()
+        .map_err(e400)?;
+    if ids.is_empty() {
+        return Err(e400("No issue ids were provided.".to_string()));
+    }
+    if ids.len() > MAX_COMPARED_ISSUES {
+        return Err(e400(format!(
+            "At most {} issues can be compared at once.",
+            MAX_COMPARED_ISSUES
+        )));
+    }
+
+    let mut rows_html = String::new();
+    for id in &ids {
+        let metrics = match get_issue_metrics(&pool, *id).await.map_err(e500)? {
+            Some(metrics) => metrics,
+            None => return Err(e400(format!("There is no issue with id {}.", id))),
+        };
+        writeln!(
+            rows_html,
+            "<tr>\
+             <td>{title}</td>\
+             <td>{published}</td>\
+             <td>{delivered}</td>\
+             <td>{opens} ({open_rate})</td>\
+             <td>{clicks} ({click_rate})</td>\
+             <td>{unsubscribed} ({unsubscribe_rate})</td>\
+             <td>{failed}</td>\
+             </tr>",
+            title = htmlescape::encode_minimal(&metrics.title),
+            published = metrics.published_at.format("%Y-%m-%d"),
+            delivered = metrics.delivered,
+            opens = metrics.unique_opens,
+            open_rate = metrics.rate(metrics.unique_opens),
+            clicks = metrics.unique_clicks,
+            click_rate = metrics.rate(metrics.unique_clicks),
+            unsubscribed = metrics.unsubscribed,
+            unsubscribe_rate = metrics.rate(metrics.unsubscribed),
+            failed = metrics.failed,
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Issue comparison</title>
+</head>
+<body>
+    <h1>Issue comparison</h1>
+    <table border="1" cellpadding="5">
+        <tr>
+            <th>Issue</th>
+            <th>Published</th>
+            <th>Delivered</th>
+            <th>Opens</th>
+            <th>Clicks</th>
+            <th>Since unsubscribed</th>
+            <th>Failures</th>
+        </tr>
+        {rows_html}
+    </table>
+    <p>Rates are against delivered emails. Opens and clicks count unique
+    readers. "Since unsubscribed" counts recipients who have unsubscribed
+    at any point after receiving the issue.</p>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
+
+// every metric for one issue - `None` if the id doesn't name an issue
+#[tracing::instrument(name = "Get issue metrics", skip(pool))]
+async fn get_issue_metrics(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Option<IssueMetrics>, anyhow::Error> {
+    let issue = sqlx::query!(
+        r#"
+        SELECT title, published_at::timestamptz as "published_at!"
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the issue.")?;
+    let Some(issue) = issue else {
+        return Ok(None);
+    };
+
+    let metrics = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM email_delivery_log
+                WHERE newsletter_issue_id = $1 AND NOT failed) as "delivered!",
+            (SELECT COUNT(*) FROM email_delivery_log
+                WHERE newsletter_issue_id = $1 AND failed) as "failed!",
+            (SELECT COUNT(DISTINCT subscriber_id) FROM email_tracking_events
+                WHERE newsletter_issue_id = $1 AND event_type = 'open') as "unique_opens!",
+            (SELECT COUNT(DISTINCT subscriber_id) FROM email_tracking_events
+                WHERE newsletter_issue_id = $1 AND event_type = 'click') as "unique_clicks!",
+            (SELECT COUNT(*) FROM email_delivery_log l
+                JOIN subscriptions s ON s.email = l.recipient_email
+                WHERE l.newsletter_issue_id = $1
+                AND NOT l.failed
+                AND s.status = 'unsubscribed') as "unsubscribed!"
+        "#,
+        issue_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to compute the issue's metrics.")?;
+
+    Ok(Some(IssueMetrics {
+        title: issue.title,
+        published_at: issue.published_at,
+        delivered: metrics.delivered,
+        failed: metrics.failed,
+        unique_opens: metrics.unique_opens,
+        unique_clicks: metrics.unique_clicks,
+        unsubscribed: metrics.unsubscribed,
+    }))
+}
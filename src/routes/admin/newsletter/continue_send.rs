@@ -0,0 +1,61 @@
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    newsletter_issue_id: Uuid,
+}
+
+/// POST /admin/newsletter/continue - release the held remainder of a soft
+/// launch without waiting for the delay to lapse.
+#[tracing::instrument(name = "Continue a soft-launched send", skip(form, pool))]
+pub async fn continue_send(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let released = release_held_tasks(&pool, form.0.newsletter_issue_id)
+        .await
+        .context("Failed to release held delivery tasks")
+        .map_err(e500)?;
+
+    FlashMessage::info(format!(
+        "{} held deliveries have been released.",
+        released
+    ))
+    .send();
+    Ok(see_other("/admin/newsletter"))
+}
+
+#[tracing::instrument(skip_all)]
+async fn release_held_tasks(pool: &PgPool, newsletter_issue_id: Uuid) -> Result<u64, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+
+    let released = sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET available_at = now()
+        WHERE
+            newsletter_issue_id = $1 AND
+            available_at > now()
+        "#,
+        newsletter_issue_id,
+    )
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected();
+
+    // nudge the worker so the released tasks go out straight away
+    sqlx::query!(
+        "SELECT pg_notify($1, '')",
+        crate::issue_delivery_worker::DELIVERY_NOTIFICATION_CHANNEL
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+    Ok(released)
+}
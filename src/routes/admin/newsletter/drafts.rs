@@ -0,0 +1,518 @@
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+// Drafts live in their own table so half-written issues never leak into
+// the archive or the delivery queue. Saving is optimistically locked: the
+// editor carries the version it loaded, and a save against a stale
+// version gets a conflict page with a diff instead of clobbering the
+// other editor's work.
+
+struct Draft {
+    title: String,
+    text_content: String,
+    html_content: String,
+    version: i32,
+    // set while unsaved autosaved work is waiting - cleared by an
+    // explicit save, a restore or a discard
+    autosaved_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// GET /admin/newsletter/drafts - every draft, newest first, plus a
+/// button to start a fresh one.
+pub async fn list_drafts(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let msg_html = crate::utils::flash_messages_html(&flash_messages);
+
+    let drafts = sqlx::query!(
+        r#"
+        SELECT newsletter_draft_id, title, updated_at
+        FROM newsletter_drafts
+        WHERE deleted_at IS NULL
+        ORDER BY updated_at DESC
+        "#,
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .map_err(e500)?;
+
+    let mut drafts_html = String::new();
+    for draft in &drafts {
+        writeln!(
+            drafts_html,
+            r#"<li><a href="/admin/newsletter/drafts/{0}">{1}</a> - last saved {2}
+    <form action="/admin/newsletter/drafts/{0}/delete" method="post" style="display:inline">
+        <button type="submit">Delete</button>
+    </form></li>"#,
+            draft.newsletter_draft_id,
+            htmlescape::encode_minimal(if draft.title.is_empty() {
+                "(untitled)"
+            } else {
+                &draft.title
+            }),
+            draft.updated_at.format("%Y-%m-%d %H:%M UTC"),
+        )
+        .unwrap();
+    }
+    if drafts.is_empty() {
+        drafts_html.push_str("<li>No drafts yet</li>");
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Newsletter drafts</title>
+</head>
+<body>
+    {msg_html}
+    <h1>Drafts</h1>
+    <ul>
+        {drafts_html}
+    </ul>
+    <form action="/admin/newsletter/drafts" method="post">
+        <button type="submit">New draft</button>
+    </form>
+    <p><a href="/admin/newsletter">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+/// POST /admin/newsletter/drafts - create an empty draft and jump
+/// straight into its editor.
+pub async fn create_draft(pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    let draft_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_drafts (
+            newsletter_draft_id, title, text_content, html_content, updated_at
+        )
+        VALUES ($1, '', '', '', now())
+        "#,
+        draft_id,
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    Ok(see_other(&format!("/admin/newsletter/drafts/{}", draft_id)))
+}
+
+/// GET /admin/newsletter/drafts/{id} - the editor, pre-filled with the
+/// stored content. The version travels in a hidden field.
+pub async fn edit_draft_form(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let draft_id = path.into_inner();
+    let draft = match get_draft(&pool, draft_id).await.map_err(e500)? {
+        Some(draft) => draft,
+        None => {
+            FlashMessage::error("That draft no longer exists.").send();
+            return Ok(see_other("/admin/newsletter/drafts"));
+        }
+    };
+
+    let msg_html = crate::utils::flash_messages_html(&flash_messages);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(editor_html(draft_id, &draft, &msg_html)))
+}
+
+#[derive(serde::Deserialize)]
+pub struct DraftForm {
+    title: String,
+    text_content: String,
+    html_content: String,
+    // the version this editor loaded - the save only lands if the row
+    // still carries it
+    version: i32,
+}
+
+/// POST /admin/newsletter/drafts/{id} - save, or surface a conflict.
+#[tracing::instrument(
+    name = "Save a newsletter draft",
+    skip(form, pool),
+    fields(draft_id=%path)
+)]
+pub async fn save_draft(
+    path: web::Path<Uuid>,
+    form: web::Form<DraftForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let draft_id = path.into_inner();
+
+    // the optimistic lock - the WHERE clause only matches if nobody else
+    // saved since this editor loaded the draft
+    let outcome = sqlx::query!(
+        r#"
+        UPDATE newsletter_drafts
+        SET title = $2,
+            text_content = $3,
+            html_content = $4,
+            version = version + 1,
+            updated_at = now(),
+            autosave_title = NULL,
+            autosave_text_content = NULL,
+            autosave_html_content = NULL,
+            autosaved_at = NULL
+        WHERE newsletter_draft_id = $1 AND version = $5 AND deleted_at IS NULL
+        "#,
+        draft_id,
+        form.title,
+        form.text_content,
+        form.html_content,
+        form.version,
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+
+    if outcome.rows_affected() == 1 {
+        FlashMessage::info("Draft saved.").send();
+        return Ok(see_other(&format!("/admin/newsletter/drafts/{}", draft_id)));
+    }
+
+    // stale version (or a deleted draft) - show the other editor's copy
+    // next to a diff of what this save would have changed
+    match get_draft(&pool, draft_id).await.map_err(e500)? {
+        Some(current) => Ok(HttpResponse::Conflict()
+            .content_type(ContentType::html())
+            .body(conflict_html(draft_id, &form, &current))),
+        None => {
+            FlashMessage::error("That draft was deleted while you were editing it.").send();
+            Ok(see_other("/admin/newsletter/drafts"))
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct AutosaveBody {
+    // all partial - the editor sends whatever changed
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    text_content: Option<String>,
+    #[serde(default)]
+    html_content: Option<String>,
+}
+
+/// PUT /admin/newsletter/drafts/{id}/autosave - the editor pushes its
+/// fields here on an interval. Deliberately version-free: autosaves only
+/// touch the shadow columns, so they can't clobber an explicit save.
+#[tracing::instrument(name = "Autosave a newsletter draft", skip(body, pool), fields(draft_id=%path))]
+pub async fn autosave_draft(
+    path: web::Path<Uuid>,
+    body: web::Json<AutosaveBody>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let draft_id = path.into_inner();
+    // absent fields keep whatever the previous autosave carried
+    let outcome = sqlx::query!(
+        r#"
+        UPDATE newsletter_drafts
+        SET autosave_title = COALESCE($2, autosave_title),
+            autosave_text_content = COALESCE($3, autosave_text_content),
+            autosave_html_content = COALESCE($4, autosave_html_content),
+            autosaved_at = now()
+        WHERE newsletter_draft_id = $1 AND deleted_at IS NULL
+        "#,
+        draft_id,
+        body.title.as_deref(),
+        body.text_content.as_deref(),
+        body.html_content.as_deref(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    if outcome.rows_affected() == 0 {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// POST /admin/newsletter/drafts/{id}/autosave/restore - promote the
+/// autosaved content to the draft proper, e.g. after a browser crash.
+#[tracing::instrument(name = "Restore an autosaved draft", skip(pool), fields(draft_id=%path))]
+pub async fn restore_autosave(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let draft_id = path.into_inner();
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_drafts
+        SET title = COALESCE(autosave_title, title),
+            text_content = COALESCE(autosave_text_content, text_content),
+            html_content = COALESCE(autosave_html_content, html_content),
+            version = version + 1,
+            updated_at = now(),
+            autosave_title = NULL,
+            autosave_text_content = NULL,
+            autosave_html_content = NULL,
+            autosaved_at = NULL
+        WHERE newsletter_draft_id = $1 AND autosaved_at IS NOT NULL
+        AND deleted_at IS NULL
+        "#,
+        draft_id,
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    FlashMessage::info("The autosaved content has been restored.").send();
+    Ok(see_other(&format!("/admin/newsletter/drafts/{}", draft_id)))
+}
+
+/// POST /admin/newsletter/drafts/{id}/autosave/discard - the autosave
+/// was noise (or the saved copy is the one wanted) - throw it away.
+#[tracing::instrument(name = "Discard an autosaved draft", skip(pool), fields(draft_id=%path))]
+pub async fn discard_autosave(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let draft_id = path.into_inner();
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_drafts
+        SET autosave_title = NULL,
+            autosave_text_content = NULL,
+            autosave_html_content = NULL,
+            autosaved_at = NULL
+        WHERE newsletter_draft_id = $1 AND deleted_at IS NULL
+        "#,
+        draft_id,
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    FlashMessage::info("The autosaved content has been discarded.").send();
+    Ok(see_other(&format!("/admin/newsletter/drafts/{}", draft_id)))
+}
+
+/// POST /admin/newsletter/drafts/{id}/delete - move the draft to the
+/// trash. Not a real DELETE: the row is stamped and sits on /admin/trash
+/// until it is restored or the retention period runs out.
+#[tracing::instrument(name = "Delete a newsletter draft", skip(pool), fields(draft_id=%path))]
+pub async fn delete_draft(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let draft_id = path.into_inner();
+    let outcome = sqlx::query!(
+        r#"
+        UPDATE newsletter_drafts
+        SET deleted_at = now()
+        WHERE newsletter_draft_id = $1 AND deleted_at IS NULL
+        "#,
+        draft_id,
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    if outcome.rows_affected() == 1 {
+        FlashMessage::info(
+            "The draft has been moved to the trash - it can be restored from /admin/trash.",
+        )
+        .send();
+    } else {
+        FlashMessage::error("That draft no longer exists.").send();
+    }
+    Ok(see_other("/admin/newsletter/drafts"))
+}
+
+async fn get_draft(pool: &PgPool, draft_id: Uuid) -> Result<Option<Draft>, sqlx::Error> {
+    sqlx::query_as!(
+        Draft,
+        r#"
+        SELECT title, text_content, html_content, version, autosaved_at
+        FROM newsletter_drafts
+        WHERE newsletter_draft_id = $1 AND deleted_at IS NULL
+        "#,
+        draft_id,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+fn editor_html(draft_id: Uuid, draft: &Draft, msg_html: &str) -> String {
+    // unsaved autosaved work waiting? offer to restore it before the
+    // editor types over it
+    let restore_banner = match draft.autosaved_at {
+        Some(at) => format!(
+            r#"<p style="background:#ffd54f;padding:0.5em">
+        There is autosaved content from {} that was never saved -
+        probably from a closed or crashed browser.
+        <form action="/admin/newsletter/drafts/{draft_id}/autosave/restore" method="post" style="display:inline">
+            <button type="submit">Restore draft</button>
+        </form>
+        <form action="/admin/newsletter/drafts/{draft_id}/autosave/discard" method="post" style="display:inline">
+            <button type="submit">Discard</button>
+        </form></p>"#,
+            at.format("%Y-%m-%d %H:%M UTC"),
+        ),
+        None => String::new(),
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Edit draft</title>
+</head>
+<body>
+    {msg_html}
+    {restore_banner}
+    <h1>Edit draft</h1>
+    <form action="/admin/newsletter/drafts/{draft_id}" method="post">
+        <input type="hidden" name="version" value="{version}">
+        <h3>Title:</h3>
+        <input type="text" style="width:100%" name="title" value="{title}">
+        <h3>Plain text content:</h3>
+        <textarea style="width:100%;height:300px" name="text_content">{text_content}</textarea>
+        <h3>HTML content:</h3>
+        <textarea style="width:100%;height:300px" name="html_content">{html_content}</textarea>
+        <br><br>
+        <button type="submit">Save draft</button>
+    </form>
+    <p><a href="/admin/newsletter/drafts">&lt;- Back</a></p>
+    <script>
+        // push the fields every 30 seconds - a browser crash then loses
+        // half a minute of typing, not an afternoon
+        setInterval(() => {{
+            const form = document.forms[0];
+            fetch("/admin/newsletter/drafts/{draft_id}/autosave", {{
+                method: "PUT",
+                headers: {{ "Content-Type": "application/json" }},
+                body: JSON.stringify({{
+                    title: form.title.value,
+                    text_content: form.text_content.value,
+                    html_content: form.html_content.value,
+                }}),
+            }}).catch(() => {{}});
+        }}, 30000);
+    </script>
+</body>
+</html>"#,
+        version = draft.version,
+        title = htmlescape::encode_minimal(&draft.title),
+        text_content = htmlescape::encode_minimal(&draft.text_content),
+        html_content = htmlescape::encode_minimal(&draft.html_content),
+    )
+}
+
+fn conflict_html(draft_id: Uuid, yours: &DraftForm, theirs: &Draft) -> String {
+    let mut diff_html = String::new();
+    for (field, yours, theirs) in [
+        ("Title", yours.title.as_str(), theirs.title.as_str()),
+        (
+            "Plain text content",
+            yours.text_content.as_str(),
+            theirs.text_content.as_str(),
+        ),
+        (
+            "HTML content",
+            yours.html_content.as_str(),
+            theirs.html_content.as_str(),
+        ),
+    ] {
+        if yours == theirs {
+            continue;
+        }
+        writeln!(
+            diff_html,
+            "<h3>{}</h3>\n<pre>{}</pre>",
+            field,
+            line_diff(yours, theirs)
+        )
+        .unwrap();
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Save conflict</title>
+</head>
+<body>
+    <h1>Somebody else saved this draft first</h1>
+    <p>
+        The draft changed while you were editing it (it is now at version
+        {version}), so your save was not applied. The differences between
+        your copy (<b>-</b>) and the saved copy (<b>+</b>) are shown below.
+        <a href="/admin/newsletter/drafts/{draft_id}">Reload the draft</a>
+        to pick up the saved copy and re-apply your changes.
+    </p>
+    {diff_html}
+</body>
+</html>"#,
+        version = theirs.version,
+    )
+}
+
+// a plain longest-common-subsequence line diff - draft bodies are small
+// enough that the quadratic table doesn't matter, and it saves a dependency
+fn line_diff(yours: &str, theirs: &str) -> String {
+    let yours: Vec<&str> = yours.lines().collect();
+    let theirs: Vec<&str> = theirs.lines().collect();
+
+    // lcs[i][j] = length of the longest common subsequence of
+    // yours[i..] and theirs[j..]
+    let mut lcs = vec![vec![0usize; theirs.len() + 1]; yours.len() + 1];
+    for i in (0..yours.len()).rev() {
+        for j in (0..theirs.len()).rev() {
+            lcs[i][j] = if yours[i] == theirs[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    // walk the table emitting removals (your lines) before additions
+    let mut output = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < yours.len() || j < theirs.len() {
+        if i < yours.len() && j < theirs.len() && yours[i] == theirs[j] {
+            writeln!(output, "  {}", htmlescape::encode_minimal(yours[i])).unwrap();
+            i += 1;
+            j += 1;
+        } else if j == theirs.len() || (i < yours.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            writeln!(output, "- {}", htmlescape::encode_minimal(yours[i])).unwrap();
+            i += 1;
+        } else {
+            writeln!(output, "+ {}", htmlescape::encode_minimal(theirs[j])).unwrap();
+            j += 1;
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::line_diff;
+
+    #[test]
+    fn unchanged_lines_are_kept_in_context() {
+        let diff = line_diff("a\nb\nc", "a\nx\nc");
+        assert_eq!(diff, "  a\n- b\n+ x\n  c\n");
+    }
+
+    #[test]
+    fn pure_additions_and_removals_diff_cleanly() {
+        assert_eq!(line_diff("a", "a\nb"), "  a\n+ b\n");
+        assert_eq!(line_diff("a\nb", "a"), "  a\n- b\n");
+    }
+}
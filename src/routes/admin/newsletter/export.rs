@@ -0,0 +1,128 @@
+use crate::utils::e500;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::io::Write;
+
+// GET /admin/newsletter/export - every published issue, zipped up as a
+// pair of files per issue (Markdown built from the plain-text content,
+// plus the rendered HTML), each with YAML front-matter so the bundle
+// drops straight into a static site generator.
+
+#[tracing::instrument(name = "Export all issues", skip_all)]
+pub async fn export_issues(pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    let issues = get_all_issues(&pool).await.map_err(e500)?;
+    let archive = build_archive(&issues).map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(
+                "newsletter-export.zip".to_string(),
+            )],
+        })
+        .body(archive))
+}
+
+struct ExportedIssue {
+    newsletter_issue_id: uuid::Uuid,
+    title: String,
+    text_content: String,
+    html_content: String,
+    published_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Fetch all issues for export", skip_all)]
+async fn get_all_issues(pool: &PgPool) -> Result<Vec<ExportedIssue>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        ExportedIssue,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            published_at::timestamptz as "published_at!"
+        FROM newsletter_issues
+        ORDER BY published_at::timestamptz
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the issues to export.")?;
+    Ok(issues)
+}
+
+fn build_archive(issues: &[ExportedIssue]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options: zip::write::SimpleFileOptions = Default::default();
+
+    for issue in issues {
+        let stem = format!(
+            "{}-{}",
+            issue.published_at.format("%Y-%m-%d"),
+            slugify(&issue.title)
+        );
+        let front_matter = front_matter(issue);
+
+        // the plain-text version doubles as the Markdown source - it is
+        // what the author actually wrote, minus our HTML rendering
+        zip.start_file(format!("{stem}.md"), options)?;
+        zip.write_all(front_matter.as_bytes())?;
+        zip.write_all(issue.text_content.as_bytes())?;
+
+        // front-matter on the .html too - most generators accept it there
+        zip.start_file(format!("{stem}.html"), options)?;
+        zip.write_all(front_matter.as_bytes())?;
+        zip.write_all(issue.html_content.as_bytes())?;
+    }
+
+    let cursor = zip.finish().context("Failed to finalise the zip archive.")?;
+    Ok(cursor.into_inner())
+}
+
+fn front_matter(issue: &ExportedIssue) -> String {
+    format!(
+        "---\ntitle: \"{}\"\ndate: {}\nid: {}\n---\n\n",
+        // keep the YAML well-formed whatever the title contains
+        issue.title.replace('\\', "\\\\").replace('"', "\\\""),
+        issue.published_at.to_rfc3339(),
+        issue.newsletter_issue_id,
+    )
+}
+
+// a filesystem-safe name: lowercase alphanumerics, everything else
+// collapsed to single hyphens
+fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    let mut last_was_hyphen = true;
+    for c in title.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+            last_was_hyphen = false;
+        } else if !last_was_hyphen {
+            slug.push('-');
+            last_was_hyphen = true;
+        }
+    }
+    let slug = slug.trim_end_matches('-').to_string();
+    if slug.is_empty() {
+        "untitled".to_string()
+    } else {
+        slug
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::slugify;
+
+    #[test]
+    fn titles_become_safe_file_names() {
+        assert_eq!(slugify("Issue #1: Hello, World!"), "issue-1-hello-world");
+        assert_eq!(slugify("---"), "untitled");
+    }
+}
@@ -1,34 +1,77 @@
 use crate::session_state::TypedSession;
 use crate::utils::{e500, populate_dynamic_html_fields, see_other};
 use actix_web::http::header::ContentType;
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
 use std::collections::HashMap;
 use std::fmt::Write;
 
 pub async fn send_newsletter_form(
     session: TypedSession,                 // defined in SessionState.rs
     flash_messages: IncomingFlashMessages, // attached if returning from failed POST req.
+    pool: web::Data<PgPool>,               // for the saved-segment dropdown
 ) -> Result<HttpResponse, actix_web::Error> {
-    // check for flash message
-    let mut msg_html = String::new();
-
     // check session is valid - if not, go back to login page
     // e500 is defined in utils - just an error wrapper that preserves context
     if session.get_user_id().map_err(e500)?.is_none() {
         return Ok(see_other("/login"));
     }
 
-    for m in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
+    let msg_html = crate::utils::flash_messages_html(&flash_messages);
 
     // Read the HTML file into a string
     let html_page = include_str!("newsletter.html");
 
+    // a failed POST stashes everything that was typed - put it back into
+    // the fields (absent stash means blank fields: the unmatched
+    // placeholders are emptied by populate_dynamic_html_fields)
+    let stashed: Option<super::post::FormData> = session.take_form_stash().map_err(e500)?;
+    let (title, text_content, html_content, tags, poll_question, poll_options) = match &stashed {
+        Some(form) => (
+            htmlescape::encode_attribute(&form.title),
+            htmlescape::encode_minimal(&form.text_content),
+            htmlescape::encode_minimal(&form.html_content),
+            htmlescape::encode_attribute(&form.tags),
+            htmlescape::encode_attribute(form.poll_question.as_deref().unwrap_or("")),
+            htmlescape::encode_minimal(form.poll_options.as_deref().unwrap_or("")),
+        ),
+        None => Default::default(),
+    };
+
+    // the saved-segment dropdown - "everyone" first, then every saved
+    // segment by name; a stashed selection survives a failed POST
+    let stashed_segment = stashed.as_ref().map(|form| form.segment.as_str());
+    let mut segment_options =
+        r#"<option value="">Everyone (no segment)</option>"#.to_string();
+    for (id, segment) in super::super::segments::get_all_segments(&pool)
+        .await
+        .map_err(e500)?
+    {
+        writeln!(
+            segment_options,
+            r#"<option value="{}"{}>{}</option>"#,
+            id,
+            if stashed_segment == Some(id.to_string().as_str()) {
+                " selected"
+            } else {
+                ""
+            },
+            htmlescape::encode_minimal(&segment.name),
+        )
+        .unwrap();
+    }
+
     // make a dict of the dynamic content
     let mut dynamic_fields = HashMap::<&str, &str>::new();
+    dynamic_fields.insert("segment_options", &segment_options);
     dynamic_fields.insert("msg_html", &msg_html);
+    dynamic_fields.insert("title", &title);
+    dynamic_fields.insert("text_content", &text_content);
+    dynamic_fields.insert("html_content", &html_content);
+    dynamic_fields.insert("tags", &tags);
+    dynamic_fields.insert("poll_question", &poll_question);
+    dynamic_fields.insert("poll_options", &poll_options);
     // make a random idempotency key - added as a hidden element to the page
     let key_string = String::from(uuid::Uuid::new_v4());
     dynamic_fields.insert("idempotency_key", &key_string);
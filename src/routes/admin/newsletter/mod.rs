@@ -1,4 +1,19 @@
+mod compare;
+pub use compare::compare_issues;
+mod continue_send;
+pub use continue_send::continue_send;
+mod drafts;
+pub use drafts::{
+    autosave_draft, create_draft, delete_draft, discard_autosave, edit_draft_form, list_drafts,
+    restore_autosave, save_draft,
+};
+mod export;
+pub use export::export_issues;
 mod get;
 pub use get::send_newsletter_form;
 mod post;
-pub use post::send_newsletter;
+pub use post::{advance_enqueue_job, enqueue_delivery_tasks, send_newsletter};
+mod recipient_count;
+pub use recipient_count::recipient_count;
+mod render;
+pub use render::render_preview;
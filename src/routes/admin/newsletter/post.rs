@@ -1,4 +1,6 @@
+use crate::clock::Clock;
 use crate::idempotency;
+use crate::session_state::TypedSession;
 use crate::{
     authentication::UserId,
     idempotency::IdempotencyKey,
@@ -11,12 +13,118 @@ use anyhow::Context;
 use sqlx::{Executor, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-#[derive(serde::Deserialize)]
+// Serialize too, so a failed validation can stash the whole form in the
+// session and the GET can put everything back (see the session's form
+// stash); the fields are pub(super) for the same reason
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 pub struct FormData {
-    title: String,
-    text_content: String,
-    html_content: String,
-    idempotency_key: String,
+    pub(super) title: String,
+    pub(super) text_content: String,
+    pub(super) html_content: String,
+    pub(super) idempotency_key: String,
+    // soft-launch controls - blank means "send to everyone immediately".
+    // kept as strings because html number inputs submit "" when untouched
+    #[serde(default)]
+    pub(super) canary_percent: Option<String>,
+    #[serde(default)]
+    pub(super) canary_delay_minutes: Option<String>,
+    // comma-separated categories ("product, essay") - blank means the
+    // issue is uncategorised and goes to everyone
+    #[serde(default)]
+    pub(super) tags: String,
+    // the paid-tier checkbox - present means "paying readers only"
+    #[serde(default)]
+    pub(super) premium_only: Option<String>,
+    // an optional one-question poll - a blank question means no poll,
+    // options are written one per line
+    #[serde(default)]
+    pub(super) poll_question: Option<String>,
+    #[serde(default)]
+    pub(super) poll_options: Option<String>,
+    // opt out of css inlining + minification for this issue - present
+    // means "send my HTML byte-for-byte"
+    #[serde(default)]
+    pub(super) skip_postprocessing: Option<String>,
+    // a saved segment's id - blank means "everyone" (see routes::admin::segments)
+    #[serde(default)]
+    pub(super) segment: String,
+    // "send anyway" past the size guard - present means the author has
+    // seen the clipping warning and wants the issue out regardless
+    #[serde(default)]
+    pub(super) size_guard_override: Option<String>,
+}
+
+// a validated soft-launch request: send to `percent`% of confirmed
+// subscribers now, hold the rest back for `delay_minutes`
+// (pub because it appears in `enqueue_delivery_tasks`'s signature - only
+// this module can construct one)
+pub struct CanarySettings {
+    percent: u8,
+    delay_minutes: u32,
+}
+
+// how long the remainder is held if the form doesn't say otherwise
+const DEFAULT_CANARY_DELAY_MINUTES: u32 = 60;
+
+// writing this anywhere in the content gets it replaced, at publish time,
+// with the results of the previous issue's poll
+const POLL_RESULTS_TAG: &str = "{{poll_results}}";
+
+// audiences at least this big skip the inline INSERT...SELECT - the publish
+// transaction only records a fill job and the delivery worker builds the
+// queue in chunks (see `advance_enqueue_job`)
+const DEFERRED_ENQUEUE_THRESHOLD: i64 = 100_000;
+
+// how many queue rows one chunk of a deferred fill inserts - big enough to
+// finish a six-figure list in dozens of transactions, small enough that no
+// single one holds locks for long
+const ENQUEUE_CHUNK_SIZE: i64 = 10_000;
+
+fn parse_poll(
+    poll_question: Option<String>,
+    poll_options: Option<String>,
+) -> Result<Option<(String, Vec<String>)>, String> {
+    let question = match poll_question.as_deref().map(str::trim) {
+        None | Some("") => return Ok(None),
+        Some(question) => question.to_string(),
+    };
+    let options: Vec<String> = poll_options
+        .as_deref()
+        .unwrap_or("")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+    if options.len() < 2 {
+        return Err("A poll needs at least two options (one per line).".to_string());
+    }
+    Ok(Some((question, options)))
+}
+
+fn parse_canary_settings(
+    canary_percent: Option<String>,
+    canary_delay_minutes: Option<String>,
+) -> Result<Option<CanarySettings>, String> {
+    let percent = match canary_percent.as_deref().map(str::trim) {
+        None | Some("") => return Ok(None),
+        Some(raw) => raw
+            .parse::<u8>()
+            .map_err(|_| format!("'{}' is not a valid canary percentage.", raw))?,
+    };
+    if !(1..=99).contains(&percent) {
+        return Err("The canary percentage must be between 1 and 99.".to_string());
+    }
+    let delay_minutes = match canary_delay_minutes.as_deref().map(str::trim) {
+        None | Some("") => DEFAULT_CANARY_DELAY_MINUTES,
+        Some(raw) => raw
+            .parse::<u32>()
+            .map_err(|_| format!("'{}' is not a valid canary delay.", raw))?,
+    };
+    Ok(Some(CanarySettings {
+        percent,
+        delay_minutes,
+    }))
 }
 
 #[tracing::instrument(
@@ -28,8 +136,66 @@ pub async fn send_newsletter(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,  // we need the postgres db and the session
     user_id: ReqData<UserId>, // extracted from the user session
+    session: TypedSession,    // carries the form stash on a failed validation
+    clock: web::Data<dyn Clock>, // timestamps the issue_published event
+    bus: web::Data<crate::message_bus::MessageBus>,
+    size_guard: web::Data<crate::configuration::EmailSizeGuardSettings>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
+    let form = form.0;
+
+    // check the soft-launch fields before we touch the database - a
+    // failure sends the author back to the form with everything they
+    // typed still in it, not to a bare 400 that eats their draft
+    let canary = match parse_canary_settings(
+        form.canary_percent.clone(),
+        form.canary_delay_minutes.clone(),
+    ) {
+        Ok(canary) => canary,
+        Err(e) => return stash_and_redirect(&session, &form, e),
+    };
+
+    // ditto the poll fields
+    let poll = match parse_poll(form.poll_question.clone(), form.poll_options.clone()) {
+        Ok(poll) => poll,
+        Err(e) => return stash_and_redirect(&session, &form, e),
+    };
+
+    // the optional saved segment - resolved up front, so a segment
+    // deleted since the form was loaded bounces the author back to the
+    // form instead of failing half-way into a publish
+    let segment = match form.segment.trim() {
+        "" => None,
+        raw => {
+            let segment_id = match raw.parse::<Uuid>() {
+                Ok(id) => id,
+                Err(_) => {
+                    return stash_and_redirect(
+                        &session,
+                        &form,
+                        "That is not a valid segment.".to_string(),
+                    );
+                }
+            };
+            match super::super::segments::get_segment(&pool, segment_id)
+                .await
+                .map_err(e500)?
+            {
+                Some(segment) => Some(segment),
+                None => {
+                    return stash_and_redirect(
+                        &session,
+                        &form,
+                        "That segment no longer exists - it may have been deleted.".to_string(),
+                    );
+                }
+            }
+        }
+    };
+
+    // kept whole for the size guard below - a rejection there sends the
+    // author back to the form with everything they typed still in it
+    let stashable = form.clone();
 
     // We must destructure the form to avoid upsetting the borrow-checker
     let FormData {
@@ -37,11 +203,73 @@ pub async fn send_newsletter(
         text_content,
         html_content,
         idempotency_key,
-    } = form.0;
+        canary_percent: _,
+        canary_delay_minutes: _,
+        tags,
+        premium_only,
+        poll_question: _,
+        poll_options: _,
+        skip_postprocessing,
+        segment: _,
+        size_guard_override,
+    } = form;
+    let premium_only = premium_only.is_some();
+    let skip_postprocessing = skip_postprocessing.is_some();
 
     // get the key & convert to our strongly typed version
     let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
 
+    // expand the poll-results merge tag before anything is stored, so the
+    // archive shows the same numbers the emails carried
+    let (text_content, html_content) = expand_poll_results(&pool, text_content, html_content)
+        .await
+        .context("Failed to expand the poll-results merge tag")
+        .map_err(e500)?;
+
+    // Gmail and Outlook ignore most of <head>, so css is inlined (and the
+    // result minified) before anything goes out - the authored original is
+    // stored alongside, and the whole step can be waived per issue
+    let authored_html_content = html_content.clone();
+    let html_content = if skip_postprocessing {
+        html_content
+    } else {
+        let processed = crate::premailer::minify_html(&crate::premailer::inline_css(&html_content));
+        // the optional dark-mode variant - a per-list toggle on
+        // /admin/settings; appended after inlining because media queries
+        // have to stay in a <style> block
+        let site = crate::site_settings::get(&pool)
+            .await
+            .context("Failed to read the site settings")
+            .map_err(e500)?;
+        if site.dark_mode_emails {
+            crate::premailer::add_dark_mode_styles(&processed)
+        } else {
+            processed
+        }
+    };
+
+    // the Gmail-clipping guard, on the bytes that will actually be sent -
+    // measured after post-processing, which is what decides the size
+    let html_kilobytes = html_content.len() as u64 / 1024;
+    if html_kilobytes >= size_guard.warn_kilobytes {
+        if size_guard.block_oversized && size_guard_override.is_none() {
+            return stash_and_redirect(
+                &session,
+                &stashable,
+                format!(
+                    "The rendered HTML is {}KB - Gmail clips messages over about {}KB. \
+                    Trim the issue, or tick \"Send anyway\" to override.",
+                    html_kilobytes, size_guard.warn_kilobytes
+                ),
+            );
+        }
+        FlashMessage::warning(format!(
+            "The rendered HTML is {}KB - Gmail may clip messages over about {}KB.",
+            html_kilobytes, size_guard.warn_kilobytes
+        ))
+        .send();
+    }
+
     // see if we already have a corresponding entry in the idempotency db
     let mut transaction = match idempotency::try_processing(&pool, &idempotency_key, *user_id)
         .await
@@ -58,24 +286,100 @@ pub async fn send_newsletter(
         }
     };
 
+    // the issue belongs to whichever newsletter its publisher administers
+    let tenant_id = get_user_tenant(&pool, *user_id)
+        .await
+        .context("Failed to look up the publisher's tenant")
+        .map_err(e500)?;
+
     // insert the newsletter into our 'newsletter issue status' table,
     // getting a unique ID for it. This table records pending tasks
     // and whether they have been completed or not
-    let newsletter_issue_id =
-        insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
+    let newsletter_issue_id = insert_newsletter_issue(
+        &mut transaction,
+        &title,
+        &text_content,
+        &html_content,
+        &authored_html_content,
+        tenant_id,
+        premium_only,
+    )
+    .await
+    .context("Failed to store newsletter issue details")
+    .map_err(e500)?;
+
+    // the issue's categories - same transaction, so the enqueue query
+    // below already sees them when it matches subscriber preferences
+    store_issue_tags(&mut transaction, newsletter_issue_id, &tags)
+        .await
+        .context("Failed to store the issue's tags")
+        .map_err(e500)?;
+
+    // the issue's poll, if any - the delivery worker renders the voting
+    // links per recipient
+    if let Some((question, options)) = &poll {
+        insert_poll(&mut transaction, newsletter_issue_id, question, options)
             .await
-            .context("Failed to store newsletter issue details")
+            .context("Failed to store the issue's poll")
             .map_err(e500)?;
+    }
 
-    // make the list of email addresses to send the nesletter to
-    // in another table
-    // adding everything to the same sqlx transaction
-    // so it can be executed in one go, and rolled back if required
-    enqueue_delivery_tasks(&mut transaction, newsletter_issue_id)
+    // a rough audience size - the tenant's confirmed subscribers, ignoring
+    // category preferences. Over-counting only means a large list takes
+    // the (safe) chunked path, so the cheap upper bound is good enough
+    let audience = count_confirmed_subscribers(&mut transaction, tenant_id)
+        .await
+        .context("Failed to size the issue's audience")
+        .map_err(e500)?;
+
+    if audience >= DEFERRED_ENQUEUE_THRESHOLD {
+        // too big to scan inside this transaction - record a fill job and
+        // let the delivery worker build the queue in resumable chunks
+        insert_enqueue_job(
+            &mut transaction,
+            newsletter_issue_id,
+            tenant_id,
+            premium_only,
+            canary.as_ref(),
+            segment.as_ref(),
+        )
+        .await
+        .context("Failed to record the enqueue job")
+        .map_err(e500)?;
+        tracing::info!(audience, "Deferred the enqueue to the delivery worker");
+    } else {
+        // make the list of email addresses to send the nesletter to
+        // in another table
+        // adding everything to the same sqlx transaction
+        // so it can be executed in one go, and rolled back if required
+        enqueue_delivery_tasks(
+            &mut transaction,
+            newsletter_issue_id,
+            tenant_id,
+            premium_only,
+            canary.as_ref(),
+        )
         .await
         .context("Failed to enqueue delivery tasks")
         .map_err(e500)?;
 
+        // a picked segment narrows the audience - applied by trimming the
+        // rows the enqueue just inserted, inside the same transaction, so the
+        // carefully-tuned static queries above stay compile-time checked
+        if let Some(segment) = &segment {
+            let trimmed =
+                apply_segment_filter(&mut transaction, newsletter_issue_id, &segment.definition)
+                    .await
+                    .context("Failed to apply the segment filter")
+                    .map_err(e500)?;
+            tracing::info!(
+                segment_name = %segment.name,
+                trimmed,
+                "Narrowed the issue's audience to a saved segment",
+            );
+        }
+    }
+
     let response = see_other("/admin/newsletter");
 
     // insert this request into the idempotency database
@@ -83,21 +387,288 @@ pub async fn send_newsletter(
         .await
         .map_err(e500)?;
 
+    // the analytics trail - after the commit, so a rolled-back publish
+    // never shows up in it
+    crate::domain_events::record(
+        &pool,
+        &bus,
+        crate::domain_events::ISSUE_PUBLISHED,
+        Some(newsletter_issue_id),
+        clock.now(),
+    )
+    .await;
+
     success_message().send();
     Ok(response)
 }
 
+// a failed validation goes back to the form: flash the reason, stash the
+// typed content so the GET can re-populate every field
+// drop every freshly-queued recipient the segment does not match. The
+// segment's conditions come from the domain-layer query-builder, so this
+// and the live count on /admin/segments can never disagree
+async fn apply_segment_filter(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    definition: &crate::domain::SegmentDefinition,
+) -> Result<u64, sqlx::Error> {
+    let mut builder: sqlx::QueryBuilder<'_, Postgres> =
+        sqlx::QueryBuilder::new("DELETE FROM issue_delivery_queue WHERE newsletter_issue_id = ");
+    builder.push_bind(newsletter_issue_id);
+    builder.push(
+        " AND NOT EXISTS (SELECT 1 FROM subscriptions s \
+         WHERE s.email = subscriber_email AND s.deleted_at IS NULL",
+    );
+    definition.push_sql(&mut builder);
+    builder.push(")");
+    let outcome = builder.build().execute(&mut **transaction).await?;
+    Ok(outcome.rows_affected())
+}
+
+fn stash_and_redirect(
+    session: &TypedSession,
+    form: &FormData,
+    error: String,
+) -> Result<HttpResponse, actix_web::Error> {
+    session.stash_form(form).map_err(e500)?;
+    FlashMessage::error(error).send();
+    Ok(see_other("/admin/newsletter"))
+}
+
 fn success_message() -> FlashMessage {
     FlashMessage::info("The newsletter issue has been queued for publishing!")
 }
 
+// the cheap audience upper bound that picks inline vs deferred enqueueing
+async fn count_confirmed_subscribers(
+    transaction: &mut Transaction<'_, Postgres>,
+    tenant_id: Uuid,
+) -> Result<i64, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM subscriptions
+        WHERE status = 'confirmed' AND deleted_at IS NULL AND tenant_id = $1
+        "#,
+        tenant_id
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(row.count)
+}
+
+// everything `advance_enqueue_job` needs to rebuild the audience later,
+// committed atomically with the issue itself. The segment is snapshotted
+// column by column rather than referenced by id - deleting the segment
+// mid-fill must not change the issue's audience
+async fn insert_enqueue_job(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    tenant_id: Uuid,
+    premium_only: bool,
+    canary: Option<&CanarySettings>,
+    segment: Option<&super::super::segments::Segment>,
+) -> Result<(), sqlx::Error> {
+    let definition = segment.map(|segment| &segment.definition);
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO issue_enqueue_jobs (
+            newsletter_issue_id,
+            tenant_id,
+            premium_only,
+            canary_percent,
+            canary_delay_minutes,
+            segment_status,
+            segment_tags,
+            segment_engaged_within_issues,
+            segment_signed_up_after,
+            segment_signed_up_before
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#,
+        newsletter_issue_id,
+        tenant_id,
+        premium_only,
+        canary.map(|canary| canary.percent as i16),
+        canary.map(|canary| canary.delay_minutes as i32),
+        definition.and_then(|d| d.status.clone()),
+        definition.map(|d| d.tags.clone()) as Option<Vec<String>>,
+        definition.and_then(|d| d.engaged_within_issues),
+        definition.and_then(|d| d.signed_up_after),
+        definition.and_then(|d| d.signed_up_before),
+    );
+    transaction.execute(query).await?;
+
+    // wake the worker once this commits - it does the actual filling
+    let notify = sqlx::query!(
+        "SELECT pg_notify($1, '')",
+        crate::issue_delivery_worker::DELIVERY_NOTIFICATION_CHANNEL
+    );
+    transaction.execute(notify).await?;
+    Ok(())
+}
+
+/// Advance the oldest pending fill job by one chunk. Called by the delivery
+/// worker between sends; returns whether there was a job to work on. Each
+/// chunk commits its queue rows together with the moved checkpoint, so a
+/// crash mid-list resumes at the checkpoint instead of rescanning - and
+/// because sends interleave with the chunks, delivery starts while the
+/// tail of the list is still being queued.
+#[tracing::instrument(skip_all)]
+pub async fn advance_enqueue_job(pool: &PgPool) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+
+    // one job at a time, oldest first - SKIP LOCKED lets parallel workers
+    // pick up different jobs instead of queueing on this row
+    let job = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, tenant_id, premium_only,
+            canary_percent, canary_delay_minutes,
+            segment_status, segment_tags, segment_engaged_within_issues,
+            segment_signed_up_after, segment_signed_up_before,
+            last_email
+        FROM issue_enqueue_jobs
+        ORDER BY created_at ASC
+        FOR UPDATE SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to look for a pending enqueue job")?;
+    let Some(job) = job else {
+        return Ok(false);
+    };
+
+    // the next slice of the audience, in email order so `last_email` can
+    // act as a keyset cursor. The predicate mirrors the one in
+    // `enqueue_delivery_tasks` - change them together
+    let definition = crate::domain::SegmentDefinition {
+        status: job.segment_status,
+        tags: job.segment_tags.unwrap_or_default(),
+        engaged_within_issues: job.segment_engaged_within_issues,
+        signed_up_after: job.segment_signed_up_after,
+        signed_up_before: job.segment_signed_up_before,
+    };
+    let mut builder: sqlx::QueryBuilder<'_, Postgres> = sqlx::QueryBuilder::new(
+        "SELECT s.email FROM subscriptions s \
+         WHERE s.status = 'confirmed' AND s.deleted_at IS NULL AND s.tenant_id = ",
+    );
+    builder.push_bind(job.tenant_id);
+    builder.push(" AND (");
+    builder.push_bind(job.premium_only);
+    builder.push(
+        " = false OR s.premium) AND ( \
+         NOT EXISTS (SELECT 1 FROM newsletter_issue_tags it \
+             WHERE it.newsletter_issue_id = ",
+    );
+    builder.push_bind(job.newsletter_issue_id);
+    builder.push(
+        ") OR NOT EXISTS (SELECT 1 FROM subscriber_category_preferences p \
+             WHERE p.subscriber_id = s.id) \
+         OR EXISTS (SELECT 1 FROM subscriber_category_preferences p \
+             JOIN newsletter_issue_tags it ON it.tag = p.category \
+             WHERE p.subscriber_id = s.id AND it.newsletter_issue_id = ",
+    );
+    builder.push_bind(job.newsletter_issue_id);
+    builder.push(")) AND s.email > ");
+    builder.push_bind(job.last_email);
+    definition.push_sql(&mut builder);
+    builder.push(" ORDER BY s.email LIMIT ");
+    builder.push_bind(ENQUEUE_CHUNK_SIZE);
+    let emails: Vec<String> = builder
+        .build_query_scalar()
+        .fetch_all(&mut *transaction)
+        .await
+        .context("Failed to scan the next audience chunk")?;
+
+    if emails.is_empty() {
+        // the scan is done - the job goes away and the queue is complete
+        sqlx::query!(
+            "DELETE FROM issue_enqueue_jobs WHERE newsletter_issue_id = $1",
+            job.newsletter_issue_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to retire a finished enqueue job")?;
+        transaction.commit().await?;
+        tracing::info!(
+            newsletter_issue_id = %job.newsletter_issue_id,
+            "Finished filling the delivery queue for a large issue",
+        );
+        return Ok(true);
+    }
+
+    // the chunk's rows, with the same canary split the inline path applies
+    let last_email = emails.last().cloned().unwrap_or_default();
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (
+            newsletter_issue_id,
+            subscriber_email,
+            available_at
+        )
+        SELECT $1, e.email,
+            CASE WHEN $3::smallint IS NULL THEN now()
+                WHEN random() * 100 < $3 THEN now()
+                ELSE now() + make_interval(mins => $4)
+            END
+        FROM unnest($2::text[]) AS e(email)
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO NOTHING
+        "#,
+        job.newsletter_issue_id,
+        &emails,
+        job.canary_percent,
+        job.canary_delay_minutes,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to enqueue an audience chunk")?;
+    sqlx::query!(
+        "UPDATE issue_enqueue_jobs SET last_email = $2 WHERE newsletter_issue_id = $1",
+        job.newsletter_issue_id,
+        last_email,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to move the enqueue checkpoint")?;
+
+    // wake any listening worker the moment these rows become visible
+    sqlx::query!(
+        "SELECT pg_notify($1, '')",
+        crate::issue_delivery_worker::DELIVERY_NOTIFICATION_CHANNEL
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    tracing::info!(
+        newsletter_issue_id = %job.newsletter_issue_id,
+        queued = emails.len(),
+        "Enqueued a chunk of a large issue's deliveries",
+    );
+    Ok(true)
+}
+
+// which newsletter the logged-in admin runs (see crate::tenancy) -
+// pub(super) so the recipient-count preview scopes itself the same way
+pub(super) async fn get_user_tenant(pool: &PgPool, user_id: Uuid) -> Result<Uuid, sqlx::Error> {
+    let row = sqlx::query!("SELECT tenant_id FROM users WHERE user_id = $1", user_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(row.tenant_id)
+}
+
 // A newsletter delivery task - with status (has it been sent to everytone or not)
 #[tracing::instrument(skip_all)]
+#[allow(clippy::too_many_arguments)]
 async fn insert_newsletter_issue(
     transaction: &mut Transaction<'_, Postgres>,
     title: &str,
     text_content: &str,
     html_content: &str,
+    authored_html_content: &str,
+    tenant_id: Uuid,
+    premium_only: bool,
 ) -> Result<Uuid, sqlx::Error> {
     // unique id for this newsletter issue
     let newsletter_issue_id = Uuid::new_v4();
@@ -110,14 +681,20 @@ async fn insert_newsletter_issue(
             title,
             text_content,
             html_content,
-            published_at
+            authored_html_content,
+            published_at,
+            tenant_id,
+            premium_only
         )
-        VALUES ($1, $2, $3, $4, now())
+        VALUES ($1, $2, $3, $4, $5, now(), $6, $7)
         "#,
         newsletter_issue_id,
         title,
         text_content,
-        html_content
+        html_content,
+        authored_html_content,
+        tenant_id,
+        premium_only
     );
 
     // execute the transaction
@@ -125,24 +702,271 @@ async fn insert_newsletter_issue(
     Ok(newsletter_issue_id)
 }
 
-// a queue of email addresses to send the newsletter to
+// split the comma-separated form field into rows - matching the shape of
+// subscriber_tags (see the JSON API). One multi-row insert via unnest
+// rather than a round trip per tag
 #[tracing::instrument(skip_all)]
-async fn enqueue_delivery_tasks(
+async fn store_issue_tags(
     transaction: &mut Transaction<'_, Postgres>,
     newsletter_issue_id: Uuid,
+    tags: &str,
 ) -> Result<(), sqlx::Error> {
+    let tags: Vec<String> = tags
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect();
+    if tags.is_empty() {
+        return Ok(());
+    }
     let query = sqlx::query!(
         r#"
-        INSERT INTO issue_delivery_queue (
-            newsletter_issue_id,
-            subscriber_email
-        )
-        SELECT $1, email
-        FROM subscriptions
-        WHERE status = 'confirmed'
+        INSERT INTO newsletter_issue_tags (newsletter_issue_id, tag)
+        SELECT $1, t.tag FROM unnest($2::text[]) AS t(tag)
+        ON CONFLICT DO NOTHING
+        "#,
+        newsletter_issue_id,
+        &tags,
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+// the poll rows, in the same transaction as the issue they belong to
+#[tracing::instrument(skip_all)]
+async fn insert_poll(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    question: &str,
+    options: &[String],
+) -> Result<(), sqlx::Error> {
+    let poll_id = Uuid::new_v4();
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO issue_polls (id, newsletter_issue_id, question)
+        VALUES ($1, $2, $3)
         "#,
+        poll_id,
         newsletter_issue_id,
+        question,
+    );
+    transaction.execute(query).await?;
+    // all the options in one insert - unnest keeps the author's ordering
+    // via WITH ORDINALITY, so `position` still matches the form
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO poll_options (id, poll_id, position, label)
+        SELECT gen_random_uuid(), $1, (t.ordinality - 1)::smallint, t.label
+        FROM unnest($2::text[]) WITH ORDINALITY AS t(label, ordinality)
+        "#,
+        poll_id,
+        options,
     );
     transaction.execute(query).await?;
     Ok(())
 }
+
+// replace `{{poll_results}}` with the results of the previous issue's
+// poll - a no-op when the tag isn't used
+async fn expand_poll_results(
+    pool: &PgPool,
+    text_content: String,
+    html_content: String,
+) -> Result<(String, String), anyhow::Error> {
+    if !text_content.contains(POLL_RESULTS_TAG) && !html_content.contains(POLL_RESULTS_TAG) {
+        return Ok((text_content, html_content));
+    }
+    let (results_text, results_html) = match latest_poll_results(pool).await? {
+        Some(results) => results,
+        // the tag with nothing to fill it - better an honest placeholder
+        // than leaving the raw tag in subscribers' inboxes
+        None => (
+            "(no poll results yet)".to_string(),
+            "<p>(no poll results yet)</p>".to_string(),
+        ),
+    };
+    Ok((
+        text_content.replace(POLL_RESULTS_TAG, &results_text),
+        html_content.replace(POLL_RESULTS_TAG, &results_html),
+    ))
+}
+
+// the most recently published poll and its vote counts, rendered as a
+// (text, html) pair
+async fn latest_poll_results(pool: &PgPool) -> Result<Option<(String, String)>, anyhow::Error> {
+    let poll = sqlx::query!(
+        r#"
+        SELECT p.id, p.question
+        FROM issue_polls p
+        JOIN newsletter_issues n ON n.newsletter_issue_id = p.newsletter_issue_id
+        ORDER BY n.published_at::timestamptz DESC
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(poll) = poll else {
+        return Ok(None);
+    };
+    let options = sqlx::query!(
+        r#"
+        SELECT o.label, COUNT(v.subscriber_id) as "votes!"
+        FROM poll_options o
+        LEFT JOIN poll_votes v ON v.option_id = o.id
+        WHERE o.poll_id = $1
+        GROUP BY o.id, o.label, o.position
+        ORDER BY o.position
+        "#,
+        poll.id,
+    )
+    .fetch_all(pool)
+    .await?;
+    let total: i64 = options.iter().map(|o| o.votes).sum();
+
+    let mut text = format!("{}\n", poll.question);
+    let mut html = format!(
+        "<p><b>{}</b><br>",
+        htmlescape::encode_minimal(&poll.question)
+    );
+    for option in &options {
+        let percent = if total > 0 {
+            (option.votes as f64 / total as f64) * 100.0
+        } else {
+            0.0
+        };
+        text.push_str(&format!(
+            "{}: {} vote(s) ({:.0}%)\n",
+            option.label, option.votes, percent
+        ));
+        html.push_str(&format!(
+            "{}: {} vote(s) ({:.0}%)<br>",
+            htmlescape::encode_minimal(&option.label),
+            option.votes,
+            percent
+        ));
+    }
+    html.push_str("</p>");
+    Ok(Some((text, html)))
+}
+
+// a queue of email addresses to send the newsletter to
+// the queue's composite primary key (issue id + email) plus the ON CONFLICT
+// guard below make enqueueing idempotent - a retried publish or overlapping
+// segments can't queue the same issue twice for one subscriber.
+// Category preferences are honoured here: an uncategorised issue goes to
+// everyone, a categorised one skips subscribers who opted into other
+// categories (no opt-ins at all still means "send me everything").
+// Only the issue's own tenant's subscribers are considered at all - in a
+// single-tenant deployment that predicate matches everybody - and a
+// premium_only issue additionally skips everyone who isn't paying
+// (pub so the criterion bench in benches/publish.rs can drive it against a
+// seeded database - nothing outside this module calls it in production)
+#[tracing::instrument(skip_all)]
+pub async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    tenant_id: Uuid,
+    premium_only: bool,
+    canary: Option<&CanarySettings>,
+) -> Result<(), sqlx::Error> {
+    match canary {
+        // a soft launch - a random slice of subscribers is queued for
+        // immediate delivery, everyone else is held back. The hold lifts
+        // automatically once the delay passes, or earlier if the operator
+        // hits "continue"
+        Some(canary) => {
+            let query = sqlx::query!(
+                r#"
+                INSERT INTO issue_delivery_queue (
+                    newsletter_issue_id,
+                    subscriber_email,
+                    available_at
+                )
+                SELECT $1, email,
+                    CASE WHEN random() * 100 < $2
+                        THEN now()
+                        ELSE now() + make_interval(mins => $3)
+                    END
+                FROM subscriptions
+                WHERE status = 'confirmed'
+                AND deleted_at IS NULL
+                AND tenant_id = $4
+                AND ($5 = false OR premium)
+                AND (
+                    NOT EXISTS (
+                        SELECT 1 FROM newsletter_issue_tags it
+                        WHERE it.newsletter_issue_id = $1
+                    )
+                    OR NOT EXISTS (
+                        SELECT 1 FROM subscriber_category_preferences p
+                        WHERE p.subscriber_id = subscriptions.id
+                    )
+                    OR EXISTS (
+                        SELECT 1
+                        FROM subscriber_category_preferences p
+                        JOIN newsletter_issue_tags it ON it.tag = p.category
+                        WHERE p.subscriber_id = subscriptions.id
+                        AND it.newsletter_issue_id = $1
+                    )
+                )
+                ON CONFLICT (newsletter_issue_id, subscriber_email) DO NOTHING
+                "#,
+                newsletter_issue_id,
+                canary.percent as f64,
+                canary.delay_minutes as i32,
+                tenant_id,
+                premium_only,
+            );
+            transaction.execute(query).await?;
+        }
+        None => {
+            let query = sqlx::query!(
+                r#"
+                INSERT INTO issue_delivery_queue (
+                    newsletter_issue_id,
+                    subscriber_email
+                )
+                SELECT $1, email
+                FROM subscriptions
+                WHERE status = 'confirmed'
+                AND deleted_at IS NULL
+                AND tenant_id = $2
+                AND ($3 = false OR premium)
+                AND (
+                    NOT EXISTS (
+                        SELECT 1 FROM newsletter_issue_tags it
+                        WHERE it.newsletter_issue_id = $1
+                    )
+                    OR NOT EXISTS (
+                        SELECT 1 FROM subscriber_category_preferences p
+                        WHERE p.subscriber_id = subscriptions.id
+                    )
+                    OR EXISTS (
+                        SELECT 1
+                        FROM subscriber_category_preferences p
+                        JOIN newsletter_issue_tags it ON it.tag = p.category
+                        WHERE p.subscriber_id = subscriptions.id
+                        AND it.newsletter_issue_id = $1
+                    )
+                )
+                ON CONFLICT (newsletter_issue_id, subscriber_email) DO NOTHING
+                "#,
+                newsletter_issue_id,
+                tenant_id,
+                premium_only,
+            );
+            transaction.execute(query).await?;
+        }
+    }
+
+    // wake the delivery worker as soon as this transaction commits - postgres
+    // holds the notification back until then, so the worker can't race ahead
+    // of the queue rows becoming visible
+    let notify = sqlx::query!(
+        "SELECT pg_notify($1, '')",
+        crate::issue_delivery_worker::DELIVERY_NOTIFICATION_CHANNEL
+    );
+    transaction.execute(notify).await?;
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use actix_web::{web, HttpResponse};
+
+// The preview endpoint behind the newsletter editor: the form posts
+// whatever is in the content pane and gets back what would actually go
+// out - sanitized HTML for the rich preview and the generated plain-text
+// alternative - without anything touching the issue tables.
+
+#[derive(serde::Deserialize)]
+pub struct RenderRequest {
+    content: String,
+    // "markdown" (the default) or "html" for operators who write it by hand
+    #[serde(default = "default_format")]
+    format: String,
+}
+
+fn default_format() -> String {
+    "markdown".to_string()
+}
+
+/// POST /admin/newsletter/render - rendered + sanitized HTML and the
+/// generated text alternative for the submitted content, as JSON.
+#[tracing::instrument(name = "Render a newsletter preview", skip_all, fields(format=%body.format))]
+pub async fn render_preview(
+    body: web::Json<RenderRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let html = match body.format.as_str() {
+        "markdown" => render_markdown(&body.content),
+        "html" => body.content.clone(),
+        other => {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("'{}' is not a supported format - use 'markdown' or 'html'.", other)
+            })));
+        }
+    };
+
+    // sanitize whichever way the html was produced - markdown happily
+    // passes raw html blocks through, so rendering alone is no defence
+    let html = ammonia::clean(&html);
+    let text = html_to_text(&html);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "html": html,
+        "text": text,
+    })))
+}
+
+fn render_markdown(markdown: &str) -> String {
+    let parser = pulldown_cmark::Parser::new(markdown);
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, parser);
+    html
+}
+
+// the plain-text alternative - strip the tags, put the newlines back
+// where block elements were, and unescape what ammonia escaped. Crude,
+// but the output only has to read well in a text-only mail client
+fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(open) = rest.find('<') {
+        text.push_str(&rest[..open]);
+        let Some(close) = rest[open..].find('>') else {
+            // an unclosed tag can't survive ammonia - just stop
+            rest = "";
+            break;
+        };
+        let tag = &rest[open + 1..open + close];
+        let name = tag
+            .trim_start_matches('/')
+            .split([' ', '/'])
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        // block-level boundaries become newlines so paragraphs don't run
+        // into each other
+        match name.as_str() {
+            "p" | "div" | "li" | "tr" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "blockquote"
+            | "pre" | "ul" | "ol" | "table"
+                if !text.ends_with('\n') && !text.is_empty() =>
+            {
+                text.push('\n')
+            }
+            "br" => text.push('\n'),
+            _ => {}
+        }
+        rest = &rest[open + close + 1..];
+    }
+    text.push_str(rest);
+
+    // undo the entity escaping for the text part
+    let text = text
+        .replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'");
+    text.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_renders_and_scripts_are_stripped() {
+        let html = ammonia::clean(&render_markdown(
+            "# Hello\n\n<script>alert('hi')</script>\n\nSome *text*.",
+        ));
+        assert!(html.contains("<h1>Hello</h1>"));
+        assert!(html.contains("<em>text</em>"));
+        assert!(!html.contains("script"));
+    }
+
+    #[test]
+    fn the_text_alternative_keeps_paragraph_breaks() {
+        let text = html_to_text("<h1>Hello</h1><p>First.</p><p>Second &amp; third.</p>");
+        assert_eq!(text, "Hello\nFirst.\nSecond & third.");
+    }
+}
@@ -1,38 +1,58 @@
 use crate::session_state::TypedSession;
-use crate::utils::{e500, populate_dynamic_html_fields, see_other};
+use crate::templates::{page, submit_button, text_field, Field};
+use crate::utils::{e500, see_other};
 use actix_web::http::header::ContentType;
 use actix_web::HttpResponse;
 use actix_web_flash_messages::IncomingFlashMessages;
-use std::collections::HashMap;
-use std::fmt::Write;
 
 pub async fn change_password_form(
     session: TypedSession,                 // defined in SessionState.rs
     flash_messages: IncomingFlashMessages, // attached if returning from failed POST req.
 ) -> Result<HttpResponse, actix_web::Error> {
-    // check for flash message
-    let mut msg_html = String::new();
     // check session is valid - if not, go back to login page
     // e500 is defined in utils - just an error wrapper that preserves context
     if session.get_user_id().map_err(e500)?.is_none() {
         return Ok(see_other("/login"));
     }
 
-    for m in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
-
-    // Read the HTML file into a string
-    let html_page = include_str!("password.html");
-
-    // make a dict of the dynamic content
-    let mut dynamic_fields = HashMap::<&str, &str>::new();
-    dynamic_fields.insert("msg_html", &msg_html);
+    let msg_html = crate::utils::flash_messages_html(&flash_messages);
 
-    // add the dynamic content
-    let populated_html = populate_dynamic_html_fields(dynamic_fields, html_page);
+    // built from the shared components (crate::templates) - the
+    // autocomplete hints let password managers fill the right slots
+    let body = format!(
+        "{msg_html}        <form action=\"/admin/password\" method=\"post\">\n{current}{new}{check}{submit}        </form>\n        <p><a href=\"/admin/dashboard\">&lt;- Back</a></p>\n",
+        current = text_field(&Field {
+            label: "Current password",
+            name: "current_password",
+            kind: "password",
+            placeholder: "Enter current password",
+            autocomplete: "current-password",
+            required: true,
+            autofocus: true,
+            ..Field::default()
+        }),
+        new = text_field(&Field {
+            label: "New password",
+            name: "new_password",
+            kind: "password",
+            placeholder: "Enter new password",
+            autocomplete: "new-password",
+            required: true,
+            ..Field::default()
+        }),
+        check = text_field(&Field {
+            label: "Confirm new password",
+            name: "new_password_check",
+            kind: "password",
+            placeholder: "Type the new password again",
+            autocomplete: "new-password",
+            required: true,
+            ..Field::default()
+        }),
+        submit = submit_button("Change password"),
+    );
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
-        .body(populated_html))
+        .body(page("Change Password", &body)))
 }
@@ -1,7 +1,14 @@
+use crate::alerts::Alerter;
 use crate::authentication;
 use crate::authentication::AuthError;
 use crate::authentication::UserId;
+use crate::clock::Clock;
+use crate::configuration::{PasswordHashSettings, PasswordPolicySettings};
+use crate::routes::account;
 use crate::routes::admin::dashboard;
+use crate::session_state::{revoke_other_sessions, TypedSession};
+use crate::signed_link::LinkSigner;
+use crate::startup::ApplicationBaseUrl;
 use crate::utils::{e500, see_other};
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
@@ -16,10 +23,18 @@ pub struct FormData {
     new_password_check: Secret<String>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn change_password(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,       // we need the postgres db and the session
     user_id: web::ReqData<UserId>, // this is attached in authentication::password
+    policy: web::Data<PasswordPolicySettings>,
+    hashing: web::Data<PasswordHashSettings>,
+    session: TypedSession,
+    alerter: web::Data<Alerter>, // security notification on success
+    base_url: web::Data<ApplicationBaseUrl>,
+    link_signer: web::Data<LinkSigner>,
+    clock: web::Data<dyn Clock>,
 ) -> Result<HttpResponse, actix_web::Error> {
     // if no active session, back to login page
     let user_id = user_id.into_inner();
@@ -40,24 +55,27 @@ pub async fn change_password(
         return Ok(see_other("/admin/password"));
     }
 
-    // check password is correct length
-    if !(12..=129).contains(&form.new_password.expose_secret().len()) {
-        FlashMessage::error("The new password must be between 12 & 129 characters.").send();
-        return Ok(see_other("/admin/password"));
-    };
-
     // gets the username from a user_id from postgres db
     let username = dashboard::get_username(*user_id, &pool)
         .await
         .map_err(e500)?;
 
+    // check the candidate against the configured policy (length, character
+    // classes, username, optionally the HaveIBeenPwned breach corpus)
+    if let Err(reason) =
+        authentication::validate_password_policy(&form.new_password, &username, &policy).await
+    {
+        FlashMessage::error(reason).send();
+        return Ok(see_other("/admin/password"));
+    };
+
     let credentials = authentication::Credentials {
         username,
         password: form.0.current_password,
     };
 
     // check the current password is correct
-    if let Err(e) = authentication::validate_credentials(credentials, &pool).await {
+    if let Err(e) = authentication::validate_credentials(credentials, &pool, &hashing).await {
         return match e {
             // wrong password - send a flash message and redirect to GET
             AuthError::InvalidCredentials(_) => {
@@ -69,9 +87,23 @@ pub async fn change_password(
         };
     }
 
-    crate::authentication::change_password(*user_id, form.0.new_password, &pool)
+    crate::authentication::change_password(*user_id, form.0.new_password, &pool, &hashing)
         .await
         .map_err(e500)?;
+
+    // whoever knew the old password may be holding a session - kill every
+    // session but this one, and rotate this one's key while we're at it
+    let current = session.get_session_id().map_err(e500)?;
+    revoke_other_sessions(&pool, *user_id, current)
+        .await
+        .map_err(e500)?;
+    session.renew();
+
+    // and tell the operator inboxes, with a kill-switch link in case it
+    // wasn't actually the admin at the keyboard
+    account::notify_password_changed(&alerter, &base_url, &link_signer, *user_id, clock.now())
+        .await;
+
     FlashMessage::info("Your password has been changed.").send();
     Ok(see_other("/admin/password"))
 }
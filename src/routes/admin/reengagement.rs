@@ -0,0 +1,267 @@
+use crate::{
+    domain::SubscriberEmail,
+    email_client::EmailClient,
+    startup::ApplicationBaseUrl,
+    utils::{e500, see_other},
+};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use chrono::Utc;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// List cleaning. A run of the campaign does two things, in order:
+// 1. unsubscribes anyone who was asked to re-confirm more than
+//    `response_window_days` ago and never clicked the link;
+// 2. finds confirmed subscribers with no open/click events across the
+//    last `issue_count` issues and emails them a re-confirmation link
+//    (handled by routes/subscriptions_reconfirm.rs).
+
+/// GET /admin/reengagement - show outstanding campaigns and the run form.
+pub async fn reengagement_form(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let outstanding = count_outstanding_campaigns(&pool).await.map_err(e500)?;
+
+    let msg_html = crate::utils::flash_messages_html(&flash_messages);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Re-engagement</title>
+</head>
+<body>
+    {msg_html}
+    <p>{outstanding} subscriber(s) have an outstanding re-confirmation request.</p>
+    <form action="/admin/reengagement" method="post">
+        <label>Treat as inactive after this many issues without engagement
+            <input type="number" name="issue_count" value="5" min="1">
+        </label>
+        <br>
+        <label>Unsubscribe non-responders after this many days
+            <input type="number" name="response_window_days" value="30" min="1">
+        </label>
+        <br>
+        <button type="submit">Run re-engagement campaign</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ReengagementForm {
+    issue_count: i64,
+    response_window_days: i32,
+}
+
+/// POST /admin/reengagement - run the campaign described above.
+#[tracing::instrument(
+    name = "Run a re-engagement campaign",
+    skip(form, pool, email_client, base_url)
+)]
+pub async fn run_reengagement(
+    form: web::Form<ReengagementForm>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // step 1: drop anyone whose window has lapsed without a response
+    let unsubscribed = unsubscribe_non_responders(&pool, form.0.response_window_days)
+        .await
+        .context("Failed to unsubscribe non-responders")
+        .map_err(e500)?;
+
+    // step 2: ask the currently-inactive cohort to re-confirm
+    let inactive = get_inactive_subscribers(&pool, form.0.issue_count)
+        .await
+        .context("Failed to identify inactive subscribers")
+        .map_err(e500)?;
+
+    let mut contacted = 0u64;
+    for subscriber in inactive {
+        let email = match SubscriberEmail::parse(subscriber.email.clone()) {
+            Ok(email) => email,
+            Err(error) => {
+                // same stance as the delivery worker - skip, don't abort
+                tracing::warn!(
+                    error.cause_chain = ?error,
+                    "Skipping an inactive subscriber - their stored email is invalid"
+                );
+                continue;
+            }
+        };
+        let reconfirm_token = generate_reconfirm_token();
+        if let Err(e) =
+            send_reconfirmation_email(&email_client, &email, &base_url.0, &reconfirm_token).await
+        {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                "Failed to send a re-confirmation email - skipping the subscriber"
+            );
+            continue;
+        }
+        // only start the clock once the email has actually gone out
+        record_campaign(&pool, subscriber.id, &reconfirm_token)
+            .await
+            .context("Failed to record a re-engagement campaign")
+            .map_err(e500)?;
+        contacted += 1;
+    }
+
+    FlashMessage::info(format!(
+        "Unsubscribed {} non-responder(s); asked {} inactive subscriber(s) to re-confirm.",
+        unsubscribed, contacted
+    ))
+    .send();
+    Ok(see_other("/admin/reengagement"))
+}
+
+struct InactiveSubscriber {
+    id: Uuid,
+    email: String,
+}
+
+#[tracing::instrument(skip_all)]
+async fn count_outstanding_campaigns(pool: &PgPool) -> Result<i64, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT COUNT(*) as "count!" FROM reengagement_campaigns"#
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row.count)
+}
+
+// one statement so the campaign rows and the status flip can't diverge
+#[tracing::instrument(skip(pool))]
+async fn unsubscribe_non_responders(
+    pool: &PgPool,
+    response_window_days: i32,
+) -> Result<u64, sqlx::Error> {
+    let outcome = sqlx::query!(
+        r#"
+        WITH expired AS (
+            DELETE FROM reengagement_campaigns
+            WHERE sent_at < now() - make_interval(days => $1)
+            RETURNING subscriber_id
+        )
+        UPDATE subscriptions
+        SET status = 'unsubscribed'
+        WHERE id IN (SELECT subscriber_id FROM expired)
+        "#,
+        response_window_days,
+    )
+    .execute(pool)
+    .await?;
+    Ok(outcome.rows_affected())
+}
+
+// confirmed subscribers with zero tracking events across the most recent
+// `issue_count` issues, excluding anyone already being chased
+#[tracing::instrument(skip(pool))]
+async fn get_inactive_subscribers(
+    pool: &PgPool,
+    issue_count: i64,
+) -> Result<Vec<InactiveSubscriber>, sqlx::Error> {
+    sqlx::query_as!(
+        InactiveSubscriber,
+        r#"
+        WITH recent_issues AS (
+            SELECT newsletter_issue_id
+            FROM newsletter_issues
+            ORDER BY published_at::timestamptz DESC
+            LIMIT $1
+        )
+        SELECT s.id, s.email
+        FROM subscriptions s
+        WHERE s.status = 'confirmed'
+        AND s.deleted_at IS NULL
+        AND NOT EXISTS (
+            SELECT 1
+            FROM email_tracking_events e
+            JOIN recent_issues r USING (newsletter_issue_id)
+            WHERE e.subscriber_id = s.id
+        )
+        AND NOT EXISTS (
+            SELECT 1
+            FROM reengagement_campaigns c
+            WHERE c.subscriber_id = s.id
+        )
+        "#,
+        issue_count,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(skip(pool, reconfirm_token))]
+async fn record_campaign(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    reconfirm_token: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO reengagement_campaigns (subscriber_id, reconfirm_token, sent_at)
+        VALUES ($1, $2, $3)
+        "#,
+        subscriber_id,
+        reconfirm_token,
+        Utc::now(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn send_reconfirmation_email(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    base_url: &str,
+    reconfirm_token: &str,
+) -> Result<(), crate::email_client::SendError> {
+    let reconfirm_link = format!(
+        "{}/subscriptions/reconfirm?token={}",
+        base_url, reconfirm_token
+    );
+    let html_body = &format!(
+        "We noticed you haven't opened the newsletter in a while.<br />\
+        Click <a href=\"{}\">here</a> to stay subscribed - otherwise we'll \
+        stop emailing you.",
+        reconfirm_link
+    );
+    let plain_text_body = &format!(
+        "We noticed you haven't opened the newsletter in a while.\n\
+        Visit {} to stay subscribed - otherwise we'll stop emailing you.",
+        reconfirm_link
+    );
+    email_client
+        .send_email(
+            email,
+            "Do you still want to hear from us?",
+            html_body,
+            plain_text_body,
+        )
+        .await?;
+    Ok(())
+}
+
+// same shape as the subscription tokens
+fn generate_reconfirm_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(25)
+        .collect()
+}
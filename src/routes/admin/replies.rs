@@ -0,0 +1,114 @@
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+// The reader replies captured by the inbound webhook (see
+// routes::inbound_email) - newest first, so catching up is a skim from
+// the top. Replies the webhook could not match to an issue still show
+// up, just without a title.
+
+// enough to catch up on without the page scrolling forever
+const REPLY_PAGE_SIZE: i64 = 50;
+
+// enough of a reply to read in the table without one essay-length
+// response swallowing the page; char-based, so a multibyte reply can't
+// be split mid-character
+fn excerpt(body: &str) -> String {
+    const LIMIT: usize = 300;
+    if body.chars().count() <= LIMIT {
+        body.to_string()
+    } else {
+        let mut shortened: String = body.chars().take(LIMIT).collect();
+        shortened.push_str("...");
+        shortened
+    }
+}
+
+/// GET /admin/replies - the most recent reader replies.
+#[tracing::instrument(name = "View reader replies", skip_all)]
+pub async fn reply_list(pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    let replies = sqlx::query!(
+        r#"
+        SELECT r.received_at, r.from_email, r.subject, r.body,
+            i.title AS "issue_title?"
+        FROM issue_replies r
+        LEFT JOIN newsletter_issues i
+            ON i.newsletter_issue_id = r.newsletter_issue_id
+        ORDER BY r.received_at DESC
+        LIMIT $1
+        "#,
+        REPLY_PAGE_SIZE,
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to fetch reader replies.")
+    .map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for reply in &replies {
+        writeln!(
+            rows_html,
+            "<tr>\
+             <td>{received_at}</td>\
+             <td>{from_email}</td>\
+             <td>{issue}</td>\
+             <td>{subject}</td>\
+             <td>{body}</td>\
+             </tr>",
+            received_at = reply.received_at.format("%Y-%m-%d %H:%M"),
+            from_email = htmlescape::encode_minimal(&reply.from_email),
+            issue = htmlescape::encode_minimal(
+                reply.issue_title.as_deref().unwrap_or("(unmatched)")
+            ),
+            subject = htmlescape::encode_minimal(&reply.subject),
+            body = htmlescape::encode_minimal(&excerpt(&reply.body)),
+        )
+        .unwrap();
+    }
+    if replies.is_empty() {
+        rows_html.push_str("<tr><td colspan=\"5\">No replies yet.</td></tr>");
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Reader replies</title>
+</head>
+<body>
+    <h1>Reader replies</h1>
+    <table border="1" cellpadding="5">
+        <tr>
+            <th>Received</th>
+            <th>From</th>
+            <th>Issue</th>
+            <th>Subject</th>
+            <th>Reply</th>
+        </tr>
+        {rows_html}
+    </table>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_replies_are_shown_whole_and_long_ones_are_cut() {
+        assert_eq!(excerpt("thanks, loved it"), "thanks, loved it");
+        let essay = "é".repeat(400);
+        let shortened = excerpt(&essay);
+        assert!(shortened.ends_with("..."));
+        assert_eq!(shortened.chars().count(), 303);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct QueryParams {
+    q: String,
+}
+
+/// GET /admin/search?q= - full-text search over subscribers and issues,
+/// results grouped by kind.
+#[tracing::instrument(name = "Admin search", skip(query, pool), fields(search_term=%query.q))]
+pub async fn admin_search(
+    query: web::Query<QueryParams>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let search_term = query.q.trim();
+
+    let (subscribers, issues) = if search_term.is_empty() {
+        (Vec::new(), Vec::new())
+    } else {
+        (
+            search_subscribers(&pool, search_term).await.map_err(e500)?,
+            search_issues(&pool, search_term).await.map_err(e500)?,
+        )
+    };
+
+    let mut subscribers_html = String::new();
+    for subscriber in &subscribers {
+        writeln!(
+            subscribers_html,
+            r#"<li>{} &lt;{}&gt; - {}
+    <form action="/admin/subscribers/{}/delete" method="post" style="display:inline">
+        <button type="submit">Delete</button>
+    </form></li>"#,
+            htmlescape::encode_minimal(&subscriber.name),
+            htmlescape::encode_minimal(&subscriber.email),
+            subscriber.status,
+            subscriber.id,
+        )
+        .unwrap();
+    }
+    if subscribers.is_empty() {
+        subscribers_html.push_str("<li>No matching subscribers</li>");
+    }
+
+    let mut issues_html = String::new();
+    for issue in &issues {
+        writeln!(
+            issues_html,
+            r#"<li><a href="/archive/{}">{}</a> - {}</li>"#,
+            issue.newsletter_issue_id,
+            htmlescape::encode_minimal(&issue.title),
+            issue.published_at.format("%Y-%m-%d")
+        )
+        .unwrap();
+    }
+    if issues.is_empty() {
+        issues_html.push_str("<li>No matching issues</li>");
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Search results</title>
+</head>
+<body>
+    <h1>Search results for "{escaped_term}"</h1>
+    <form action="/admin/search" method="get">
+        <input type="text" name="q" value="{escaped_term}">
+        <input type="submit" value="Search">
+    </form>
+    <h2>Subscribers</h2>
+    <ul>
+        {subscribers_html}
+    </ul>
+    <h2>Issues</h2>
+    <ul>
+        {issues_html}
+    </ul>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+            escaped_term = htmlescape::encode_minimal(search_term),
+        )))
+}
+
+struct SubscriberSearchResult {
+    id: Uuid,
+    name: String,
+    email: String,
+    status: String,
+}
+
+// the to_tsvector expression must stay in sync with the GIN index created
+// in the full-text search migration, or postgres falls back to a seq scan
+#[tracing::instrument(skip_all)]
+async fn search_subscribers(
+    pool: &PgPool,
+    search_term: &str,
+) -> Result<Vec<SubscriberSearchResult>, anyhow::Error> {
+    let results = sqlx::query_as!(
+        SubscriberSearchResult,
+        r#"
+        SELECT id, name, email, status as "status!"
+        FROM subscriptions
+        WHERE to_tsvector('english', name || ' ' || email)
+            @@ websearch_to_tsquery('english', $1)
+        AND deleted_at IS NULL
+        ORDER BY subscribed_at DESC
+        LIMIT 50
+        "#,
+        search_term,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to search subscribers.")?;
+    Ok(results)
+}
+
+struct IssueSearchResult {
+    newsletter_issue_id: Uuid,
+    title: String,
+    published_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(skip_all)]
+async fn search_issues(
+    pool: &PgPool,
+    search_term: &str,
+) -> Result<Vec<IssueSearchResult>, anyhow::Error> {
+    let results = sqlx::query_as!(
+        IssueSearchResult,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            title,
+            published_at::timestamptz as "published_at!"
+        FROM newsletter_issues
+        WHERE to_tsvector('english', title || ' ' || text_content)
+            @@ websearch_to_tsquery('english', $1)
+        ORDER BY published_at::timestamptz DESC
+        LIMIT 50
+        "#,
+        search_term,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to search issues.")?;
+    Ok(results)
+}
@@ -0,0 +1,317 @@
+use crate::domain::SegmentDefinition;
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use std::fmt::Write;
+use uuid::Uuid;
+
+// Saved segments - named subscriber filters, defined once and reused at
+// publish time. The filter itself lives in crate::domain::SegmentDefinition;
+// this module is the CRUD around it plus the live membership counts.
+
+pub struct Segment {
+    pub name: String,
+    pub definition: SegmentDefinition,
+}
+
+/// GET /admin/segments - every saved segment with its live membership
+/// count, plus the form to define a new one.
+pub async fn segments_page(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let msg_html = crate::utils::flash_messages_html(&flash_messages);
+
+    let segments = get_all_segments(&pool).await.map_err(e500)?;
+
+    let mut segments_html = String::new();
+    for (id, segment) in &segments {
+        // counted fresh on every page load - a segment is a filter, not a
+        // snapshot, so the number moves as subscribers come and go
+        let count = count_members(&pool, &segment.definition)
+            .await
+            .map_err(e500)?;
+        writeln!(
+            segments_html,
+            r#"<li><b>{}</b> - {} - {} member(s)
+    <form action="/admin/segments/{}/delete" method="post" style="display:inline">
+        <button type="submit">Delete</button>
+    </form></li>"#,
+            htmlescape::encode_minimal(&segment.name),
+            htmlescape::encode_minimal(&describe(&segment.definition)),
+            count,
+            id,
+        )
+        .unwrap();
+    }
+    if segments.is_empty() {
+        segments_html.push_str("<li>No saved segments yet</li>");
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Segments</title>
+</head>
+<body>
+    {msg_html}
+    <h1>Segments</h1>
+    <ul>
+        {segments_html}
+    </ul>
+    <h2>New segment</h2>
+    <form action="/admin/segments" method="post">
+        <label>Name: <input type="text" name="name" required></label><br><br>
+        <label>Status:
+            <select name="status">
+                <option value="">(any)</option>
+                <option value="confirmed">confirmed</option>
+                <option value="pending_confirmation">pending_confirmation</option>
+                <option value="unsubscribed">unsubscribed</option>
+            </select>
+        </label><br><br>
+        <label>Tags (comma-separated, all must be present):
+            <input type="text" name="tags">
+        </label><br><br>
+        <label>Opened or clicked one of the last
+            <input type="number" name="engaged_within_issues" min="1" style="width:4em">
+            issues (blank = any engagement)
+        </label><br><br>
+        <label>Signed up between
+            <input type="date" name="signed_up_after"> and
+            <input type="date" name="signed_up_before"> (either side optional)
+        </label><br><br>
+        <button type="submit">Save segment</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct SegmentForm {
+    name: String,
+    // blank inputs submit "" - each one means "no condition"
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    engaged_within_issues: String,
+    #[serde(default)]
+    signed_up_after: String,
+    #[serde(default)]
+    signed_up_before: String,
+}
+
+/// POST /admin/segments - save a new segment definition.
+#[tracing::instrument(name = "Create a segment", skip_all, fields(segment_name=%form.name))]
+pub async fn create_segment(
+    form: web::Form<SegmentForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let form = form.into_inner();
+    let name = form.name.trim().to_string();
+    if name.is_empty() {
+        FlashMessage::error("A segment needs a name.").send();
+        return Ok(see_other("/admin/segments"));
+    }
+    let definition = match parse_definition(&form) {
+        Ok(definition) => definition,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/segments"));
+        }
+    };
+
+    let outcome = sqlx::query!(
+        r#"
+        INSERT INTO segments (
+            id, name, status, tags, engaged_within_issues,
+            signed_up_after, signed_up_before
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (name) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        name,
+        definition.status,
+        &definition.tags,
+        definition.engaged_within_issues,
+        definition.signed_up_after,
+        definition.signed_up_before,
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    if outcome.rows_affected() == 1 {
+        FlashMessage::info(format!("The segment '{}' has been saved.", name)).send();
+    } else {
+        FlashMessage::error(format!("A segment named '{}' already exists.", name)).send();
+    }
+    Ok(see_other("/admin/segments"))
+}
+
+/// POST /admin/segments/{id}/delete - drop a saved segment. Only the
+/// definition goes - the subscribers it matched are untouched.
+#[tracing::instrument(name = "Delete a segment", skip(pool), fields(segment_id=%path))]
+pub async fn delete_segment(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let segment_id = path.into_inner();
+    let outcome = sqlx::query!(r#"DELETE FROM segments WHERE id = $1"#, segment_id)
+        .execute(pool.get_ref())
+        .await
+        .map_err(e500)?;
+    if outcome.rows_affected() == 1 {
+        FlashMessage::info("The segment has been deleted.").send();
+    } else {
+        FlashMessage::error("That segment no longer exists.").send();
+    }
+    Ok(see_other("/admin/segments"))
+}
+
+/// A saved segment by id - used by the publish flow to resolve the
+/// segment picked in the newsletter form.
+pub async fn get_segment(pool: &PgPool, segment_id: Uuid) -> Result<Option<Segment>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT name, status, tags, engaged_within_issues,
+            signed_up_after, signed_up_before
+        FROM segments
+        WHERE id = $1
+        "#,
+        segment_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| Segment {
+        name: r.name,
+        definition: SegmentDefinition {
+            status: r.status,
+            tags: r.tags,
+            engaged_within_issues: r.engaged_within_issues,
+            signed_up_after: r.signed_up_after,
+            signed_up_before: r.signed_up_before,
+        },
+    }))
+}
+
+/// Every saved segment, for the list page and the publish-time dropdown.
+pub async fn get_all_segments(pool: &PgPool) -> Result<Vec<(Uuid, Segment)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT id, name, status, tags, engaged_within_issues,
+            signed_up_after, signed_up_before
+        FROM segments
+        ORDER BY name
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            (
+                r.id,
+                Segment {
+                    name: r.name,
+                    definition: SegmentDefinition {
+                        status: r.status,
+                        tags: r.tags,
+                        engaged_within_issues: r.engaged_within_issues,
+                        signed_up_after: r.signed_up_after,
+                        signed_up_before: r.signed_up_before,
+                    },
+                },
+            )
+        })
+        .collect())
+}
+
+// how many live subscribers the definition matches right now
+#[tracing::instrument(skip_all)]
+async fn count_members(
+    pool: &PgPool,
+    definition: &SegmentDefinition,
+) -> Result<i64, anyhow::Error> {
+    let mut builder: QueryBuilder<'_, Postgres> =
+        QueryBuilder::new("SELECT COUNT(*) FROM subscriptions s WHERE s.deleted_at IS NULL");
+    definition.push_sql(&mut builder);
+    let count: i64 = builder.build_query_scalar().fetch_one(pool).await?;
+    Ok(count)
+}
+
+fn parse_definition(form: &SegmentForm) -> Result<SegmentDefinition, String> {
+    let status = match form.status.trim() {
+        "" => None,
+        status => Some(status.to_string()),
+    };
+    let tags: Vec<String> = form
+        .tags
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect();
+    let engaged_within_issues = match form.engaged_within_issues.trim() {
+        "" => None,
+        raw => Some(
+            raw.parse::<i32>()
+                .map_err(|_| format!("'{}' is not a valid issue count.", raw))?,
+        ),
+    };
+    let parse_date = |raw: &str| -> Result<Option<chrono::NaiveDate>, String> {
+        match raw.trim() {
+            "" => Ok(None),
+            raw => raw
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("'{}' is not a valid date.", raw)),
+        }
+    };
+    let definition = SegmentDefinition {
+        status,
+        tags,
+        engaged_within_issues,
+        signed_up_after: parse_date(&form.signed_up_after)?,
+        signed_up_before: parse_date(&form.signed_up_before)?,
+    };
+    definition.validate()?;
+    Ok(definition)
+}
+
+// a one-line human rendering of the definition for the list page
+fn describe(definition: &SegmentDefinition) -> String {
+    let mut parts = Vec::new();
+    if let Some(status) = &definition.status {
+        parts.push(format!("status {}", status));
+    }
+    if !definition.tags.is_empty() {
+        parts.push(format!("tagged {}", definition.tags.join(" + ")));
+    }
+    if let Some(issues) = definition.engaged_within_issues {
+        parts.push(format!("engaged within the last {} issue(s)", issues));
+    }
+    match (definition.signed_up_after, definition.signed_up_before) {
+        (Some(after), Some(before)) => {
+            parts.push(format!("signed up {} to {}", after, before));
+        }
+        (Some(after), None) => parts.push(format!("signed up after {}", after)),
+        (None, Some(before)) => parts.push(format!("signed up before {}", before)),
+        (None, None) => {}
+    }
+    if parts.is_empty() {
+        "everyone".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
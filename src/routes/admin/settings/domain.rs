@@ -0,0 +1,240 @@
+use crate::authentication::UserId;
+use crate::clock::Clock;
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// The custom link domain editor behind /admin/settings/domain. A tenant
+// can point links (confirmation, unsubscribe, archive, feeds) at its own
+// domain instead of the shared base url - but only after proving it owns
+// the domain by publishing a DNS TXT record with the token we hand out.
+// The verified domain is picked up by the tenant directory on the next
+// restart (see crate::tenancy).
+
+// what the TXT record's value has to look like
+fn expected_txt_record(token: &str) -> String {
+    format!("zero2prod-verify={}", token)
+}
+
+struct LinkDomain {
+    tenant_id: Uuid,
+    domain: Option<String>,
+    token: Option<String>,
+    verified_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+// the logged-in admin's tenant and its current domain configuration
+async fn get_link_domain(pool: &PgPool, user_id: Uuid) -> Result<LinkDomain, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT t.id, t.link_domain, t.link_domain_token, t.link_domain_verified_at
+        FROM tenants t
+        JOIN users u ON u.tenant_id = t.id
+        WHERE u.user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to fetch the tenant's link domain.")?;
+    Ok(LinkDomain {
+        tenant_id: row.id,
+        domain: row.link_domain,
+        token: row.link_domain_token,
+        verified_at: row.link_domain_verified_at,
+    })
+}
+
+/// GET /admin/settings/domain - show the domain, its verification status
+/// and the TXT record still to be published if it isn't verified yet.
+pub async fn link_domain_form(
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let state = get_link_domain(&pool, **user_id).await.map_err(e500)?;
+
+    let msg_html = crate::utils::flash_messages_html(&flash_messages);
+
+    let status_html = match (&state.domain, &state.token, state.verified_at) {
+        (Some(domain), _, Some(verified_at)) => format!(
+            "<p><b>{}</b> is verified (since {}). Links use it after the \
+             next restart.</p>",
+            htmlescape::encode_minimal(domain),
+            verified_at.format("%Y-%m-%d %H:%M UTC"),
+        ),
+        (Some(domain), Some(token), None) => format!(
+            r#"<p><b>{domain}</b> is not verified yet. Publish this DNS record, then hit verify:</p>
+    <pre>{domain}.  TXT  "{record}"</pre>
+    <form action="/admin/settings/domain/verify" method="post">
+        <button type="submit">Verify</button>
+    </form>"#,
+            domain = htmlescape::encode_minimal(domain),
+            record = htmlescape::encode_minimal(&expected_txt_record(token)),
+        ),
+        _ => "<p>No custom domain is configured - links use the shared base url.</p>".to_string(),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Custom link domain</title>
+</head>
+<body>
+    {msg_html}
+    <h1>Custom link domain</h1>
+    {status_html}
+    <form action="/admin/settings/domain" method="post">
+        <label>Domain (blank to remove)
+            <input type="text" name="link_domain" value="{domain}" placeholder="news.example.com">
+        </label>
+        <button type="submit">Save</button>
+    </form>
+    <p><a href="/admin/settings">&lt;- Back to settings</a></p>
+</body>
+</html>"#,
+            domain = htmlescape::encode_attribute(state.domain.as_deref().unwrap_or("")),
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct LinkDomainForm {
+    link_domain: String,
+}
+
+/// POST /admin/settings/domain - set (or clear) the domain. Changing it
+/// always resets verification: the token is fresh, the old proof is void.
+#[tracing::instrument(name = "Save a custom link domain", skip_all)]
+pub async fn save_link_domain(
+    form: web::Form<LinkDomainForm>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let state = get_link_domain(&pool, **user_id).await.map_err(e500)?;
+
+    // tolerate a pasted url - we only want the host part
+    let domain = form
+        .link_domain
+        .trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/')
+        .to_lowercase();
+
+    let (domain, token) = if domain.is_empty() {
+        (None, None)
+    } else {
+        (
+            Some(domain),
+            Some(crate::routes::subscriptions::generate_subscription_token()),
+        )
+    };
+    sqlx::query!(
+        r#"
+        UPDATE tenants
+        SET link_domain = $2, link_domain_token = $3, link_domain_verified_at = NULL
+        WHERE id = $1
+        "#,
+        state.tenant_id,
+        domain,
+        token,
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+
+    match domain {
+        Some(_) => FlashMessage::info(
+            "The domain has been saved. Publish the TXT record below, then verify it.",
+        )
+        .send(),
+        None => FlashMessage::info("The custom domain has been removed.").send(),
+    }
+    Ok(see_other("/admin/settings/domain"))
+}
+
+/// POST /admin/settings/domain/verify - look the TXT records up and flip
+/// the domain to verified if the token is among them.
+#[tracing::instrument(name = "Verify a custom link domain", skip_all)]
+pub async fn verify_link_domain(
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let state = get_link_domain(&pool, **user_id).await.map_err(e500)?;
+    let (Some(domain), Some(token)) = (&state.domain, &state.token) else {
+        FlashMessage::error("There is no domain waiting to be verified.").send();
+        return Ok(see_other("/admin/settings/domain"));
+    };
+
+    match txt_records(domain).await {
+        Ok(records) if records.contains(&expected_txt_record(token)) => {
+            sqlx::query!(
+                "UPDATE tenants SET link_domain_verified_at = $2 WHERE id = $1",
+                state.tenant_id,
+                clock.now(),
+            )
+            .execute(pool.get_ref())
+            .await
+            .map_err(e500)?;
+            FlashMessage::info(
+                "The domain has been verified. Links switch over on the next restart.",
+            )
+            .send();
+        }
+        Ok(_) => {
+            FlashMessage::error(
+                "The TXT record was not found. DNS changes can take a while to \
+                 propagate - try again in a few minutes.",
+            )
+            .send();
+        }
+        Err(e) => {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                "Failed to look up the TXT records for a domain verification",
+            );
+            FlashMessage::error("The DNS lookup failed. Please try again.").send();
+        }
+    }
+    Ok(see_other("/admin/settings/domain"))
+}
+
+// the TXT records for a name, via DNS-over-HTTPS - we already carry
+// reqwest, and this saves us a whole resolver dependency
+async fn txt_records(domain: &str) -> Result<Vec<String>, anyhow::Error> {
+    let response = reqwest::Client::new()
+        .get("https://dns.google/resolve")
+        .query(&[("name", domain), ("type", "TXT")])
+        .header("accept", "application/dns-json")
+        .send()
+        .await
+        .context("The DNS-over-HTTPS request failed.")?
+        .error_for_status()
+        .context("The DNS-over-HTTPS request was rejected.")?;
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .context("Failed to parse the DNS response.")?;
+    let records = body["Answer"]
+        .as_array()
+        .map(|answers| {
+            answers
+                .iter()
+                .filter_map(|answer| answer["data"].as_str())
+                // resolvers quote TXT payloads
+                .map(|data| data.trim_matches('"').to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(records)
+}
@@ -0,0 +1,13 @@
+mod domain;
+pub use domain::{link_domain_form, save_link_domain, verify_link_domain};
+
+mod pages;
+pub use pages::{edit_pages_form, save_page};
+
+mod site;
+pub use site::{save_site_settings, site_settings_form};
+
+mod templates;
+pub use templates::{
+    email_templates_form, save_compliance_footer, save_email_templates, save_reply_ack,
+};
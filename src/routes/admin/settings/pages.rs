@@ -0,0 +1,82 @@
+use crate::custom_pages::{self, Page};
+use crate::utils::{e400, e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+// The tiny CMS behind /admin/settings/pages - one textarea per public
+// page, pre-filled with the stored override (or the built-in default on
+// first visit). See crate::custom_pages for where these get served.
+
+/// GET /admin/settings/pages - show an editor per customizable page.
+pub async fn edit_pages_form(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let msg_html = crate::utils::flash_messages_html(&flash_messages);
+
+    let mut editors_html = String::new();
+    for page in Page::ALL {
+        let html = custom_pages::get_override(&pool, page)
+            .await
+            .map_err(e500)?
+            .unwrap_or_else(|| custom_pages::default_html(page).to_string());
+        writeln!(
+            editors_html,
+            r#"<h2>{slug}</h2>
+            <form action="/admin/settings/pages" method="post">
+                <input type="hidden" name="slug" value="{slug}">
+                <textarea name="html" rows="10" cols="80">{html}</textarea>
+                <br>
+                <button type="submit">Save</button>
+            </form>"#,
+            slug = page.slug(),
+            html = htmlescape::encode_minimal(&html),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Page settings</title>
+</head>
+<body>
+    {msg_html}
+    <h1>Public pages</h1>
+    {editors_html}
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct PageForm {
+    slug: String,
+    html: String,
+}
+
+/// POST /admin/settings/pages - store the HTML for one page.
+#[tracing::instrument(name = "Save a custom page", skip(form, pool), fields(slug = %form.slug))]
+pub async fn save_page(
+    form: web::Form<PageForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let page = Page::from_slug(&form.slug)
+        .ok_or_else(|| anyhow::anyhow!("'{}' is not a customizable page", form.slug))
+        .map_err(e400)?;
+
+    custom_pages::set_override(&pool, page, &form.html)
+        .await
+        .map_err(e500)?;
+
+    FlashMessage::info(format!("The {} page has been updated.", page.slug())).send();
+    Ok(see_other("/admin/settings/pages"))
+}
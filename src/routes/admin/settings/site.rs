@@ -0,0 +1,120 @@
+use crate::site_settings::{self, SiteSettings};
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+
+// The editor behind /admin/settings - see crate::site_settings for where
+// these values end up.
+
+/// GET /admin/settings - show the current branding/compliance settings.
+pub async fn site_settings_form(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let settings = site_settings::get(&pool).await.map_err(e500)?;
+
+    let msg_html = crate::utils::flash_messages_html(&flash_messages);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Site settings</title>
+</head>
+<body>
+    {msg_html}
+    <h1>Site settings</h1>
+    <form action="/admin/settings" method="post">
+        <label>Newsletter name
+            <input type="text" name="newsletter_name" value="{newsletter_name}">
+        </label>
+        <br>
+        <label>Logo URL
+            <input type="text" name="logo_url" value="{logo_url}">
+        </label>
+        <br>
+        <label>Accent colour
+            <input type="text" name="accent_color" value="{accent_color}">
+        </label>
+        <br>
+        <label>Footer address (required by CAN-SPAM)
+            <input type="text" name="footer_address" value="{footer_address}">
+        </label>
+        <br>
+        <label>Social links (one URL per line)
+            <textarea name="social_links" rows="4" cols="60">{social_links}</textarea>
+        </label>
+        <br>
+        <label>robots.txt rules (the sitemap line is appended automatically)
+            <textarea name="robots_txt" rows="4" cols="60">{robots_txt}</textarea>
+        </label>
+        <br>
+        <label>
+            <input type="checkbox" name="dark_mode_emails" value="true"{dark_mode_checked}>
+            Dark-mode emails - append a prefers-color-scheme variant to every issue
+        </label>
+        <br>
+        <button type="submit">Save</button>
+    </form>
+    <p><a href="/admin/settings/pages">Edit public pages</a></p>
+    <p><a href="/admin/settings/domain">Custom link domain</a></p>
+    <p><a href="/admin/settings/templates">Email templates</a></p>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+            newsletter_name = htmlescape::encode_attribute(&settings.newsletter_name),
+            logo_url = htmlescape::encode_attribute(&settings.logo_url),
+            accent_color = htmlescape::encode_attribute(&settings.accent_color),
+            footer_address = htmlescape::encode_attribute(&settings.footer_address),
+            social_links = htmlescape::encode_minimal(&settings.social_links),
+            robots_txt = htmlescape::encode_minimal(&settings.robots_txt),
+            dark_mode_checked = if settings.dark_mode_emails {
+                " checked"
+            } else {
+                ""
+            },
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct SiteSettingsForm {
+    newsletter_name: String,
+    logo_url: String,
+    accent_color: String,
+    footer_address: String,
+    social_links: String,
+    robots_txt: String,
+    // checkboxes submit nothing at all when unticked
+    dark_mode_emails: Option<String>,
+}
+
+/// POST /admin/settings - persist the submitted settings.
+#[tracing::instrument(name = "Save the site settings", skip_all)]
+pub async fn save_site_settings(
+    form: web::Form<SiteSettingsForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let form = form.0;
+    site_settings::update(
+        &pool,
+        &SiteSettings {
+            newsletter_name: form.newsletter_name,
+            logo_url: form.logo_url,
+            accent_color: form.accent_color,
+            footer_address: form.footer_address,
+            social_links: form.social_links,
+            robots_txt: form.robots_txt,
+            dark_mode_emails: form.dark_mode_emails.is_some(),
+        },
+    )
+    .await
+    .map_err(e500)?;
+
+    FlashMessage::info("The site settings have been updated.").send();
+    Ok(see_other("/admin/settings"))
+}
@@ -0,0 +1,350 @@
+use crate::authentication::UserId;
+use crate::email_templates::{
+    self, EmailTemplate, COMPLIANCE_FOOTER, CONFIRMATION, CONFIRMATION_LINK_TAG,
+    FOOTER_ADDRESS_TAG, NEWSLETTER_NAME_TAG, REPLY_ACK, UNSUBSCRIBE_LINK_TAG,
+};
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// The email template editor behind /admin/settings/templates: the
+// confirmation email and the compliance footer the delivery worker
+// appends to every issue - see crate::email_templates for the storage
+// side. Each form is prefilled with the built-in copy, so editing always
+// starts from something that works; saving blank fields drops the
+// override and returns to the built-in copy.
+
+// the built-in confirmation copy, as shown before any override is saved.
+// Keep this in sync with the fallback in send_confirmation_email.
+fn built_in_confirmation(newsletter_name: &str) -> EmailTemplate {
+    EmailTemplate {
+        subject: format!("Welcome to {}!", newsletter_name),
+        html_body: format!(
+            "Welcome to {}!<br />\
+               Click <a href=\"{}\">here</a> to confirm your subscription.",
+            newsletter_name, CONFIRMATION_LINK_TAG
+        ),
+        text_body: format!(
+            "Welcome to {}!\nVisit {} to confirm your subscription.",
+            newsletter_name, CONFIRMATION_LINK_TAG
+        ),
+    }
+}
+
+// the same status line both sections show above their form
+fn override_status(overridden: bool) -> &'static str {
+    if overridden {
+        "This copy has been edited. Save all fields blank to return to the built-in copy."
+    } else {
+        "This is the built-in copy - edits are saved as an override."
+    }
+}
+
+// which newsletter the logged-in admin runs (see crate::tenancy)
+async fn get_user_tenant(pool: &PgPool, user_id: Uuid) -> Result<Uuid, sqlx::Error> {
+    let row = sqlx::query!("SELECT tenant_id FROM users WHERE user_id = $1", user_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(row.tenant_id)
+}
+
+/// GET /admin/settings/templates - show the confirmation email copy,
+/// either the saved override or the built-in default.
+pub async fn email_templates_form(
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tenant_id = get_user_tenant(&pool, **user_id).await.map_err(e500)?;
+    let template = email_templates::get(&pool, tenant_id, CONFIRMATION)
+        .await
+        .map_err(e500)?;
+    let overridden = template.is_some();
+    let template = match template {
+        Some(template) => template,
+        None => {
+            let settings = crate::site_settings::get(&pool).await.map_err(e500)?;
+            built_in_confirmation(&settings.newsletter_name)
+        }
+    };
+
+    let footer = email_templates::get(&pool, tenant_id, COMPLIANCE_FOOTER)
+        .await
+        .map_err(e500)?;
+    let footer_overridden = footer.is_some();
+    let footer = footer.unwrap_or_else(email_templates::built_in_compliance_footer);
+
+    // unlike the others there is no built-in copy here - a missing row
+    // means the auto-responder is off
+    let ack = email_templates::get(&pool, tenant_id, REPLY_ACK)
+        .await
+        .map_err(e500)?;
+    let ack_status = if ack.is_some() {
+        "The auto-responder is on. Save all fields blank to switch it off."
+    } else {
+        "The auto-responder is off - saving a subject and both bodies switches it on."
+    };
+    let ack = ack.unwrap_or(EmailTemplate {
+        subject: String::new(),
+        html_body: String::new(),
+        text_body: String::new(),
+    });
+
+    let msg_html = crate::utils::flash_messages_html(&flash_messages);
+
+    let status = override_status(overridden);
+    let footer_status = override_status(footer_overridden);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Email templates</title>
+</head>
+<body>
+    {msg_html}
+    <h1>Email templates</h1>
+    <h2>Confirmation email</h2>
+    <p><i>{status}</i></p>
+    <p>Both bodies must contain the <code>{tag}</code> merge tag - it is
+    replaced with the subscriber's confirmation link at send time. The
+    footer address from the site settings is appended automatically.</p>
+    <form action="/admin/settings/templates" method="post">
+        <label>Subject
+            <input type="text" name="subject" value="{subject}" style="width:100%">
+        </label>
+        <br>
+        <label>HTML body
+            <textarea name="html_body" rows="8" cols="80">{html_body}</textarea>
+        </label>
+        <br>
+        <label>Plain text body
+            <textarea name="text_body" rows="8" cols="80">{text_body}</textarea>
+        </label>
+        <br>
+        <button type="submit">Save</button>
+    </form>
+    <h2>Compliance footer</h2>
+    <p><i>{footer_status}</i></p>
+    <p>Appended to every outgoing issue, below the content. Both bodies
+    must contain the <code>{unsubscribe_tag}</code> and
+    <code>{address_tag}</code> merge tags - they become the recipient's
+    signed unsubscribe link and the physical address from the site
+    settings. <code>{name_tag}</code> is substituted too, if present.</p>
+    <form action="/admin/settings/templates/footer" method="post">
+        <label>HTML body
+            <textarea name="html_body" rows="8" cols="80">{footer_html_body}</textarea>
+        </label>
+        <br>
+        <label>Plain text body
+            <textarea name="text_body" rows="8" cols="80">{footer_text_body}</textarea>
+        </label>
+        <br>
+        <button type="submit">Save</button>
+    </form>
+    <h2>Reply auto-acknowledgement</h2>
+    <p><i>{ack_status}</i></p>
+    <p>Sent automatically when a reader replies to an issue (see the
+    inbound webhook) - at most once per sender per day, and never to
+    auto-generated mail. <code>{name_tag}</code> is substituted in all
+    three fields.</p>
+    <form action="/admin/settings/templates/reply-ack" method="post">
+        <label>Subject
+            <input type="text" name="subject" value="{ack_subject}" style="width:100%">
+        </label>
+        <br>
+        <label>HTML body
+            <textarea name="html_body" rows="8" cols="80">{ack_html_body}</textarea>
+        </label>
+        <br>
+        <label>Plain text body
+            <textarea name="text_body" rows="8" cols="80">{ack_text_body}</textarea>
+        </label>
+        <br>
+        <button type="submit">Save</button>
+    </form>
+    <p><a href="/admin/settings">&lt;- Back to settings</a></p>
+</body>
+</html>"#,
+            tag = htmlescape::encode_minimal(CONFIRMATION_LINK_TAG),
+            subject = htmlescape::encode_attribute(&template.subject),
+            html_body = htmlescape::encode_minimal(&template.html_body),
+            text_body = htmlescape::encode_minimal(&template.text_body),
+            unsubscribe_tag = htmlescape::encode_minimal(UNSUBSCRIBE_LINK_TAG),
+            address_tag = htmlescape::encode_minimal(FOOTER_ADDRESS_TAG),
+            name_tag = htmlescape::encode_minimal(NEWSLETTER_NAME_TAG),
+            footer_html_body = htmlescape::encode_minimal(&footer.html_body),
+            footer_text_body = htmlescape::encode_minimal(&footer.text_body),
+            ack_subject = htmlescape::encode_attribute(&ack.subject),
+            ack_html_body = htmlescape::encode_minimal(&ack.html_body),
+            ack_text_body = htmlescape::encode_minimal(&ack.text_body),
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct EmailTemplateForm {
+    subject: String,
+    html_body: String,
+    text_body: String,
+}
+
+/// POST /admin/settings/templates - save the confirmation copy, or drop
+/// the override when every field is blank.
+#[tracing::instrument(name = "Save the confirmation email template", skip_all)]
+pub async fn save_email_templates(
+    form: web::Form<EmailTemplateForm>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tenant_id = get_user_tenant(&pool, **user_id).await.map_err(e500)?;
+    let form = form.0;
+
+    // blank everything = back to the built-in copy
+    if form.subject.trim().is_empty()
+        && form.html_body.trim().is_empty()
+        && form.text_body.trim().is_empty()
+    {
+        email_templates::delete(&pool, tenant_id, CONFIRMATION)
+            .await
+            .map_err(e500)?;
+        FlashMessage::info("The confirmation email is back to the built-in copy.").send();
+        return Ok(see_other("/admin/settings/templates"));
+    }
+
+    // without the merge tag the email would carry no link at all
+    if !form.html_body.contains(CONFIRMATION_LINK_TAG)
+        || !form.text_body.contains(CONFIRMATION_LINK_TAG)
+    {
+        FlashMessage::error(format!(
+            "Both bodies must contain the {} merge tag.",
+            CONFIRMATION_LINK_TAG
+        ))
+        .send();
+        return Ok(see_other("/admin/settings/templates"));
+    }
+
+    email_templates::upsert(
+        &pool,
+        tenant_id,
+        CONFIRMATION,
+        &EmailTemplate {
+            subject: form.subject,
+            html_body: form.html_body,
+            text_body: form.text_body,
+        },
+    )
+    .await
+    .map_err(e500)?;
+
+    FlashMessage::info("The confirmation email copy has been updated.").send();
+    Ok(see_other("/admin/settings/templates"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ComplianceFooterForm {
+    html_body: String,
+    text_body: String,
+}
+
+/// POST /admin/settings/templates/footer - save the compliance footer
+/// copy, or drop the override when both bodies are blank.
+#[tracing::instrument(name = "Save the compliance footer template", skip_all)]
+pub async fn save_compliance_footer(
+    form: web::Form<ComplianceFooterForm>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tenant_id = get_user_tenant(&pool, **user_id).await.map_err(e500)?;
+    let form = form.0;
+
+    // blank everything = back to the built-in copy
+    if form.html_body.trim().is_empty() && form.text_body.trim().is_empty() {
+        email_templates::delete(&pool, tenant_id, COMPLIANCE_FOOTER)
+            .await
+            .map_err(e500)?;
+        FlashMessage::info("The compliance footer is back to the built-in copy.").send();
+        return Ok(see_other("/admin/settings/templates"));
+    }
+
+    // the whole point of the footer is the unsubscribe link and the
+    // physical address - refuse to save a copy that drops either
+    for tag in [UNSUBSCRIBE_LINK_TAG, FOOTER_ADDRESS_TAG] {
+        if !form.html_body.contains(tag) || !form.text_body.contains(tag) {
+            FlashMessage::error(format!("Both bodies must contain the {} merge tag.", tag)).send();
+            return Ok(see_other("/admin/settings/templates"));
+        }
+    }
+
+    email_templates::upsert(
+        &pool,
+        tenant_id,
+        COMPLIANCE_FOOTER,
+        &EmailTemplate {
+            // a footer has no subject
+            subject: String::new(),
+            html_body: form.html_body,
+            text_body: form.text_body,
+        },
+    )
+    .await
+    .map_err(e500)?;
+
+    FlashMessage::info("The compliance footer copy has been updated.").send();
+    Ok(see_other("/admin/settings/templates"))
+}
+
+/// POST /admin/settings/templates/reply-ack - save the auto-responder
+/// copy (switching it on), or switch it off when every field is blank.
+#[tracing::instrument(name = "Save the reply acknowledgement template", skip_all)]
+pub async fn save_reply_ack(
+    form: web::Form<EmailTemplateForm>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tenant_id = get_user_tenant(&pool, **user_id).await.map_err(e500)?;
+    let form = form.0;
+
+    // blank everything = the auto-responder is off
+    if form.subject.trim().is_empty()
+        && form.html_body.trim().is_empty()
+        && form.text_body.trim().is_empty()
+    {
+        email_templates::delete(&pool, tenant_id, REPLY_ACK)
+            .await
+            .map_err(e500)?;
+        FlashMessage::info("The reply auto-responder has been switched off.").send();
+        return Ok(see_other("/admin/settings/templates"));
+    }
+
+    // a half-filled ack would go out half-empty - require all three
+    if form.subject.trim().is_empty()
+        || form.html_body.trim().is_empty()
+        || form.text_body.trim().is_empty()
+    {
+        FlashMessage::error("The auto-acknowledgement needs a subject and both bodies.").send();
+        return Ok(see_other("/admin/settings/templates"));
+    }
+
+    email_templates::upsert(
+        &pool,
+        tenant_id,
+        REPLY_ACK,
+        &EmailTemplate {
+            subject: form.subject,
+            html_body: form.html_body,
+            text_body: form.text_body,
+        },
+    )
+    .await
+    .map_err(e500)?;
+
+    FlashMessage::info("The reply auto-responder is on.").send();
+    Ok(see_other("/admin/settings/templates"))
+}
@@ -0,0 +1,375 @@
+use crate::utils::{e500, see_other};
+use actix_web::http::header::{ContentDisposition, ContentType, DispositionParam, DispositionType};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+// The subscriber list and the operations on it. Single-subscriber
+// deletes come from the search page; everything that works on a
+// selection - tagging, unsubscribing, deleting, exporting - goes through
+// the bulk endpoint below.
+
+// the page shows the newest slice of the list - anything older is a
+// search away, and a page with fifty thousand checkboxes helps nobody
+const LIST_PAGE_SIZE: i64 = 500;
+
+// bulk work is done in slices so the log shows progress on a large
+// selection instead of going quiet until the commit
+const BULK_CHUNK_SIZE: usize = 500;
+
+/// GET /admin/subscribers - the subscriber list, newest first, with a
+/// checkbox per row and a bulk-action bar at the bottom.
+pub async fn subscriber_list(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let msg_html = crate::utils::flash_messages_html(&flash_messages);
+
+    let total = sqlx::query!(
+        r#"SELECT COUNT(*) as "count!" FROM subscriptions WHERE deleted_at IS NULL"#
+    )
+    .fetch_one(pool.get_ref())
+    .await
+    .map_err(e500)?
+    .count;
+
+    let subscribers = sqlx::query!(
+        r#"
+        SELECT id, email, name, status as "status!", subscribed_at
+        FROM subscriptions
+        WHERE deleted_at IS NULL
+        ORDER BY subscribed_at DESC
+        LIMIT $1
+        "#,
+        LIST_PAGE_SIZE,
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for subscriber in &subscribers {
+        writeln!(
+            rows_html,
+            r#"<tr>
+        <td><input type="checkbox" name="subscriber_id" value="{id}"></td>
+        <td>{name}</td>
+        <td><a href="/admin/subscribers/{id}">{email}</a></td>
+        <td>{status}</td>
+        <td>{subscribed_at}</td>
+    </tr>"#,
+            id = subscriber.id,
+            name = htmlescape::encode_minimal(&subscriber.name),
+            email = htmlescape::encode_minimal(&subscriber.email),
+            status = subscriber.status,
+            subscribed_at = subscriber.subscribed_at.format("%Y-%m-%d"),
+        )
+        .unwrap();
+    }
+
+    let truncation_note = if total > subscribers.len() as i64 {
+        format!(
+            "<p><i>Showing the newest {} of {} subscribers - use \
+             <a href=\"/admin/search\">search</a> to reach the rest.</i></p>",
+            subscribers.len(),
+            total,
+        )
+    } else {
+        String::new()
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Subscribers</title>
+</head>
+<body>
+    {msg_html}
+    <h1>Subscribers</h1>
+    {truncation_note}
+    <form action="/admin/subscribers/bulk" method="post">
+        <table border="1" cellpadding="4">
+            <tr>
+                <th><input type="checkbox" onclick="
+                    for (const box of document.querySelectorAll('input[name=subscriber_id]'))
+                        box.checked = this.checked;
+                "></th>
+                <th>Name</th><th>Email</th><th>Status</th><th>Subscribed</th>
+            </tr>
+            {rows_html}
+        </table>
+        <br>
+        <label>With the selected subscribers:
+            <select name="bulk_action">
+                <option value="tag">Add tag</option>
+                <option value="untag">Remove tag</option>
+                <option value="unsubscribe">Unsubscribe</option>
+                <option value="delete">Move to trash</option>
+                <option value="export">Export as CSV</option>
+            </select>
+        </label>
+        <input type="text" name="tag" placeholder="tag (for add/remove tag)">
+        <button type="submit">Apply</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+enum BulkAction {
+    Tag,
+    Untag,
+    Unsubscribe,
+    Delete,
+    Export,
+}
+
+/// POST /admin/subscribers/bulk - apply one action to a selection. The
+/// mutating actions run inside a single transaction: a failure halfway
+/// through a big selection rolls the whole thing back rather than
+/// leaving it half-tagged.
+#[tracing::instrument(name = "Bulk subscriber action", skip_all)]
+pub async fn bulk_subscriber_action(
+    body: web::Form<Vec<(String, String)>>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // repeated checkbox fields don't fit a struct through serde_urlencoded,
+    // so the pairs come in raw and get sorted out by hand
+    let mut subscriber_ids = Vec::new();
+    let mut action = None;
+    let mut tag = None;
+    for (name, value) in body.into_inner() {
+        match name.as_str() {
+            "subscriber_id" => match value.parse::<Uuid>() {
+                Ok(id) => subscriber_ids.push(id),
+                Err(_) => {
+                    FlashMessage::error("The selection contained an invalid subscriber id.")
+                        .send();
+                    return Ok(see_other("/admin/subscribers"));
+                }
+            },
+            "bulk_action" => action = Some(value),
+            "tag" => tag = Some(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    let action = match action.as_deref() {
+        Some("tag") => BulkAction::Tag,
+        Some("untag") => BulkAction::Untag,
+        Some("unsubscribe") => BulkAction::Unsubscribe,
+        Some("delete") => BulkAction::Delete,
+        Some("export") => BulkAction::Export,
+        _ => {
+            FlashMessage::error("That is not a recognised bulk action.").send();
+            return Ok(see_other("/admin/subscribers"));
+        }
+    };
+    if subscriber_ids.is_empty() {
+        FlashMessage::error("No subscribers were selected.").send();
+        return Ok(see_other("/admin/subscribers"));
+    }
+    let tag = tag.filter(|t| !t.is_empty());
+    let tag = match (&action, tag) {
+        (BulkAction::Tag | BulkAction::Untag, None) => {
+            FlashMessage::error("Adding or removing a tag needs a tag name.").send();
+            return Ok(see_other("/admin/subscribers"));
+        }
+        (_, tag) => tag.unwrap_or_default(),
+    };
+
+    // the export doesn't mutate anything - it short-circuits before the
+    // transaction and answers with the file itself
+    if let BulkAction::Export = action {
+        let csv = export_selection(&pool, &subscriber_ids).await.map_err(e500)?;
+        return Ok(HttpResponse::Ok()
+            .content_type("text/csv")
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename("subscribers.csv".to_string())],
+            })
+            .body(csv));
+    }
+
+    let selected = subscriber_ids.len();
+    let mut affected: u64 = 0;
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    for (index, chunk) in subscriber_ids.chunks(BULK_CHUNK_SIZE).enumerate() {
+        affected += match action {
+            BulkAction::Tag => {
+                // selecting from subscriptions rather than unnesting the ids
+                // directly keeps a stale id in the form from tripping the
+                // foreign key
+                sqlx::query!(
+                    r#"
+                    INSERT INTO subscriber_tags (subscriber_id, tag)
+                    SELECT id, $2
+                    FROM subscriptions
+                    WHERE id = ANY($1) AND deleted_at IS NULL
+                    ON CONFLICT (subscriber_id, tag) DO NOTHING
+                    "#,
+                    chunk,
+                    tag,
+                )
+                .execute(&mut *transaction)
+                .await
+                .map_err(e500)?
+                .rows_affected()
+            }
+            BulkAction::Untag => sqlx::query!(
+                r#"DELETE FROM subscriber_tags WHERE subscriber_id = ANY($1) AND tag = $2"#,
+                chunk,
+                tag,
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(e500)?
+            .rows_affected(),
+            BulkAction::Unsubscribe => sqlx::query!(
+                r#"
+                UPDATE subscriptions
+                SET status = 'unsubscribed'
+                WHERE id = ANY($1) AND status != 'unsubscribed' AND deleted_at IS NULL
+                "#,
+                chunk,
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(e500)?
+            .rows_affected(),
+            BulkAction::Delete => {
+                // the same two steps as a single delete: stamp the rows,
+                // then drop their queued deliveries
+                let emails: Vec<String> = sqlx::query!(
+                    r#"
+                    UPDATE subscriptions
+                    SET deleted_at = now()
+                    WHERE id = ANY($1) AND deleted_at IS NULL
+                    RETURNING email
+                    "#,
+                    chunk,
+                )
+                .fetch_all(&mut *transaction)
+                .await
+                .map_err(e500)?
+                .into_iter()
+                .map(|r| r.email)
+                .collect();
+                sqlx::query!(
+                    r#"DELETE FROM issue_delivery_queue WHERE subscriber_email = ANY($1)"#,
+                    &emails,
+                )
+                .execute(&mut *transaction)
+                .await
+                .map_err(e500)?;
+                emails.len() as u64
+            }
+            BulkAction::Export => unreachable!("handled above"),
+        };
+        tracing::info!(
+            processed = (index * BULK_CHUNK_SIZE + chunk.len()).min(selected),
+            selected,
+            "Applying a bulk subscriber action",
+        );
+    }
+    transaction.commit().await.map_err(e500)?;
+
+    FlashMessage::info(match action {
+        BulkAction::Tag => format!("Tagged {} subscriber(s) with '{}'.", affected, tag),
+        BulkAction::Untag => format!("Removed '{}' from {} subscriber(s).", tag, affected),
+        BulkAction::Unsubscribe => format!("Unsubscribed {} subscriber(s).", affected),
+        BulkAction::Delete => format!(
+            "Moved {} subscriber(s) to the trash - restore them from /admin/trash if this was a mistake.",
+            affected,
+        ),
+        BulkAction::Export => unreachable!("handled above"),
+    })
+    .send();
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(skip_all)]
+async fn export_selection(
+    pool: &PgPool,
+    subscriber_ids: &[Uuid],
+) -> Result<Vec<u8>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT email, name, status as "status!", subscribed_at, premium
+        FROM subscriptions
+        WHERE id = ANY($1) AND deleted_at IS NULL
+        ORDER BY subscribed_at DESC
+        "#,
+        subscriber_ids,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["email", "name", "status", "subscribed_at", "premium"])?;
+    for row in &rows {
+        writer.write_record([
+            row.email.as_str(),
+            row.name.as_str(),
+            row.status.as_str(),
+            &row.subscribed_at.to_rfc3339(),
+            if row.premium { "true" } else { "false" },
+        ])?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+/// POST /admin/subscribers/{id}/delete - move a subscriber to the trash.
+/// A soft delete: the row is stamped with deleted_at and every live-path
+/// query stops seeing it, but /admin/trash can bring it back until the
+/// retention period runs out. Any deliveries already queued for the
+/// address are dropped in the same transaction - restoring the
+/// subscriber later does not resurrect them.
+#[tracing::instrument(name = "Delete a subscriber", skip(pool), fields(subscriber_id=%path))]
+pub async fn delete_subscriber(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = path.into_inner();
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    let deleted = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET deleted_at = now()
+        WHERE id = $1 AND deleted_at IS NULL
+        RETURNING email
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(e500)?;
+    let email = match deleted {
+        Some(row) => row.email,
+        None => {
+            FlashMessage::error("That subscriber no longer exists.").send();
+            return Ok(see_other("/admin/dashboard"));
+        }
+    };
+    sqlx::query!(
+        r#"DELETE FROM issue_delivery_queue WHERE subscriber_email = $1"#,
+        email,
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+
+    FlashMessage::info(format!(
+        "{} has been moved to the trash - restore them from /admin/trash if this was a mistake.",
+        email,
+    ))
+    .send();
+    Ok(see_other("/admin/dashboard"))
+}
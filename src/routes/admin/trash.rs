@@ -0,0 +1,343 @@
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::fmt::Write;
+use uuid::Uuid;
+
+// The recoverable side of deleting things. "Delete" buttons elsewhere
+// only stamp deleted_at; the rows land here, invisible to every live
+// query, until someone restores them or the retention period runs out.
+// Purging is the real DELETE - there is no way back from it.
+
+/// How long trashed rows stay restorable, in days - a wrapper so actix
+/// can hand the configured value to the handlers by type.
+pub struct TrashRetention(pub i32);
+
+/// GET /admin/trash - everything awaiting the sweep, with restore and
+/// purge buttons per row.
+pub async fn trash_page(
+    pool: web::Data<PgPool>,
+    retention: web::Data<TrashRetention>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    // the retention sweep is lazy - it runs whenever the trash is opened.
+    // Nothing reads trashed rows in the meantime (every live query
+    // filters them out), so there is no need for a background job; a row
+    // just lingers a little past its deadline if nobody looks
+    if let Err(e) = sweep_expired(&pool, retention.0).await {
+        tracing::warn!(
+            error.cause_chain = ?e,
+            "Failed to sweep expired rows out of the trash",
+        );
+    }
+
+    let msg_html = crate::utils::flash_messages_html(&flash_messages);
+
+    let subscribers = sqlx::query!(
+        r#"
+        SELECT id, email, name, deleted_at as "deleted_at!"
+        FROM subscriptions
+        WHERE deleted_at IS NOT NULL
+        ORDER BY deleted_at DESC
+        "#,
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .map_err(e500)?;
+
+    let mut subscribers_html = String::new();
+    for subscriber in &subscribers {
+        writeln!(
+            subscribers_html,
+            r#"<li>{1} &lt;{2}&gt; - deleted {3}
+    <form action="/admin/trash/subscribers/{0}/restore" method="post" style="display:inline">
+        <button type="submit">Restore</button>
+    </form>
+    <form action="/admin/trash/subscribers/{0}/purge" method="post" style="display:inline">
+        <button type="submit">Purge now</button>
+    </form></li>"#,
+            subscriber.id,
+            htmlescape::encode_minimal(&subscriber.name),
+            htmlescape::encode_minimal(&subscriber.email),
+            subscriber.deleted_at.format("%Y-%m-%d %H:%M UTC"),
+        )
+        .unwrap();
+    }
+    if subscribers.is_empty() {
+        subscribers_html.push_str("<li>No subscribers in the trash</li>");
+    }
+
+    let drafts = sqlx::query!(
+        r#"
+        SELECT newsletter_draft_id, title, deleted_at as "deleted_at!"
+        FROM newsletter_drafts
+        WHERE deleted_at IS NOT NULL
+        ORDER BY deleted_at DESC
+        "#,
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .map_err(e500)?;
+
+    let mut drafts_html = String::new();
+    for draft in &drafts {
+        writeln!(
+            drafts_html,
+            r#"<li>{1} - deleted {2}
+    <form action="/admin/trash/drafts/{0}/restore" method="post" style="display:inline">
+        <button type="submit">Restore</button>
+    </form>
+    <form action="/admin/trash/drafts/{0}/purge" method="post" style="display:inline">
+        <button type="submit">Purge now</button>
+    </form></li>"#,
+            draft.newsletter_draft_id,
+            htmlescape::encode_minimal(if draft.title.is_empty() {
+                "(untitled)"
+            } else {
+                &draft.title
+            }),
+            draft.deleted_at.format("%Y-%m-%d %H:%M UTC"),
+        )
+        .unwrap();
+    }
+    if drafts.is_empty() {
+        drafts_html.push_str("<li>No drafts in the trash</li>");
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Trash</title>
+</head>
+<body>
+    {msg_html}
+    <h1>Trash</h1>
+    <p>
+        Deleted items can be restored for {retention_days} day(s) after
+        deletion - after that they are purged for good the next time this
+        page is opened.
+    </p>
+    <h2>Subscribers</h2>
+    <ul>
+        {subscribers_html}
+    </ul>
+    <h2>Drafts</h2>
+    <ul>
+        {drafts_html}
+    </ul>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+            retention_days = retention.0,
+        )))
+}
+
+/// POST /admin/trash/subscribers/{id}/restore - bring a subscriber back.
+/// Their status, tags and premium flag are exactly as they were; only
+/// deliveries that were queued when they were deleted are gone.
+#[tracing::instrument(name = "Restore a subscriber from the trash", skip(pool), fields(subscriber_id=%path))]
+pub async fn restore_subscriber(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = path.into_inner();
+    let restored = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET deleted_at = NULL
+        WHERE id = $1 AND deleted_at IS NOT NULL
+        RETURNING email
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    match restored {
+        Some(row) => FlashMessage::info(format!("{} has been restored.", row.email)).send(),
+        None => FlashMessage::error("That subscriber is not in the trash.").send(),
+    }
+    Ok(see_other("/admin/trash"))
+}
+
+/// POST /admin/trash/subscribers/{id}/purge - the real DELETE, without
+/// waiting for the retention period.
+#[tracing::instrument(name = "Purge a subscriber from the trash", skip(pool), fields(subscriber_id=%path))]
+pub async fn purge_subscriber(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = path.into_inner();
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    let purged = purge_subscriber_rows(&mut transaction, &[subscriber_id])
+        .await
+        .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+    if purged == 1 {
+        FlashMessage::info("The subscriber has been permanently deleted.").send();
+    } else {
+        FlashMessage::error("That subscriber is not in the trash.").send();
+    }
+    Ok(see_other("/admin/trash"))
+}
+
+/// POST /admin/trash/drafts/{id}/restore - put a draft back on the
+/// drafts list, content and version intact.
+#[tracing::instrument(name = "Restore a draft from the trash", skip(pool), fields(draft_id=%path))]
+pub async fn restore_draft(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let draft_id = path.into_inner();
+    let outcome = sqlx::query!(
+        r#"
+        UPDATE newsletter_drafts
+        SET deleted_at = NULL
+        WHERE newsletter_draft_id = $1 AND deleted_at IS NOT NULL
+        "#,
+        draft_id,
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    if outcome.rows_affected() == 1 {
+        FlashMessage::info("The draft has been restored.").send();
+    } else {
+        FlashMessage::error("That draft is not in the trash.").send();
+    }
+    Ok(see_other("/admin/trash"))
+}
+
+/// POST /admin/trash/drafts/{id}/purge - the real DELETE, without
+/// waiting for the retention period.
+#[tracing::instrument(name = "Purge a draft from the trash", skip(pool), fields(draft_id=%path))]
+pub async fn purge_draft(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let draft_id = path.into_inner();
+    let outcome = sqlx::query!(
+        r#"
+        DELETE FROM newsletter_drafts
+        WHERE newsletter_draft_id = $1 AND deleted_at IS NOT NULL
+        "#,
+        draft_id,
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    if outcome.rows_affected() == 1 {
+        FlashMessage::info("The draft has been permanently deleted.").send();
+    } else {
+        FlashMessage::error("That draft is not in the trash.").send();
+    }
+    Ok(see_other("/admin/trash"))
+}
+
+// permanently delete everything that has sat in the trash longer than
+// the retention period
+#[tracing::instrument(skip(pool))]
+async fn sweep_expired(pool: &PgPool, retention_days: i32) -> Result<(), anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+
+    let expired: Vec<Uuid> = sqlx::query!(
+        r#"
+        SELECT id
+        FROM subscriptions
+        WHERE deleted_at < now() - make_interval(days => $1)
+        "#,
+        retention_days,
+    )
+    .fetch_all(&mut *transaction)
+    .await?
+    .into_iter()
+    .map(|r| r.id)
+    .collect();
+    if !expired.is_empty() {
+        let purged = purge_subscriber_rows(&mut transaction, &expired).await?;
+        tracing::info!(purged, "Swept expired subscribers out of the trash");
+    }
+
+    let drafts = sqlx::query!(
+        r#"
+        DELETE FROM newsletter_drafts
+        WHERE deleted_at < now() - make_interval(days => $1)
+        "#,
+        retention_days,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    if drafts.rows_affected() > 0 {
+        tracing::info!(
+            purged = drafts.rows_affected(),
+            "Swept expired drafts out of the trash",
+        );
+    }
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+// the real DELETE behind both the purge button and the sweep. Several
+// tables reference subscriptions(id) without ON DELETE CASCADE, so the
+// referencing rows go first - keep this list in sync with any migration
+// that adds such a foreign key
+async fn purge_subscriber_rows(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_ids: &[Uuid],
+) -> Result<u64, sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = ANY($1)"#,
+        subscriber_ids,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"DELETE FROM poll_votes WHERE subscriber_id = ANY($1)"#,
+        subscriber_ids,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"DELETE FROM issue_feedback WHERE subscriber_id = ANY($1)"#,
+        subscriber_ids,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"DELETE FROM email_change_requests WHERE subscriber_id = ANY($1)"#,
+        subscriber_ids,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"DELETE FROM email_tracking_events WHERE subscriber_id = ANY($1)"#,
+        subscriber_ids,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"DELETE FROM reengagement_campaigns WHERE subscriber_id = ANY($1)"#,
+        subscriber_ids,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    // only rows that are actually in the trash - a purge of a live id is
+    // a no-op rather than an accident
+    let outcome = sqlx::query!(
+        r#"
+        DELETE FROM subscriptions
+        WHERE id = ANY($1) AND deleted_at IS NOT NULL
+        "#,
+        subscriber_ids,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(outcome.rows_affected())
+}
@@ -0,0 +1,61 @@
+use crate::authentication::UserId;
+use crate::session_state;
+use crate::utils::{e500, see_other};
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+// Offboarding: flipping `users.active` off and sweeping the account's
+// sessions in one go. The subscriber-facing API authenticates with the
+// shared server token, not per-user keys, so there is nothing further to
+// revoke there - should per-user keys ever arrive, this is where they die.
+
+#[derive(serde::Deserialize)]
+pub struct DeactivateUserForm {
+    username: String,
+}
+
+/// POST /admin/users/deactivate - lock a user out, sessions included.
+#[tracing::instrument(name = "Deactivate a user", skip(form, pool))]
+pub async fn deactivate_user(
+    form: web::Form<DeactivateUserForm>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let target = sqlx::query!(
+        "SELECT user_id FROM users WHERE username = $1 AND active",
+        form.username.trim(),
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    let Some(target) = target else {
+        FlashMessage::error("No active user with that username exists.").send();
+        return Ok(see_other("/admin/dashboard"));
+    };
+    // locking yourself out of the admin helps nobody
+    if target.user_id == **user_id {
+        FlashMessage::error("You cannot deactivate your own account.").send();
+        return Ok(see_other("/admin/dashboard"));
+    }
+
+    sqlx::query!(
+        "UPDATE users SET active = false WHERE user_id = $1",
+        target.user_id,
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    // every session goes - `keep: None` means no exceptions
+    session_state::revoke_other_sessions(&pool, target.user_id, None)
+        .await
+        .map_err(e500)?;
+
+    FlashMessage::info(format!(
+        "'{}' has been deactivated and their sessions revoked.",
+        form.username.trim()
+    ))
+    .send();
+    Ok(see_other("/admin/dashboard"))
+}
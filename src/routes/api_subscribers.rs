@@ -0,0 +1,223 @@
+use crate::clock::Clock;
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::email_client::EmailClient;
+use crate::routes::subscriptions::{
+    generate_subscription_token, send_confirmation_email, store_token,
+    CONFIRMATION_LINK_VALIDITY_DAYS,
+};
+use crate::signed_link::{LinkSigner, SUBSCRIPTION_CONFIRMATION};
+use crate::site_settings;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::e500;
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+// the shared secret guarding /api/v1 - a newtype, so app_data can't hand
+// out some other secret by accident. None means the API is switched off.
+pub struct ApiKey(pub Option<Secret<String>>);
+
+// the JSON body Zapier/Make-style integrations POST at us. Sources that
+// have already double-opted the reader in set `already_confirmed` so we
+// don't ask them a second time.
+#[derive(serde::Deserialize)]
+pub struct ApiSubscriberRequest {
+    email: String,
+    name: String,
+    #[serde(default)]
+    already_confirmed: bool,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+// POST /api/v1/subscribers
+#[tracing::instrument(
+    name = "Adding a subscriber via the API",
+    skip_all,
+    fields(subscriber_email = %body.email)
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_subscriber(
+    request: HttpRequest,
+    body: web::Json<ApiSubscriberRequest>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    link_signer: web::Data<LinkSigner>,
+    clock: web::Data<dyn Clock>,
+    api_key: web::Data<ApiKey>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // no key configured means the API simply doesn't exist
+    let Some(expected_key) = &api_key.0 else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let provided_key = request
+        .headers()
+        .get("X-Api-Key")
+        .and_then(|value| value.to_str().ok());
+    // compared without short-circuiting, so response times don't reveal
+    // how much of a guessed key was right
+    let authorized = provided_key.is_some_and(|provided| {
+        crate::authentication::constant_time_eq(
+            provided.as_bytes(),
+            expected_key.expose_secret().as_bytes(),
+        )
+    });
+    if !authorized {
+        // and a missing/wrong key costs the same as a failed login would
+        crate::authentication::spend_dummy_verification()
+            .await
+            .map_err(e500)?;
+        return Ok(HttpResponse::Unauthorized()
+            .json(serde_json::json!({ "error": "Invalid or missing X-Api-Key header." })));
+    }
+
+    let body = body.into_inner();
+    // the same validation the public form goes through
+    let new_subscriber = match parse_subscriber(&body) {
+        Ok(new_subscriber) => new_subscriber,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e })));
+        }
+    };
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+
+    let status = if body.already_confirmed {
+        "confirmed"
+    } else {
+        "pending_confirmation"
+    };
+    let subscriber_id =
+        match insert_api_subscriber(&mut transaction, &new_subscriber, status, clock.now()).await {
+            Ok(subscriber_id) => subscriber_id,
+            // a duplicate email is the integration re-syncing, not a bug -
+            // tell it so instead of a 500
+            Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("23505") => {
+                return Ok(HttpResponse::Conflict()
+                    .json(serde_json::json!({ "error": "This email address is already subscribed." })));
+            }
+            Err(e) => {
+                return Err(e500(
+                    anyhow::Error::from(e).context("Failed to insert the subscriber"),
+                ));
+            }
+        };
+
+    store_tags(&mut transaction, subscriber_id, &body.tags)
+        .await
+        .context("Failed to store the subscriber's tags")
+        .map_err(e500)?;
+
+    // unconfirmed signups still get the usual double-opt-in email
+    if !body.already_confirmed {
+        let subscription_token = generate_subscription_token();
+        store_token(&mut transaction, subscriber_id, &subscription_token)
+            .await
+            .context("Failed to store the confirmation token")
+            .map_err(e500)?;
+
+        let settings = site_settings::get(&pool)
+            .await
+            .context("Failed to load the site settings")
+            .map_err(e500)?;
+        let signed_params = link_signer.query_fragment(
+            subscriber_id,
+            SUBSCRIPTION_CONFIRMATION,
+            clock.now() + chrono::Duration::days(CONFIRMATION_LINK_VALIDITY_DAYS),
+        );
+        // API-created subscribers belong to the default tenant
+        let template = crate::email_templates::get(
+            &pool,
+            crate::tenancy::DEFAULT_TENANT_ID,
+            crate::email_templates::CONFIRMATION,
+        )
+        .await
+        .context("Failed to load the confirmation email template")
+        .map_err(e500)?;
+        send_confirmation_email(
+            &email_client,
+            new_subscriber,
+            &base_url.0,
+            &subscription_token,
+            &signed_params,
+            &settings,
+            template.as_ref(),
+        )
+        .await
+        .context("Failed to send the confirmation email")
+        .map_err(e500)?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the new subscriber")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Created().json(serde_json::json!({
+        "id": subscriber_id,
+        "status": status,
+    })))
+}
+
+fn parse_subscriber(body: &ApiSubscriberRequest) -> Result<NewSubscriber, String> {
+    let name = SubscriberName::parse(body.name.clone())?;
+    let email = SubscriberEmail::parse(body.email.clone())?;
+    Ok(NewSubscriber { email, name })
+}
+
+// like subscriptions::insert_subscriber, but the caller picks the status -
+// integrations vouch for consent the public form can't assume
+async fn insert_api_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    new_subscriber: &NewSubscriber,
+    status: &str,
+    subscribed_at: chrono::DateTime<chrono::Utc>,
+) -> Result<Uuid, sqlx::Error> {
+    let subscriber_id = Uuid::new_v4();
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, acquisition_source)
+        VALUES ($1, $2, $3, $4, $5, 'api')
+        "#,
+        subscriber_id,
+        new_subscriber.email.as_ref(),
+        new_subscriber.name.as_ref(),
+        subscribed_at,
+        status,
+    );
+    transaction.execute(query).await?;
+    Ok(subscriber_id)
+}
+
+async fn store_tags(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    tags: &[String],
+) -> Result<(), sqlx::Error> {
+    for tag in tags {
+        // skip anything the integration left blank
+        let tag = tag.trim();
+        if tag.is_empty() {
+            continue;
+        }
+        let query = sqlx::query!(
+            r#"
+            INSERT INTO subscriber_tags (subscriber_id, tag)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+            subscriber_id,
+            tag,
+        );
+        transaction.execute(query).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,634 @@
+use actix_web::http::header::{self, ETag, EntityTag, HttpDate, LastModified};
+use actix_web::{http::header::ContentType, web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::fmt::Write;
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+use crate::clock::Clock;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::signed_link::{LinkSigner, ARCHIVE_ACCESS};
+use crate::site_settings;
+use crate::utils::{e500, see_other};
+
+// Public, read-only views over published newsletter issues.
+// Feed readers poll these aggressively, so we hand out validators
+// (ETag + Last-Modified) and answer with an empty 304 when nothing changed.
+
+// ?tag= narrows the archive (and the RSS feed) to one category
+#[derive(serde::Deserialize)]
+pub struct ArchiveQueryParams {
+    tag: Option<String>,
+}
+
+/// GET /archive - a list of every published issue with links to each one,
+/// optionally filtered to a single tag.
+#[tracing::instrument(name = "View the newsletter archive", skip(request, pool, query))]
+pub async fn archive(
+    request: HttpRequest,
+    query: web::Query<ArchiveQueryParams>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tag_filter = query.tag.as_deref().map(str::trim).filter(|t| !t.is_empty());
+    let issues = get_published_issues(&pool, tag_filter).await.map_err(e500)?;
+    let settings = site_settings::get(&pool).await.map_err(e500)?;
+
+    // the etag covers every issue id + publication timestamp, so publishing
+    // (or re-publishing) anything invalidates cached copies of the list -
+    // the branding goes in too, since it is part of the rendered page
+    let mut hasher = Sha256::new();
+    for issue in &issues {
+        hasher.update(issue.newsletter_issue_id.as_bytes());
+        hasher.update(issue.published_at.to_rfc3339().as_bytes());
+        for tag in &issue.tags {
+            hasher.update(tag.as_bytes());
+        }
+        hasher.update([issue.premium_only as u8]);
+    }
+    // a filtered and an unfiltered list must never share a cache entry
+    hasher.update(tag_filter.unwrap_or("").as_bytes());
+    hasher.update(settings.newsletter_name.as_bytes());
+    hasher.update(settings.logo_url.as_bytes());
+    hasher.update(settings.accent_color.as_bytes());
+    hasher.update(settings.footer_address.as_bytes());
+    hasher.update(settings.social_links.as_bytes());
+    let etag = EntityTag::new_strong(hex::encode(hasher.finalize()));
+
+    // the list was last modified when the newest issue went out
+    let last_modified = issues.first().map(|i| to_http_timestamp(i.published_at));
+
+    if is_not_modified(&request, &etag, last_modified) {
+        return Ok(not_modified_response(etag, last_modified));
+    }
+
+    let mut issue_list_html = String::new();
+    for issue in &issues {
+        // each tag links to the filtered view of the archive
+        let mut tags_html = String::new();
+        for tag in &issue.tags {
+            write!(
+                tags_html,
+                r#" <a href="/archive?tag={}">[{}]</a>"#,
+                urlencoding::encode(tag),
+                htmlescape::encode_minimal(tag),
+            )
+            .unwrap();
+        }
+        writeln!(
+            issue_list_html,
+            r#"<li><a href="/archive/{}">{}</a> - {}{}{}</li>"#,
+            issue.newsletter_issue_id,
+            htmlescape::encode_minimal(&issue.title),
+            issue.published_at.format("%Y-%m-%d"),
+            tags_html,
+            if issue.premium_only {
+                " <em>(premium)</em>"
+            } else {
+                ""
+            },
+        )
+        .unwrap();
+    }
+    let filter_html = match tag_filter {
+        Some(tag) => format!(
+            r#"<p>Showing issues tagged "{}" - <a href="/archive">show all</a></p>"#,
+            htmlescape::encode_minimal(tag)
+        ),
+        None => String::new(),
+    };
+
+    // the operator's branding (see /admin/settings)
+    let name = htmlescape::encode_minimal(&settings.newsletter_name);
+    let logo_html = if settings.logo_url.is_empty() {
+        String::new()
+    } else {
+        format!(
+            r#"<img src="{}" alt="{}" style="max-height: 4em;">"#,
+            htmlescape::encode_attribute(&settings.logo_url),
+            htmlescape::encode_attribute(&settings.newsletter_name),
+        )
+    };
+    let mut footer_html = String::new();
+    for link in settings.social_links_list() {
+        let link = htmlescape::encode_attribute(link);
+        write!(footer_html, r#"<a href="{link}">{link}</a> "#).unwrap();
+    }
+    if !settings.footer_address.is_empty() {
+        write!(
+            footer_html,
+            "<address>{}</address>",
+            htmlescape::encode_minimal(&settings.footer_address)
+        )
+        .unwrap();
+    }
+
+    let mut response = HttpResponse::Ok();
+    response.content_type(ContentType::html());
+    response.insert_header(ETag(etag));
+    if let Some(last_modified) = last_modified {
+        response.insert_header(LastModified(HttpDate::from(last_modified)));
+    }
+    Ok(response.body(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>{name} - archive</title>
+    <style>h1 {{ color: {accent}; }}</style>
+</head>
+<body>
+    {logo_html}
+    <h1>{name} - archive</h1>
+    {filter_html}
+    <ul>
+        {issue_list_html}
+    </ul>
+    <footer>{footer_html}</footer>
+</body>
+</html>"#,
+        accent = htmlescape::encode_minimal(&settings.accent_color),
+    )))
+}
+
+// how much of a premium issue everyone gets to read
+const FREE_PREVIEW_PARAGRAPHS: usize = 2;
+
+// how long an emailed reading link stays clickable
+const ARCHIVE_ACCESS_LINK_VALIDITY_DAYS: i64 = 7;
+
+// the signed parameters of an emailed reading link - all optional, the
+// plain (locked) archive url carries none of them. `requested` is just
+// the post-redirect banner flag
+#[derive(serde::Deserialize)]
+pub struct ArchiveAccessParams {
+    subscriber_id: Option<Uuid>,
+    expires_at: Option<i64>,
+    purpose: Option<String>,
+    key_version: Option<u32>,
+    tag: Option<String>,
+    requested: Option<bool>,
+}
+
+/// GET /archive/{issue_id} - the full html content of a single issue.
+/// Premium issues show a preview unless the url carries a valid signed
+/// reading link (see `request_archive_access`).
+#[tracing::instrument(name = "View an archived issue", skip_all)]
+pub async fn archive_issue(
+    request: HttpRequest,
+    path: web::Path<Uuid>,
+    query: web::Query<ArchiveAccessParams>,
+    pool: web::Data<PgPool>,
+    link_signer: web::Data<LinkSigner>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = path.into_inner();
+
+    let issue = match get_published_issue(&pool, issue_id).await.map_err(e500)? {
+        Some(issue) => issue,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let unlocked =
+        !issue.premium_only || verify_reading_link(&query, &link_signer, clock.now(), &pool).await;
+
+    // issues are immutable once published - content + timestamp pins the
+    // etag. Locked and unlocked renderings must never share a cache entry
+    let mut hasher = Sha256::new();
+    hasher.update(issue.title.as_bytes());
+    hasher.update(issue.html_content.as_bytes());
+    hasher.update(issue.published_at.to_rfc3339().as_bytes());
+    hasher.update([unlocked as u8, query.requested.unwrap_or(false) as u8]);
+    let etag = EntityTag::new_strong(hex::encode(hasher.finalize()));
+
+    let last_modified = Some(to_http_timestamp(issue.published_at));
+
+    if is_not_modified(&request, &etag, last_modified) {
+        return Ok(not_modified_response(etag, last_modified));
+    }
+
+    let body = if unlocked {
+        issue.html_content
+    } else {
+        paywall_page(&issue, issue_id, query.requested.unwrap_or(false))
+    };
+
+    let mut response = HttpResponse::Ok();
+    response.content_type(ContentType::html());
+    response.insert_header(ETag(etag));
+    if let Some(last_modified) = last_modified {
+        response.insert_header(LastModified(HttpDate::from(last_modified)));
+    }
+    Ok(response.body(body))
+}
+
+// a reading link is good if all its parameters are present, the signature
+// checks out for the archive-access purpose, and the subscriber it names
+// is still paying
+async fn verify_reading_link(
+    query: &ArchiveAccessParams,
+    link_signer: &LinkSigner,
+    now: DateTime<Utc>,
+    pool: &PgPool,
+) -> bool {
+    let (Some(subscriber_id), Some(expires_at), Some(purpose), Some(key_version), Some(tag)) = (
+        query.subscriber_id,
+        query.expires_at,
+        query.purpose.as_deref(),
+        query.key_version,
+        query.tag.as_deref(),
+    ) else {
+        return false;
+    };
+    if let Err(e) = link_signer.verify(
+        subscriber_id,
+        expires_at,
+        purpose,
+        key_version,
+        tag,
+        ARCHIVE_ACCESS,
+        now,
+    ) {
+        tracing::warn!(
+            error.cause_chain = ?e,
+            "Rejected an archive reading link"
+        );
+        return false;
+    }
+    // a cancelled subscription means the links they were mailed stop
+    // working too, without waiting for them to expire
+    match sqlx::query!(
+        "SELECT premium FROM subscriptions WHERE id = $1 AND deleted_at IS NULL",
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    {
+        Ok(Some(row)) => row.premium,
+        Ok(None) => false,
+        Err(e) => {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                "Failed to check the premium flag for a reading link",
+            );
+            false
+        }
+    }
+}
+
+// the locked rendering: a free preview, then the paywall
+fn paywall_page(issue: &ArchiveIssue, issue_id: Uuid, requested: bool) -> String {
+    let banner = if requested {
+        "<p><i>If that address belongs to a paying subscriber, a reading \
+         link is on its way.</i></p>"
+    } else {
+        ""
+    };
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>{title}</title>
+</head>
+<body>
+    {preview}
+    <hr>
+    <p><b>This is a premium issue.</b> The rest is for paying readers.</p>
+    {banner}
+    <form action="/archive/{issue_id}/access" method="post">
+        <label>Already premium? We'll email you a reading link:
+            <input type="email" name="email" required>
+        </label>
+        <button type="submit">Send my link</button>
+    </form>
+    <p>Not premium yet? <a href="/premium">Upgrade here.</a></p>
+</body>
+</html>"#,
+        title = htmlescape::encode_minimal(&issue.title),
+        preview = preview_html(&issue.html_content, FREE_PREVIEW_PARAGRAPHS),
+    )
+}
+
+// the first n paragraphs of the stored html. Issues without paragraph
+// markup get no preview at all - better than leaking the whole thing
+fn preview_html(html: &str, paragraphs: usize) -> String {
+    let mut preview = String::new();
+    for (count, chunk) in html.split_inclusive("</p>").enumerate() {
+        if count >= paragraphs || !chunk.contains("</p>") {
+            break;
+        }
+        preview.push_str(chunk);
+    }
+    preview
+}
+
+#[derive(serde::Deserialize)]
+pub struct ArchiveAccessForm {
+    email: String,
+}
+
+/// POST /archive/{issue_id}/access - email a signed reading link to a
+/// paying subscriber. The response never says whether the address
+/// qualified - that would make it an enumeration oracle.
+#[tracing::instrument(name = "Request an archive reading link", skip_all)]
+#[allow(clippy::too_many_arguments)] // they are all actix extractors
+pub async fn request_archive_access(
+    path: web::Path<Uuid>,
+    form: web::Form<ArchiveAccessForm>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    link_signer: web::Data<LinkSigner>,
+    clock: web::Data<dyn Clock>,
+    base_url: web::Data<crate::startup::ApplicationBaseUrl>,
+    tenant: crate::tenancy::Tenant,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = path.into_inner();
+
+    let issue = match get_published_issue(&pool, issue_id).await.map_err(e500)? {
+        Some(issue) => issue,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+    if !issue.premium_only {
+        // nothing to unlock
+        return Ok(see_other(&format!("/archive/{}", issue_id)));
+    }
+
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT id, email
+        FROM subscriptions
+        WHERE email = $1 AND status = 'confirmed' AND premium
+        AND tenant_id = $2 AND deleted_at IS NULL
+        "#,
+        form.email.trim(),
+        tenant.id,
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(e500)?;
+
+    if let Some(subscriber) = subscriber {
+        let fragment = link_signer.query_fragment(
+            subscriber.id,
+            ARCHIVE_ACCESS,
+            clock.now() + chrono::Duration::days(ARCHIVE_ACCESS_LINK_VALIDITY_DAYS),
+        );
+        let link = format!(
+            "{}/archive/{}?{}",
+            tenant.link_base_url_or(&base_url.0),
+            issue_id,
+            fragment
+        );
+        let recipient = SubscriberEmail::parse(subscriber.email).map_err(e500)?;
+        email_client
+            .send_email(
+                &recipient,
+                &format!("Your reading link: {}", issue.title),
+                &format!(
+                    "<p>Here is your reading link for <b>{}</b>:</p>\
+                     <p><a href=\"{}\">Read the full issue</a></p>\
+                     <p>The link works for {} days.</p>",
+                    htmlescape::encode_minimal(&issue.title),
+                    link,
+                    ARCHIVE_ACCESS_LINK_VALIDITY_DAYS,
+                ),
+                &format!(
+                    "Here is your reading link for {}:\n{}\nThe link works for {} days.\n",
+                    issue.title, link, ARCHIVE_ACCESS_LINK_VALIDITY_DAYS,
+                ),
+            )
+            .await
+            .map_err(e500)?;
+    }
+
+    // the same answer whether or not anything was sent
+    Ok(see_other(&format!("/archive/{}?requested=true", issue_id)))
+}
+
+/// GET /rss - an RSS 2.0 feed over the archive, filterable with ?tag=
+/// just like the html view. Issue tags become `<category>` elements.
+#[tracing::instrument(name = "Serve the RSS feed", skip_all)]
+pub async fn rss_feed(
+    request: HttpRequest,
+    query: web::Query<ArchiveQueryParams>,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<crate::startup::ApplicationBaseUrl>,
+    tenant: crate::tenancy::Tenant,
+) -> Result<HttpResponse, actix_web::Error> {
+    // feed readers keep absolute links around for a long time - hand out
+    // the tenant's own domain where one is verified
+    let base_url = tenant.link_base_url_or(&base_url.0);
+    let tag_filter = query.tag.as_deref().map(str::trim).filter(|t| !t.is_empty());
+    let issues = get_published_issues(&pool, tag_filter).await.map_err(e500)?;
+    let settings = site_settings::get(&pool).await.map_err(e500)?;
+
+    // same validator recipe as the html archive
+    let mut hasher = Sha256::new();
+    for issue in &issues {
+        hasher.update(issue.newsletter_issue_id.as_bytes());
+        hasher.update(issue.published_at.to_rfc3339().as_bytes());
+        for tag in &issue.tags {
+            hasher.update(tag.as_bytes());
+        }
+    }
+    hasher.update(tag_filter.unwrap_or("").as_bytes());
+    hasher.update(settings.newsletter_name.as_bytes());
+    let etag = EntityTag::new_strong(hex::encode(hasher.finalize()));
+    let last_modified = issues.first().map(|i| to_http_timestamp(i.published_at));
+
+    if is_not_modified(&request, &etag, last_modified) {
+        return Ok(not_modified_response(etag, last_modified));
+    }
+
+    let mut items_xml = String::new();
+    for issue in &issues {
+        let mut categories_xml = String::new();
+        for tag in &issue.tags {
+            write!(
+                categories_xml,
+                "<category>{}</category>",
+                htmlescape::encode_minimal(tag)
+            )
+            .unwrap();
+        }
+        let link = format!("{}/archive/{}", base_url, issue.newsletter_issue_id);
+        writeln!(
+            items_xml,
+            "        <item><title>{title}</title><link>{link}</link>\
+             <guid>{link}</guid><pubDate>{pub_date}</pubDate>{categories_xml}</item>",
+            title = htmlescape::encode_minimal(&issue.title),
+            pub_date = issue.published_at.to_rfc2822(),
+        )
+        .unwrap();
+    }
+
+    let title = match tag_filter {
+        Some(tag) => format!("{} - {}", settings.newsletter_name, tag),
+        None => settings.newsletter_name.clone(),
+    };
+
+    let mut response = HttpResponse::Ok();
+    response.content_type("application/rss+xml; charset=utf-8");
+    response.insert_header(ETag(etag));
+    if let Some(last_modified) = last_modified {
+        response.insert_header(LastModified(HttpDate::from(last_modified)));
+    }
+    Ok(response.body(format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0">
+    <channel>
+        <title>{title}</title>
+        <link>{base}/archive</link>
+        <description>{title}</description>
+{items_xml}    </channel>
+</rss>"#,
+        title = htmlescape::encode_minimal(&title),
+        base = base_url,
+    )))
+}
+
+struct ArchiveIssueSummary {
+    newsletter_issue_id: Uuid,
+    title: String,
+    published_at: DateTime<Utc>,
+    tags: Vec<String>,
+    premium_only: bool,
+}
+
+struct ArchiveIssue {
+    title: String,
+    html_content: String,
+    published_at: DateTime<Utc>,
+    premium_only: bool,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_published_issues(
+    pool: &PgPool,
+    tag_filter: Option<&str>,
+) -> Result<Vec<ArchiveIssueSummary>, anyhow::Error> {
+    // published_at is stored as TEXT - cast it back to a proper timestamp
+    // so chrono can work with it
+    let issues = sqlx::query_as!(
+        ArchiveIssueSummary,
+        r#"
+        SELECT
+            n.newsletter_issue_id,
+            n.title,
+            n.published_at::timestamptz as "published_at!",
+            COALESCE(
+                array_agg(t.tag ORDER BY t.tag) FILTER (WHERE t.tag IS NOT NULL),
+                '{}'
+            ) as "tags!: Vec<String>",
+            n.premium_only
+        FROM newsletter_issues n
+        LEFT JOIN newsletter_issue_tags t USING (newsletter_issue_id)
+        WHERE $1::text IS NULL OR EXISTS (
+            SELECT 1 FROM newsletter_issue_tags f
+            WHERE f.newsletter_issue_id = n.newsletter_issue_id AND f.tag = $1
+        )
+        GROUP BY n.newsletter_issue_id, n.title, n.published_at, n.premium_only
+        ORDER BY n.published_at::timestamptz DESC
+        "#,
+        tag_filter,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch published issues from the database.")?;
+    Ok(issues)
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_published_issue(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Option<ArchiveIssue>, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        ArchiveIssue,
+        r#"
+        SELECT
+            title,
+            html_content,
+            published_at::timestamptz as "published_at!",
+            premium_only
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch an archived issue from the database.")?;
+    Ok(issue)
+}
+
+// http dates only carry second resolution - truncate the db timestamp
+// before comparing, otherwise a stored microsecond component makes every
+// `If-Modified-Since` revalidation look stale
+fn to_http_timestamp(published_at: DateTime<Utc>) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(published_at.timestamp().max(0) as u64)
+}
+
+/// Check the conditional request headers against the resource's validators.
+/// `If-None-Match` wins over `If-Modified-Since` when both are present,
+/// as per RFC 9110.
+fn is_not_modified(
+    request: &HttpRequest,
+    etag: &EntityTag,
+    last_modified: Option<SystemTime>,
+) -> bool {
+    if let Some(header_value) = request.headers().get(header::IF_NONE_MATCH) {
+        let Ok(value) = header_value.to_str() else {
+            return false;
+        };
+        // a weak comparison - clients may hand back `W/"..."` for an etag
+        // we served as strong
+        return value.trim() == "*"
+            || value.split(',').any(|candidate| {
+                candidate.trim().trim_start_matches("W/").trim_matches('"') == etag.tag()
+            });
+    }
+
+    if let (Some(header_value), Some(last_modified)) = (
+        request.headers().get(header::IF_MODIFIED_SINCE),
+        last_modified,
+    ) {
+        if let Ok(value) = header_value.to_str() {
+            if let Ok(if_modified_since) = value.parse::<HttpDate>() {
+                return HttpDate::from(last_modified) <= if_modified_since;
+            }
+        }
+    }
+
+    false
+}
+
+// an empty 304 - we re-send the validators so caches can refresh their metadata
+fn not_modified_response(etag: EntityTag, last_modified: Option<SystemTime>) -> HttpResponse {
+    let mut response = HttpResponse::NotModified();
+    response.insert_header(ETag(etag));
+    if let Some(last_modified) = last_modified {
+        response.insert_header(LastModified(HttpDate::from(last_modified)));
+    }
+    response.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::preview_html;
+
+    #[test]
+    fn the_preview_stops_after_the_configured_paragraphs() {
+        let html = "<p>one</p><p>two</p><p>three</p>";
+        assert_eq!(preview_html(html, 2), "<p>one</p><p>two</p>");
+    }
+
+    #[test]
+    fn content_without_paragraph_markup_gets_no_preview() {
+        assert_eq!(preview_html("<div>everything at once</div>", 2), "");
+    }
+}
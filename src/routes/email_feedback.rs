@@ -0,0 +1,99 @@
+//! The email provider's bounce/complaint webhook. Postmark POSTs one
+//! JSON object per event; bounces and spam complaints are stored in
+//! `email_feedback_events` for the deliverability dashboard, anything
+//! else is acknowledged and dropped. Redeliveries (Postmark retries any
+//! event it thinks went unacknowledged) are recognised by the provider's
+//! event id and not double-counted. Postmark doesn't sign its webhooks,
+//! so the endpoint is guarded by a shared token instead - configured
+//! under `email_client.webhook_token` and appended to the webhook URL's
+//! headers in the provider's settings.
+
+use crate::clock::Clock;
+use crate::utils::e500;
+use actix_web::{web, HttpRequest, HttpResponse};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// the shared secret guarding /webhooks/email - a newtype, so app_data
+// can't hand out some other secret by accident. None means the endpoint
+// simply doesn't exist.
+pub struct EmailWebhookToken(pub Option<Secret<String>>);
+
+// the fields we care about from Postmark's bounce/complaint payloads -
+// everything else in the body is ignored
+#[derive(serde::Deserialize)]
+pub struct EmailFeedbackPayload {
+    #[serde(rename = "RecordType")]
+    record_type: String,
+    #[serde(rename = "Email")]
+    email: String,
+    // the provider's own event id - Postmark redelivers events it thinks
+    // went unacknowledged, and this is how a redelivery is recognised
+    #[serde(rename = "ID", default)]
+    id: Option<i64>,
+}
+
+/// POST /webhooks/email - record a bounce or complaint notification.
+#[tracing::instrument(name = "Handle an email feedback webhook", skip_all)]
+pub async fn email_feedback_webhook(
+    request: HttpRequest,
+    body: web::Json<EmailFeedbackPayload>,
+    pool: web::Data<PgPool>,
+    clock: web::Data<dyn Clock>,
+    token: web::Data<EmailWebhookToken>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // no token configured means the endpoint simply doesn't exist
+    let Some(expected_token) = &token.0 else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let provided_token = request
+        .headers()
+        .get("X-Webhook-Token")
+        .and_then(|value| value.to_str().ok());
+    let authorized = provided_token.is_some_and(|provided| {
+        crate::authentication::constant_time_eq(
+            provided.as_bytes(),
+            expected_token.expose_secret().as_bytes(),
+        )
+    });
+    if !authorized {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+
+    let event_type = match body.record_type.as_str() {
+        "Bounce" => "bounce",
+        "SpamComplaint" => "complaint",
+        // an event type we don't track - a 200 stops the provider retrying
+        other => {
+            tracing::info!(record_type = %other, "Ignoring an untracked email event");
+            return Ok(HttpResponse::Ok().finish());
+        }
+    };
+
+    // a redelivered event (same provider id) is dropped, not double-counted
+    let outcome = sqlx::query!(
+        r#"
+        INSERT INTO email_feedback_events
+            (id, event_type, recipient_email, occurred_at, provider_event_id)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (provider_event_id) WHERE provider_event_id IS NOT NULL
+        DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        event_type,
+        body.email,
+        clock.now(),
+        body.id,
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+
+    if outcome.rows_affected() == 0 {
+        tracing::info!(event_type = %event_type, "Ignoring a redelivered email feedback event");
+    } else {
+        tracing::info!(event_type = %event_type, "Recorded an email feedback event");
+    }
+    Ok(HttpResponse::Ok().finish())
+}
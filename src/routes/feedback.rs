@@ -0,0 +1,99 @@
+//! One-click issue feedback. The delivery worker appends a "how was this
+//! issue?" footer to every outgoing email - five links, one per score,
+//! all carrying the same signed parameters (the link proves who is
+//! clicking, the score rides alongside in plain query parameters, like
+//! the issue id in the path). Clicking again overwrites the earlier
+//! score - readers are allowed to change their mind.
+
+use crate::clock::Clock;
+use crate::signed_link::{LinkSigner, ISSUE_FEEDBACK};
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// the signed parameters plus the chosen score - named apart from the
+// other handlers' structs because routes are glob re-exported
+#[derive(serde::Deserialize)]
+pub struct FeedbackParameters {
+    score: i16,
+    subscriber_id: Uuid,
+    expires_at: i64,
+    purpose: String,
+    key_version: u32,
+    tag: String,
+}
+
+/// GET /feedback/{issue_id} - record a score for an issue and thank the
+/// reader.
+#[tracing::instrument(name = "Record issue feedback", skip_all)]
+pub async fn record_feedback(
+    path: web::Path<Uuid>,
+    parameters: web::Query<FeedbackParameters>,
+    pool: web::Data<PgPool>,
+    link_signer: web::Data<LinkSigner>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = path.into_inner();
+    if let Err(e) = link_signer.verify(
+        parameters.subscriber_id,
+        parameters.expires_at,
+        &parameters.purpose,
+        parameters.key_version,
+        &parameters.tag,
+        ISSUE_FEEDBACK,
+        clock.now(),
+    ) {
+        tracing::warn!(error.cause_chain = ?e, "Rejected an invalid feedback link");
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+    if !(1..=5).contains(&parameters.score) {
+        return Ok(HttpResponse::BadRequest().finish());
+    }
+
+    // a valid signature doesn't vouch for the issue id in the path - check
+    // it names a real issue before the insert trips over the foreign key
+    let issue = sqlx::query!(
+        "SELECT title FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id,
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    let issue = match issue {
+        Some(issue) => issue,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_feedback (newsletter_issue_id, subscriber_id, score, received_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (newsletter_issue_id, subscriber_id)
+        DO UPDATE SET score = EXCLUDED.score, received_at = EXCLUDED.received_at
+        "#,
+        issue_id,
+        parameters.subscriber_id,
+        parameters.score,
+        clock.now(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+
+    tracing::info!(
+        newsletter_issue_id = %issue_id,
+        score = parameters.score,
+        "A reader scored an issue"
+    );
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            "<p>Thanks! You rated <b>{}</b> {} out of 5.</p>\
+             <p>Clicked the wrong link? Just click the one you meant - \
+             the latest score wins.</p>",
+            htmlescape::encode_minimal(&issue.title),
+            parameters.score,
+        )))
+}
@@ -1,4 +1,6 @@
-use actix_web::{HttpRequest, HttpResponse, Responder};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use sqlx::PgPool;
 
 // Http GET Handlers ############################################################
 
@@ -15,3 +17,92 @@ pub async fn greet(req: HttpRequest) -> impl Responder {
 pub async fn health_check() -> impl Responder {
     HttpResponse::Ok() // an OK status Http response - many options in the docs
 }
+
+// build metadata baked in at compile time (see build.rs) - which binary
+// is actually running, answerable over http
+const GIT_SHA: &str = env!("VERGEN_GIT_SHA");
+const BUILD_TIMESTAMP: &str = env!("VERGEN_BUILD_TIMESTAMP");
+const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// GET /version - the running build's metadata. JSON when the caller asks
+/// for it (Accept: application/json), plain text otherwise.
+pub async fn version(request: HttpRequest) -> HttpResponse {
+    let wants_json = request
+        .headers()
+        .get(actix_web::http::header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"));
+
+    if wants_json {
+        HttpResponse::Ok().json(version_payload())
+    } else {
+        HttpResponse::Ok()
+            .content_type(ContentType::plaintext())
+            .body(format!(
+                "version: {}\ngit_sha: {}\nbuild_timestamp: {}\n",
+                PKG_VERSION, GIT_SHA, BUILD_TIMESTAMP,
+            ))
+    }
+}
+
+/// GET /ready - like the health check, but only green once the database
+/// *and* the session store answer, and carrying the same build metadata
+/// as /version so one call verifies both "is it up" and "is it the right
+/// build". An instance that can't reach Redis would bounce every admin to
+/// the login form - better for the orchestrator to route around it.
+pub async fn ready_check(
+    pool: web::Data<PgPool>,
+    session_store: web::Data<crate::redis_sessions::ResilientSessionStore>,
+) -> HttpResponse {
+    let database = match sqlx::query("SELECT 1").execute(pool.get_ref()).await {
+        Ok(_) => serde_json::json!("ok"),
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "The readiness database probe failed");
+            serde_json::json!("failed")
+        }
+    };
+
+    let stats = crate::redis_sessions::session_store_snapshot();
+    let session_store = match session_store.ping().await {
+        Ok(latency) => serde_json::json!({
+            "status": "ok",
+            "latency_ms": latency.as_secs_f64() * 1000.0,
+            "errors": stats.errors,
+        }),
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "The readiness session store probe failed");
+            serde_json::json!({ "status": "failed", "errors": stats.errors })
+        }
+    };
+
+    let ready = database == serde_json::json!("ok") && session_store["status"] == "ok";
+    let payload = serde_json::json!({
+        "status": if ready { "ready" } else { "unavailable" },
+        "version": version_payload(),
+        "checks": {
+            "database": database,
+            "session_store": session_store,
+        },
+    });
+    if ready {
+        HttpResponse::Ok().json(payload)
+    } else {
+        HttpResponse::ServiceUnavailable().json(payload)
+    }
+}
+
+/// GET /metrics - the delivery pipeline gauges in OpenMetrics format,
+/// as last sampled by the worker monitor (see crate::queue_metrics).
+pub async fn metrics() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(crate::queue_metrics::OPENMETRICS_CONTENT_TYPE)
+        .body(crate::queue_metrics::render())
+}
+
+fn version_payload() -> serde_json::Value {
+    serde_json::json!({
+        "version": PKG_VERSION,
+        "git_sha": GIT_SHA,
+        "build_timestamp": BUILD_TIMESTAMP,
+    })
+}
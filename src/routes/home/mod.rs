@@ -3,18 +3,13 @@ use actix_web::http::header::ContentType;
 use actix_web::HttpResponse;
 use actix_web_flash_messages::IncomingFlashMessages;
 use std::collections::HashMap;
-use std::fmt::Write;
 // use crate::utils::see_other;
 
 pub async fn home(
     flash_messages: IncomingFlashMessages, // attached if returning from failed POST req.
 ) -> Result<HttpResponse, actix_web::Error> {
     // check for flash message
-    let mut msg_html = String::new();
-
-    for m in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
+    let msg_html = crate::utils::flash_messages_html(&flash_messages);
 
     // Read the HTML file into a string
     let html_page = include_str!("home.html");
@@ -0,0 +1,375 @@
+//! The email provider's inbound webhook - replies readers send to an
+//! issue. Postmark receives mail for the reply-to address and POSTs one
+//! JSON object per message; each is stored in `issue_replies` (matched
+//! to the issue via the `In-Reply-To` header and to the subscriber via
+//! the sender address, where possible) and announced on the event
+//! webhooks - so replies land somewhere an admin actually looks instead
+//! of an unmonitored mailbox. Guarded by the same shared token as the
+//! bounce/complaint webhook.
+//!
+//! When the tenant has saved a `reply_ack` template, each stored reply
+//! is also answered with an automatic acknowledgement - guarded against
+//! mail loops (RFC 3834) and rate-limited per sender, so a thread or a
+//! rogue auto-responder on the other side can't turn into a ping-pong.
+
+use crate::clock::Clock;
+use crate::domain::SubscriberEmail;
+use crate::email_client::{EmailClient, MessageExtras};
+use crate::event_webhooks::EventWebhooks;
+use crate::utils::e500;
+use actix_web::{web, HttpRequest, HttpResponse};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::EmailWebhookToken;
+
+// at most one acknowledgement per sender in this window - a
+// back-and-forth thread should not collect one ack per message
+const ACK_RATE_LIMIT_HOURS: i32 = 24;
+
+// the fields we use from Postmark's inbound payload - the full object
+// carries far more (attachments, the raw MIME, a parsed address for
+// every recipient), all ignored
+#[derive(serde::Deserialize)]
+pub struct InboundEmailPayload {
+    #[serde(rename = "FromFull")]
+    from_full: InboundAddress,
+    #[serde(rename = "Subject", default)]
+    subject: String,
+    #[serde(rename = "TextBody", default)]
+    text_body: String,
+    // the provider's attempt at isolating just the reply, without the
+    // quoted original underneath - often empty
+    #[serde(rename = "StrippedTextReply", default)]
+    stripped_text_reply: String,
+    #[serde(rename = "Headers", default)]
+    headers: Vec<InboundHeader>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct InboundAddress {
+    #[serde(rename = "Email")]
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct InboundHeader {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Value")]
+    value: String,
+}
+
+/// POST /webhooks/inbound - store a reader's reply to an issue.
+#[tracing::instrument(name = "Handle an inbound email webhook", skip_all)]
+pub async fn inbound_email_webhook(
+    request: HttpRequest,
+    body: web::Json<InboundEmailPayload>,
+    pool: web::Data<PgPool>,
+    clock: web::Data<dyn Clock>,
+    token: web::Data<EmailWebhookToken>,
+    webhooks: web::Data<EventWebhooks>,
+    email_client: web::Data<EmailClient>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // same token guard as the bounce/complaint webhook - no token
+    // configured means the endpoint simply doesn't exist
+    let Some(expected_token) = &token.0 else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let provided_token = request
+        .headers()
+        .get("X-Webhook-Token")
+        .and_then(|value| value.to_str().ok());
+    let authorized = provided_token.is_some_and(|provided| {
+        crate::authentication::constant_time_eq(
+            provided.as_bytes(),
+            expected_token.expose_secret().as_bytes(),
+        )
+    });
+    if !authorized {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+
+    let body = body.0;
+
+    // the In-Reply-To header carries the Message-ID we got back from the
+    // provider at send time - the delivery log maps it to the issue. A
+    // miss (manual forwards, clients that drop the header) just means an
+    // unmatched reply
+    let issue = match in_reply_to_message_id(&body.headers) {
+        Some(message_id) => find_issue(&pool, &message_id).await.map_err(e500)?,
+        None => None,
+    };
+    let subscriber = find_subscriber(&pool, &body.from_full.email)
+        .await
+        .map_err(e500)?;
+
+    let reply_body = if body.stripped_text_reply.trim().is_empty() {
+        &body.text_body
+    } else {
+        &body.stripped_text_reply
+    };
+
+    let reply_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_replies
+            (id, newsletter_issue_id, subscriber_id, from_email, subject, body, received_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        reply_id,
+        issue.as_ref().map(|(id, _)| *id),
+        subscriber.as_ref().map(|(id, _)| *id),
+        body.from_full.email,
+        body.subject,
+        reply_body,
+        clock.now(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+
+    // the ack is best-effort: a failure here must not 500 the webhook,
+    // or the provider retries and the reply is stored twice
+    if let Some((_, tenant_id)) = &subscriber {
+        if let Err(e) = maybe_acknowledge(&pool, &email_client, &body, *tenant_id, reply_id).await {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                "Failed to auto-acknowledge a reader reply",
+            );
+        }
+    }
+
+    // tell the admins - stored but unread is barely better than an
+    // unmonitored mailbox
+    if webhooks.is_enabled() {
+        let about = match &issue {
+            Some((_, title)) => format!(" to \"{}\"", title),
+            None => String::new(),
+        };
+        webhooks
+            .announce(&format!(
+                "{} replied{} - the reply is waiting at /admin/replies.",
+                body.from_full.email, about
+            ))
+            .await;
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+// "<uuid@mtasv.net>" -> "uuid": the provider's MessageID is the local
+// part; the angle brackets and domain are the transport's framing
+fn in_reply_to_message_id(headers: &[InboundHeader]) -> Option<String> {
+    let value = headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case("In-Reply-To"))
+        .map(|header| header.value.trim())?;
+    let value = value.strip_prefix('<').unwrap_or(value);
+    let value = value.strip_suffix('>').unwrap_or(value);
+    let local = value.split('@').next().unwrap_or(value);
+    if local.is_empty() {
+        None
+    } else {
+        Some(local.to_string())
+    }
+}
+
+// the issue behind a provider MessageID, via the delivery log - the
+// title comes along for the announcement
+async fn find_issue(
+    pool: &PgPool,
+    provider_message_id: &str,
+) -> Result<Option<(Uuid, String)>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT l.newsletter_issue_id, i.title
+        FROM email_delivery_log l
+        JOIN newsletter_issues i ON i.newsletter_issue_id = l.newsletter_issue_id
+        WHERE l.provider_message_id = $1
+        "#,
+        provider_message_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| (r.newsletter_issue_id, r.title)))
+}
+
+// the subscriber (and their tenant - which list's auto-responder
+// applies) behind the sender address
+async fn find_subscriber(pool: &PgPool, email: &str) -> Result<Option<(Uuid, Uuid)>, sqlx::Error> {
+    let row = sqlx::query!(
+        "SELECT id, tenant_id FROM subscriptions WHERE email = $1 AND deleted_at IS NULL",
+        email,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| (r.id, r.tenant_id)))
+}
+
+// send the tenant's acknowledgement for a stored reply, if one is
+// configured and neither loop protection nor the rate limit objects
+async fn maybe_acknowledge(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    body: &InboundEmailPayload,
+    tenant_id: Uuid,
+    reply_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    // no saved template = the auto-responder is off for this list
+    let Some(template) =
+        crate::email_templates::get(pool, tenant_id, crate::email_templates::REPLY_ACK).await?
+    else {
+        return Ok(());
+    };
+
+    if is_auto_generated(&body.headers, &body.from_full.email) {
+        tracing::info!("Skipping the reply acknowledgement - the reply is auto-generated mail");
+        return Ok(());
+    }
+
+    // at most one ack per sender per window, counted off the replies we
+    // have already acknowledged
+    let recently_acknowledged = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM issue_replies
+            WHERE from_email = $1
+            AND acknowledged
+            AND received_at > now() - make_interval(hours => $2)
+        ) AS "exists!"
+        "#,
+        body.from_full.email,
+        ACK_RATE_LIMIT_HOURS as f64,
+    )
+    .fetch_one(pool)
+    .await?;
+    if recently_acknowledged {
+        tracing::info!("Skipping the reply acknowledgement - the sender was acked recently");
+        return Ok(());
+    }
+
+    let recipient = SubscriberEmail::parse(body.from_full.email.clone())
+        .map_err(|e| anyhow::anyhow!("The sender address is not a valid recipient: {}", e))?;
+
+    let settings = crate::site_settings::get(pool).await?;
+    let substitute = |text: &str| {
+        text.replace(
+            crate::email_templates::NEWSLETTER_NAME_TAG,
+            &settings.newsletter_name,
+        )
+    };
+
+    // declare the ack as auto-generated (RFC 3834), so the other side's
+    // auto-responder - the mirror image of the guard above - stays quiet
+    let extras = MessageExtras {
+        headers: vec![
+            ("Auto-Submitted".into(), "auto-replied".into()),
+            ("X-Auto-Response-Suppress".into(), "All".into()),
+        ],
+        ..Default::default()
+    };
+    email_client
+        .send_email_with(
+            &recipient,
+            &substitute(&template.subject),
+            &substitute(&template.html_body),
+            &substitute(&template.text_body),
+            extras,
+        )
+        .await?;
+
+    sqlx::query!(
+        "UPDATE issue_replies SET acknowledged = TRUE WHERE id = $1",
+        reply_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// auto-generated mail must never be answered automatically - two
+// auto-responders pointed at each other will happily fill both inboxes.
+// RFC 3834's Auto-Submitted is the official signal; the Precedence
+// values and the sender-name conventions are the folklore that predates it
+fn is_auto_generated(headers: &[InboundHeader], from_email: &str) -> bool {
+    let header_value = |name: &str| {
+        headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case(name))
+            .map(|header| header.value.trim().to_ascii_lowercase())
+    };
+    if let Some(value) = header_value("Auto-Submitted") {
+        if value != "no" {
+            return true;
+        }
+    }
+    if header_value("X-Auto-Response-Suppress").is_some() {
+        return true;
+    }
+    if let Some(value) = header_value("Precedence") {
+        if ["bulk", "list", "junk", "auto_reply"].contains(&value.as_str()) {
+            return true;
+        }
+    }
+    let from = from_email.to_ascii_lowercase();
+    ["no-reply", "noreply", "mailer-daemon", "postmaster"]
+        .iter()
+        .any(|needle| from.contains(needle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(name: &str, value: &str) -> InboundHeader {
+        InboundHeader {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+
+    #[test]
+    fn the_message_id_is_extracted_from_in_reply_to() {
+        let headers = vec![
+            header("X-Spam-Status", "No"),
+            header("in-reply-to", " <abc-123@mtasv.net> "),
+        ];
+        assert_eq!(
+            in_reply_to_message_id(&headers).as_deref(),
+            Some("abc-123")
+        );
+    }
+
+    #[test]
+    fn a_missing_or_empty_header_matches_nothing() {
+        assert_eq!(in_reply_to_message_id(&[]), None);
+        assert_eq!(
+            in_reply_to_message_id(&[header("In-Reply-To", "<>")]),
+            None
+        );
+    }
+
+    #[test]
+    fn auto_generated_mail_is_recognised() {
+        // the RFC 3834 signal, in either case
+        assert!(is_auto_generated(
+            &[header("auto-submitted", "Auto-Replied")],
+            "reader@example.com"
+        ));
+        // "no" is the explicit all-clear
+        assert!(!is_auto_generated(
+            &[header("Auto-Submitted", "no")],
+            "reader@example.com"
+        ));
+        // the pre-RFC folklore
+        assert!(is_auto_generated(
+            &[header("Precedence", "bulk")],
+            "reader@example.com"
+        ));
+        assert!(is_auto_generated(&[], "no-reply@example.com"));
+        // an ordinary human reply
+        assert!(!is_auto_generated(&[], "reader@example.com"));
+    }
+}
@@ -1,9 +1,14 @@
+use crate::alerts::Alerter;
 use crate::authentication::{validate_credentials, AuthError, Credentials};
-use crate::routes::error_chain_fmt;
-use crate::session_state::TypedSession;
+use crate::clock::Clock;
+use crate::configuration::PasswordHashSettings;
+use crate::routes::{account, error_chain_fmt};
+use crate::session_state::{register_session, SessionLimit, TypedSession};
+use crate::signed_link::LinkSigner;
+use crate::startup::ApplicationBaseUrl;
 use actix_web::error::InternalError;
 use actix_web::http::header::LOCATION;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::Secret;
 use sqlx::PgPool;
@@ -16,13 +21,21 @@ pub struct FormData {
 }
 
 #[tracing::instrument(
-    skip(pool, form, session),
+    skip(request, pool, form, session, alerter, clock, hashing, session_limit, base_url, link_signer),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn login(
+    request: HttpRequest, // for the caller's ip and user agent
     form: web::Form<FormData>, // deserialised from httpresp
     pool: web::Data<PgPool>,
     session: TypedSession, // the cookie-defined session - in our customn wrapper (see session_state)
+    alerter: web::Data<Alerter>, // hears about repeated failed attempts
+    clock: web::Data<dyn Clock>,
+    hashing: web::Data<PasswordHashSettings>, // for the transparent re-hash
+    session_limit: web::Data<SessionLimit>, // concurrent sessions allowed per user
+    base_url: web::Data<ApplicationBaseUrl>, // for the lock link in security emails
+    link_signer: web::Data<LinkSigner>,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
     let credentials = Credentials {
         username: form.0.username, // form.0 as FormData wrapped in Form
@@ -32,10 +45,26 @@ pub async fn login(
     tracing::Span::current().record("username", tracing::field::display(&credentials.username));
 
     // check the username and password are correct
-    match validate_credentials(credentials, &pool).await {
+    match validate_credentials(credentials, &pool, &hashing).await {
         Ok(user_id) => {
             tracing::Span::current().record("user_id", tracing::field::display(&user_id));
 
+            // a locked account stays out even with the right password -
+            // that's the whole point of the kill switch
+            match account::is_locked(&pool, user_id).await {
+                Ok(true) => {
+                    FlashMessage::error(
+                        "This account has been locked. Contact the operator to unlock it.",
+                    )
+                    .send();
+                    return Ok(HttpResponse::SeeOther()
+                        .insert_header((LOCATION, "/login"))
+                        .finish());
+                }
+                Ok(false) => {}
+                Err(e) => return Err(login_redirect(LoginError::UnexpectedError(e))),
+            }
+
             // if so, start a 'session' - ie a cookie that means the user doesn't have to
             // login again for a while.
             session.renew();
@@ -43,6 +72,38 @@ pub async fn login(
             session
                 .insert_user_id(user_id) // attach the userId to the 'session' - this will be checked in admin/dashboard
                 .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+
+            // register this session and enforce the concurrent-session
+            // cap - past the limit the user's oldest session makes way
+            let session_id = uuid::Uuid::new_v4();
+            session
+                .insert_session_id(session_id)
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            register_session(&pool, user_id, session_id, clock.now(), session_limit.0)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+
+            // a sign-in from a device we haven't seen before earns the
+            // operators a heads-up email (best-effort, never blocks)
+            let connection_info = request.connection_info().clone();
+            let ip = connection_info.realip_remote_addr().unwrap_or("unknown");
+            let user_agent = request
+                .headers()
+                .get(actix_web::http::header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or("unknown");
+            account::note_login(
+                &pool,
+                &alerter,
+                &base_url,
+                &link_signer,
+                user_id,
+                ip,
+                user_agent,
+                clock.now(),
+            )
+            .await;
+
             // re-route to the admin dashboard
             Ok(HttpResponse::SeeOther()
                 .insert_header((LOCATION, "/admin/dashboard"))
@@ -51,7 +112,12 @@ pub async fn login(
         // if error, propogate it with context
         Err(e) => {
             let e = match e {
-                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+                AuthError::InvalidCredentials(_) => {
+                    // a run of these in a short window smells like
+                    // credential stuffing - the alerter keeps count
+                    alerter.record_login_failure(clock.now()).await;
+                    LoginError::AuthError(e.into())
+                }
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
             };
 
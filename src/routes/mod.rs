@@ -1,14 +1,44 @@
+mod account;
 mod admin;
+mod api_subscribers;
+mod archive;
+mod email_feedback;
+mod feedback;
 mod health_check;
 mod home;
+mod inbound_email;
 mod login;
+mod my_subscription;
+mod poll;
+mod preferences;
+mod premium;
+mod seo;
 mod subscriptions;
+mod subscriptions_change_email;
 mod subscriptions_confirm;
+mod subscriptions_reconfirm;
+mod tracking;
+mod unsubscribe;
 
 // re-export
+pub use account::*;
 pub use admin::*;
+pub use api_subscribers::*;
+pub use archive::*;
+pub use email_feedback::*;
+pub use feedback::*;
 pub use health_check::*;
 pub use home::*;
+pub use inbound_email::*;
 pub use login::*;
+pub use my_subscription::*;
+pub use poll::*;
+pub use preferences::*;
+pub use premium::*;
+pub use seo::*;
 pub use subscriptions::*;
+pub use subscriptions_change_email::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_reconfirm::*;
+pub use tracking::*;
+pub use unsubscribe::*;
@@ -0,0 +1,283 @@
+//! A self-service "my subscription" area for subscribers. There are no
+//! passwords: the reader asks for a magic link to their inbox, and the
+//! link - a short-lived signed link, same mechanism as confirmation and
+//! preference links - opens a page showing their profile, category
+//! preferences and billing status. Owning the inbox *is* the credential,
+//! which is exactly the property everything else here already relies on.
+
+use crate::clock::Clock;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::signed_link::{LinkSigner, PREFERENCE_CENTER, SUBSCRIBER_LOGIN};
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+// deliberately short - the link is meant to be clicked straight from the
+// inbox, not bookmarked; anyone who comes back later just requests a new one
+const LOGIN_LINK_VALIDITY_MINUTES: i64 = 45;
+
+// the preference-center link embedded in the page gets the same lifetime
+// as the ones sent by email elsewhere
+const PREFERENCE_LINK_VALIDITY_DAYS: i64 = 30;
+
+// every signed parameter is optional: without them the page shows the
+// login form instead of failing to deserialize
+#[derive(serde::Deserialize)]
+pub struct MySubscriptionParameters {
+    subscriber_id: Option<Uuid>,
+    expires_at: Option<i64>,
+    purpose: Option<String>,
+    key_version: Option<u32>,
+    tag: Option<String>,
+    sent: Option<bool>,
+}
+
+/// GET /my-subscription - the profile page when the query carries a valid
+/// login link, the "email me a link" form otherwise.
+#[tracing::instrument(name = "View my subscription", skip_all)]
+pub async fn my_subscription(
+    query: web::Query<MySubscriptionParameters>,
+    pool: web::Data<PgPool>,
+    link_signer: web::Data<LinkSigner>,
+    clock: web::Data<dyn Clock>,
+    base_url: web::Data<crate::startup::ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = match verify_login_link(&query, &link_signer, clock.now()) {
+        Some(subscriber_id) => subscriber_id,
+        None => return Ok(login_form_page(query.sent.unwrap_or(false))),
+    };
+
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT email, name, status, subscribed_at, premium
+        FROM subscriptions
+        WHERE id = $1 AND deleted_at IS NULL
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    let subscriber = match subscriber {
+        Some(subscriber) => subscriber,
+        // a valid signature for a row that no longer exists - the
+        // subscriber was deleted after the link went out
+        None => return Ok(login_form_page(false)),
+    };
+
+    let opted_in = sqlx::query!(
+        "SELECT category FROM subscriber_category_preferences WHERE subscriber_id = $1 ORDER BY category",
+        subscriber_id,
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    let preferences_html = if opted_in.is_empty() {
+        "You receive every issue.".to_string()
+    } else {
+        let mut list = String::new();
+        for row in &opted_in {
+            if !list.is_empty() {
+                list.push_str(", ");
+            }
+            list.push_str(&htmlescape::encode_minimal(&row.category));
+        }
+        format!("You only receive: {list}.")
+    };
+    let preferences_link = format!(
+        "{}/preferences?{}",
+        base_url.0,
+        link_signer.query_fragment(
+            subscriber_id,
+            PREFERENCE_CENTER,
+            clock.now() + chrono::Duration::days(PREFERENCE_LINK_VALIDITY_DAYS),
+        )
+    );
+
+    let billing_html = if subscriber.premium {
+        "<p>You are on the <b>premium</b> tier - premium-only issues and \
+         the full archive are yours. Thank you for supporting the newsletter!</p>"
+            .to_string()
+    } else {
+        "<p>You are on the <b>free</b> tier. \
+         <a href=\"/premium\">Go premium</a> to receive premium-only issues \
+         and unlock the full archive.</p>"
+            .to_string()
+    };
+
+    let mut body = String::new();
+    write!(
+        body,
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>My subscription</title>
+</head>
+<body>
+    <h1>My subscription</h1>
+    <h3>Profile</h3>
+    <p>Name: {name}<br>
+    Email: {email}<br>
+    Status: {status}<br>
+    Subscribed since: {subscribed_at}</p>
+    <h3>Preferences</h3>
+    <p>{preferences_html}
+    <a href="{preferences_link}">Change your preferences</a>.</p>
+    <h3>Billing</h3>
+    {billing_html}
+    <p><a href="/archive">&lt;- Back to the archive</a></p>
+</body>
+</html>"#,
+        name = htmlescape::encode_minimal(&subscriber.name),
+        email = htmlescape::encode_minimal(&subscriber.email),
+        status = htmlescape::encode_minimal(&subscriber.status),
+        subscribed_at = subscriber.subscribed_at.format("%Y-%m-%d"),
+    )
+    .unwrap();
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(body))
+}
+
+#[derive(serde::Deserialize)]
+pub struct LoginLinkForm {
+    email: String,
+}
+
+/// POST /my-subscription - email a magic login link. As with every other
+/// email-taking form, the response never reveals whether the address is
+/// actually subscribed.
+#[tracing::instrument(name = "Request a magic login link", skip_all)]
+pub async fn request_login_link(
+    form: web::Form<LoginLinkForm>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    link_signer: web::Data<LinkSigner>,
+    clock: web::Data<dyn Clock>,
+    base_url: web::Data<crate::startup::ApplicationBaseUrl>,
+    tenant: crate::tenancy::Tenant,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT id, email
+        FROM subscriptions
+        WHERE email = $1 AND status = 'confirmed'
+        AND tenant_id = $2 AND deleted_at IS NULL
+        "#,
+        form.email.trim(),
+        tenant.id,
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(e500)?;
+
+    if let Some(subscriber) = subscriber {
+        let fragment = link_signer.query_fragment(
+            subscriber.id,
+            SUBSCRIBER_LOGIN,
+            clock.now() + chrono::Duration::minutes(LOGIN_LINK_VALIDITY_MINUTES),
+        );
+        let link = format!(
+            "{}/my-subscription?{}",
+            tenant.link_base_url_or(&base_url.0),
+            fragment
+        );
+        let recipient = SubscriberEmail::parse(subscriber.email).map_err(e500)?;
+        email_client
+            .send_email(
+                &recipient,
+                "Your login link",
+                &format!(
+                    "<p>Here is your link to manage your subscription:</p>\
+                     <p><a href=\"{}\">My subscription</a></p>\
+                     <p>The link works for {} minutes. If you didn't request \
+                     it, you can safely ignore this email.</p>",
+                    link, LOGIN_LINK_VALIDITY_MINUTES,
+                ),
+                &format!(
+                    "Here is your link to manage your subscription:\n{}\n\
+                     The link works for {} minutes. If you didn't request it, \
+                     you can safely ignore this email.\n",
+                    link, LOGIN_LINK_VALIDITY_MINUTES,
+                ),
+            )
+            .await
+            .map_err(e500)?;
+    }
+
+    // the same answer whether or not anything was sent
+    Ok(see_other("/my-subscription?sent=true"))
+}
+
+// `Some(subscriber_id)` only when every signed parameter is present and
+// the signature checks out
+fn verify_login_link(
+    query: &MySubscriptionParameters,
+    link_signer: &LinkSigner,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<Uuid> {
+    let (subscriber_id, expires_at, purpose, key_version, tag) = match (
+        query.subscriber_id,
+        query.expires_at,
+        query.purpose.as_deref(),
+        query.key_version,
+        query.tag.as_deref(),
+    ) {
+        (Some(id), Some(expires_at), Some(purpose), Some(key_version), Some(tag)) => {
+            (id, expires_at, purpose, key_version, tag)
+        }
+        _ => return None,
+    };
+    link_signer
+        .verify(
+            subscriber_id,
+            expires_at,
+            purpose,
+            key_version,
+            tag,
+            SUBSCRIBER_LOGIN,
+            now,
+        )
+        .map_err(|e| {
+            tracing::warn!(error.cause_chain = ?e, "Rejected an invalid login link");
+        })
+        .ok()?;
+    Some(subscriber_id)
+}
+
+fn login_form_page(sent: bool) -> HttpResponse {
+    let banner = if sent {
+        "<p><i>If that address has a confirmed subscription, a login link \
+         is on its way. It works for 45 minutes.</i></p>"
+    } else {
+        ""
+    };
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>My subscription</title>
+</head>
+<body>
+    <h1>My subscription</h1>
+    {banner}
+    <p>Enter your email address and we'll send you a link to manage your
+    subscription - no password needed.</p>
+    <form action="/my-subscription" method="post">
+        <label>Email
+            <input type="email" name="email" placeholder="you@example.com">
+        </label>
+        <button type="submit">Email me a login link</button>
+    </form>
+</body>
+</html>"#
+        ))
+}
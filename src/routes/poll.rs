@@ -0,0 +1,97 @@
+//! Poll voting. The delivery worker renders an issue's poll as one
+//! signed link per option (see the feedback module for the scheme - the
+//! link proves who is clicking, the chosen option rides in the query).
+//! One vote per subscriber per poll; voting again overwrites the earlier
+//! choice.
+
+use crate::clock::Clock;
+use crate::signed_link::{LinkSigner, POLL_VOTE};
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// the signed parameters plus the chosen option - named apart from the
+// other handlers' structs because routes are glob re-exported
+#[derive(serde::Deserialize)]
+pub struct PollVoteParameters {
+    option: Uuid,
+    subscriber_id: Uuid,
+    expires_at: i64,
+    purpose: String,
+    key_version: u32,
+    tag: String,
+}
+
+/// GET /poll/{poll_id}/vote - record a vote and thank the reader.
+#[tracing::instrument(name = "Record a poll vote", skip_all)]
+pub async fn record_poll_vote(
+    path: web::Path<Uuid>,
+    parameters: web::Query<PollVoteParameters>,
+    pool: web::Data<PgPool>,
+    link_signer: web::Data<LinkSigner>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let poll_id = path.into_inner();
+    if let Err(e) = link_signer.verify(
+        parameters.subscriber_id,
+        parameters.expires_at,
+        &parameters.purpose,
+        parameters.key_version,
+        &parameters.tag,
+        POLL_VOTE,
+        clock.now(),
+    ) {
+        tracing::warn!(error.cause_chain = ?e, "Rejected an invalid poll vote link");
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+
+    // a valid signature doesn't vouch for the ids in the path and query -
+    // the option has to belong to the poll it claims to
+    let option = sqlx::query!(
+        r#"
+        SELECT o.label, p.question
+        FROM poll_options o
+        JOIN issue_polls p ON p.id = o.poll_id
+        WHERE o.id = $1 AND o.poll_id = $2
+        "#,
+        parameters.option,
+        poll_id,
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    let option = match option {
+        Some(option) => option,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO poll_votes (poll_id, option_id, subscriber_id, received_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (poll_id, subscriber_id)
+        DO UPDATE SET option_id = EXCLUDED.option_id, received_at = EXCLUDED.received_at
+        "#,
+        poll_id,
+        parameters.option,
+        parameters.subscriber_id,
+        clock.now(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+
+    tracing::info!(poll_id = %poll_id, "A reader voted in a poll");
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            "<p>Vote recorded!</p>\
+             <p><b>{}</b><br>You chose: {}</p>\
+             <p>Changed your mind? Just click another option - the latest \
+             vote wins.</p>",
+            htmlescape::encode_minimal(&option.question),
+            htmlescape::encode_minimal(&option.label),
+        )))
+}
@@ -0,0 +1,213 @@
+//! The preference center - a public page, reached through a signed link
+//! (same mechanism as confirmation and unsubscribe links), where a
+//! subscriber picks which categories of issue they want. No opt-ins at
+//! all means "send me everything"; uncategorised issues always go to
+//! everyone either way - the enqueue query in the newsletter publish
+//! handler is where these rules are enforced.
+
+use crate::clock::Clock;
+use crate::signed_link::{LinkSigner, PREFERENCE_CENTER};
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+// the signed parameters, as on every other signed link - named apart from
+// the other handlers' `Parameters` structs because routes are glob
+// re-exported
+#[derive(serde::Deserialize)]
+pub struct PreferenceParameters {
+    subscriber_id: Uuid,
+    expires_at: i64,
+    purpose: String,
+    key_version: u32,
+    tag: String,
+}
+
+/// GET /preferences - a checkbox per known category, pre-ticked with the
+/// subscriber's current opt-ins.
+#[tracing::instrument(name = "View category preferences", skip_all)]
+pub async fn preferences_form(
+    parameters: web::Query<PreferenceParameters>,
+    pool: web::Data<PgPool>,
+    link_signer: web::Data<LinkSigner>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if verify_link(&parameters, &link_signer, clock.now()).is_err() {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+
+    let categories = known_categories(&pool).await.map_err(e500)?;
+    let opted_in = current_preferences(&pool, parameters.subscriber_id)
+        .await
+        .map_err(e500)?;
+
+    let mut checkboxes_html = String::new();
+    for category in &categories {
+        writeln!(
+            checkboxes_html,
+            r#"            <label><input type="checkbox" name="category_{escaped}" value="on"{checked}> {escaped}</label><br>"#,
+            escaped = htmlescape::encode_minimal(category),
+            checked = if opted_in.contains(category) {
+                " checked"
+            } else {
+                ""
+            },
+        )
+        .unwrap();
+    }
+    if categories.is_empty() {
+        checkboxes_html.push_str("            <p>No categories have been published yet.</p>\n");
+    }
+
+    // assembled from the shared components (crate::templates) - the
+    // checkbox group sits under a fieldset so the legend is announced
+    // with each box
+    let body = format!(
+        r#"        <h1>Email preferences</h1>
+        <p>
+            Tick the categories you want to receive. Leave everything unticked
+            to receive every issue. Issues without a category always go to
+            everyone.
+        </p>
+        <form action="/preferences" method="post">
+            <input type="hidden" name="subscriber_id" value="{subscriber_id}">
+            <input type="hidden" name="expires_at" value="{expires_at}">
+            <input type="hidden" name="purpose" value="{purpose}">
+            <input type="hidden" name="key_version" value="{key_version}">
+            <input type="hidden" name="tag" value="{tag}">
+{fieldset}{submit}        </form>
+"#,
+        subscriber_id = parameters.subscriber_id,
+        expires_at = parameters.expires_at,
+        purpose = htmlescape::encode_attribute(&parameters.purpose),
+        key_version = parameters.key_version,
+        tag = htmlescape::encode_attribute(&parameters.tag),
+        fieldset = crate::templates::fieldset("Issue categories", &checkboxes_html),
+        submit = crate::templates::submit_button("Save preferences"),
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(crate::templates::page("Email preferences", &body)))
+}
+
+/// POST /preferences - replace the subscriber's opt-ins with the ticked
+/// set. The signed parameters ride along as hidden fields.
+#[tracing::instrument(name = "Save category preferences", skip_all)]
+pub async fn save_preferences(
+    // checkboxes submit under dynamic names ("category_<name>"), so the
+    // body is taken as raw pairs rather than a struct
+    form: web::Form<Vec<(String, String)>>,
+    pool: web::Data<PgPool>,
+    link_signer: web::Data<LinkSigner>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let field = |name: &str| {
+        form.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .ok_or_else(|| actix_web::error::ErrorBadRequest(format!("Missing field '{name}'.")))
+    };
+    let parameters = PreferenceParameters {
+        subscriber_id: field("subscriber_id")?.parse().map_err(|_| {
+            actix_web::error::ErrorBadRequest("'subscriber_id' is not a valid uuid.")
+        })?,
+        expires_at: field("expires_at")?
+            .parse()
+            .map_err(|_| actix_web::error::ErrorBadRequest("'expires_at' is not a number."))?,
+        purpose: field("purpose")?.to_string(),
+        key_version: field("key_version")?
+            .parse()
+            .map_err(|_| actix_web::error::ErrorBadRequest("'key_version' is not a number."))?,
+        tag: field("tag")?.to_string(),
+    };
+    if verify_link(&parameters, &link_signer, clock.now()).is_err() {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+
+    // only categories that actually exist get stored - anything else in
+    // the body is someone playing with the form
+    let known = known_categories(&pool).await.map_err(e500)?;
+    let selected: Vec<&str> = form
+        .iter()
+        .filter_map(|(key, _)| key.strip_prefix("category_"))
+        .filter(|category| known.iter().any(|k| k == category))
+        .collect();
+
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    sqlx::query!(
+        "DELETE FROM subscriber_category_preferences WHERE subscriber_id = $1",
+        parameters.subscriber_id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(e500)?;
+    for category in &selected {
+        sqlx::query!(
+            r#"
+            INSERT INTO subscriber_category_preferences (subscriber_id, category)
+            VALUES ($1, $2)
+            "#,
+            parameters.subscriber_id,
+            category,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(e500)?;
+    }
+    transaction.commit().await.map_err(e500)?;
+
+    tracing::info!(
+        subscriber_id = %parameters.subscriber_id,
+        opted_in = selected.len(),
+        "A subscriber updated their category preferences"
+    );
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body("<p>Your preferences have been saved.</p>"))
+}
+
+fn verify_link(
+    parameters: &PreferenceParameters,
+    link_signer: &LinkSigner,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(), ()> {
+    link_signer
+        .verify(
+            parameters.subscriber_id,
+            parameters.expires_at,
+            &parameters.purpose,
+            parameters.key_version,
+            &parameters.tag,
+            PREFERENCE_CENTER,
+            now,
+        )
+        .map_err(|e| {
+            tracing::warn!(error.cause_chain = ?e, "Rejected an invalid preference link");
+        })
+}
+
+// every category any published issue has carried - the only sensible
+// things to offer checkboxes for
+async fn known_categories(pool: &PgPool) -> Result<Vec<String>, sqlx::Error> {
+    let rows = sqlx::query!("SELECT DISTINCT tag FROM newsletter_issue_tags ORDER BY tag")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|r| r.tag).collect())
+}
+
+async fn current_preferences(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<String>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT category FROM subscriber_category_preferences WHERE subscriber_id = $1",
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|r| r.category).collect())
+}
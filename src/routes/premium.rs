@@ -0,0 +1,219 @@
+use crate::clock::Clock;
+use crate::payments::Payments;
+use crate::site_settings;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// The public face of the paid tier: /premium explains it and starts a
+// Stripe checkout; /webhooks/stripe is where Stripe reports back. The
+// `premium` flag only ever changes in the webhook handler - a redirect
+// to the success url proves nothing, redirects can be forged.
+
+#[derive(serde::Deserialize)]
+pub struct PremiumPageParams {
+    // where the reader came back from ("success"/"cancelled"), or what
+    // went wrong starting the checkout
+    #[serde(default)]
+    outcome: Option<String>,
+}
+
+/// GET /premium - the paid-tier page, with an outcome banner after a
+/// round-trip to Stripe.
+#[tracing::instrument(name = "Serve the premium page", skip_all)]
+pub async fn premium_page(
+    query: web::Query<PremiumPageParams>,
+    pool: web::Data<PgPool>,
+    payments: web::Data<Payments>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let settings = site_settings::get(&pool).await.map_err(e500)?;
+
+    let banner = match query.outcome.as_deref() {
+        Some("success") => {
+            "<p><b>Thank you!</b> Your upgrade is being processed - premium \
+             issues will start arriving with the next send.</p>"
+        }
+        Some("cancelled") => "<p>The checkout was cancelled - no charge was made.</p>",
+        Some("unknown") => {
+            "<p>We couldn't match that address to a confirmed subscription. \
+             Subscribe (and confirm) first, then upgrade.</p>"
+        }
+        _ => "",
+    };
+
+    let body = if payments.is_enabled() {
+        format!(
+            r#"{banner}
+    <p>Premium readers receive subscriber-only issues.</p>
+    <form action="/premium" method="post">
+        <label>Your email address
+            <input type="email" name="email" required>
+        </label>
+        <button type="submit">Upgrade</button>
+    </form>"#
+        )
+    } else {
+        // no keys configured - don't offer a checkout that can't work
+        format!("{}<p>This newsletter has no paid tier - everything is free.</p>", banner)
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>{name} - Premium</title>
+</head>
+<body>
+    <h1>{name} Premium</h1>
+    {body}
+    <p><a href="/archive">&lt;- Back to the archive</a></p>
+</body>
+</html>"#,
+            name = htmlescape::encode_minimal(&settings.newsletter_name),
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct CheckoutForm {
+    email: String,
+}
+
+/// POST /premium - start a checkout for a confirmed subscriber and send
+/// them off to Stripe's hosted payment page.
+#[tracing::instrument(name = "Start a premium checkout", skip_all)]
+pub async fn start_checkout(
+    form: web::Form<CheckoutForm>,
+    pool: web::Data<PgPool>,
+    payments: web::Data<Payments>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    tenant: crate::tenancy::Tenant,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !payments.is_enabled() {
+        return Ok(see_other("/premium"));
+    }
+
+    // only confirmed subscribers of this newsletter can upgrade
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT id, email
+        FROM subscriptions
+        WHERE email = $1 AND status = 'confirmed'
+        AND tenant_id = $2 AND deleted_at IS NULL
+        "#,
+        form.email.trim(),
+        tenant.id,
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    let Some(subscriber) = subscriber else {
+        return Ok(see_other("/premium?outcome=unknown"));
+    };
+
+    let checkout_url = payments
+        .create_checkout_session(
+            subscriber.id,
+            &subscriber.email,
+            &tenant.link_base_url_or(&base_url.0),
+        )
+        .await
+        .map_err(e500)?;
+    Ok(see_other(&checkout_url))
+}
+
+/// POST /webhooks/stripe - Stripe reporting a subscription change. The
+/// signature is the credential; the body is only trusted once it checks
+/// out.
+#[tracing::instrument(name = "Handle a Stripe webhook", skip_all)]
+pub async fn stripe_webhook(
+    body: web::Bytes,
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    payments: web::Data<Payments>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(signature) = request
+        .headers()
+        .get("Stripe-Signature")
+        .and_then(|value| value.to_str().ok())
+    else {
+        return Ok(HttpResponse::BadRequest().finish());
+    };
+    if let Err(e) = payments.verify_webhook_signature(&body, signature, clock.now()) {
+        tracing::warn!(
+            error.cause_chain = ?e,
+            "Rejected a Stripe webhook",
+        );
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+
+    let Ok(event) = serde_json::from_slice::<serde_json::Value>(&body) else {
+        return Ok(HttpResponse::BadRequest().finish());
+    };
+
+    // errors below bubble up as a 500, which makes Stripe retry - exactly
+    // what we want for a transient database problem
+    match event["type"].as_str() {
+        Some("checkout.session.completed") => {
+            let subscriber_id = event["data"]["object"]["client_reference_id"]
+                .as_str()
+                .and_then(|id| Uuid::parse_str(id).ok());
+            let customer_id = event["data"]["object"]["customer"].as_str();
+            if let Some(subscriber_id) = subscriber_id {
+                grant_premium(&pool, subscriber_id, customer_id)
+                    .await
+                    .map_err(e500)?;
+                tracing::info!(%subscriber_id, "A subscriber upgraded to premium");
+            }
+        }
+        Some("customer.subscription.deleted") => {
+            if let Some(customer_id) = event["data"]["object"]["customer"].as_str() {
+                revoke_premium(&pool, customer_id).await.map_err(e500)?;
+            }
+        }
+        // everything else is noise we acknowledge so Stripe stops sending it
+        _ => {}
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(skip_all)]
+async fn grant_premium(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    customer_id: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET premium = true, stripe_customer_id = $2
+        WHERE id = $1 AND deleted_at IS NULL
+        "#,
+        subscriber_id,
+        customer_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn revoke_premium(pool: &PgPool, customer_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET premium = false
+        WHERE stripe_customer_id = $1 AND deleted_at IS NULL
+        "#,
+        customer_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! What the crawlers see: /sitemap.xml points them at the archive pages,
+//! /robots.txt serves the operator-configured rules (see /admin/settings)
+//! with the sitemap location appended.
+
+use crate::site_settings;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// GET /sitemap.xml - the archive index plus one entry per published
+/// issue, regenerated from the database on every request.
+#[tracing::instrument(name = "Serve the sitemap", skip_all)]
+pub async fn sitemap_xml(
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    tenant: crate::tenancy::Tenant,
+) -> Result<HttpResponse, actix_web::Error> {
+    // crawlers index whatever origin we print here - a tenant's verified
+    // custom domain wins over the shared base url
+    let base_url = tenant.link_base_url_or(&base_url.0);
+    let issues = get_issue_locations(&pool).await.map_err(e500)?;
+
+    let mut urls_xml = String::new();
+    // the archive list itself changes whenever anything is published
+    writeln!(
+        urls_xml,
+        "    <url><loc>{}/archive</loc>{}</url>",
+        base_url,
+        issues
+            .first()
+            .map(|i| format!("<lastmod>{}</lastmod>", i.published_at.format("%Y-%m-%d")))
+            .unwrap_or_default(),
+    )
+    .unwrap();
+    for issue in &issues {
+        writeln!(
+            urls_xml,
+            "    <url><loc>{}/archive/{}</loc><lastmod>{}</lastmod></url>",
+            base_url,
+            issue.newsletter_issue_id,
+            issue.published_at.format("%Y-%m-%d"),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/xml; charset=utf-8")
+        .body(format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+{urls_xml}</urlset>"#,
+        )))
+}
+
+/// GET /robots.txt - the operator's rules, plus where the sitemap lives.
+#[tracing::instrument(name = "Serve robots.txt", skip_all)]
+pub async fn robots_txt(
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    tenant: crate::tenancy::Tenant,
+) -> Result<HttpResponse, actix_web::Error> {
+    let settings = site_settings::get(&pool).await.map_err(e500)?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(format!(
+            "{}\n\nSitemap: {}/sitemap.xml\n",
+            settings.robots_txt.trim_end(),
+            tenant.link_base_url_or(&base_url.0),
+        )))
+}
+
+struct IssueLocation {
+    newsletter_issue_id: Uuid,
+    published_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_issue_locations(pool: &PgPool) -> Result<Vec<IssueLocation>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        IssueLocation,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            published_at::timestamptz as "published_at!"
+        FROM newsletter_issues
+        ORDER BY published_at::timestamptz DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch issue locations for the sitemap.")?;
+    Ok(issues)
+}
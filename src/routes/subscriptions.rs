@@ -1,12 +1,15 @@
 use crate::{
+    clock::Clock,
     domain::{NewSubscriber, SubscriberEmail, SubscriberName},
     email_client::EmailClient,
+    signed_link::{LinkSigner, SUBSCRIPTION_CONFIRMATION},
+    site_settings::{self, SiteSettings},
     startup::ApplicationBaseUrl,
 };
 use actix_web::http::StatusCode;
 use actix_web::{web, HttpResponse, ResponseError};
 use anyhow::Context;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use sqlx::{Executor, PgPool, Postgres, Transaction};
@@ -109,6 +112,29 @@ pub struct FormData {
     name: String,
 }
 
+// an optional `?ref=` query parameter lets signup links attribute the
+// subscriber to an acquisition channel (twitter, blog-footer, ...)
+#[derive(serde::Deserialize)]
+pub struct SubscribeParameters {
+    #[serde(rename = "ref")]
+    source: Option<String>,
+}
+
+impl SubscribeParameters {
+    // trim and cap the referrer so a mangled link can't stuff junk into
+    // the database; an empty value is the same as no value
+    fn acquisition_source(&self) -> Option<&str> {
+        self.source
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| match s.char_indices().nth(100) {
+                Some((idx, _)) => &s[..idx],
+                None => s,
+            })
+    }
+}
+
 // and the handler itself - it must accept a web::Form<FormData> - ie the struct above
 // All arguments in the signature of a route handler must implement the
 // FromRequest trait, which means the info can be extracted, or deserialised - you can then
@@ -132,23 +158,45 @@ impl TryFrom<FormData> for NewSubscriber {
 #[tracing::instrument( // this macro registers everything that happens in the below fn as part of a new SPAN
     name = "Adding a new subscriber", //a message associated to the function span
     // all fn args are automatically added to the log
-    skip(form, connection_pool, email_client, base_url), // we don't want to log stuff about these variables
+    skip(body, parameters, connection_pool, email_client, base_url, link_signer, clock, tenant), // we don't want to log stuff about these variables
     fields( // here we can add futher things of explicitly state how you want to display things
-    subscriber_email = %form.email,
-    subscriber_name = %form.name // the % - we are telling tracing to use their Display implementation
+    // recorded in the handler body once the payload has been unwrapped
+    subscriber_email = tracing::field::Empty,
+    subscriber_name = tracing::field::Empty
     )
 )]
+#[allow(clippy::too_many_arguments)] // they are all actix extractors
 pub async fn subscribe(
-    form: web::Form<FormData>, // FormData defined above
+    // content negotiation - SPAs and mobile apps POST `application/json`,
+    // the signup form POSTs urlencoded; both carry the same fields and
+    // run through the same validation below
+    body: actix_web::Either<web::Form<FormData>, web::Json<FormData>>,
+    parameters: web::Query<SubscribeParameters>, // the optional `?ref=`
     connection_pool: web::Data<PgPool>,
     email_client: web::Data<EmailClient>, //form data contains
     // our http request info in FormData but also anything attached with .app_data(data) in Web::Data <- we did this
     // with email_client and PgPool in the Run fn in Startup.rs
     base_url: web::Data<ApplicationBaseUrl>, // address for the confirmation email
+    link_signer: web::Data<LinkSigner>, // binds the link to this subscriber, with an expiry
+    clock: web::Data<dyn Clock>, // injectable time source - tests use a frozen one
+    tenant: crate::tenancy::Tenant, // which newsletter this host serves
 ) -> Result<HttpResponse, SubscribeError> {
-    // web::form is a wrapper around FormData (Form<FormData>) -
-    // access the formdata by form.0
-    let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
+    // remember which flavour the caller spoke - they get answers in kind
+    let (form, wants_json) = match body {
+        actix_web::Either::Left(form) => (form.0, false),
+        actix_web::Either::Right(json) => (json.0, true),
+    };
+    tracing::Span::current().record("subscriber_email", tracing::field::display(&form.email));
+    tracing::Span::current().record("subscriber_name", tracing::field::display(&form.name));
+
+    let new_subscriber: NewSubscriber = match form.try_into() {
+        Ok(new_subscriber) => new_subscriber,
+        Err(e) if wants_json => {
+            return Ok(HttpResponse::BadRequest()
+                .json(serde_json::json!({ "error": e })));
+        }
+        Err(e) => return Err(SubscribeError::ValidationError(e)),
+    };
 
     // create an sqlx 'transaction' that groups together sqlx queries so that you don't
     // get stuck in an interim state if the program crashes 1/2 way through
@@ -161,9 +209,15 @@ pub async fn subscribe(
     // Box pointer as we own the data (so can't be a reference) and UnexpectedError accepts
     // a dynamic type (dyn) which cannot be sized at compile time
 
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
-        .await
-        .context("Failed to insert new subscriber in the database.")?;
+    let subscriber_id = insert_subscriber(
+        &mut transaction,
+        &new_subscriber,
+        parameters.acquisition_source(),
+        clock.now(),
+        tenant.id,
+    )
+    .await
+    .context("Failed to insert new subscriber in the database.")?;
 
     let subscription_token = generate_subscription_token();
 
@@ -171,12 +225,38 @@ pub async fn subscribe(
         .await
         .context("Failed to store the confirmation token for a new subscriber.")?;
 
+    // the operator's branding/compliance details go into the email
+    let settings = site_settings::get(&connection_pool)
+        .await
+        .context("Failed to load the site settings.")?;
+
+    // the tenant's edited confirmation copy, if any
+    let template = crate::email_templates::get(
+        &connection_pool,
+        tenant.id,
+        crate::email_templates::CONFIRMATION,
+    )
+    .await
+    .context("Failed to load the confirmation email template.")?;
+
+    // sign the link parameters - the signature binds them to this
+    // subscriber and puts a deadline on the link
+    let signed_params = link_signer.query_fragment(
+        subscriber_id,
+        SUBSCRIPTION_CONFIRMATION,
+        clock.now() + chrono::Duration::days(CONFIRMATION_LINK_VALIDITY_DAYS),
+    );
+
     // try to send out the email
     match send_confirmation_email(
         &email_client,
         new_subscriber,
-        &base_url.0,
+        // a verified custom domain takes precedence over the shared one
+        &tenant.link_base_url_or(&base_url.0),
         &subscription_token,
+        &signed_params,
+        &settings,
+        template.as_ref(),
     )
     .await
     .context("Failed to send a confirmation email, please retry.")
@@ -187,6 +267,10 @@ pub async fn subscribe(
                 .commit()
                 .await
                 .context("Failed to commit SQL transaction to store a new subscriber.")?;
+            if wants_json {
+                return Ok(HttpResponse::Ok()
+                    .json(serde_json::json!({ "status": "pending_confirmation" })));
+            }
             return Ok(HttpResponse::Ok().finish());
         }
         Err(e) => {
@@ -237,40 +321,71 @@ pub async fn store_token(
 
 #[tracing::instrument(
     name = "Send a confirmation email to a new subscriber",
-    skip(email_client, new_subscriber)
+    skip(email_client, new_subscriber, settings, template)
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn send_confirmation_email(
     email_client: &EmailClient,
     new_subscriber: NewSubscriber,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
-    // make a confirmation link - inlcude a subscription token
+    signed_params: &str,
+    settings: &SiteSettings,
+    // the tenant's edited copy, if any - `None` falls back to the
+    // built-in wording below
+    template: Option<&crate::email_templates::EmailTemplate>,
+) -> Result<(), crate::email_client::SendError> {
+    // make a confirmation link - inlcude a subscription token plus the
+    // HMAC-signed parameters (subscriber id, expiry, purpose)
     let confirmation_link = format!(
-        "{}/subscriptions/confirm?subscription_token={}",
-        base_url, subscription_token
-    );
-
-    let html_body = &format!(
-        "Welcome to our newsletter!<br />\
-           Click <a href=\"{}\">here</a> to confirm your subscription.",
-        confirmation_link
+        "{}/subscriptions/confirm?subscription_token={}&{}",
+        base_url, subscription_token, signed_params
     );
 
-    let plain_text_body = &format!(
-        "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
-        confirmation_link
-    );
+    // the physical address is a CAN-SPAM requirement - append it when set
+    let address_footer = if settings.footer_address.is_empty() {
+        String::new()
+    } else {
+        format!("\n\n{}", settings.footer_address)
+    };
+
+    let (subject, html_body, plain_text_body) = match template {
+        Some(template) => (
+            template.subject.clone(),
+            format!(
+                "{}{}",
+                template
+                    .html_body
+                    .replace(crate::email_templates::CONFIRMATION_LINK_TAG, &confirmation_link),
+                address_footer
+            ),
+            format!(
+                "{}{}",
+                template
+                    .text_body
+                    .replace(crate::email_templates::CONFIRMATION_LINK_TAG, &confirmation_link),
+                address_footer
+            ),
+        ),
+        None => (
+            format!("Welcome to {}!", settings.newsletter_name),
+            format!(
+                "Welcome to {}!<br />\
+                   Click <a href=\"{}\">here</a> to confirm your subscription.{}",
+                settings.newsletter_name, confirmation_link, address_footer
+            ),
+            format!(
+                "Welcome to {}!\nVisit {} to confirm your subscription.{}",
+                settings.newsletter_name, confirmation_link, address_footer
+            ),
+        ),
+    };
 
     // send a confirmation email to the new subscriber
     email_client
-        .send_email(
-            &new_subscriber.email,
-            "Welcome!!",
-            html_body,
-            plain_text_body,
-        )
-        .await
+        .send_email(&new_subscriber.email, &subject, &html_body, &plain_text_body)
+        .await?;
+    Ok(())
 }
 
 #[tracing::instrument(
@@ -280,19 +395,24 @@ pub async fn send_confirmation_email(
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
+    acquisition_source: Option<&str>,
+    subscribed_at: DateTime<Utc>,
+    tenant_id: Uuid,
 ) -> Result<Uuid, sqlx::Error> {
     let subscriber_id = Uuid::new_v4();
 
     // insert form data to the db with this query
     let query = sqlx::query!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, acquisition_source, tenant_id)
+        VALUES ($1, $2, $3, $4, 'pending_confirmation', $5, $6)
         "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(), // the &str of our username type inner value
-        Utc::now()                    // timestamp
+        subscribed_at,                // timestamp - from the injected clock
+        acquisition_source,
+        tenant_id
     );
 
     transaction.execute(query).await?; // Using the `?` operator to return early
@@ -300,8 +420,11 @@ pub async fn insert_subscriber(
     Ok(subscriber_id)
 }
 
+// how long a confirmation link stays clickable
+pub(crate) const CONFIRMATION_LINK_VALIDITY_DAYS: i64 = 3;
+
 // a random sequence of alphanumeric chars
-fn generate_subscription_token() -> String {
+pub(crate) fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
@@ -0,0 +1,263 @@
+use crate::{
+    domain::SubscriberEmail, email_client::EmailClient, routes::subscriptions::error_chain_fmt,
+    startup::ApplicationBaseUrl,
+};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::Utc;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// A subscriber-driven email change:
+// 1. POST /subscriptions/change_email with the current and new address;
+// 2. the NEW address receives a confirmation link carrying a one-off token;
+// 3. GET /subscriptions/change_email/confirm swaps the address over.
+// Until step 3 happens the old address stays active and keeps receiving
+// issues - an unconfirmed (or mistyped) new address changes nothing.
+
+#[derive(thiserror::Error)]
+pub enum EmailChangeError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for EmailChangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for EmailChangeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            EmailChangeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            EmailChangeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct EmailChangeForm {
+    email: String,
+    new_email: String,
+}
+
+#[tracing::instrument(
+    name = "Request a subscriber email change",
+    skip(form, pool, email_client, base_url),
+    fields(new_email = %form.new_email)
+)]
+pub async fn request_email_change(
+    form: web::Form<EmailChangeForm>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, EmailChangeError> {
+    let current_email = SubscriberEmail::parse(form.0.email)
+        .map_err(EmailChangeError::ValidationError)?;
+    let new_email = SubscriberEmail::parse(form.0.new_email)
+        .map_err(EmailChangeError::ValidationError)?;
+
+    // only a known, confirmed subscriber can move their address
+    let subscriber_id = get_confirmed_subscriber_id(&pool, current_email.as_ref())
+        .await
+        .context("Failed to look up the subscriber.")?
+        .ok_or_else(|| {
+            EmailChangeError::ValidationError(
+                "There is no confirmed subscription for that address.".to_string(),
+            )
+        })?;
+
+    let change_token = generate_change_token();
+
+    store_change_request(&pool, subscriber_id, new_email.as_ref(), &change_token)
+        .await
+        .context("Failed to store the email change request.")?;
+
+    // the confirmation goes to the NEW address - proof its owner wants it
+    send_change_confirmation_email(&email_client, &new_email, &base_url.0, &change_token)
+        .await
+        .context("Failed to send the email change confirmation email.")?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(serde::Deserialize)]
+pub struct EmailChangeParameters {
+    change_token: String,
+}
+
+#[tracing::instrument(name = "Confirm a subscriber email change", skip(parameters, pool))]
+pub async fn confirm_email_change(
+    parameters: web::Query<EmailChangeParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, EmailChangeError> {
+    let change = get_change_request(&pool, &parameters.change_token)
+        .await
+        .context("Failed to look up the email change request.")?
+        .ok_or_else(|| {
+            EmailChangeError::ValidationError(
+                "The change token is not valid or has already been used.".to_string(),
+            )
+        })?;
+
+    apply_email_change(&pool, &change)
+        .await
+        .context("Failed to apply the email change.")?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+struct EmailChangeRequest {
+    change_token: String,
+    subscriber_id: Uuid,
+    new_email: String,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_confirmed_subscriber_id(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT id
+        FROM subscriptions
+        WHERE email = $1 AND status = 'confirmed' AND deleted_at IS NULL
+        "#,
+        email,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| r.id))
+}
+
+#[tracing::instrument(skip(pool, change_token))]
+async fn store_change_request(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    new_email: &str,
+    change_token: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO email_change_requests (
+            change_token,
+            subscriber_id,
+            new_email,
+            requested_at
+        )
+        VALUES ($1, $2, $3, $4)
+        "#,
+        change_token,
+        subscriber_id,
+        new_email,
+        Utc::now(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_change_request(
+    pool: &PgPool,
+    change_token: &str,
+) -> Result<Option<EmailChangeRequest>, sqlx::Error> {
+    sqlx::query_as!(
+        EmailChangeRequest,
+        r#"
+        SELECT change_token, subscriber_id, new_email
+        FROM email_change_requests
+        WHERE change_token = $1
+        "#,
+        change_token,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// swap the address over and burn the token - one transaction, so a crash
+// can't leave a used token behind
+#[tracing::instrument(skip_all)]
+async fn apply_email_change(
+    pool: &PgPool,
+    change: &EmailChangeRequest,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET email = $1
+        WHERE id = $2 AND deleted_at IS NULL
+        "#,
+        change.new_email,
+        change.subscriber_id,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM email_change_requests
+        WHERE change_token = $1
+        "#,
+        change.change_token,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn send_change_confirmation_email(
+    email_client: &EmailClient,
+    new_email: &SubscriberEmail,
+    base_url: &str,
+    change_token: &str,
+) -> Result<(), crate::email_client::SendError> {
+    let confirmation_link = format!(
+        "{}/subscriptions/change_email/confirm?change_token={}",
+        base_url, change_token
+    );
+
+    let html_body = &format!(
+        "A change of email address was requested for your newsletter subscription.<br />\
+           Click <a href=\"{}\">here</a> to confirm it. If this wasn't you, ignore this email.",
+        confirmation_link
+    );
+
+    let plain_text_body = &format!(
+        "A change of email address was requested for your newsletter subscription.\n\
+        Visit {} to confirm it. If this wasn't you, ignore this email.",
+        confirmation_link
+    );
+
+    email_client
+        .send_email(
+            new_email,
+            "Confirm your new email address",
+            html_body,
+            plain_text_body,
+        )
+        .await?;
+    Ok(())
+}
+
+// a random sequence of alphanumeric chars - same shape as the
+// subscription tokens
+fn generate_change_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(25)
+        .collect()
+}
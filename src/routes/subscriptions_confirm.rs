@@ -1,4 +1,10 @@
+use crate::clock::Clock;
+use crate::custom_pages::{self, Page};
+use crate::event_webhooks::EventWebhooks;
+use crate::message_bus::MessageBus;
 use crate::routes::subscriptions::error_chain_fmt;
+use crate::signed_link::{LinkSigner, SUBSCRIPTION_CONFIRMATION};
+use actix_web::http::header::ContentType;
 use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
 use anyhow::Context;
 use sqlx::PgPool;
@@ -23,20 +29,110 @@ impl ResponseError for ConfirmError {
 }
 
 // defines all the query parameters that we expect to see in the incoming request
+// - the token plus the HMAC-signed parameters added in subscriptions.rs
 #[derive(serde::Deserialize)]
 pub struct Parameters {
     subscription_token: String,
+    subscriber_id: Uuid,
+    expires_at: i64,
+    purpose: String,
+    key_version: u32,
+    tag: String,
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, pool))]
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(parameters, pool, link_signer, clock, webhooks, bus)
+)]
 // If the deserialize fails from web::Query
 // a 400 Bad Request is automatically returned to the caller
 pub async fn confirm(
     parameters: web::Query<Parameters>,
     pool: web::Data<PgPool>,
-) -> Result<HttpResponse, ConfirmError> {
+    link_signer: web::Data<LinkSigner>,
+    clock: web::Data<dyn Clock>,
+    webhooks: web::Data<EventWebhooks>,
+    bus: web::Data<MessageBus>,
+) -> HttpResponse {
+    // the signature first - nothing else in the link can be trusted
+    // until it checks out
+    if let Err(e) = link_signer.verify(
+        parameters.subscriber_id,
+        parameters.expires_at,
+        &parameters.purpose,
+        parameters.key_version,
+        &parameters.tag,
+        SUBSCRIPTION_CONFIRMATION,
+        clock.now(),
+    ) {
+        tracing::warn!(
+            error.cause_chain = ?e,
+            "Rejected a confirmation link"
+        );
+        return HttpResponse::BadRequest()
+            .content_type(ContentType::html())
+            .body(custom_pages::render(&pool, Page::Error).await);
+    }
+
+    // rather than the bare 200/500 we used to return, serve the
+    // operator-configurable pages (see crate::custom_pages)
+    match try_confirm(&pool, &parameters.subscription_token, parameters.subscriber_id).await {
+        Ok(newly_confirmed) => {
+            // the analytics trail - pseudonymous, best-effort
+            if newly_confirmed {
+                crate::domain_events::record(
+                    &pool,
+                    &bus,
+                    crate::domain_events::SUBSCRIBER_CONFIRMED,
+                    Some(parameters.subscriber_id),
+                    clock.now(),
+                )
+                .await;
+            }
+            // clicking the link twice is fine, but only the first click is
+            // news - and the reader shouldn't wait on our chat channel, so
+            // the announcements happen off the request path
+            if newly_confirmed && webhooks.is_enabled() {
+                let pool = pool.clone();
+                let webhooks = webhooks.clone();
+                tokio::spawn(async move {
+                    webhooks.announce("A new subscriber just confirmed.").await;
+                    match count_confirmed_subscribers(&pool).await {
+                        Ok(count) => webhooks.announce_if_milestone(count).await,
+                        Err(e) => {
+                            tracing::warn!(
+                                error.cause_chain = ?e,
+                                "Failed to count subscribers for the milestone check",
+                            );
+                        }
+                    }
+                });
+            }
+            HttpResponse::Ok()
+                .content_type(ContentType::html())
+                .body(custom_pages::render(&pool, Page::Confirmation).await)
+        }
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                "Failed to confirm a pending subscriber"
+            );
+            HttpResponse::InternalServerError()
+                .content_type(ContentType::html())
+                .body(custom_pages::render(&pool, Page::Error).await)
+        }
+    }
+}
+
+// `Ok(true)` means the subscriber flipped to confirmed on this request,
+// `Ok(false)` that they already were (a re-clicked link)
+async fn try_confirm(
+    pool: &PgPool,
+    subscription_token: &str,
+    signed_subscriber_id: Uuid,
+) -> Result<bool, ConfirmError> {
     //get the subscriber_id from the subscription token
-    let id = match get_subscriber_id_from_token(&pool, &parameters.subscription_token).await {
+    let id = match get_subscriber_id_from_token(pool, subscription_token).await {
         Ok(inner_id) => inner_id,
         Err(e) => return Err(e),
     };
@@ -44,10 +140,15 @@ pub async fn confirm(
     // although it's OK above, it could in theory still be none
     let id_ok = id.ok_or(anyhow::anyhow!("No user associated with the token"))?;
 
-    match confirm_subscriber(&pool, id_ok).await {
-        Ok(_) => Ok(HttpResponse::Ok().finish()),
-        Err(e) => Err(e),
+    // the stored token and the signed parameters must agree on who is
+    // being confirmed
+    if id_ok != signed_subscriber_id {
+        return Err(ConfirmError::ConfirmSubscriberFailedError(anyhow::anyhow!(
+            "The subscription token does not belong to the signed subscriber."
+        )));
     }
+
+    confirm_subscriber(pool, id_ok).await
 }
 
 /// Fetch a subsciber_id from an auth token sent in a confirmation email.
@@ -75,19 +176,34 @@ pub async fn get_subscriber_id_from_token(
 }
 
 /// Marks a subscriber as 'Confirmed' from 'Pending Confirmation'
-/// In the database.
+/// In the database. Returns whether the row actually changed - false
+/// means they were confirmed already.
 ///
 /// # Errors
 ///
 /// This function will return an error if cannot connect to db.
 #[tracing::instrument(name = "Mark subscriber as confirmed", skip(subscriber_id, pool))]
-pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), ConfirmError> {
-    sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
+pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<bool, ConfirmError> {
+    let outcome = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = 'confirmed'
+        WHERE id = $1 AND status != 'confirmed' AND deleted_at IS NULL
+        "#,
         subscriber_id
     )
     .execute(pool)
     .await
     .context("Failed to confirm the subscriber in the database.")?;
-    Ok(())
+    Ok(outcome.rows_affected() > 0)
+}
+
+// feeds the milestone announcement after a fresh confirmation
+async fn count_confirmed_subscribers(pool: &PgPool) -> Result<i64, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT COUNT(*) as "count!" FROM subscriptions WHERE status = 'confirmed' AND deleted_at IS NULL"#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count confirmed subscribers.")?;
+    Ok(row.count)
 }
@@ -0,0 +1,45 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+
+// The landing endpoint for re-engagement emails (see
+// routes/admin/reengagement.rs). Clicking the link deletes the campaign
+// row, which is all "staying subscribed" requires - subscribers who never
+// click are unsubscribed once the response window lapses.
+
+#[derive(serde::Deserialize)]
+pub struct ReconfirmParameters {
+    token: String,
+}
+
+#[tracing::instrument(name = "Re-confirm a subscription", skip(parameters, pool))]
+pub async fn reconfirm(
+    parameters: web::Query<ReconfirmParameters>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    match clear_campaign(&pool, &parameters.token).await {
+        // an unknown token - either mistyped or already used
+        Ok(0) => HttpResponse::Unauthorized().finish(),
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                "Failed to clear the re-engagement campaign"
+            );
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(skip_all)]
+async fn clear_campaign(pool: &PgPool, token: &str) -> Result<u64, sqlx::Error> {
+    let outcome = sqlx::query!(
+        r#"
+        DELETE FROM reengagement_campaigns
+        WHERE reconfirm_token = $1
+        "#,
+        token,
+    )
+    .execute(pool)
+    .await?;
+    Ok(outcome.rows_affected())
+}
@@ -0,0 +1,93 @@
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// Engagement tracking endpoints. Issue templates embed
+//   <img src="{base_url}/track/open/{issue_id}/{subscriber_id}" />
+// and rewrite outbound links through
+//   {base_url}/track/click/{issue_id}/{subscriber_id}?url={target}
+// so opens and clicks land in `email_tracking_events`.
+
+// the smallest valid transparent GIF - served as the open-tracking pixel
+const TRACKING_PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00,
+    0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+#[tracing::instrument(name = "Record an email open", skip(pool))]
+pub async fn track_open(
+    path: web::Path<(Uuid, Uuid)>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let (newsletter_issue_id, subscriber_id) = path.into_inner();
+    // a failure to record must never break the email rendering in the
+    // subscriber's client - log it and serve the pixel regardless
+    if let Err(e) = record_event(&pool, newsletter_issue_id, subscriber_id, "open").await {
+        tracing::warn!(
+            error.cause_chain = ?e,
+            "Failed to record an email open event"
+        );
+    }
+    HttpResponse::Ok()
+        .content_type("image/gif")
+        .body(TRACKING_PIXEL)
+}
+
+#[derive(serde::Deserialize)]
+pub struct ClickParameters {
+    url: String,
+}
+
+#[tracing::instrument(name = "Record an email click", skip(pool, parameters))]
+pub async fn track_click(
+    path: web::Path<(Uuid, Uuid)>,
+    parameters: web::Query<ClickParameters>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    // refuse to act as an open redirect for arbitrary schemes
+    if !parameters.url.starts_with("http://") && !parameters.url.starts_with("https://") {
+        return HttpResponse::BadRequest().finish();
+    }
+    let (newsletter_issue_id, subscriber_id) = path.into_inner();
+    // same stance as the pixel - the redirect matters more than the record
+    if let Err(e) = record_event(&pool, newsletter_issue_id, subscriber_id, "click").await {
+        tracing::warn!(
+            error.cause_chain = ?e,
+            "Failed to record an email click event"
+        );
+    }
+    HttpResponse::SeeOther()
+        .insert_header((actix_web::http::header::LOCATION, parameters.url.clone()))
+        .finish()
+}
+
+#[tracing::instrument(skip(pool))]
+async fn record_event(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    subscriber_id: Uuid,
+    event_type: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO email_tracking_events (
+            id,
+            newsletter_issue_id,
+            subscriber_id,
+            event_type,
+            occurred_at
+        )
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        Uuid::new_v4(),
+        newsletter_issue_id,
+        subscriber_id,
+        event_type,
+        Utc::now(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
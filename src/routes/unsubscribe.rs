@@ -0,0 +1,109 @@
+//! RFC 8058 one-click unsubscribe. Mail clients that honour
+//! `List-Unsubscribe-Post` (Gmail's "Unsubscribe" button, most notably)
+//! POST to the URL from the `List-Unsubscribe` header without showing the
+//! reader any page at all - so this endpoint must act on the request
+//! alone. The signed parameters in the URL are the credential; the body
+//! (`List-Unsubscribe=One-Click`) carries no information and is ignored.
+//! The visible unsubscribe link in every issue's compliance footer points
+//! here as well - a human click arrives as a GET and is served the same
+//! way, with the response page doing the explaining.
+
+use crate::clock::Clock;
+use crate::message_bus::MessageBus;
+use crate::signed_link::{LinkSigner, ONE_CLICK_UNSUBSCRIBE, PREFERENCE_CENTER};
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// how long the "manage your categories instead" link on the unsubscribe
+// response stays valid
+const PREFERENCE_LINK_VALIDITY_DAYS: i64 = 30;
+
+// the signed parameters minted by the delivery worker's header and
+// footer injection
+#[derive(serde::Deserialize)]
+pub struct OneClickParameters {
+    subscriber_id: Uuid,
+    expires_at: i64,
+    purpose: String,
+    key_version: u32,
+    tag: String,
+}
+
+#[tracing::instrument(name = "One-click unsubscribe", skip_all)]
+pub async fn one_click_unsubscribe(
+    parameters: web::Query<OneClickParameters>,
+    pool: web::Data<PgPool>,
+    link_signer: web::Data<LinkSigner>,
+    clock: web::Data<dyn Clock>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    bus: web::Data<MessageBus>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // the signature first - nothing else in the link can be trusted
+    // until it checks out
+    if let Err(e) = link_signer.verify(
+        parameters.subscriber_id,
+        parameters.expires_at,
+        &parameters.purpose,
+        parameters.key_version,
+        &parameters.tag,
+        ONE_CLICK_UNSUBSCRIBE,
+        clock.now(),
+    ) {
+        tracing::warn!(error.cause_chain = ?e, "Rejected an invalid one-click unsubscribe");
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+
+    let updated = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'unsubscribed'
+        WHERE id = $1 AND status != 'unsubscribed' AND deleted_at IS NULL
+        "#,
+        parameters.subscriber_id,
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(e500)?;
+
+    // only the first click is an event - replays of the same link (mail
+    // clients prefetch, people double-click) change nothing
+    if updated.rows_affected() > 0 {
+        crate::domain_events::record(
+            &pool,
+            &bus,
+            crate::domain_events::SUBSCRIBER_UNSUBSCRIBED,
+            Some(parameters.subscriber_id),
+            clock.now(),
+        )
+        .await;
+    }
+
+    tracing::info!(
+        subscriber_id = %parameters.subscriber_id,
+        "A subscriber unsubscribed via the one-click header"
+    );
+    // any 2xx tells the mail client it worked - but some clients do show
+    // the response to the reader, so offer the preference center as a
+    // lighter-touch alternative to leaving entirely
+    let preferences_link = format!(
+        "{}/preferences?{}",
+        base_url.0,
+        link_signer.query_fragment(
+            parameters.subscriber_id,
+            PREFERENCE_CENTER,
+            clock.now() + chrono::Duration::days(PREFERENCE_LINK_VALIDITY_DAYS),
+        )
+    );
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            "<p>You have been unsubscribed.</p>\
+             <p>Only wanted fewer emails? You can \
+             <a href=\"{preferences_link}\">pick which categories you receive</a> \
+             instead.</p>"
+        )))
+}
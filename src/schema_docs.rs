@@ -0,0 +1,281 @@
+//! The `schema` maintenance subcommand - documentation for the live schema.
+//!
+//! The migrations directory is the source of truth, but by now it is a
+//! long list of deltas; working out what a table looks like today means
+//! replaying them in your head. This introspects an up-to-date database
+//! instead and prints the net result as an entity-relationship diagram -
+//! Mermaid (renders straight on GitHub) or Graphviz dot - together with
+//! any `COMMENT ON` annotations the schema carries.
+
+use crate::configuration::DatabaseSettings;
+use anyhow::Context;
+use sqlx::{Connection, PgConnection, Row};
+use std::fmt::Write;
+
+pub enum ErdFormat {
+    Mermaid,
+    Dot,
+}
+
+/// The optional format argument: `zero2prod schema [mermaid|dot]`.
+pub fn format_argument() -> Result<ErdFormat, anyhow::Error> {
+    match std::env::args().nth(2).as_deref() {
+        None | Some("mermaid") => Ok(ErdFormat::Mermaid),
+        Some("dot") => Ok(ErdFormat::Dot),
+        Some(other) => Err(anyhow::anyhow!(
+            "Unknown ERD format '{}': expected mermaid or dot",
+            other
+        )),
+    }
+}
+
+struct Table {
+    name: String,
+    comment: Option<String>,
+    columns: Vec<Column>,
+}
+
+struct Column {
+    name: String,
+    data_type: String,
+    primary_key: bool,
+    comment: Option<String>,
+}
+
+struct ForeignKey {
+    child: String,
+    parent: String,
+    columns: String,
+}
+
+/// Introspect the configured database and print the ERD to stdout.
+pub async fn emit(database: &DatabaseSettings, format: ErdFormat) -> Result<(), anyhow::Error> {
+    let mut connection = PgConnection::connect_with(&database.connection_options())
+        .await
+        .context("Failed to connect to Postgres")?;
+    let tables = load_tables(&mut connection).await?;
+    let foreign_keys = load_foreign_keys(&mut connection).await?;
+
+    let output = match format {
+        ErdFormat::Mermaid => render_mermaid(&tables, &foreign_keys),
+        ErdFormat::Dot => render_dot(&tables, &foreign_keys),
+    };
+    println!("{}", output);
+    Ok(())
+}
+
+// every user-facing table - monthly partition children are skipped, the
+// parent already describes their shape, as is sqlx's bookkeeping table
+async fn load_tables(connection: &mut PgConnection) -> Result<Vec<Table>, anyhow::Error> {
+    let rows = sqlx::query(
+        r#"
+        SELECT c.relname AS name, obj_description(c.oid, 'pg_class') AS comment
+        FROM pg_class c
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE n.nspname = 'public'
+            AND c.relkind IN ('r', 'p')
+            AND NOT c.relispartition
+            AND c.relname <> '_sqlx_migrations'
+        ORDER BY c.relname
+        "#,
+    )
+    .fetch_all(&mut *connection)
+    .await
+    .context("Failed to list the tables")?;
+
+    let mut tables = Vec::new();
+    for row in rows {
+        let name: String = row.get("name");
+        let columns = load_columns(connection, &name).await?;
+        tables.push(Table {
+            comment: row.get("comment"),
+            name,
+            columns,
+        });
+    }
+    Ok(tables)
+}
+
+async fn load_columns(
+    connection: &mut PgConnection,
+    table: &str,
+) -> Result<Vec<Column>, anyhow::Error> {
+    let rows = sqlx::query(
+        r#"
+        SELECT a.attname AS name,
+            format_type(a.atttypid, a.atttypmod) AS data_type,
+            col_description(a.attrelid, a.attnum) AS comment,
+            EXISTS (
+                SELECT 1 FROM pg_index i
+                WHERE i.indrelid = a.attrelid
+                    AND i.indisprimary
+                    AND a.attnum = ANY(i.indkey::int2[])
+            ) AS primary_key
+        FROM pg_attribute a
+        WHERE a.attrelid = $1::regclass
+            AND a.attnum > 0
+            AND NOT a.attisdropped
+        ORDER BY a.attnum
+        "#,
+    )
+    .bind(table)
+    .fetch_all(connection)
+    .await
+    .with_context(|| format!("Failed to describe the columns of {}", table))?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Column {
+            name: row.get("name"),
+            data_type: row.get("data_type"),
+            primary_key: row.get("primary_key"),
+            comment: row.get("comment"),
+        })
+        .collect())
+}
+
+// the edges of the diagram, one per foreign key, labelled with the
+// referencing column(s)
+async fn load_foreign_keys(
+    connection: &mut PgConnection,
+) -> Result<Vec<ForeignKey>, anyhow::Error> {
+    let rows = sqlx::query(
+        r#"
+        SELECT conrelid::regclass::text AS child,
+            confrelid::regclass::text AS parent,
+            (
+                SELECT string_agg(a.attname, ', ' ORDER BY cols.ord)
+                FROM unnest(conkey) WITH ORDINALITY AS cols(attnum, ord)
+                JOIN pg_attribute a
+                    ON a.attrelid = conrelid AND a.attnum = cols.attnum
+            ) AS columns
+        FROM pg_constraint
+        WHERE contype = 'f' AND connamespace = 'public'::regnamespace
+        ORDER BY child, parent
+        "#,
+    )
+    .fetch_all(connection)
+    .await
+    .context("Failed to list the foreign keys")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| ForeignKey {
+            child: row.get("child"),
+            parent: row.get("parent"),
+            columns: row.get("columns"),
+        })
+        .collect())
+}
+
+fn render_mermaid(tables: &[Table], foreign_keys: &[ForeignKey]) -> String {
+    let mut out = String::from("erDiagram\n");
+    for table in tables {
+        if let Some(comment) = &table.comment {
+            writeln!(out, "    %% {}", comment.replace('\n', " ")).unwrap();
+        }
+        writeln!(out, "    {} {{", table.name).unwrap();
+        for column in &table.columns {
+            write!(
+                out,
+                "        {} {}",
+                mermaid_type(&column.data_type),
+                column.name
+            )
+            .unwrap();
+            if column.primary_key {
+                out.push_str(" PK");
+            }
+            if let Some(comment) = &column.comment {
+                write!(out, " \"{}\"", comment.replace(['"', '\n'], " ")).unwrap();
+            }
+            out.push('\n');
+        }
+        out.push_str("    }\n");
+    }
+    for fk in foreign_keys {
+        writeln!(
+            out,
+            "    {} }}o--|| {} : \"{}\"",
+            fk.child, fk.parent, fk.columns
+        )
+        .unwrap();
+    }
+    out
+}
+
+// Mermaid attribute types must be a single word - fold the SQL spelling
+// into something it accepts
+fn mermaid_type(data_type: &str) -> String {
+    let shortened = data_type
+        .replace("timestamp with time zone", "timestamptz")
+        .replace("character varying", "varchar")
+        .replace("[]", "_array");
+    shortened
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn render_dot(tables: &[Table], foreign_keys: &[ForeignKey]) -> String {
+    let mut out = String::from("digraph schema {\n    rankdir=LR;\n    node [shape=record];\n");
+    for table in tables {
+        let mut label = format!("{{{}", table.name);
+        if let Some(comment) = &table.comment {
+            write!(label, "\\n{}", dot_escape(comment)).unwrap();
+        }
+        for column in &table.columns {
+            write!(
+                label,
+                "|{}: {}{}",
+                column.name,
+                dot_escape(&column.data_type),
+                if column.primary_key { " (PK)" } else { "" }
+            )
+            .unwrap();
+            if let Some(comment) = &column.comment {
+                write!(label, "\\n{}", dot_escape(comment)).unwrap();
+            }
+        }
+        label.push('}');
+        writeln!(out, "    \"{}\" [label=\"{}\"];", table.name, label).unwrap();
+    }
+    for fk in foreign_keys {
+        writeln!(
+            out,
+            "    \"{}\" -> \"{}\" [label=\"{}\"];",
+            fk.child, fk.parent, fk.columns
+        )
+        .unwrap();
+    }
+    out.push('}');
+    out
+}
+
+// record labels give |, {, }, <, > and quotes structural meaning
+fn dot_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '|' | '{' | '}' | '<' | '>' | '"' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mermaid_type;
+
+    #[test]
+    fn sql_type_names_become_single_mermaid_words() {
+        assert_eq!(mermaid_type("timestamp with time zone"), "timestamptz");
+        assert_eq!(mermaid_type("character varying(255)"), "varchar_255_");
+        assert_eq!(mermaid_type("text[]"), "text_array");
+    }
+}
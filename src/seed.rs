@@ -0,0 +1,145 @@
+//! `zero2prod seed` - fill a fresh database with plausible demo data so
+//! local development (and screenshots) don't start from a blank slate:
+//! an admin login with a known password, a few hundred fake subscribers
+//! spread over the past months, and a handful of published issues.
+//! Everything is inserted with ON CONFLICT DO NOTHING, so running it
+//! against a database that already has data is harmless.
+
+use crate::authentication::compute_password_hash;
+use crate::configuration::{DatabaseSettings, PasswordHashSettings};
+use crate::startup;
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use fake::faker::internet::en::SafeEmail;
+use fake::faker::name::en::Name;
+use fake::Fake;
+use rand::Rng;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const SUBSCRIBER_COUNT: usize = 250;
+
+// printed to stdout after seeding - a demo password, not a secret
+const ADMIN_USERNAME: &str = "admin";
+const ADMIN_PASSWORD: &str = "everythinghastostartsomewhere";
+
+pub async fn seed(database: &DatabaseSettings) -> Result<(), anyhow::Error> {
+    let pool = startup::get_connection_pool(database);
+
+    seed_admin_user(&pool).await?;
+    let subscribers = seed_subscribers(&pool).await?;
+    let issues = seed_issues(&pool).await?;
+
+    tracing::info!(subscribers, issues, "Seeding complete");
+    println!("Seeded {} subscribers and {} issues.", subscribers, issues);
+    println!(
+        "Admin login: username '{}', password '{}'.",
+        ADMIN_USERNAME, ADMIN_PASSWORD
+    );
+    Ok(())
+}
+
+// upsert rather than insert - the migrations already create an 'admin'
+// row, we just make sure its password is the documented demo one
+async fn seed_admin_user(pool: &PgPool) -> Result<(), anyhow::Error> {
+    let password_hash = compute_password_hash(
+        Secret::new(ADMIN_PASSWORD.to_string()),
+        &PasswordHashSettings::default(),
+    )
+    .context("Failed to hash the demo admin password")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (username) DO UPDATE SET password_hash = EXCLUDED.password_hash
+        "#,
+        Uuid::new_v4(),
+        ADMIN_USERNAME,
+        password_hash.expose_secret(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to seed the admin user")?;
+    Ok(())
+}
+
+async fn seed_subscribers(pool: &PgPool) -> Result<u64, anyhow::Error> {
+    let mut rng = rand::thread_rng();
+    let now = Utc::now();
+    let mut inserted = 0;
+    for _ in 0..SUBSCRIBER_COUNT {
+        let email: String = SafeEmail().fake();
+        let name: String = Name().fake();
+        // sign-ups spread over the past six months, skewed however the
+        // dice fall - enough to make the dashboard charts look lived-in
+        let subscribed_at = now - Duration::minutes(rng.gen_range(0..(180 * 24 * 60)));
+        // mostly confirmed, with a realistic tail of pending and lapsed
+        let status = match rng.gen_range(0..100) {
+            0..=79 => "confirmed",
+            80..=92 => "pending_confirmation",
+            _ => "unsubscribed",
+        };
+        let outcome = sqlx::query!(
+            r#"
+            INSERT INTO subscriptions
+                (id, email, name, subscribed_at, status, acquisition_source)
+            VALUES ($1, $2, $3, $4, $5, 'seed')
+            ON CONFLICT (email) DO NOTHING
+            "#,
+            Uuid::new_v4(),
+            email,
+            name,
+            subscribed_at,
+            status,
+        )
+        .execute(pool)
+        .await
+        .context("Failed to seed a subscriber")?;
+        // the fakers repeat themselves occasionally - duplicates just
+        // mean we seed slightly fewer than SUBSCRIBER_COUNT
+        inserted += outcome.rows_affected();
+    }
+    Ok(inserted)
+}
+
+async fn seed_issues(pool: &PgPool) -> Result<u64, anyhow::Error> {
+    let samples = [
+        ("Welcome to the newsletter", 90),
+        ("What we shipped this quarter", 60),
+        ("A deep dive into our delivery pipeline", 30),
+        ("Reader questions, answered", 7),
+    ];
+    let mut inserted = 0;
+    for (title, days_ago) in samples {
+        let published_at = (Utc::now() - Duration::days(days_ago)).to_rfc3339();
+        let text_content = format!(
+            "{}\n\nThis is a sample issue created by `zero2prod seed`. \
+            It exists so the archive and admin screens have something to show.",
+            title
+        );
+        let html_content = format!(
+            "<h1>{}</h1><p>This is a sample issue created by <code>zero2prod seed</code>. \
+            It exists so the archive and admin screens have something to show.</p>",
+            title
+        );
+        let outcome = sqlx::query!(
+            r#"
+            INSERT INTO newsletter_issues
+                (newsletter_issue_id, title, text_content, html_content, published_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (newsletter_issue_id) DO NOTHING
+            "#,
+            Uuid::new_v4(),
+            title,
+            text_content,
+            html_content,
+            published_at,
+        )
+        .execute(pool)
+        .await
+        .context("Failed to seed an issue")?;
+        inserted += outcome.rows_affected();
+    }
+    Ok(inserted)
+}
@@ -1,4 +1,7 @@
 use actix_session::{Session, SessionGetError, SessionInsertError};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
 use uuid::Uuid;
 
 use actix_session::SessionExt;
@@ -16,6 +19,9 @@ pub struct TypedSession(Session);
 
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const SESSION_ID_KEY: &'static str = "session_id";
+    const IMPERSONATING_KEY: &'static str = "impersonating_user_id";
+    const FORM_STASH_KEY: &'static str = "form_stash";
 
     pub fn renew(&self) {
         self.0.renew();
@@ -26,11 +32,143 @@ impl TypedSession {
     pub fn get_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
         self.0.get(Self::USER_ID_KEY)
     }
+    // the id this session is registered under in `user_sessions` - the
+    // cookie alone is not enough, the registry row must still exist
+    pub fn insert_session_id(&self, session_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::SESSION_ID_KEY, session_id)
+    }
+    pub fn get_session_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::SESSION_ID_KEY)
+    }
+    // the impersonation claim - while set, requests run as this user
+    // instead of the session's own (see reject_anonymous_users)
+    pub fn insert_impersonated_user_id(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::IMPERSONATING_KEY, user_id)
+    }
+    pub fn get_impersonated_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::IMPERSONATING_KEY)
+    }
+    pub fn stop_impersonating(&self) {
+        self.0.remove(Self::IMPERSONATING_KEY);
+    }
+    // a one-shot stash for re-populating a form after a failed validation:
+    // the POST stashes what was typed before redirecting back, the GET
+    // takes it out again. Taking consumes the stash, so stale input can't
+    // resurface on a later, unrelated visit
+    pub fn stash_form<T: serde::Serialize>(&self, form: &T) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::FORM_STASH_KEY, form)
+    }
+    pub fn take_form_stash<T: serde::de::DeserializeOwned>(
+        &self,
+    ) -> Result<Option<T>, SessionGetError> {
+        let stash = self.0.get(Self::FORM_STASH_KEY)?;
+        self.0.remove(Self::FORM_STASH_KEY);
+        Ok(stash)
+    }
     pub fn log_out(self) {
         self.0.purge()
     }
 }
 
+// the per-user concurrent-session cap, registered as app data in startup -
+// a newtype so it can't be confused with any other number
+pub struct SessionLimit(pub usize);
+
+/// Record a fresh login in the session registry and enforce the cap:
+/// anything beyond the newest `limit` sessions is evicted, oldest first.
+/// actix-session owns the actual Redis keys, so eviction is enforced at
+/// the door instead - an evicted cookie fails the registry check in
+/// `reject_anonymous_users` on its next request and is purged there.
+pub async fn register_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    session_id: Uuid,
+    now: DateTime<Utc>,
+    limit: usize,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO user_sessions (session_id, user_id, created_at)
+        VALUES ($1, $2, $3)
+        "#,
+        session_id,
+        user_id,
+        now,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to register the session")?;
+
+    // 0 means uncapped
+    if limit > 0 {
+        sqlx::query!(
+            r#"
+            DELETE FROM user_sessions
+            WHERE user_id = $1
+            AND session_id NOT IN (
+                SELECT session_id FROM user_sessions
+                WHERE user_id = $1
+                ORDER BY created_at DESC
+                LIMIT $2
+            )
+            "#,
+            user_id,
+            limit as i64,
+        )
+        .execute(pool)
+        .await
+        .context("Failed to evict sessions beyond the cap")?;
+    }
+    Ok(())
+}
+
+/// Whether a session id still has its registry row - `false` means it was
+/// evicted by the cap or revoked by a password change.
+pub async fn session_is_active(pool: &PgPool, session_id: Uuid) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        "SELECT session_id FROM user_sessions WHERE session_id = $1",
+        session_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to check the session registry")?;
+    Ok(row.is_some())
+}
+
+/// Drop every registered session for a user except (optionally) the one
+/// making the request - what a password change calls.
+pub async fn revoke_other_sessions(
+    pool: &PgPool,
+    user_id: Uuid,
+    keep: Option<Uuid>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM user_sessions
+        WHERE user_id = $1
+        AND ($2::uuid IS NULL OR session_id != $2)
+        "#,
+        user_id,
+        keep,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke the user's other sessions")?;
+    Ok(())
+}
+
+/// Remove a single session's registry row - what logging out calls.
+pub async fn remove_session(pool: &PgPool, session_id: Uuid) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        "DELETE FROM user_sessions WHERE session_id = $1",
+        session_id,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to remove the session from the registry")?;
+    Ok(())
+}
+
 // to overcomplicate things - instead of just taking a Session as an argument
 // in 'new()' - we have implemented this new type as an Actix::web extractor
 impl FromRequest for TypedSession {
@@ -0,0 +1,74 @@
+use sqlx::PgPool;
+
+// Operator-editable branding and compliance details, kept as a single row
+// in `site_settings` and edited at /admin/settings. The name, logo and
+// accent colour dress up the public pages; the footer address is the
+// physical address CAN-SPAM requires on every email.
+
+pub struct SiteSettings {
+    pub newsletter_name: String,
+    pub logo_url: String,
+    pub accent_color: String,
+    pub footer_address: String,
+    // one URL per line
+    pub social_links: String,
+    // served verbatim at /robots.txt (see routes::seo)
+    pub robots_txt: String,
+    // append the dark-mode style variant to outgoing issues (see
+    // crate::premailer::add_dark_mode_styles)
+    pub dark_mode_emails: bool,
+}
+
+impl SiteSettings {
+    /// The configured social links, one per non-empty line.
+    pub fn social_links_list(&self) -> Vec<&str> {
+        self.social_links
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect()
+    }
+}
+
+/// The current settings - the migration seeds the row, so it always exists.
+#[tracing::instrument(skip_all)]
+pub async fn get(pool: &PgPool) -> Result<SiteSettings, sqlx::Error> {
+    sqlx::query_as!(
+        SiteSettings,
+        r#"
+        SELECT newsletter_name, logo_url, accent_color, footer_address, social_links, robots_txt,
+            dark_mode_emails
+        FROM site_settings
+        "#,
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Replace the current settings.
+#[tracing::instrument(skip_all)]
+pub async fn update(pool: &PgPool, settings: &SiteSettings) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE site_settings
+        SET
+            newsletter_name = $1,
+            logo_url = $2,
+            accent_color = $3,
+            footer_address = $4,
+            social_links = $5,
+            robots_txt = $6,
+            dark_mode_emails = $7
+        "#,
+        settings.newsletter_name,
+        settings.logo_url,
+        settings.accent_color,
+        settings.footer_address,
+        settings.social_links,
+        settings.robots_txt,
+        settings.dark_mode_emails,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -1,14 +1,22 @@
 use crate::authentication;
+use crate::clock::{Clock, SystemClock};
 use crate::configuration::DatabaseSettings;
-use crate::configuration::Settings;
+use crate::alerts::Alerter;
+use crate::configuration::{
+    AlertSettings, EmailSizeGuardSettings, EventWebhookSettings, HmacKeySettings,
+    MessageBusSettings, PasswordHashSettings, PasswordPolicySettings, PaymentSettings,
+    ServerTuningSettings, Settings, WorkerMonitorSettings,
+};
+use crate::event_webhooks::EventWebhooks;
 use crate::{email_client::EmailClient, routes};
-use actix_session::storage::RedisSessionStore;
 use actix_session::SessionMiddleware;
 use actix_web::cookie::Key;
+use actix_web::dev::ResponseHead;
 use actix_web::middleware;
+use actix_web::HttpRequest;
 use actix_web::{dev::Server, web, App, HttpServer};
-use actix_web_flash_messages::storage::CookieMessageStore;
-use actix_web_flash_messages::FlashMessagesFramework;
+use actix_web_flash_messages::storage::{CookieMessageStore, FlashMessageStore, LoadError, StoreError};
+use actix_web_flash_messages::{FlashMessage, FlashMessagesFramework};
 use secrecy::{ExposeSecret, Secret};
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
@@ -22,33 +30,81 @@ pub struct Application {
 }
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
+        Self::build_with_clock(configuration, std::sync::Arc::new(SystemClock)).await
+    }
+
+    // the seam the test suite uses to swap the real clock for a
+    // `crate::clock::TestClock` - production code always goes through `build`
+    pub async fn build_with_clock(
+        configuration: Settings,
+        clock: std::sync::Arc<dyn Clock>,
+    ) -> Result<Self, anyhow::Error> {
         // generate a connection to the database with the connection options
         // generated in configuration.rs
         // we use a pool of possible connections for concurrent queries
         let connection_pool = get_connection_pool(&configuration.database);
 
+        // grabbed before `client()` consumes the settings - the webhook
+        // endpoint needs it, the client itself doesn't
+        let email_webhook_token = configuration.email_client.webhook_token.clone();
         let email_client = configuration.email_client.client();
 
-        // set the address an port from config file
-        let address = format!(
-            "{}:{}",
-            configuration.application.host, configuration.application.port
-        );
-
-        // we want a random available port
-        // specifying port 0 gives a random available port assigned by the OS
-        // but we need to know which port it is so we can send requests to it
-        // create a TcpListener to track which port is assigned for the server to bind
-        let listener = TcpListener::bind(address)?;
+        // a socket handed to us by systemd (socket activation) takes
+        // precedence over binding our own - that way a restart never
+        // drops connections, systemd queues them on the socket it owns
+        let listener = match inherited_listener()? {
+            Some(listener) => listener,
+            None => {
+                // set the address an port from config file
+                let address = format!(
+                    "{}:{}",
+                    configuration.application.host, configuration.application.port
+                );
+
+                // we want a random available port
+                // specifying port 0 gives a random available port assigned by the OS
+                // but we need to know which port it is so we can send requests to it
+                // create a TcpListener to track which port is assigned for the server to bind
+                TcpListener::bind(address)?
+            }
+        };
         println!("Connected to {}", listener.local_addr()?);
         let port = listener.local_addr().unwrap().port();
+
+        // any extra addresses from the config - typically the IPv6
+        // wildcard, so dual-stack deployments answer on both families
+        let mut extra_listeners = Vec::new();
+        for address in &configuration.application.additional_bind_addresses {
+            let extra = TcpListener::bind(address)?;
+            println!("Also listening on {}", extra.local_addr()?);
+            extra_listeners.push(extra);
+        }
+
         let server = run(
             listener,
+            extra_listeners,
             connection_pool,
             email_client,
             configuration.application.base_url,
             configuration.application.hmac_secret,
+            configuration.application.hmac_key_version,
+            configuration.application.previous_hmac_secrets,
             configuration.redis_uri,
+            clock,
+            configuration.worker_monitor,
+            configuration.application.tuning,
+            configuration.alerts,
+            configuration.event_webhooks,
+            configuration.application.api_key,
+            configuration.password_policy,
+            configuration.password_hashing,
+            configuration.application.max_sessions_per_user,
+            configuration.application.behind_proxy,
+            configuration.application.trash_retention_days,
+            configuration.message_bus,
+            configuration.payments,
+            email_webhook_token,
+            configuration.email_size_guard,
         )
         .await?;
         Ok(Self { port, server })
@@ -65,6 +121,51 @@ impl Application {
     }
 }
 
+// The sd_listen_fds(3) protocol: systemd sets LISTEN_PID/LISTEN_FDS and
+// passes the already-bound socket(s) starting at file descriptor 3.
+// Returns None when we weren't socket-activated, so the caller binds
+// normally.
+fn inherited_listener() -> Result<Option<TcpListener>, anyhow::Error> {
+    #[cfg(unix)]
+    {
+        use std::os::fd::FromRawFd;
+
+        // the first activation fd always lands here
+        const SD_LISTEN_FDS_START: i32 = 3;
+
+        let (Ok(pid), Ok(fds)) = (std::env::var("LISTEN_PID"), std::env::var("LISTEN_FDS")) else {
+            return Ok(None);
+        };
+        // the variables are addressed to a specific pid - anything else is
+        // stale environment inherited from a parent process
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return Ok(None);
+        }
+        let fds: i32 = fds
+            .parse()
+            .map_err(|_| anyhow::anyhow!("LISTEN_FDS is set but is not a number: {}", fds))?;
+        if fds < 1 {
+            return Ok(None);
+        }
+        if fds > 1 {
+            tracing::warn!(
+                listen_fds = fds,
+                "More than one socket was passed in - only the first is used",
+            );
+        }
+
+        // SAFETY: per the protocol the descriptor is open, bound and ours -
+        // and nothing else in this process will ever use fd 3
+        let listener = unsafe { TcpListener::from_raw_fd(SD_LISTEN_FDS_START) };
+        // systemd hands the socket over in blocking mode
+        listener.set_nonblocking(true)?;
+        tracing::info!("Inherited the listening socket from the service manager");
+        Ok(Some(listener))
+    }
+    #[cfg(not(unix))]
+    Ok(None)
+}
+
 pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
     // connect lazy means no connections will be made until we need one
     PgPoolOptions::new().connect_lazy_with(configuration.connection_options())
@@ -81,13 +182,32 @@ pub struct ApplicationBaseUrl(pub String);
 /// # Errors
 ///
 /// This function will return an error if the server fails to start.
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
     listener: TcpListener,
+    extra_listeners: Vec<TcpListener>,
     db_pool: PgPool,
     email_client: EmailClient,
     base_url: String,
     hmac_secret: Secret<String>,
+    hmac_key_version: u32,
+    previous_hmac_secrets: Vec<HmacKeySettings>,
     redis_uri: Secret<String>,
+    clock: std::sync::Arc<dyn Clock>,
+    worker_monitor: WorkerMonitorSettings,
+    tuning: ServerTuningSettings,
+    alerts: AlertSettings,
+    event_webhooks: EventWebhookSettings,
+    api_key: Option<Secret<String>>,
+    password_policy: PasswordPolicySettings,
+    password_hashing: PasswordHashSettings,
+    max_sessions_per_user: usize,
+    behind_proxy: bool,
+    trash_retention_days: i32,
+    message_bus: MessageBusSettings,
+    payments: PaymentSettings,
+    email_webhook_token: Option<Secret<String>>,
+    email_size_guard: EmailSizeGuardSettings,
 ) -> Result<Server, anyhow::Error> {
     // argument TcpListener allows us to find the port that is assigned
     // to this server by the OS - only needed if you are using a random port (port 0)
@@ -100,17 +220,89 @@ pub async fn run(
     // pass out multiple refs
     let email_client = web::Data::new(email_client);
 
+    // one origin to rule them all - see crate::canonical for when this
+    // actually redirects anything
+    let canonical_origin =
+        web::Data::new(crate::canonical::CanonicalOrigin::new(&base_url, behind_proxy));
+
     // this is the address we can the confirmation link to navigate to
     let base_url = web::Data::new(ApplicationBaseUrl(base_url));
 
     // for signed cookies, we make a location to store cookies, and register a message framework
     // this is HMAC tagginging key - defined in config base.yaml
     let signing_key = Key::from(hmac_secret.expose_secret().as_bytes());
-    let message_store = CookieMessageStore::builder(signing_key.clone()).build();
-    let message_framework = FlashMessagesFramework::builder(message_store).build();
+    // flash cookies signed with a retired key are still readable during
+    // the rotation grace period - new cookies always use the current key
+    let mut message_stores = vec![CookieMessageStore::builder(signing_key.clone()).build()];
+    for previous in &previous_hmac_secrets {
+        let key = Key::from(previous.secret.expose_secret().as_bytes());
+        message_stores.push(CookieMessageStore::builder(key).build());
+    }
+    let message_framework =
+        FlashMessagesFramework::builder(FallbackMessageStore(message_stores)).build();
+
+    // the signer for confirmation-style links - same secrets as the cookies
+    let mut link_signer =
+        crate::signed_link::LinkSigner::with_current_key(hmac_key_version, hmac_secret.clone());
+    for previous in previous_hmac_secrets {
+        link_signer.add_previous_key(previous.version, previous.secret);
+    }
+    let link_signer = web::Data::new(link_signer);
+
+    // handlers extract this as `web::Data<dyn Clock>` - unsized, so it has
+    // to be built from the Arc rather than with `Data::new`
+    let clock: web::Data<dyn Clock> = web::Data::from(clock);
+
+    // the diagnostics page compares heartbeat ages against these thresholds
+    let worker_monitor = web::Data::new(worker_monitor);
+
+    // the shared alerter - handlers (e.g. login) report incidents to it,
+    // it decides whether anything actually goes out
+    let alerter = web::Data::new(Alerter::new(&alerts, email_client.clone().into_inner())?);
+
+    // and its happy-path sibling - confirmations and milestones
+    let event_webhooks = web::Data::new(EventWebhooks::new(&event_webhooks));
+
+    // the optional analytics mirror - a no-op unless a bus is configured
+    // (and the `message-bus` feature was compiled in)
+    let message_bus = web::Data::new(crate::message_bus::MessageBus::new(&message_bus));
+
+    // who answers on which host - single-tenant unless rows were added to
+    // the tenants table (see crate::tenancy)
+    let tenants = web::Data::new(crate::tenancy::TenantDirectory::load(&db_pool).await);
+
+    // the optional Stripe-backed paid tier (see crate::payments)
+    let payments = web::Data::new(crate::payments::Payments::new(&payments));
+
+    // the shared secret guarding the bounce/complaint webhook
+    let email_webhook_token = web::Data::new(routes::EmailWebhookToken(email_webhook_token));
+
+    // the shared secret for the machine-facing /api/v1 routes
+    let api_key = web::Data::new(routes::ApiKey(api_key));
+
+    // what /admin/password accepts as a new password
+    let password_policy = web::Data::new(password_policy);
+
+    // the Argon2 variant and costs new hashes are made with
+    let password_hashing = web::Data::new(password_hashing);
+
+    // how many sessions one user may hold at once
+    let session_limit = web::Data::new(crate::session_state::SessionLimit(max_sessions_per_user));
+
+    // how long the trash holds on to deleted rows
+    let trash_retention = web::Data::new(routes::TrashRetention(trash_retention_days));
+
+    // the oversized-issue warning (or block) on the publish form
+    let email_size_guard = web::Data::new(email_size_guard);
 
     // similar store but for sessions:
-    let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
+    // (actix-session only signs with a single key - session cookies issued
+    // before a rotation simply fail validation and the user logs in again)
+    // pooled, sentinel-aware and failure-tolerant - see crate::redis_sessions
+    let redis_store = crate::redis_sessions::build_store(&redis_uri).await?;
+
+    // the same store, extractable - /ready and /admin/diagnostics ping it
+    let session_store = web::Data::new(redis_store.clone());
 
     // create a server - this binds to socket and has options for
     // security etc, but needs an App to do something - passed in a closure
@@ -120,6 +312,12 @@ pub async fn run(
         App::new()
             // register 'middleware'
             .wrap(TracingLogger::default()) //we wrap the App in a logger - we need an implementation of the Log Trait to receive - done in main!
+            // wrong host / plain http -> 301 to the canonical base url
+            .wrap(middleware::from_fn(
+                crate::canonical::enforce_canonical_origin,
+            ))
+            // which newsletter this request is for - see crate::tenancy
+            .wrap(middleware::from_fn(crate::tenancy::resolve_tenant))
             .wrap(message_framework.clone()) // for secure cookies
             .wrap(SessionMiddleware::new(
                 redis_store.clone(),
@@ -129,22 +327,291 @@ pub async fn run(
             .route("/", web::get().to(routes::home))
             // .route("/", web::post().to(routes::home_post))
             .route("/health_check", web::get().to(routes::health_check))
+            .route("/version", web::get().to(routes::version))
+            .route("/ready", web::get().to(routes::ready_check))
+            .route("/metrics", web::get().to(routes::metrics))
+            .route("/archive", web::get().to(routes::archive))
+            .route("/archive/{issue_id}", web::get().to(routes::archive_issue))
+            // a paying reader asking for a signed reading link to a
+            // paywalled issue
+            .route(
+                "/archive/{issue_id}/access",
+                web::post().to(routes::request_archive_access),
+            )
+            .route("/rss", web::get().to(routes::rss_feed))
+            .route("/sitemap.xml", web::get().to(routes::sitemap_xml))
+            .route("/robots.txt", web::get().to(routes::robots_txt))
+            // the preference center - public, the signed link is the credential
+            .route("/preferences", web::get().to(routes::preferences_form))
+            .route("/preferences", web::post().to(routes::save_preferences))
+            // the paid tier - checkout starts here, Stripe reports back on
+            // the webhook (authenticated by its signature, not a session)
+            .route("/premium", web::get().to(routes::premium_page))
+            .route("/premium", web::post().to(routes::start_checkout))
+            .route("/webhooks/stripe", web::post().to(routes::stripe_webhook))
+            // the email provider's bounce/complaint notifications
+            // (authenticated by a shared token, not a session)
+            .route(
+                "/webhooks/email",
+                web::post().to(routes::email_feedback_webhook),
+            )
+            // reader replies forwarded by the provider's inbound stream -
+            // same shared-token guard as the feedback webhook
+            .route(
+                "/webhooks/inbound",
+                web::post().to(routes::inbound_email_webhook),
+            )
+            // subscriber self-service - a magic link is the credential,
+            // there is no password to forget
+            .route("/my-subscription", web::get().to(routes::my_subscription))
+            .route(
+                "/my-subscription",
+                web::post().to(routes::request_login_link),
+            )
             .route("/login", web::get().to(routes::login_form))
             .route("/login", web::post().to(routes::login))
             .route("/subscriptions", web::post().to(routes::subscribe))
+            // the machine-facing API (Zapier and friends) - guarded by the
+            // X-Api-Key header, not by a session
+            .route(
+                "/api/v1/subscribers",
+                web::post().to(routes::create_subscriber),
+            )
             .route("/subscriptions/confirm", web::get().to(routes::confirm))
+            .route(
+                "/subscriptions/change_email",
+                web::post().to(routes::request_email_change),
+            )
+            .route(
+                "/subscriptions/change_email/confirm",
+                web::get().to(routes::confirm_email_change),
+            )
+            .route("/subscriptions/reconfirm", web::get().to(routes::reconfirm))
+            // the "this wasn't me" kill switch from security emails - no
+            // session required, the signed link is the credential
+            .route("/account/lock", web::get().to(routes::lock_account))
+            // mail clients POST here straight from the List-Unsubscribe
+            // header (RFC 8058) - again, the signed link is the credential
+            .route(
+                "/unsubscribe/one-click",
+                web::post().to(routes::one_click_unsubscribe),
+            )
+            // the visible unsubscribe link in the compliance footer lands
+            // here too - a human click arrives as a GET, not a POST
+            .route(
+                "/unsubscribe/one-click",
+                web::get().to(routes::one_click_unsubscribe),
+            )
+            .route(
+                "/track/open/{issue_id}/{subscriber_id}",
+                web::get().to(routes::track_open),
+            )
+            .route(
+                "/track/click/{issue_id}/{subscriber_id}",
+                web::get().to(routes::track_click),
+            )
+            // one-click issue feedback from the email footer
+            .route(
+                "/feedback/{issue_id}",
+                web::get().to(routes::record_feedback),
+            )
+            // poll voting, same scheme
+            .route(
+                "/poll/{poll_id}/vote",
+                web::get().to(routes::record_poll_vote),
+            )
             // group the /admin routes into a scope - and we will add a middleware just to them
             .service(
                 web::scope("/admin")
+                    // authorization - registered first so it runs inside
+                    // authentication (the last wrap is the outermost)
+                    .wrap(middleware::from_fn(
+                        authentication::enforce_role_permissions,
+                    ))
                     // the middleware
                     .wrap(middleware::from_fn(authentication::reject_anonymous_users))
                     // the routes to wrap
                     .route("/dashboard", web::get().to(routes::admin_dashboard))
+                    .route("/search", web::get().to(routes::admin_search))
+                    .route(
+                        "/deliverability",
+                        web::get().to(routes::deliverability_dashboard),
+                    )
+                    .route(
+                        "/deliverability/data",
+                        web::get().to(routes::deliverability_data),
+                    )
+                    .route(
+                        "/diagnostics",
+                        web::get().to(routes::worker_diagnostics),
+                    )
+                    .route("/replies", web::get().to(routes::reply_list))
+                    .route("/activity", web::get().to(routes::activity_feed))
+                    .route(
+                        "/reengagement",
+                        web::get().to(routes::reengagement_form),
+                    )
+                    .route(
+                        "/reengagement",
+                        web::post().to(routes::run_reengagement),
+                    )
+                    .route(
+                        "/subscribers/import",
+                        web::get().to(routes::import_form),
+                    )
+                    .route(
+                        "/subscribers/import",
+                        web::post().to(routes::import_subscribers),
+                    )
+                    .route("/settings", web::get().to(routes::site_settings_form))
+                    .route("/settings", web::post().to(routes::save_site_settings))
+                    .route(
+                        "/settings/pages",
+                        web::get().to(routes::edit_pages_form),
+                    )
+                    .route("/settings/pages", web::post().to(routes::save_page))
+                    .route(
+                        "/settings/domain",
+                        web::get().to(routes::link_domain_form),
+                    )
+                    .route(
+                        "/settings/domain",
+                        web::post().to(routes::save_link_domain),
+                    )
+                    .route(
+                        "/settings/domain/verify",
+                        web::post().to(routes::verify_link_domain),
+                    )
+                    .route(
+                        "/settings/templates",
+                        web::get().to(routes::email_templates_form),
+                    )
+                    .route(
+                        "/settings/templates",
+                        web::post().to(routes::save_email_templates),
+                    )
+                    .route(
+                        "/settings/templates/footer",
+                        web::post().to(routes::save_compliance_footer),
+                    )
+                    .route(
+                        "/settings/templates/reply-ack",
+                        web::post().to(routes::save_reply_ack),
+                    )
+                    .route("/delivery/pause", web::post().to(routes::pause_delivery))
+                    .route("/delivery/resume", web::post().to(routes::resume_delivery))
+                    .route(
+                        "/impersonate",
+                        web::post().to(routes::start_impersonation),
+                    )
+                    .route(
+                        "/impersonate/stop",
+                        web::post().to(routes::stop_impersonation),
+                    )
+                    .route(
+                        "/users/deactivate",
+                        web::post().to(routes::deactivate_user),
+                    )
+                    .route(
+                        "/subscribers",
+                        web::get().to(routes::subscriber_list),
+                    )
+                    .route(
+                        "/subscribers/bulk",
+                        web::post().to(routes::bulk_subscriber_action),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/delete",
+                        web::post().to(routes::delete_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        web::get().to(routes::subscriber_detail),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/notes",
+                        web::post().to(routes::add_subscriber_note),
+                    )
+                    .route("/segments", web::get().to(routes::segments_page))
+                    .route("/segments", web::post().to(routes::create_segment))
+                    .route(
+                        "/segments/{segment_id}/delete",
+                        web::post().to(routes::delete_segment),
+                    )
+                    .route("/trash", web::get().to(routes::trash_page))
+                    .route(
+                        "/trash/subscribers/{subscriber_id}/restore",
+                        web::post().to(routes::restore_subscriber),
+                    )
+                    .route(
+                        "/trash/subscribers/{subscriber_id}/purge",
+                        web::post().to(routes::purge_subscriber),
+                    )
+                    .route(
+                        "/trash/drafts/{draft_id}/restore",
+                        web::post().to(routes::restore_draft),
+                    )
+                    .route(
+                        "/trash/drafts/{draft_id}/purge",
+                        web::post().to(routes::purge_draft),
+                    )
                     .route("/password", web::get().to(routes::change_password_form))
                     .route("/password", web::post().to(routes::change_password))
                     .route("/logout", web::post().to(routes::log_out))
+                    .route(
+                        "/newsletter/export",
+                        web::get().to(routes::export_issues),
+                    )
+                    .route(
+                        "/newsletter/compare",
+                        web::get().to(routes::compare_issues),
+                    )
+                    .route(
+                        "/newsletter/recipient_count",
+                        web::get().to(routes::recipient_count),
+                    )
+                    .route(
+                        "/newsletter/render",
+                        web::post().to(routes::render_preview),
+                    )
+                    .route(
+                        "/newsletter/drafts",
+                        web::get().to(routes::list_drafts),
+                    )
+                    .route(
+                        "/newsletter/drafts",
+                        web::post().to(routes::create_draft),
+                    )
+                    .route(
+                        "/newsletter/drafts/{draft_id}",
+                        web::get().to(routes::edit_draft_form),
+                    )
+                    .route(
+                        "/newsletter/drafts/{draft_id}",
+                        web::post().to(routes::save_draft),
+                    )
+                    .route(
+                        "/newsletter/drafts/{draft_id}/autosave",
+                        web::put().to(routes::autosave_draft),
+                    )
+                    .route(
+                        "/newsletter/drafts/{draft_id}/autosave/restore",
+                        web::post().to(routes::restore_autosave),
+                    )
+                    .route(
+                        "/newsletter/drafts/{draft_id}/autosave/discard",
+                        web::post().to(routes::discard_autosave),
+                    )
+                    .route(
+                        "/newsletter/drafts/{draft_id}/delete",
+                        web::post().to(routes::delete_draft),
+                    )
                     .route("/newsletter", web::get().to(routes::send_newsletter_form))
-                    .route("/newsletter", web::post().to(routes::send_newsletter)),
+                    .route("/newsletter", web::post().to(routes::send_newsletter))
+                    .route(
+                        "/newsletter/continue",
+                        web::post().to(routes::continue_send),
+                    ),
             )
             // define 'application state' - data that will be passed with the request and
             // accessible by having an argument web::Data<type> on your route receiver function
@@ -153,10 +620,49 @@ pub async fn run(
             .app_data(db_pool.clone()) // passes the connection to db as part of an 'application state'
             .app_data(email_client.clone()) // same for the email client
             .app_data(base_url.clone()) // same for the url for conf. email
+            .app_data(canonical_origin.clone()) // read by the redirect middleware
+            .app_data(link_signer.clone()) // for signing/verifying confirmation links
+            .app_data(clock.clone()) // the time source - swappable in tests
+            .app_data(worker_monitor.clone()) // thresholds for /admin/diagnostics
+            .app_data(alerter.clone()) // operator alerts (email/webhook)
+            .app_data(event_webhooks.clone()) // chat notifications for good news
+            .app_data(message_bus.clone()) // mirrors domain events to NATS
+            .app_data(tenants.clone()) // host-to-tenant resolution
+            .app_data(payments.clone()) // Stripe checkout + webhook verification
+            .app_data(email_webhook_token.clone()) // guards /webhooks/email
+            .app_data(api_key.clone()) // guards /api/v1
+            .app_data(password_policy.clone()) // enforced on password changes
+            .app_data(password_hashing.clone()) // Argon2 settings for new hashes
+            .app_data(session_limit.clone()) // concurrent-session cap
+            .app_data(trash_retention.clone()) // retention period for /admin/trash
+            .app_data(session_store.clone()) // Redis health for /ready and /admin/diagnostics
+            .app_data(email_size_guard.clone()) // the Gmail-clipping size guard
             .app_data(web::Data::new(HmacSecret(hmac_secret.clone()))) // a secret appended to http requests so we can check it's ours
     })
-    .listen(listener)? // binds to the port identified by listener
-    .run(); // run the server
+    // connection tuning from the configuration - see ServerTuningSettings.
+    // HTTP/2 needs no switch here: actix negotiates it over ALPN whenever
+    // TLS terminates in-process; in this deployment TLS terminates at the
+    // platform's load balancer, so connections arrive as HTTP/1.1
+    .keep_alive(std::time::Duration::from_secs(tuning.keep_alive_seconds))
+    .client_request_timeout(std::time::Duration::from_millis(
+        tuning.client_request_timeout_milliseconds,
+    ))
+    .max_connections(tuning.max_connections);
+
+    // zero means "leave it to actix" - one worker per logical core
+    let server = if tuning.worker_threads > 0 {
+        server.workers(tuning.worker_threads)
+    } else {
+        server
+    };
+
+    let mut server = server.listen(listener)?; // binds to the port identified by listener
+    // every additional address serves the same App - actix accepts on
+    // all registered listeners with the one worker pool
+    for extra in extra_listeners {
+        server = server.listen(extra)?;
+    }
+    let server = server.run(); // run the server
 
     //.await // Don't call await here - if you want to run other tasks async, return the server.
     // if you prefer to have the server as blocking - this fn can be async and call await here
@@ -166,3 +672,37 @@ pub async fn run(
 
 #[derive(Clone)]
 pub struct HmacSecret(pub Secret<String>);
+
+// A flash-message store that writes with the current signing key but can
+// read cookies signed with any key still inside the rotation grace period.
+struct FallbackMessageStore(Vec<CookieMessageStore>);
+
+impl FlashMessageStore for FallbackMessageStore {
+    fn load(&self, request: &HttpRequest) -> Result<Vec<FlashMessage>, LoadError> {
+        let mut last_error = None;
+        for store in &self.0 {
+            match store.load(request) {
+                // a failed integrity check may just mean the cookie was
+                // signed with an older key - fall through to the next one
+                Err(e @ LoadError::IntegrityCheckFailed(_)) => last_error = Some(e),
+                outcome => return outcome,
+            }
+        }
+        match last_error {
+            Some(e) => Err(e),
+            // no stores configured can't happen - the current key is
+            // always present - but the compiler doesn't know that
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn store(
+        &self,
+        messages: &[FlashMessage],
+        request: HttpRequest,
+        response: &mut ResponseHead,
+    ) -> Result<(), StoreError> {
+        // always write with the current key
+        self.0[0].store(messages, request, response)
+    }
+}
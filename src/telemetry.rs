@@ -1,10 +1,13 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
 use tokio::task::JoinHandle;
 use tracing::subscriber::set_global_default;
 use tracing::Subscriber;
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
+use tracing_subscriber::filter::filter_fn;
 use tracing_subscriber::fmt::MakeWriter;
-use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
+use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Layer, Registry};
 
 /// Compose multiple layers into a `tracing`'s subscriber.
 ///
@@ -41,9 +44,87 @@ where
     // and output data
     // .with() allows you to add 'extension traits'
     Registry::default()
-        .with(env_filter)
-        .with(JsonStorageLayer)
-        .with(formatting_layer)
+        // the query-timing counters get their own filter: sqlx emits
+        // ordinary statements at TRACE (see DatabaseSettings), which the
+        // env filter would drop before the log output - the counters
+        // still need to see every one of them
+        .with(QueryTimingLayer.with_filter(filter_fn(|metadata| {
+            metadata.target().starts_with("sqlx::query")
+        })))
+        .with(
+            JsonStorageLayer
+                .and_then(formatting_layer)
+                .with_filter(env_filter),
+        )
+}
+
+/// Cumulative timings for one statement shape, as counted by
+/// [`QueryTimingLayer`] since the process started.
+#[derive(Clone, Copy, Default)]
+pub struct QueryStats {
+    pub count: u64,
+    pub total_secs: f64,
+    pub max_secs: f64,
+}
+
+// keyed by sqlx's statement summary (the first few words of the SQL) -
+// enough to tell statements apart without unbounded cardinality, since
+// bind parameters never appear in it
+static QUERY_STATS: Mutex<BTreeMap<String, QueryStats>> = Mutex::new(BTreeMap::new());
+
+/// Everything the counters have seen so far, heaviest total time first.
+pub fn query_timing_snapshot() -> Vec<(String, QueryStats)> {
+    let stats = QUERY_STATS.lock().unwrap();
+    let mut rows: Vec<_> = stats.iter().map(|(k, v)| (k.clone(), *v)).collect();
+    rows.sort_by(|a, b| b.1.total_secs.total_cmp(&a.1.total_secs));
+    rows
+}
+
+// a layer that watches sqlx's per-statement events go past and keeps
+// running totals - the slow-statement WARNs tell you about one bad query,
+// these tell you which statement your process spends its database time on
+struct QueryTimingLayer;
+
+impl<S: Subscriber> Layer<S> for QueryTimingLayer {
+    fn on_event(
+        &self,
+        event: &tracing::Event<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let mut visitor = QueryTimingVisitor::default();
+        event.record(&mut visitor);
+        let Some(summary) = visitor.summary else {
+            return;
+        };
+        let mut stats = QUERY_STATS.lock().unwrap();
+        let entry = stats.entry(summary).or_default();
+        entry.count += 1;
+        entry.total_secs += visitor.elapsed_secs;
+        entry.max_secs = entry.max_secs.max(visitor.elapsed_secs);
+    }
+}
+
+// plucks the two fields we care about out of sqlx's event
+#[derive(Default)]
+struct QueryTimingVisitor {
+    summary: Option<String>,
+    elapsed_secs: f64,
+}
+
+impl tracing::field::Visit for QueryTimingVisitor {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        if field.name() == "summary" {
+            self.summary = Some(value.to_string());
+        }
+    }
+
+    fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
+        if field.name() == "elapsed_secs" {
+            self.elapsed_secs = value;
+        }
+    }
+
+    fn record_debug(&mut self, _field: &tracing::field::Field, _value: &dyn std::fmt::Debug) {}
 }
 
 /// Register a subscriber as global default to process span data.
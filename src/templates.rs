@@ -0,0 +1,210 @@
+//! Shared HTML building blocks for the server-rendered pages.
+//!
+//! The forms used to be ad-hoc inline strings, each with its own ideas
+//! about labelling - most inputs had no programmatic label at all, which
+//! screen readers surface as "edit text, blank". Pages assemble their
+//! markup from these components instead: every input gets an explicit
+//! `<label for>`, errors are tied to their field with `aria-describedby`,
+//! the first field (or the first invalid one) takes focus, and the shell
+//! carries the `lang` attribute exactly once. Values are escaped here,
+//! so callers pass raw strings.
+
+use std::fmt::Write;
+
+/// The document shell - everything page-specific goes inside `<main>`.
+pub fn page(title: &str, body_html: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <title>{title}</title>
+</head>
+<body>
+    <main>
+{body_html}    </main>
+</body>
+</html>"#,
+        title = htmlescape::encode_minimal(title),
+    )
+}
+
+/// One form field's worth of configuration. Spread-friendly:
+/// `Field { label: "Username", name: "username", ..Field::default() }`.
+pub struct Field<'a> {
+    pub label: &'a str,
+    pub name: &'a str,
+    /// the input's `type` attribute
+    pub kind: &'a str,
+    pub placeholder: &'a str,
+    pub value: &'a str,
+    /// empty means "omit the attribute"
+    pub autocomplete: &'a str,
+    pub required: bool,
+    /// where focus lands when the page opens - at most one per page
+    pub autofocus: bool,
+    /// rendered next to the field and announced with it (aria-describedby)
+    pub error: Option<&'a str>,
+}
+
+impl Default for Field<'_> {
+    fn default() -> Self {
+        Self {
+            label: "",
+            name: "",
+            kind: "text",
+            placeholder: "",
+            value: "",
+            autocomplete: "",
+            required: false,
+            autofocus: false,
+            error: None,
+        }
+    }
+}
+
+/// A labelled `<input>` - the label points at the field by id, so
+/// clicking it focuses the input and assistive tech announces it.
+pub fn text_field(field: &Field) -> String {
+    let mut html = format!(
+        "        <p>\n            <label for=\"{name}\">{label}</label><br>\n            <input type=\"{kind}\" id=\"{name}\" name=\"{name}\"",
+        name = htmlescape::encode_attribute(field.name),
+        label = htmlescape::encode_minimal(field.label),
+        kind = htmlescape::encode_attribute(field.kind),
+    );
+    push_common_attributes(&mut html, field);
+    if !field.value.is_empty() {
+        write!(
+            html,
+            " value=\"{}\"",
+            htmlescape::encode_attribute(field.value)
+        )
+        .unwrap();
+    }
+    html.push('>');
+    push_error(&mut html, field);
+    html.push_str("\n        </p>\n");
+    html
+}
+
+/// A labelled `<textarea>` - same associations as [`text_field`].
+pub fn textarea_field(field: &Field, rows: u32) -> String {
+    let mut html = format!(
+        "        <p>\n            <label for=\"{name}\">{label}</label><br>\n            <textarea id=\"{name}\" name=\"{name}\" rows=\"{rows}\"",
+        name = htmlescape::encode_attribute(field.name),
+        label = htmlescape::encode_minimal(field.label),
+    );
+    push_common_attributes(&mut html, field);
+    write!(
+        html,
+        ">{}</textarea>",
+        htmlescape::encode_minimal(field.value)
+    )
+    .unwrap();
+    push_error(&mut html, field);
+    html.push_str("\n        </p>\n");
+    html
+}
+
+fn push_common_attributes(html: &mut String, field: &Field) {
+    if !field.placeholder.is_empty() {
+        write!(
+            html,
+            " placeholder=\"{}\"",
+            htmlescape::encode_attribute(field.placeholder)
+        )
+        .unwrap();
+    }
+    if !field.autocomplete.is_empty() {
+        write!(
+            html,
+            " autocomplete=\"{}\"",
+            htmlescape::encode_attribute(field.autocomplete)
+        )
+        .unwrap();
+    }
+    if field.required {
+        html.push_str(" required aria-required=\"true\"");
+    }
+    if field.autofocus {
+        html.push_str(" autofocus");
+    }
+    if field.error.is_some() {
+        write!(
+            html,
+            " aria-invalid=\"true\" aria-describedby=\"{}-error\"",
+            htmlescape::encode_attribute(field.name)
+        )
+        .unwrap();
+    }
+}
+
+fn push_error(html: &mut String, field: &Field) {
+    if let Some(error) = field.error {
+        write!(
+            html,
+            "\n            <span id=\"{}-error\" class=\"field-error\">{}</span>",
+            htmlescape::encode_attribute(field.name),
+            htmlescape::encode_minimal(error),
+        )
+        .unwrap();
+    }
+}
+
+/// A group of related controls (checkboxes, radios) under one announced
+/// heading - `<fieldset>`/`<legend>` is what screen readers understand.
+pub fn fieldset(legend: &str, controls_html: &str) -> String {
+    format!(
+        "        <fieldset>\n            <legend>{}</legend>\n{}        </fieldset>\n",
+        htmlescape::encode_minimal(legend),
+        controls_html,
+    )
+}
+
+pub fn submit_button(text: &str) -> String {
+    format!(
+        "        <button type=\"submit\">{}</button>\n",
+        htmlescape::encode_minimal(text)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{text_field, Field};
+
+    #[test]
+    fn labels_point_at_their_field() {
+        let html = text_field(&Field {
+            label: "Username",
+            name: "username",
+            ..Field::default()
+        });
+        assert!(html.contains(r#"<label for="username">Username</label>"#));
+        assert!(html.contains(r#"id="username""#));
+    }
+
+    #[test]
+    fn errors_are_associated_and_flagged() {
+        let html = text_field(&Field {
+            label: "Email",
+            name: "email",
+            error: Some("Not a valid address"),
+            ..Field::default()
+        });
+        assert!(html.contains(r#"aria-invalid="true""#));
+        assert!(html.contains(r#"aria-describedby="email-error""#));
+        assert!(html.contains(r#"<span id="email-error" class="field-error">Not a valid address</span>"#));
+    }
+
+    #[test]
+    fn values_are_escaped() {
+        let html = text_field(&Field {
+            label: "Title",
+            name: "title",
+            value: r#""><script>"#,
+            ..Field::default()
+        });
+        assert!(!html.contains("<script>"));
+    }
+}
@@ -0,0 +1,217 @@
+//! Multi-tenant mode: one deployment hosting several independent
+//! newsletters, told apart by the host the request arrived on. The
+//! `tenants` table maps hosts to tenants; a NULL host marks the
+//! catch-all default tenant (the nil uuid) that owns every pre-existing
+//! row and every request on an unclaimed host - so a single-tenant
+//! deployment behaves exactly as before without configuring anything.
+//!
+//! The directory is loaded once at startup - adding a tenant means a
+//! restart, which is fine at the rate tenants appear. Resolution happens
+//! in middleware; handlers that care extract a [`Tenant`].
+//!
+//! A tenant can also bring its own link domain (news.example.com) for
+//! the confirmation, unsubscribe and archive links we hand out - set and
+//! verified under /admin/settings/domain, honoured via
+//! [`Tenant::link_base_url_or`] once DNS proves ownership.
+//!
+//! Scope so far: signups are stamped with the resolving tenant, issues
+//! with their publisher's tenant, and delivery only fans an issue out to
+//! its own tenant's subscribers. The admin screens still show everything
+//! to everyone - per-tenant admin separation rides on `users.tenant_id`
+//! and lands as those screens grow filters.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// The tenant every row belonged to before tenants existed.
+pub const DEFAULT_TENANT_ID: Uuid = Uuid::nil();
+
+// the resolved tenant for a request - cloned into the request extensions
+// by the middleware, extracted by handlers that need it
+#[derive(Clone)]
+pub struct Tenant {
+    pub id: Uuid,
+    pub name: String,
+    // "https://<link_domain>", present only once the domain is verified
+    pub link_base_url: Option<String>,
+}
+
+impl Tenant {
+    /// The origin this tenant's outgoing links should live under - the
+    /// verified custom domain if there is one, the deployment's shared
+    /// base url otherwise.
+    pub fn link_base_url_or(&self, fallback: &str) -> String {
+        self.link_base_url
+            .clone()
+            .unwrap_or_else(|| fallback.to_string())
+    }
+}
+
+// every tenant, keyed by the host that resolves to it
+pub struct TenantDirectory {
+    by_host: HashMap<String, Tenant>,
+    default: Tenant,
+}
+
+impl TenantDirectory {
+    /// Load the directory from the `tenants` table. Infallible by design:
+    /// if the table can't be read (the database is still coming up, say)
+    /// we fall back to single-tenant mode rather than refusing to boot.
+    pub async fn load(pool: &PgPool) -> Self {
+        let rows = match sqlx::query!(
+            "SELECT id, name, host, link_domain, link_domain_verified_at FROM tenants"
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(rows) => rows,
+            Err(e) => {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    "Failed to load the tenant directory. Running single-tenant.",
+                );
+                return Self::single_tenant();
+            }
+        };
+        let mut by_host = HashMap::new();
+        let mut default = None;
+        for row in rows {
+            let tenant = Tenant {
+                id: row.id,
+                name: row.name,
+                // an unverified domain is ignored - anyone can type a
+                // domain into a form, only DNS proves they own it
+                link_base_url: row
+                    .link_domain
+                    .filter(|_| row.link_domain_verified_at.is_some())
+                    .map(|domain| format!("https://{}", domain)),
+            };
+            match row.host {
+                Some(host) => {
+                    by_host.insert(host, tenant);
+                }
+                // the NULL-host row is the catch-all
+                None => default = Some(tenant),
+            }
+        }
+        Self {
+            by_host,
+            // the migration seeds the default row, but a directory must
+            // always be able to resolve something
+            default: default.unwrap_or_else(|| Self::single_tenant().default),
+        }
+    }
+
+    fn single_tenant() -> Self {
+        Self {
+            by_host: HashMap::new(),
+            default: Tenant {
+                id: DEFAULT_TENANT_ID,
+                name: "default".to_string(),
+                link_base_url: None,
+            },
+        }
+    }
+
+    fn resolve(&self, host: &str) -> Tenant {
+        // hosts are stored without a port, requests may carry one
+        let host = host.split(':').next().unwrap_or(host);
+        self.by_host.get(host).unwrap_or(&self.default).clone()
+    }
+}
+
+// stamps every request with its tenant, so handlers can just extract it
+pub async fn resolve_tenant(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if let Some(directory) = req.app_data::<web::Data<TenantDirectory>>() {
+        // connection_info honours X-Forwarded-Host behind a proxy
+        let host = req.connection_info().host().to_string();
+        let tenant = directory.resolve(&host);
+        req.extensions_mut().insert(tenant);
+    }
+    next.call(req).await
+}
+
+impl FromRequest for Tenant {
+    type Error = actix_web::Error;
+    type Future = std::future::Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        std::future::ready(req.extensions().get::<Tenant>().cloned().ok_or_else(|| {
+            // only reachable if the middleware wasn't registered
+            actix_web::error::ErrorInternalServerError("No tenant was resolved for this request")
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tenant, TenantDirectory, DEFAULT_TENANT_ID};
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    fn directory_with(host: &str, tenant_id: Uuid) -> TenantDirectory {
+        let mut by_host = HashMap::new();
+        by_host.insert(
+            host.to_string(),
+            Tenant {
+                id: tenant_id,
+                name: "acme".to_string(),
+                link_base_url: None,
+            },
+        );
+        TenantDirectory {
+            by_host,
+            default: Tenant {
+                id: DEFAULT_TENANT_ID,
+                name: "default".to_string(),
+                link_base_url: None,
+            },
+        }
+    }
+
+    #[test]
+    fn a_mapped_host_resolves_to_its_tenant() {
+        let tenant_id = Uuid::new_v4();
+        let directory = directory_with("acme.example.com", tenant_id);
+        assert_eq!(directory.resolve("acme.example.com").id, tenant_id);
+    }
+
+    #[test]
+    fn the_port_is_ignored_when_resolving() {
+        let tenant_id = Uuid::new_v4();
+        let directory = directory_with("acme.example.com", tenant_id);
+        assert_eq!(directory.resolve("acme.example.com:8000").id, tenant_id);
+    }
+
+    #[test]
+    fn an_unclaimed_host_falls_back_to_the_default_tenant() {
+        let directory = directory_with("acme.example.com", Uuid::new_v4());
+        assert_eq!(directory.resolve("other.example.com").id, DEFAULT_TENANT_ID);
+    }
+
+    #[test]
+    fn links_use_the_custom_domain_only_when_one_is_set() {
+        let mut tenant = Tenant {
+            id: Uuid::new_v4(),
+            name: "acme".to_string(),
+            link_base_url: None,
+        };
+        assert_eq!(
+            tenant.link_base_url_or("https://shared.example.com"),
+            "https://shared.example.com"
+        );
+        tenant.link_base_url = Some("https://news.acme.com".to_string());
+        assert_eq!(
+            tenant.link_base_url_or("https://shared.example.com"),
+            "https://news.acme.com"
+        );
+    }
+}
@@ -1,9 +1,45 @@
 use actix_web::http::header::LOCATION;
 use actix_web::HttpResponse;
+use actix_web_flash_messages::{IncomingFlashMessages, Level};
 use regex::Regex;
 use std::collections::HashMap;
+use std::fmt::Write;
 // use actix_web::http::StatusCode;
 
+// how long the milder flash messages invite the template layer to keep
+// them around before fading them out
+const FLASH_AUTO_DISMISS_MILLISECONDS: u32 = 8_000;
+
+// The one way flash messages become markup - every page that shows them
+// goes through here, so a given severity looks (and, via the ARIA role,
+// sounds) the same everywhere. Errors and warnings interrupt
+// (role="alert") and stay on screen; anything milder is announced
+// politely (role="status") and carries its dismissal delay as a data
+// attribute for the front-end to act on.
+pub fn flash_messages_html(flash_messages: &IncomingFlashMessages) -> String {
+    let mut html = String::new();
+    for message in flash_messages.iter() {
+        let (class, role, auto_dismiss) = match message.level() {
+            Level::Error => ("flash-error", "alert", None),
+            Level::Warning => ("flash-warning", "alert", None),
+            _ => ("flash-info", "status", Some(FLASH_AUTO_DISMISS_MILLISECONDS)),
+        };
+        let dismiss_attribute = auto_dismiss
+            .map(|ms| format!(" data-auto-dismiss-ms=\"{}\"", ms))
+            .unwrap_or_default();
+        writeln!(
+            html,
+            "<p class=\"{}\" role=\"{}\"{}><i>{}</i></p>",
+            class,
+            role,
+            dismiss_attribute,
+            htmlescape::encode_minimal(message.content()),
+        )
+        .unwrap();
+    }
+    html
+}
+
 // take a generic, displayable error
 // Return an opaque 500 while preserving the error root's cause for logging.
 pub fn e500<T>(e: T) -> actix_web::Error
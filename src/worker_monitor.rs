@@ -0,0 +1,144 @@
+//! Watches the delivery pipeline from the outside: the workers heartbeat
+//! into `worker_heartbeats` (see issue_delivery_worker.rs), and this task
+//! periodically checks that somebody is still beating and that the queue
+//! isn't silting up. Problems are logged, and emailed to the configured
+//! address if one is set.
+
+use crate::alerts::Alerter;
+use crate::clock::{Clock, SystemClock};
+use crate::configuration::{Settings, WorkerMonitorSettings};
+use crate::startup;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Everything currently wrong with the delivery pipeline, in human-readable
+/// form - an empty list means healthy. Shared between the monitor task and
+/// the admin diagnostics page.
+pub async fn find_problems(
+    pool: &PgPool,
+    settings: &WorkerMonitorSettings,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Vec<String>, anyhow::Error> {
+    let mut problems = Vec::new();
+
+    // the freshest heartbeat across every worker that has ever reported
+    let last_seen = sqlx::query!(r#"SELECT max(last_seen_at) AS last_seen_at FROM worker_heartbeats"#)
+        .fetch_one(pool)
+        .await?
+        .last_seen_at;
+
+    // only rows that are actually dequeue-able count - a retry scheduled
+    // for the future is not stuck, it's waiting
+    let queue = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "depth!", min(available_at) AS oldest_available_at
+        FROM issue_delivery_queue
+        WHERE available_at <= $1
+        "#,
+        now
+    )
+    .fetch_one(pool)
+    .await?;
+
+    match last_seen {
+        Some(at) => {
+            let silence = (now - at).num_seconds();
+            if silence >= settings.heartbeat_timeout_seconds as i64 {
+                problems.push(format!(
+                    "No worker heartbeat for {}s (threshold: {}s).",
+                    silence, settings.heartbeat_timeout_seconds
+                ));
+            }
+        }
+        // a worker that has never beaten is only a problem once there is
+        // work waiting for it
+        None if queue.depth > 0 => {
+            problems
+                .push("Tasks are queued but no worker has ever reported a heartbeat.".to_string());
+        }
+        None => {}
+    }
+
+    if let Some(oldest) = queue.oldest_available_at {
+        let age = (now - oldest).num_seconds();
+        if age >= settings.max_queue_age_seconds as i64 {
+            problems.push(format!(
+                "The oldest deliverable task has been waiting for {}s (threshold: {}s, {} task(s) queued).",
+                age, settings.max_queue_age_seconds, queue.depth
+            ));
+        }
+    }
+
+    Ok(problems)
+}
+
+// run as a task in main(), alongside the API and the worker
+pub async fn run_monitor_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    run_monitor_with_clock(configuration, Arc::new(SystemClock)).await
+}
+
+// the clock parameter exists for the same reason as the worker's - tests
+// can step through check intervals without real sleeps
+pub async fn run_monitor_with_clock(
+    configuration: Settings,
+    clock: Arc<dyn Clock>,
+) -> Result<(), anyhow::Error> {
+    let pool = startup::get_connection_pool(&configuration.database);
+    let settings = configuration.worker_monitor;
+
+    // alerts go out through the shared alerter (de-duplication included) -
+    // the older worker_monitor.alert_email setting keeps working as an
+    // extra recipient on top of alerts.admin_emails
+    let mut alert_settings = configuration.alerts.clone();
+    if let Some(address) = settings.alert_email.clone() {
+        alert_settings.admin_emails.push(address);
+    }
+    let alerter = Alerter::new(
+        &alert_settings,
+        Arc::new(configuration.email_client.client()),
+    )?;
+
+    loop {
+        clock
+            .sleep(Duration::from_secs(settings.check_interval_seconds))
+            .await;
+        let now = clock.now();
+
+        // refresh the /metrics gauges on the same cadence - the endpoint
+        // itself only ever serves this cached sample
+        if let Err(e) = crate::queue_metrics::sample(&pool, now).await {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                "Failed to sample the delivery queue for /metrics",
+            );
+        }
+
+        let problems = match find_problems(&pool, &settings, now).await {
+            Ok(problems) => problems,
+            Err(e) => {
+                // a flaky database connection shouldn't kill the monitor
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    "Failed to check the delivery pipeline's health",
+                );
+                continue;
+            }
+        };
+
+        if problems.is_empty() {
+            continue;
+        }
+
+        tracing::warn!(problems = ?problems, "The delivery pipeline looks unhealthy");
+
+        alerter
+            .notify(
+                "delivery-pipeline",
+                "Delivery worker alert",
+                &problems.join("\n"),
+                now,
+            )
+            .await;
+    }
+}
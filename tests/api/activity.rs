@@ -0,0 +1,110 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use uuid::Uuid;
+use zero2prod::domain_events::{EMAIL_DELIVERED, SUBSCRIBER_CONFIRMED};
+
+// `n` confirmation events for one subscriber, a minute apart so the
+// stream has a stable order
+async fn seed_events(app: &TestApp, event_type: &str, n: i32) -> Uuid {
+    let subscriber_id = app.seed_confirmed_subscribers(1).await[0];
+    for i in 0..n {
+        sqlx::query!(
+            "INSERT INTO events (id, event_type, subject_id, occurred_at)
+            VALUES ($1, $2, $3, now() - make_interval(mins => $4))",
+            Uuid::new_v4(),
+            event_type,
+            subscriber_id,
+            i,
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+    subscriber_id
+}
+
+async fn get_activity_html(app: &TestApp, query: &str) -> String {
+    app.api_client
+        .get(format!("{}/admin/activity{}", &app.address, query))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_activity_stream() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/activity", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn load_more_walks_the_whole_stream_without_repeats() {
+    // Arrange - one more event than fits on a page
+    let app = spawn_app().await;
+    seed_events(&app, SUBSCRIBER_CONFIRMED, 21).await;
+    app.login().await;
+
+    // Act - the first page came back full, so it links to the next
+    let first_page = get_activity_html(&app, "").await;
+    let more_href = first_page
+        .split('"')
+        .find(|s| s.starts_with("/admin/activity?before_us="))
+        .expect("The first page should link to the next one.")
+        .to_string();
+    let second_page = get_activity_html(&app, &more_href["/admin/activity".len()..]).await;
+
+    // Assert - 20 rows, then the leftover 1, and the stream ends there
+    assert_eq!(first_page.matches("confirmed their subscription").count(), 20);
+    assert_eq!(second_page.matches("confirmed their subscription").count(), 1);
+    assert!(second_page.contains("That is the whole stream."));
+}
+
+#[tokio::test]
+async fn per_email_delivery_events_stay_out_of_the_stream() {
+    // Arrange - a big send's worth of delivery events and one confirmation
+    let app = spawn_app().await;
+    seed_events(&app, EMAIL_DELIVERED, 5).await;
+    seed_events(&app, SUBSCRIBER_CONFIRMED, 1).await;
+    app.login().await;
+
+    // Act
+    let html = get_activity_html(&app, "").await;
+
+    // Assert - only the confirmation shows
+    assert_eq!(html.matches("<li>").count(), 1);
+    assert!(html.contains("confirmed their subscription"));
+}
+
+#[tokio::test]
+async fn a_malformed_cursor_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/admin/activity?before_us=yesterday&before_id=42",
+            &app.address
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
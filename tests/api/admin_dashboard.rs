@@ -28,8 +28,22 @@ async fn logout_clears_session_state() {
     assert_is_redirect_to(&response, "/");
     // Act - Part 4 - Follow the redirect
     let html_page = app.get_login_html().await;
-    assert!(html_page.contains(r#"<p><i>You have successfully logged out.</i></p>"#));
+    assert!(html_page.contains(r#"<p class="flash-info" role="status" data-auto-dismiss-ms="8000"><i>You have successfully logged out.</i></p>"#));
     // Act - Part 5 - Attempt to load admin panel
     let response = app.get_admin_dashboard().await;
     assert_is_redirect_to(&response, "/login");
 }
+
+#[tokio::test]
+async fn the_dashboard_reports_seeded_subscriber_counts() {
+    // Arrange
+    let app = spawn_app().await;
+    app.seed_confirmed_subscribers(3).await;
+    app.login().await;
+
+    // Act
+    let html_page = app.get_admin_dashboard_html().await;
+
+    // Assert
+    assert!(html_page.contains("3 total - 3 confirmed, 0 pending confirmation"));
+}
@@ -0,0 +1,103 @@
+use crate::helpers::spawn_app;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn archive_lists_published_issues_with_validators() {
+    // Arrange
+    let app = spawn_app().await;
+    let issue_id = app.seed_issue("Issue One").await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/archive", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert - the page links to the issue and carries cache validators
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers().get("ETag").is_some());
+    assert!(response.headers().get("Last-Modified").is_some());
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Issue One"));
+    assert!(html_page.contains(&issue_id.to_string()));
+}
+
+#[tokio::test]
+async fn archive_returns_304_when_etag_matches() {
+    // Arrange
+    let app = spawn_app().await;
+    app.seed_issue("Issue One").await;
+
+    let first_response = app
+        .api_client
+        .get(format!("{}/archive", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let etag = first_response.headers().get("ETag").unwrap().clone();
+
+    // Act - revalidate with the etag we were handed
+    let second_response = app
+        .api_client
+        .get(format!("{}/archive", &app.address))
+        .header("If-None-Match", etag)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert - an empty 304
+    assert_eq!(second_response.status().as_u16(), 304);
+    assert_eq!(second_response.text().await.unwrap(), "");
+}
+
+#[tokio::test]
+async fn archived_issue_returns_304_when_not_modified_since() {
+    // Arrange
+    let app = spawn_app().await;
+    let issue_id = app.seed_issue("Issue One").await;
+
+    let first_response = app
+        .api_client
+        .get(format!("{}/archive/{}", &app.address, issue_id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(first_response.status().as_u16(), 200);
+    let last_modified = first_response
+        .headers()
+        .get("Last-Modified")
+        .unwrap()
+        .clone();
+
+    // Act - revalidate against the timestamp we were handed
+    let second_response = app
+        .api_client
+        .get(format!("{}/archive/{}", &app.address, issue_id))
+        .header("If-Modified-Since", last_modified)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(second_response.status().as_u16(), 304);
+}
+
+#[tokio::test]
+async fn unknown_archived_issue_returns_404() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/archive/{}", &app.address, Uuid::new_v4()))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -0,0 +1,141 @@
+use crate::helpers::{configure_database, spawn_app};
+use uuid::Uuid;
+use zero2prod::backup;
+use zero2prod::configuration;
+
+// a second, empty database to restore into - named with a fresh UUID so
+// `zero2prod clean-test-dbs` sweeps it up like every other test database
+async fn fresh_database() -> configuration::DatabaseSettings {
+    let mut database = configuration::get_configuration()
+        .expect("Failed to read configuration.")
+        .database;
+    database.database_name = Uuid::new_v4().to_string();
+    configure_database(&database).await;
+    database
+}
+
+#[tokio::test]
+async fn a_backup_round_trips_into_an_empty_database() {
+    // Arrange - an app with one tagged subscriber and one issue
+    let app = spawn_app().await;
+    let subscriber_id = app.seed_confirmed_subscribers(1).await[0];
+    sqlx::query!(
+        "INSERT INTO subscriber_tags (subscriber_id, tag) VALUES ($1, 'vip')",
+        subscriber_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.seed_issue("Issue One").await;
+    let source = app.db_config().clone();
+    let archive_path = std::env::temp_dir().join(format!("zero2prod-backup-{}.json", Uuid::new_v4()));
+
+    // Act - back up the app's database and restore into an empty one
+    backup::backup(&source, &archive_path)
+        .await
+        .expect("The backup failed.");
+    let target = fresh_database().await;
+    backup::restore(&target, &archive_path)
+        .await
+        .expect("The restore failed.");
+
+    // Assert - the subscriber (with their tag) and the issue came across
+    let pool = zero2prod::startup::get_connection_pool(&target);
+    let subscribers = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM subscriptions"#)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(subscribers.count, 1);
+    let tags = sqlx::query!(r#"SELECT tag FROM subscriber_tags WHERE subscriber_id = $1"#, subscriber_id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(tags.len(), 1);
+    assert_eq!(tags[0].tag, "vip");
+    let issues = sqlx::query!(r#"SELECT title FROM newsletter_issues"#)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].title, "Issue One");
+
+    std::fs::remove_file(&archive_path).unwrap();
+}
+
+#[tokio::test]
+async fn restore_does_not_clobber_an_existing_subscriber() {
+    // Arrange - back up a database, then restore it into ITSELF
+    let app = spawn_app().await;
+    let subscriber_id = app.seed_confirmed_subscribers(1).await[0];
+    let source = app.db_config().clone();
+    let archive_path = std::env::temp_dir().join(format!("zero2prod-backup-{}.json", Uuid::new_v4()));
+    backup::backup(&source, &archive_path)
+        .await
+        .expect("The backup failed.");
+
+    // the subscriber renames themselves after the backup was taken
+    sqlx::query!(
+        "UPDATE subscriptions SET name = 'Renamed' WHERE id = $1",
+        subscriber_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    backup::restore(&source, &archive_path)
+        .await
+        .expect("The restore failed.");
+
+    // Assert - the live row wins, and no duplicate appeared
+    let saved = sqlx::query!("SELECT name FROM subscriptions WHERE id = $1", subscriber_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.name, "Renamed");
+    let subscribers = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscribers.count, 1);
+
+    std::fs::remove_file(&archive_path).unwrap();
+}
+
+#[tokio::test]
+async fn restore_refuses_an_archive_from_the_future() {
+    // Arrange - an archive with a version this build has never heard of
+    let target = fresh_database().await;
+    let archive_path = std::env::temp_dir().join(format!("zero2prod-backup-{}.json", Uuid::new_v4()));
+    std::fs::write(
+        &archive_path,
+        serde_json::json!({
+            "version": 999,
+            "created_at": chrono::Utc::now(),
+            "subscribers": [],
+            "issues": [],
+            "custom_pages": [],
+            "site_settings": {
+                "newsletter_name": "",
+                "logo_url": "",
+                "accent_color": "",
+                "footer_address": "",
+                "social_links": "",
+            },
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    // Act
+    let outcome = backup::restore(&target, &archive_path).await;
+
+    // Assert - refused rather than guessed at
+    assert!(outcome.is_err());
+    assert!(outcome
+        .unwrap_err()
+        .to_string()
+        .contains("version 999 is not supported"));
+
+    std::fs::remove_file(&archive_path).unwrap();
+}
@@ -0,0 +1,103 @@
+use crate::helpers::spawn_app;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn a_confirmed_subscriber_can_move_to_a_new_address() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = app.seed_confirmed_subscribers(1).await[0];
+    let old_email = sqlx::query!("SELECT email FROM subscriptions WHERE id = $1", subscriber_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act - request the change
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions/change_email", &app.address))
+        .form(&serde_json::json!({
+            "email": old_email,
+            "new_email": "new.address@example.com",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    // the old address stays active until the new one confirms
+    let saved = sqlx::query!("SELECT email FROM subscriptions WHERE id = $1", subscriber_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.email, old_email);
+
+    // Act - "click" the link that went to the NEW address
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let links = app.get_confirmation_links(email_request);
+    reqwest::get(links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert - the address swapped over and the token is burned
+    let saved = sqlx::query!("SELECT email FROM subscriptions WHERE id = $1", subscriber_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.email, "new.address@example.com");
+    let leftover = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM email_change_requests"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(leftover.count, 0);
+}
+
+#[tokio::test]
+async fn an_unknown_address_cannot_request_a_change() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act - nobody is subscribed with this address
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions/change_email", &app.address))
+        .form(&serde_json::json!({
+            "email": "nobody@example.com",
+            "new_email": "new.address@example.com",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert - rejected, and no confirmation email went out
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(app.email_server.received_requests().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn a_bogus_change_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/subscriptions/change_email/confirm?change_token=not-a-real-token",
+            &app.address
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -55,7 +55,7 @@ async fn new_password_fields_must_match() {
     let html_page = app.get_change_password_html().await;
 
     assert!(html_page.contains(
-        "<p><i>You entered two different new passwords - the field values must match.</i></p>"
+        r#"<p class="flash-error" role="alert"><i>You entered two different new passwords - the field values must match.</i></p>"#
     ));
 }
 
@@ -87,7 +87,7 @@ async fn current_password_must_be_valid() {
 
     // Act - Part 3 - Follow the redirect
     let html_page = app.get_change_password_html().await;
-    assert!(html_page.contains("<p><i>The current password is incorrect.</i></p>"));
+    assert!(html_page.contains(r#"<p class="flash-error" role="alert"><i>The current password is incorrect.</i></p>"#));
 }
 
 #[tokio::test]
@@ -113,13 +113,13 @@ async fn changing_password_works() {
     assert_is_redirect_to(&response, "/admin/password");
     // Act - Part 3 - Follow the redirect
     let html_page = app.get_change_password_html().await;
-    assert!(html_page.contains("<p><i>Your password has been changed.</i></p>"));
+    assert!(html_page.contains(r#"<p class="flash-info" role="status" data-auto-dismiss-ms="8000"><i>Your password has been changed.</i></p>"#));
     // Act - Part 4 - Logout
     let response = app.post_logout().await;
     assert_is_redirect_to(&response, "/");
     // Act - Part 5 - Follow the redirect
     let html_page = app.get_login_html().await;
-    assert!(html_page.contains("<p><i>You have successfully logged out.</i></p>"));
+    assert!(html_page.contains(r#"<p class="flash-info" role="status" data-auto-dismiss-ms="8000"><i>You have successfully logged out.</i></p>"#));
     // Act - Part 6 - Login using the new password
     let login_body = serde_json::json!({
         "username": &app.test_user.username,
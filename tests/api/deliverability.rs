@@ -0,0 +1,97 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use uuid::Uuid;
+
+// a day's worth of traffic: deliveries to two domains, plus feedback
+async fn seed_traffic(app: &TestApp) {
+    let issue_id = app.seed_issue("Issue under scrutiny").await;
+    for (i, domain) in ["gmail.com", "gmail.com", "outlook.com"].iter().enumerate() {
+        sqlx::query!(
+            "INSERT INTO email_delivery_log (id, newsletter_issue_id, recipient_email, sent_at)
+            VALUES ($1, $2, $3, now())",
+            Uuid::new_v4(),
+            issue_id,
+            format!("reader-{}@{}", i, domain),
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+    sqlx::query!(
+        "INSERT INTO email_feedback_events (id, event_type, recipient_email, occurred_at)
+        VALUES ($1, 'bounce', 'reader-0@gmail.com', now())",
+        Uuid::new_v4(),
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_deliverability_dashboard() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/deliverability", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_dashboard_breaks_rates_down_by_domain() {
+    // Arrange
+    let app = spawn_app().await;
+    seed_traffic(&app).await;
+    app.login().await;
+
+    // Act
+    let html = app
+        .api_client
+        .get(format!("{}/admin/deliverability", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+
+    // Assert - gmail got 2 deliveries and 1 bounce (50%), outlook is clean
+    assert!(html.contains("gmail.com"));
+    assert!(html.contains("1 (50.0%)"));
+    assert!(html.contains("outlook.com"));
+    assert!(html.contains("0 (0.0%)"));
+}
+
+#[tokio::test]
+async fn the_data_endpoint_serves_the_same_numbers_as_json() {
+    // Arrange
+    let app = spawn_app().await;
+    seed_traffic(&app).await;
+    app.login().await;
+
+    // Act
+    let rows: serde_json::Value = app
+        .api_client
+        .get(format!("{}/admin/deliverability/data", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap();
+
+    // Assert - one row per (day, domain), ordered by deliveries
+    let rows = rows.as_array().unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0]["domain"], "gmail.com");
+    assert_eq!(rows[0]["delivered"], 2);
+    assert_eq!(rows[0]["bounces"], 1);
+    assert_eq!(rows[1]["domain"], "outlook.com");
+    assert_eq!(rows[1]["bounces"], 0);
+}
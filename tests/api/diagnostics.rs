@@ -0,0 +1,95 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_diagnostics() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/diagnostics", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_fresh_heartbeat_shows_as_healthy() {
+    // Arrange - a worker that reported a second ago
+    let app = spawn_app().await;
+    let worker_id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO worker_heartbeats (worker_id, started_at, last_seen_at)
+        VALUES ($1, now() - interval '1 minute', now())",
+        worker_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.login().await;
+
+    // Act
+    let html_page = app
+        .api_client
+        .get(format!("{}/admin/diagnostics", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+
+    // Assert - the worker is listed and nothing is being complained about
+    assert!(html_page.contains(&worker_id.to_string()));
+    assert!(html_page.contains("All checks passing."));
+    assert!(!html_page.contains("STALE"));
+}
+
+#[tokio::test]
+async fn a_silent_worker_and_a_stuck_task_are_flagged() {
+    // Arrange - a worker last seen ten minutes ago (the default timeout is
+    // 90s) and a deliverable task that has been waiting two hours (default
+    // threshold: one hour)
+    let app = spawn_app().await;
+    let worker_id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO worker_heartbeats (worker_id, started_at, last_seen_at)
+        VALUES ($1, now() - interval '1 hour', now() - interval '10 minutes')",
+        worker_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let issue_id = app.seed_issue("Stuck Issue").await;
+    sqlx::query!(
+        "INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email, available_at)
+        VALUES ($1, 'stuck@example.com', now() - interval '2 hours')",
+        issue_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.login().await;
+
+    // Act
+    let html_page = app
+        .api_client
+        .get(format!("{}/admin/diagnostics", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+
+    // Assert - both the monitor's complaints and the inline flag show up
+    assert!(html_page.contains("No worker heartbeat for"));
+    assert!(html_page.contains("The oldest deliverable task has been waiting for"));
+    assert!(html_page.contains("STALE"));
+    assert!(!html_page.contains("All checks passing."));
+}
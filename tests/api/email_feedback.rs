@@ -0,0 +1,118 @@
+use crate::helpers::spawn_app;
+
+// the token spawn_app configures for the webhook endpoints
+const WEBHOOK_TOKEN: &str = "test-webhook-token";
+
+#[tokio::test]
+async fn a_bounce_is_recorded() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/webhooks/email", &app.address))
+        .header("X-Webhook-Token", WEBHOOK_TOKEN)
+        .json(&serde_json::json!({
+            "RecordType": "Bounce",
+            "Email": "bounced@example.com",
+            "ID": 42,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT event_type, recipient_email FROM email_feedback_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.event_type, "bounce");
+    assert_eq!(saved.recipient_email, "bounced@example.com");
+}
+
+#[tokio::test]
+async fn a_redelivered_event_is_not_double_counted() {
+    // Arrange
+    let app = spawn_app().await;
+    let payload = serde_json::json!({
+        "RecordType": "SpamComplaint",
+        "Email": "annoyed@example.com",
+        "ID": 42,
+    });
+
+    // Act - the provider delivers the same event twice
+    for _ in 0..2 {
+        let response = app
+            .api_client
+            .post(format!("{}/webhooks/email", &app.address))
+            .header("X-Webhook-Token", WEBHOOK_TOKEN)
+            .json(&payload)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // Assert - one stored event
+    let saved = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM email_feedback_events"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.count, 1);
+}
+
+#[tokio::test]
+async fn an_untracked_event_type_is_acknowledged_and_dropped() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act - Postmark also sends Delivery, Open, Click, ... events
+    let response = app
+        .api_client
+        .post(format!("{}/webhooks/email", &app.address))
+        .header("X-Webhook-Token", WEBHOOK_TOKEN)
+        .json(&serde_json::json!({
+            "RecordType": "Delivery",
+            "Email": "fine@example.com",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert - a 200 (so the provider stops retrying), nothing stored
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM email_feedback_events"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.count, 0);
+}
+
+#[tokio::test]
+async fn a_wrong_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/webhooks/email", &app.address))
+        .header("X-Webhook-Token", "not-the-token")
+        .json(&serde_json::json!({
+            "RecordType": "Bounce",
+            "Email": "bounced@example.com",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert - rejected, nothing stored
+    assert_eq!(response.status().as_u16(), 401);
+    let saved = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM email_feedback_events"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.count, 0);
+}
@@ -7,7 +7,9 @@ use uuid::Uuid;
 use wiremock::MockServer;
 use zero2prod::configuration;
 use zero2prod::email_client::EmailClient;
-use zero2prod::issue_delivery_worker::{try_execute_task, ExecutionOutcome};
+use zero2prod::event_webhooks::EventWebhooks;
+use zero2prod::issue_delivery_worker::{self, try_execute_task, ExecutionOutcome};
+use zero2prod::signed_link::LinkSigner;
 use zero2prod::startup;
 use zero2prod::{startup::get_connection_pool, telemetry};
 
@@ -37,6 +39,50 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: reqwest::Client, // the http request client
     pub email_client: EmailClient,
+    // kept so the Drop guard below knows which database to tear down
+    db_config: configuration::DatabaseSettings,
+}
+
+// Every test gets its own uniquely named database - without this guard
+// they pile up in the Postgres instance forever. Dropping a database is
+// async work, but Drop is sync: push it onto a fresh runtime on a
+// separate thread and wait for it to finish.
+// (`zero2prod clean-test-dbs` sweeps up anything a hard-killed run left behind.)
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        let config = self.db_config.clone();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Failed to build a runtime for the database cleanup.");
+            runtime.block_on(async move {
+                let maintenance_settings = configuration::DatabaseSettings {
+                    database_name: "postgres".to_string(),
+                    username: "postgres".to_string(),
+                    password: Secret::new("password".to_string()),
+                    ..config.clone()
+                };
+                let mut connection =
+                    PgConnection::connect_with(&maintenance_settings.connection_options())
+                        .await
+                        .expect("Failed to connect to Postgres");
+                // FORCE kicks out the app's own pool connections
+                connection
+                    .execute(
+                        format!(
+                            r#"DROP DATABASE "{}" WITH (FORCE);"#,
+                            config.database_name
+                        )
+                        .as_str(),
+                    )
+                    .await
+                    .expect("Failed to drop the test database.");
+            });
+        })
+        .join()
+        .expect("The database cleanup thread panicked.");
+    }
 }
 
 impl TestApp {
@@ -52,11 +98,30 @@ impl TestApp {
 
     // send all emails in the queue
     pub async fn dispatch_all_pending_emails(&self) {
+        // a throttle generous enough to never slow a test down, and no
+        // webhooks configured - nothing to announce to
+        let rate_limiter = issue_delivery_worker::email_rate_limiter(1000, 1000);
+        let webhooks = EventWebhooks::new(&configuration::EventWebhookSettings::default());
+        let recipient_links = issue_delivery_worker::RecipientLinks::new(
+            self.address.clone(),
+            LinkSigner::new(Secret::new("test-secret".to_string())),
+        );
+        // no bus configured - the mirror is a no-op
+        let bus = zero2prod::message_bus::MessageBus::new(
+            &configuration::MessageBusSettings::default(),
+        );
         loop {
-            if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
-                    .await
-                    .unwrap()
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &rate_limiter,
+                &webhooks,
+                &recipient_links,
+                &bus,
+                chrono::Utc::now(),
+            )
+            .await
+            .unwrap()
             {
                 break;
             }
@@ -98,6 +163,64 @@ impl TestApp {
         ConfirmationLinks { html, plain_text }
     }
 
+    /// Log in as the seeded test user - the session cookie sticks to
+    /// `api_client`, so subsequent admin requests are authenticated.
+    pub async fn login(&self) {
+        let response = self
+            .post_login(&serde_json::json!({
+                "username": &self.test_user.username,
+                "password": &self.test_user.password
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/dashboard");
+    }
+
+    /// Insert `n` confirmed subscribers straight into the database,
+    /// skipping the subscribe + confirm dance.
+    pub async fn seed_confirmed_subscribers(&self, n: usize) -> Vec<Uuid> {
+        let mut ids = Vec::with_capacity(n);
+        for i in 0..n {
+            let id = Uuid::new_v4();
+            sqlx::query!(
+                "INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+                VALUES ($1, $2, $3, now(), 'confirmed')",
+                id,
+                format!("subscriber-{}-{}@example.com", i, id),
+                format!("Subscriber {}", i),
+            )
+            .execute(&self.db_pool)
+            .await
+            .expect("Failed to seed a confirmed subscriber.");
+            ids.push(id);
+        }
+        ids
+    }
+
+    /// Insert a published newsletter issue straight into the database.
+    pub async fn seed_issue(&self, title: &str) -> Uuid {
+        let issue_id = Uuid::new_v4();
+        sqlx::query!(
+            r#"
+            INSERT INTO newsletter_issues (
+                newsletter_issue_id,
+                title,
+                text_content,
+                html_content,
+                published_at
+            )
+            VALUES ($1, $2, $3, $4, now())
+            "#,
+            issue_id,
+            title,
+            "Plain text content",
+            "<p>HTML content</p>",
+        )
+        .execute(&self.db_pool)
+        .await
+        .expect("Failed to seed a newsletter issue.");
+        issue_id
+    }
+
     // body is a generic type that can be deserialised
     pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
     where
@@ -189,6 +312,12 @@ impl TestApp {
     pub async fn get_publish_newsletter_html(&self) -> String {
         self.get_publish_newsletter().await.text().await.unwrap()
     }
+
+    /// The test database's connection settings - for code paths (backup,
+    /// restore) that take `DatabaseSettings` rather than a pool.
+    pub fn db_config(&self) -> &configuration::DatabaseSettings {
+        &self.db_config
+    }
 }
 
 // a fake user of the API
@@ -247,6 +376,17 @@ impl TestUser {
 
 // don't propogate errors here - as only for testing - crash the program
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with_seed(|_| async {}).await
+}
+
+/// Like [`spawn_app`], but runs `seed` against the freshly migrated
+/// database before the application boots - for state the app only reads
+/// at startup, like the tenant directory.
+pub async fn spawn_app_with_seed<F, Fut>(seed: F) -> TestApp
+where
+    F: FnOnce(PgPool) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
     //first set up telemetry spans
     // The first time `initialize` is invoked the code in `TRACING` is executed.
     // All other invocations will instead skip execution.
@@ -267,11 +407,15 @@ pub async fn spawn_app() -> TestApp {
         c.application.port = 0;
         // Use the mock server as email API
         c.email_client.base_url = email_server.uri();
+        // The bounce/complaint webhook is guarded by a shared token -
+        // without one the endpoint answers 404 to everybody
+        c.email_client.webhook_token = Some(Secret::new("test-webhook-token".to_string()));
         c
     };
 
     // Create and migrate the database
-    configure_database(&configuration.database).await;
+    let pool = configure_database(&configuration.database).await;
+    seed(pool).await;
 
     // Launch the application as a background task
     let application = startup::Application::build(configuration.clone())
@@ -301,6 +445,7 @@ pub async fn spawn_app() -> TestApp {
         port: application_port,
         test_user: TestUser::generate(),
         api_client: client,
+        db_config: configuration.database.clone(),
         email_client: configuration.email_client.client(),
     };
 
@@ -16,7 +16,7 @@ async fn an_error_flash_message_is_set_on_failure() {
     // Act pt 2 - follow the redirect - there should be a cookie
     // and an Auth Failed message
     let html_page = app.get_login_html().await;
-    assert!(html_page.contains(r#"<p><i>Authentication failed</i></p>"#));
+    assert!(html_page.contains(r#"<p class="flash-error" role="alert"><i>Authentication failed</i></p>"#));
 
     // Act part 3 - reload the login page - the cookie should be gone
     let html_page = app.get_login_html().await;
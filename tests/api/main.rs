@@ -1,8 +1,21 @@
+mod activity;
 mod admin_dashboard;
+mod archive;
+mod backup;
+mod change_email;
 mod change_password;
+mod deliverability;
+mod diagnostics;
+mod email_feedback;
 mod health_check;
 mod helpers;
 mod login;
+mod my_subscription;
 mod newsletters;
+mod poll;
+mod preferences;
+mod reengagement;
+mod subscribers;
 mod subscriptions;
 mod subscriptions_confirm;
+mod tenancy;
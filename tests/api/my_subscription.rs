@@ -0,0 +1,109 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn a_magic_link_opens_the_subscription_page() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = app.seed_confirmed_subscribers(1).await[0];
+    let email = sqlx::query!("SELECT email FROM subscriptions WHERE id = $1", subscriber_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act - ask for a link, then "click" it
+    let response = app
+        .api_client
+        .post(format!("{}/my-subscription", &app.address))
+        .form(&serde_json::json!({ "email": email }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/my-subscription?sent=true");
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let links = app.get_confirmation_links(email_request);
+    let html_page = app
+        .api_client
+        .get(links.html)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+
+    // Assert - the profile page, not the login form
+    assert!(html_page.contains(&email));
+    assert!(html_page.contains("Subscriber 0"));
+}
+
+#[tokio::test]
+async fn an_unknown_address_gets_the_same_answer_and_no_email() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/my-subscription", &app.address))
+        .form(&serde_json::json!({ "email": "nobody@example.com" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert - indistinguishable from the subscribed case on the outside
+    assert_is_redirect_to(&response, "/my-subscription?sent=true");
+    assert!(app.email_server.received_requests().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn a_tampered_link_falls_back_to_the_login_form() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = app.seed_confirmed_subscribers(1).await[0];
+    let email = sqlx::query!("SELECT email FROM subscriptions WHERE id = $1", subscriber_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.api_client
+        .post(format!("{}/my-subscription", &app.address))
+        .form(&serde_json::json!({ "email": email }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let links = app.get_confirmation_links(email_request);
+
+    // Act - break the signature by swapping the subscriber id
+    let tampered = links
+        .html
+        .to_string()
+        .replace(&subscriber_id.to_string(), &uuid::Uuid::new_v4().to_string());
+    let html_page = app
+        .api_client
+        .get(tampered)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+
+    // Assert - no profile details leak, the reader is asked to log in again
+    assert!(!html_page.contains(&email));
+    assert!(html_page.contains("email"));
+}
@@ -35,7 +35,7 @@ async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
     // Act - Part 2 - Follow the redirect
     let html_page = app.get_publish_newsletter_html().await;
     assert!(
-        html_page.contains("<p><i>The newsletter issue has been queued for publishing!</i></p>")
+        html_page.contains(r#"<p class="flash-info" role="status" data-auto-dismiss-ms="8000"><i>The newsletter issue has been queued for publishing!</i></p>"#)
     );
     app.dispatch_all_pending_emails().await;
     // Mock verifies on Drop that we haven't sent the newsletter email
@@ -118,7 +118,7 @@ async fn newsletters_are_delivered_to_confirmed_subscribers() {
     // Act - Part 2 - Follow the redirect
     let html_page = app.get_publish_newsletter_html().await;
     assert!(
-        html_page.contains("<p><i>The newsletter issue has been queued for publishing!</i></p>")
+        html_page.contains(r#"<p class="flash-info" role="status" data-auto-dismiss-ms="8000"><i>The newsletter issue has been queued for publishing!</i></p>"#)
     );
     app.dispatch_all_pending_emails().await;
     // Mock verifies on Drop that we have sent the newsletter email
@@ -184,7 +184,7 @@ async fn newsletter_creation_is_idempotent() {
     // Act - Part 2 - Follow the redirect
     let html_page = app.get_publish_newsletter_html().await;
     assert!(
-        html_page.contains("<p><i>The newsletter issue has been queued for publishing!</i></p>")
+        html_page.contains(r#"<p class="flash-info" role="status" data-auto-dismiss-ms="8000"><i>The newsletter issue has been queued for publishing!</i></p>"#)
     );
 
     // Act - Part 3 - Submit newsletter form **again**
@@ -194,7 +194,7 @@ async fn newsletter_creation_is_idempotent() {
     // Act - Part 4 - Follow the redirect
     let html_page = app.get_publish_newsletter_html().await;
     assert!(
-        html_page.contains("<p><i>The newsletter issue has been queued for publishing!</i></p>")
+        html_page.contains(r#"<p class="flash-info" role="status" data-auto-dismiss-ms="8000"><i>The newsletter issue has been queued for publishing!</i></p>"#)
     );
     app.dispatch_all_pending_emails().await;
     // Mock verifies on Drop that we have sent the newsletter email **once**
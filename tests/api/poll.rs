@@ -0,0 +1,131 @@
+use crate::helpers::{spawn_app, TestApp};
+use uuid::Uuid;
+use zero2prod::configuration;
+use zero2prod::signed_link::{LinkSigner, POLL_VOTE};
+
+// a poll with two options, attached to a seeded issue
+async fn seed_poll(app: &TestApp) -> (Uuid, Uuid, Uuid) {
+    let issue_id = app.seed_issue("Issue with a poll").await;
+    let poll_id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO issue_polls (id, newsletter_issue_id, question)
+        VALUES ($1, $2, 'Tabs or spaces?')",
+        poll_id,
+        issue_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let mut option_ids = Vec::new();
+    for (position, label) in [(1i16, "Tabs"), (2, "Spaces")] {
+        let option_id = Uuid::new_v4();
+        sqlx::query!(
+            "INSERT INTO poll_options (id, poll_id, position, label)
+            VALUES ($1, $2, $3, $4)",
+            option_id,
+            poll_id,
+            position,
+            label,
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+        option_ids.push(option_id);
+    }
+    (poll_id, option_ids[0], option_ids[1])
+}
+
+// a voting link signed the same way the delivery worker signs them
+fn vote_link(app: &TestApp, poll_id: Uuid, option_id: Uuid, subscriber_id: Uuid) -> String {
+    let secret = configuration::get_configuration()
+        .expect("Failed to read configuration.")
+        .application
+        .hmac_secret;
+    let fragment = LinkSigner::new(secret).query_fragment(
+        subscriber_id,
+        POLL_VOTE,
+        chrono::Utc::now() + chrono::Duration::days(30),
+    );
+    format!(
+        "{}/poll/{}/vote?option={}&{}",
+        app.address, poll_id, option_id, fragment
+    )
+}
+
+#[tokio::test]
+async fn voting_records_one_overwritable_vote_per_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = app.seed_confirmed_subscribers(1).await[0];
+    let (poll_id, tabs, spaces) = seed_poll(&app).await;
+
+    // Act - vote, then change your mind
+    for option_id in [tabs, spaces] {
+        let response = app
+            .api_client
+            .get(vote_link(&app, poll_id, option_id, subscriber_id))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // Assert - one vote, and the latest choice won
+    let votes = sqlx::query!("SELECT option_id FROM poll_votes WHERE poll_id = $1", poll_id)
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(votes.len(), 1);
+    assert_eq!(votes[0].option_id, spaces);
+}
+
+#[tokio::test]
+async fn an_option_from_another_poll_is_rejected() {
+    // Arrange - a valid signature pointing at an option the poll in the
+    // path does not own
+    let app = spawn_app().await;
+    let subscriber_id = app.seed_confirmed_subscribers(1).await[0];
+    let (_, tabs, _) = seed_poll(&app).await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(vote_link(&app, Uuid::new_v4(), tabs, subscriber_id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert - refused, nothing stored
+    assert_eq!(response.status().as_u16(), 404);
+    let votes = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM poll_votes"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(votes.count, 0);
+}
+
+#[tokio::test]
+async fn a_tampered_vote_link_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = app.seed_confirmed_subscribers(1).await[0];
+    let (poll_id, tabs, _) = seed_poll(&app).await;
+
+    // Act - swap the subscriber id after signing
+    let tampered = vote_link(&app, poll_id, tabs, subscriber_id)
+        .replace(&subscriber_id.to_string(), &Uuid::new_v4().to_string());
+    let response = app
+        .api_client
+        .get(tampered)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    let votes = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM poll_votes"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(votes.count, 0);
+}
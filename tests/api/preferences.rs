@@ -0,0 +1,170 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::configuration;
+use zero2prod::signed_link::{LinkSigner, PREFERENCE_CENTER};
+
+// a preference-center link signed the same way the delivery worker signs
+// them - the test configuration uses the same hmac secret as the app
+fn preference_link(app: &TestApp, subscriber_id: Uuid) -> String {
+    let secret = configuration::get_configuration()
+        .expect("Failed to read configuration.")
+        .application
+        .hmac_secret;
+    let fragment = LinkSigner::new(secret).query_fragment(
+        subscriber_id,
+        PREFERENCE_CENTER,
+        chrono::Utc::now() + chrono::Duration::days(30),
+    );
+    format!("{}/preferences?{}", app.address, fragment)
+}
+
+#[tokio::test]
+async fn a_categorised_issue_skips_subscribers_opted_into_other_categories() {
+    // Arrange - three confirmed subscribers: one with no opt-ins at all,
+    // one opted into 'product', one opted into 'essay' only
+    let app = spawn_app().await;
+    let ids = app.seed_confirmed_subscribers(3).await;
+    let (everything, product_fan, essay_fan) = (ids[0], ids[1], ids[2]);
+    for (subscriber_id, category) in [(product_fan, "product"), (essay_fan, "essay")] {
+        sqlx::query!(
+            "INSERT INTO subscriber_category_preferences (subscriber_id, category)
+            VALUES ($1, $2)",
+            subscriber_id,
+            category,
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.login().await;
+
+    // Act - publish an issue tagged 'product'
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "A product issue",
+            "text_content": "Plain text",
+            "html_content": "<p>HTML</p>",
+            "tags": "product",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert - the no-opt-ins subscriber and the product fan got it, the
+    // essay fan did not
+    let mut recipients: Vec<String> = Vec::new();
+    for request in app.email_server.received_requests().await.unwrap() {
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        recipients.push(body["To"].as_str().unwrap().to_string());
+    }
+    recipients.sort();
+    let mut expected: Vec<String> = Vec::new();
+    for subscriber_id in [everything, product_fan] {
+        let row = sqlx::query!("SELECT email FROM subscriptions WHERE id = $1", subscriber_id)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+        expected.push(row.email);
+    }
+    expected.sort();
+    assert_eq!(recipients, expected);
+}
+
+#[tokio::test]
+async fn the_preference_center_round_trips_an_opt_in() {
+    // Arrange - two categories exist, courtesy of a published issue
+    let app = spawn_app().await;
+    let subscriber_id = app.seed_confirmed_subscribers(1).await[0];
+    let issue_id = app.seed_issue("Issue One").await;
+    for category in ["essay", "product"] {
+        sqlx::query!(
+            "INSERT INTO newsletter_issue_tags (newsletter_issue_id, tag) VALUES ($1, $2)",
+            issue_id,
+            category,
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+    let link = preference_link(&app, subscriber_id);
+
+    // Act - the form offers both categories, unticked
+    let html_page = app
+        .api_client
+        .get(&link)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("category_product"));
+    assert!(html_page.contains("category_essay"));
+    assert!(!html_page.contains("checked"));
+
+    // Act - tick 'product' and save; the signed fields ride along as the
+    // hidden inputs would carry them
+    let query = link.split_once('?').unwrap().1;
+    let mut form: Vec<(String, String)> = serde_urlencoded::from_str(query).unwrap();
+    form.push(("category_product".to_string(), "on".to_string()));
+    let response = app
+        .api_client
+        .post(format!("{}/preferences", &app.address))
+        .form(&form)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Assert - stored, and pre-ticked on the next visit
+    let saved = sqlx::query!(
+        "SELECT category FROM subscriber_category_preferences WHERE subscriber_id = $1",
+        subscriber_id,
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].category, "product");
+    let html_page = app
+        .api_client
+        .get(&link)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("checked"));
+}
+
+#[tokio::test]
+async fn a_tampered_preference_link_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = app.seed_confirmed_subscribers(1).await[0];
+    let link = preference_link(&app, subscriber_id);
+
+    // Act - swap the subscriber id without re-signing
+    let tampered = link.replace(
+        &subscriber_id.to_string(),
+        &Uuid::new_v4().to_string(),
+    );
+    let response = app
+        .api_client
+        .get(&tampered)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
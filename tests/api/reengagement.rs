@@ -0,0 +1,118 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_run_a_campaign() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/reengagement", &app.address))
+        .form(&serde_json::json!({
+            "issue_count": 5,
+            "response_window_days": 30,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn inactive_subscribers_are_asked_to_reconfirm_and_a_click_keeps_them() {
+    // Arrange - a confirmed subscriber who never engaged with the issue
+    let app = spawn_app().await;
+    let subscriber_id = app.seed_confirmed_subscribers(1).await[0];
+    app.seed_issue("Issue One").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.login().await;
+
+    // Act - run the campaign
+    let response = app
+        .api_client
+        .post(format!("{}/admin/reengagement", &app.address))
+        .form(&serde_json::json!({
+            "issue_count": 5,
+            "response_window_days": 30,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/admin/reengagement");
+
+    // Assert - one re-confirmation email went out and the chase is recorded
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let campaigns = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM reengagement_campaigns"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(campaigns.count, 1);
+
+    // Act - the subscriber clicks the link
+    let links = app.get_confirmation_links(email_request);
+    let response = reqwest::get(links.html).await.unwrap();
+
+    // Assert - they stay confirmed and are no longer being chased
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status FROM subscriptions WHERE id = $1", subscriber_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+    let campaigns = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM reengagement_campaigns"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(campaigns.count, 0);
+}
+
+#[tokio::test]
+async fn non_responders_are_unsubscribed_once_the_window_lapses() {
+    // Arrange - a subscriber who was asked to re-confirm 40 days ago
+    let app = spawn_app().await;
+    let subscriber_id = app.seed_confirmed_subscribers(1).await[0];
+    sqlx::query!(
+        "INSERT INTO reengagement_campaigns (subscriber_id, reconfirm_token, sent_at)
+        VALUES ($1, 'stale-token', now() - interval '40 days')",
+        subscriber_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.login().await;
+
+    // Act - a 30-day window has long lapsed for them
+    let response = app
+        .api_client
+        .post(format!("{}/admin/reengagement", &app.address))
+        .form(&serde_json::json!({
+            "issue_count": 5,
+            "response_window_days": 30,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/admin/reengagement");
+
+    // Assert - unsubscribed, campaign row cleared, and no email was sent
+    let saved = sqlx::query!("SELECT status FROM subscriptions WHERE id = $1", subscriber_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+    let campaigns = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM reengagement_campaigns"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(campaigns.count, 0);
+    assert!(app.email_server.received_requests().await.unwrap().is_empty());
+}
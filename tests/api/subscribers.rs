@@ -0,0 +1,122 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_run_a_bulk_action() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/subscribers/bulk", &app.address))
+        .form(&[("bulk_action", "unsubscribe")])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_bulk_unsubscribe_only_touches_the_selection() {
+    // Arrange - three subscribers, two selected
+    let app = spawn_app().await;
+    let ids = app.seed_confirmed_subscribers(3).await;
+    app.login().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/subscribers/bulk", &app.address))
+        .form(&[
+            ("subscriber_id", ids[0].to_string()),
+            ("subscriber_id", ids[1].to_string()),
+            ("bulk_action", "unsubscribe".to_string()),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert - the selection is unsubscribed, the third is untouched
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let unsubscribed = sqlx::query!(
+        r#"SELECT COUNT(*) as "count!" FROM subscriptions WHERE status = 'unsubscribed'"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(unsubscribed.count, 2);
+    let survivor = sqlx::query!("SELECT status FROM subscriptions WHERE id = $1", ids[2])
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(survivor.status, "confirmed");
+}
+
+#[tokio::test]
+async fn an_unrecognised_bulk_action_changes_nothing() {
+    // Arrange
+    let app = spawn_app().await;
+    let ids = app.seed_confirmed_subscribers(1).await;
+    app.login().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/subscribers/bulk", &app.address))
+        .form(&[
+            ("subscriber_id", ids[0].to_string()),
+            ("bulk_action", "explode".to_string()),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert - bounced back to the list with the subscriber intact
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let saved = sqlx::query!("SELECT status FROM subscriptions WHERE id = $1", ids[0])
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+    let html = app
+        .api_client
+        .get(format!("{}/admin/subscribers", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("That is not a recognised bulk action."));
+}
+
+#[tokio::test]
+async fn tagging_without_a_tag_name_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let ids = app.seed_confirmed_subscribers(1).await;
+    app.login().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/subscribers/bulk", &app.address))
+        .form(&[
+            ("subscriber_id", ids[0].to_string()),
+            ("bulk_action", "tag".to_string()),
+            ("tag", "  ".to_string()),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let tags = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM subscriber_tags"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(tags.count, 0);
+}
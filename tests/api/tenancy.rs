@@ -0,0 +1,85 @@
+use crate::helpers::{spawn_app_with_seed, TestApp};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::tenancy::DEFAULT_TENANT_ID;
+
+// a second newsletter living on its own host - the directory is loaded
+// at startup, so the row has to exist before the app boots
+async fn app_with_tenant(host: &str) -> (TestApp, Uuid) {
+    let tenant_id = Uuid::new_v4();
+    let host = host.to_string();
+    let app = spawn_app_with_seed(|pool| async move {
+        sqlx::query!(
+            "INSERT INTO tenants (id, name, host) VALUES ($1, 'acme', $2)",
+            tenant_id,
+            host,
+        )
+        .execute(&pool)
+        .await
+        .expect("Failed to seed a tenant.");
+    })
+    .await;
+    (app, tenant_id)
+}
+
+#[tokio::test]
+async fn a_signup_is_stamped_with_the_tenant_its_host_resolves_to() {
+    // Arrange
+    let (app, tenant_id) = app_with_tenant("acme.example.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act - the request arrives on the tenant's host
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("X-Forwarded-Host", "acme.example.com")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT tenant_id FROM subscriptions WHERE email = 'ursula_le_guin@gmail.com'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.tenant_id, tenant_id);
+}
+
+#[tokio::test]
+async fn a_signup_on_an_unclaimed_host_belongs_to_the_default_tenant() {
+    // Arrange
+    let (app, tenant_id) = app_with_tenant("acme.example.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act - a host no tenant has claimed
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("X-Forwarded-Host", "somebody.else.example.com")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert - the catch-all default tenant, not the seeded one
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT tenant_id FROM subscriptions WHERE email = 'ursula_le_guin@gmail.com'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.tenant_id, DEFAULT_TENANT_ID);
+    assert_ne!(saved.tenant_id, tenant_id);
+}